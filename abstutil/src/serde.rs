@@ -148,3 +148,85 @@ pub fn deserialize_usize<'de, D: Deserializer<'de>>(d: D) -> Result<usize, D::Er
     let x = <u32>::deserialize(d)?;
     Ok(x as usize)
 }
+
+/// Deserializes a field appended to the end of a struct stored with bincode. Files written before
+/// the field existed just end early, so fall back to the default value. Any other error still
+/// fails. Also use `#[serde(default)]`, so formats that name their fields handle a missing one the
+/// same way.
+pub fn deserialize_appended<'de, D: Deserializer<'de>, T: Deserialize<'de> + Default>(
+    d: D,
+) -> Result<T, D::Error> {
+    match T::deserialize(d) {
+        Ok(x) => Ok(x),
+        Err(err) if is_unexpected_eof(&err) => Ok(T::default()),
+        Err(err) => Err(err),
+    }
+}
+
+// The deserializer is generic, so the error can only be recognized by its message. bincode reports
+// running out of input as an I/O error, worded differently for slices and readers.
+fn is_unexpected_eof<E: std::fmt::Display>(err: &E) -> bool {
+    match err.to_string().strip_prefix("io error: ") {
+        Some(msg) => {
+            msg.is_empty()
+                || msg == "unexpected end of file"
+                || msg == "failed to fill whole buffer"
+        }
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize)]
+    struct Before {
+        x: usize,
+    }
+
+    #[derive(Deserialize)]
+    struct After {
+        x: usize,
+        #[serde(default, deserialize_with = "deserialize_appended")]
+        y: Vec<usize>,
+    }
+
+    #[test]
+    fn appended_field() {
+        let after: After = from_binary(&to_binary(&Before { x: 3 })).unwrap();
+        assert_eq!(after.x, 3);
+        assert!(after.y.is_empty());
+
+        let after: After = from_json(to_json(&Before { x: 3 }).as_bytes()).unwrap();
+        assert!(after.y.is_empty());
+
+        let bytes = to_binary(&Before { x: 3 });
+        let after: After = from_binary_reader(std::io::Cursor::new(bytes)).unwrap();
+        assert!(after.y.is_empty());
+    }
+
+    #[derive(Serialize)]
+    struct Corrupted {
+        x: usize,
+        flags: Vec<u8>,
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct WithFlags {
+        #[allow(dead_code)]
+        x: usize,
+        #[serde(default, deserialize_with = "deserialize_appended")]
+        #[allow(dead_code)]
+        flags: Vec<bool>,
+    }
+
+    #[test]
+    fn corrupted_appended_field() {
+        let bytes = to_binary(&Corrupted {
+            x: 3,
+            flags: vec![1, 5],
+        });
+        assert!(from_binary::<WithFlags>(&bytes).is_err());
+    }
+}
@@ -28,8 +28,11 @@ impl FindHome {
                     .into_widget(ctx),
                 ctx.style().btn_close_widget(ctx),
             ]),
-            // TODO Adjust text to say bikeshed, or otherwise reflect the options chosen
-            "Select the types of businesses you want within a 15 minute walkshed.".text_widget(ctx),
+            format!(
+                "Select the types of businesses you want within a 15 minute {}.",
+                options.movement.describe()
+            )
+            .text_widget(ctx),
             Widget::custom_row(
                 AmenityType::all()
                     .into_iter()
@@ -94,6 +97,8 @@ fn score_houses(
     let mut satisfied_per_bldg: Counter<BuildingID> = Counter::new();
 
     let map = &app.map;
+    let transit_rides = options.movement.transit_rides(app);
+    let transit_rides = transit_rides.as_deref();
     for times in timer.parallelize("find houses close to amenities", amenities, |category| {
         // For each category, find all matching stores
         let mut stores = Vec::new();
@@ -102,7 +107,10 @@ fn score_houses(
                 stores.push(Spot::Building(b.id));
             }
         }
-        options.movement.clone().times_from(map, stores)
+        options
+            .movement
+            .clone()
+            .times_from(map, transit_rides, stores)
     }) {
         for (b, _) in times {
             satisfied_per_bldg.inc(b);
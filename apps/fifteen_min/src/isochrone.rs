@@ -1,8 +1,9 @@
 use std::collections::{HashMap, HashSet};
+use std::rc::Rc;
 
 use abstutil::MultiMap;
-use connectivity::Spot;
-use geom::Duration;
+use connectivity::{Spot, TransitRides};
+use geom::{Duration, Time};
 use map_gui::tools::draw_isochrone;
 use map_model::{
     connectivity, AmenityType, BuildingID, BuildingType, IntersectionID, LaneType, Map, Path,
@@ -57,12 +58,29 @@ impl Options {
 pub enum MovementOptions {
     Walking(connectivity::WalkingOptions),
     Biking,
+    /// Walking and riding public transit, leaving at some time
+    Transit(connectivity::WalkingOptions, Time),
 }
 
 impl MovementOptions {
+    /// Searches riding transit need to know when vehicles reach each stop. This is shared by all
+    /// searches on the same map.
+    pub fn transit_rides(&self, app: &App) -> Option<Rc<TransitRides>> {
+        match self {
+            MovementOptions::Transit(_, _) => Some(app.session.transit_rides(&app.map)),
+            MovementOptions::Walking(_) | MovementOptions::Biking => None,
+        }
+    }
+
     /// Calculate the quickest time to reach buildings across the map from any of the starting
-    /// points, subject to the walking/biking settings configured in these Options.
-    pub fn times_from(self, map: &Map, starts: Vec<Spot>) -> HashMap<BuildingID, Duration> {
+    /// points, subject to the walking/biking/transit settings configured in these Options.
+    /// `transit_rides` must come from `MovementOptions::transit_rides`.
+    pub fn times_from(
+        self,
+        map: &Map,
+        transit_rides: Option<&TransitRides>,
+        starts: Vec<Spot>,
+    ) -> HashMap<BuildingID, Duration> {
         match self {
             MovementOptions::Walking(opts) => {
                 connectivity::all_walking_costs_from(map, starts, Duration::minutes(15), opts)
@@ -73,6 +91,22 @@ impl MovementOptions {
                 Duration::minutes(15),
                 PathConstraints::Bike,
             ),
+            MovementOptions::Transit(opts, departure) => connectivity::all_transit_costs_from(
+                map,
+                starts,
+                Duration::minutes(15),
+                opts,
+                transit_rides.expect("transit searches need TransitRides"),
+                Some(departure),
+            ),
+        }
+    }
+
+    pub fn describe(&self) -> &'static str {
+        match self {
+            MovementOptions::Walking(_) => "walk",
+            MovementOptions::Biking => "bike ride",
+            MovementOptions::Transit(_, _) => "walk or transit ride",
         }
     }
 }
@@ -85,7 +119,12 @@ impl Isochrone {
         options: Options,
     ) -> Isochrone {
         let spot_starts = start.iter().map(|b_id| Spot::Building(*b_id)).collect();
-        let time_to_reach_building = options.movement.clone().times_from(&app.map, spot_starts);
+        let transit_rides = options.movement.transit_rides(app);
+        let time_to_reach_building =
+            options
+                .movement
+                .clone()
+                .times_from(&app.map, transit_rides.as_deref(), spot_starts);
 
         let mut amenities_reachable = MultiMap::new();
         let mut population = 0;
@@ -155,7 +194,10 @@ impl Isochrone {
         }
 
         let constraints = match self.options.movement {
-            MovementOptions::Walking(_) => PathConstraints::Pedestrian,
+            // TODO Show the transit rides too
+            MovementOptions::Walking(_) | MovementOptions::Transit(_, _) => {
+                PathConstraints::Pedestrian
+            }
             MovementOptions::Biking => PathConstraints::Bike,
         };
 
@@ -192,7 +234,12 @@ impl BorderIsochrone {
         options: Options,
     ) -> BorderIsochrone {
         let spot_starts = start.iter().map(|i_id| Spot::Border(*i_id)).collect();
-        let time_to_reach_building = options.movement.clone().times_from(&app.map, spot_starts);
+        let transit_rides = options.movement.transit_rides(app);
+        let time_to_reach_building =
+            options
+                .movement
+                .clone()
+                .times_from(&app.map, transit_rides.as_deref(), spot_starts);
 
         // Generate a single polygon showing 15 minutes from the border
         let thresholds = vec![0.1, Duration::minutes(15).inner_seconds()];
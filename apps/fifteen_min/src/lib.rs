@@ -1,7 +1,13 @@
 #![allow(clippy::type_complexity)]
 
+use std::cell::RefCell;
+use std::rc::Rc;
+
 use structopt::StructOpt;
 
+use abstio::MapName;
+use map_model::connectivity::TransitRides;
+use map_model::Map;
 use widgetry::Settings;
 
 #[macro_use]
@@ -13,7 +19,28 @@ mod find_home;
 mod isochrone;
 mod viewer;
 
-type App = map_gui::SimpleApp<()>;
+type App = map_gui::SimpleApp<Session>;
+
+#[derive(Default)]
+pub struct Session {
+    /// When transit vehicles reach each stop on the current map. It's slow to calculate, so it's
+    /// only done the first time a search needs it.
+    transit_rides: RefCell<Option<(MapName, Rc<TransitRides>)>>,
+}
+
+impl Session {
+    pub fn transit_rides(&self, map: &Map) -> Rc<TransitRides> {
+        let mut cache = self.transit_rides.borrow_mut();
+        if let Some((name, rides)) = cache.as_ref() {
+            if name == map.get_name() {
+                return rides.clone();
+            }
+        }
+        let rides = Rc::new(TransitRides::new(map));
+        *cache = Some((map.get_name().clone(), rides.clone()));
+        rides
+    }
+}
 
 pub fn main() {
     let settings = Settings::new("15-minute neighborhoods");
@@ -29,17 +56,24 @@ fn run(mut settings: Settings) {
         .update_widgetry_settings(settings)
         .canvas_settings(options.canvas_settings.clone());
     widgetry::run(settings, |ctx| {
-        map_gui::SimpleApp::new(ctx, options, args.map_name(), args.cam, (), |ctx, app| {
-            vec![
-                map_gui::tools::TitleScreen::new_state(
-                    ctx,
-                    app,
-                    map_gui::tools::Executable::FifteenMin,
-                    Box::new(|ctx, app, _| viewer::Viewer::random_start(ctx, app)),
-                ),
-                viewer::Viewer::random_start(ctx, app),
-            ]
-        })
+        map_gui::SimpleApp::new(
+            ctx,
+            options,
+            args.map_name(),
+            args.cam,
+            Session::default(),
+            |ctx, app| {
+                vec![
+                    map_gui::tools::TitleScreen::new_state(
+                        ctx,
+                        app,
+                        map_gui::tools::Executable::FifteenMin,
+                        Box::new(|ctx, app, _| viewer::Viewer::random_start(ctx, app)),
+                    ),
+                    viewer::Viewer::random_start(ctx, app),
+                ]
+            },
+        )
     });
 }
 
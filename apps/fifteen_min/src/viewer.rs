@@ -5,7 +5,7 @@
 //! See https://github.com/a-b-street/abstreet/issues/393 for more context.
 
 use abstutil::prettyprint_usize;
-use geom::{Distance, Duration};
+use geom::{Distance, Duration, Time};
 use map_gui::tools::{draw_isochrone, CityPicker, Navigator};
 use map_gui::ID;
 use map_model::connectivity::WalkingOptions;
@@ -236,12 +236,16 @@ fn options_to_controls(ctx: &mut EventCtx, opts: &Options) -> Widget {
         "biking",
         None,
         match opts.movement {
-            MovementOptions::Walking(_) => true,
+            MovementOptions::Walking(_) | MovementOptions::Transit(_, _) => true,
             MovementOptions::Biking => false,
         },
     )];
+    let departure = match opts.movement {
+        MovementOptions::Transit(_, departure) => Some(departure),
+        MovementOptions::Walking(_) | MovementOptions::Biking => None,
+    };
     match opts.movement {
-        MovementOptions::Walking(ref opts) => {
+        MovementOptions::Walking(ref opts) | MovementOptions::Transit(ref opts, _) => {
             rows.push(Toggle::switch(
                 ctx,
                 "Allow walking on the shoulder of the road without a sidewalk",
@@ -259,6 +263,29 @@ fn options_to_controls(ctx: &mut EventCtx, opts: &Options) -> Widget {
             ));
 
             rows.push(ColorLegend::row(ctx, Color::BLUE, "unwalkable roads"));
+
+            rows.push(Toggle::switch(
+                ctx,
+                "Ride public transit",
+                None,
+                departure.is_some(),
+            ));
+            if let Some(departure) = departure {
+                rows.push(Widget::row(vec![
+                    "Leave at".text_widget(ctx).centered_vert(),
+                    Widget::dropdown(
+                        ctx,
+                        "departure",
+                        departure,
+                        (5..=23)
+                            .map(|hour| {
+                                let time = Time::START_OF_DAY + Duration::hours(hour);
+                                Choice::new(time.ampm_tostring(), time)
+                            })
+                            .collect(),
+                    ),
+                ]));
+            }
         }
         MovementOptions::Biking => {}
     }
@@ -267,14 +294,27 @@ fn options_to_controls(ctx: &mut EventCtx, opts: &Options) -> Widget {
 
 fn options_from_controls(panel: &Panel) -> MovementOptions {
     if panel.is_checked("walking / biking") {
-        MovementOptions::Walking(WalkingOptions {
+        let opts = WalkingOptions {
             allow_shoulders: panel
                 .maybe_is_checked("Allow walking on the shoulder of the road without a sidewalk")
                 .unwrap_or(true),
             walking_speed: panel
                 .maybe_dropdown_value("speed")
                 .unwrap_or_else(WalkingOptions::default_speed),
-        })
+        };
+        if panel
+            .maybe_is_checked("Ride public transit")
+            .unwrap_or(false)
+        {
+            MovementOptions::Transit(
+                opts,
+                panel
+                    .maybe_dropdown_value("departure")
+                    .unwrap_or_else(|| Time::START_OF_DAY + Duration::hours(8)),
+            )
+        } else {
+            MovementOptions::Walking(opts)
+        }
     } else {
         MovementOptions::Biking
     }
@@ -548,7 +588,9 @@ impl State<App> for ExploreAmenities {
 
 pub fn draw_unwalkable_roads(ctx: &mut EventCtx, app: &App, opts: &Options) -> Drawable {
     let allow_shoulders = match opts.movement {
-        MovementOptions::Walking(ref opts) => opts.allow_shoulders,
+        MovementOptions::Walking(ref opts) | MovementOptions::Transit(ref opts, _) => {
+            opts.allow_shoulders
+        }
         MovementOptions::Biking => {
             return Drawable::empty(ctx);
         }
@@ -224,6 +224,20 @@ fn params_to_controls(ctx: &mut EventCtx, mode: TripMode, params: &RoutingParams
             ),
         ]));
     }
+    if mode == TripMode::Drive {
+        rows.push(Widget::row(vec![
+            "Value of time (per hour, for road pricing):"
+                .text_widget(ctx)
+                .margin_right(20),
+            Spinner::f64_widget(
+                ctx,
+                "value_of_time",
+                (1.0, 200.0),
+                params.value_of_time,
+                1.0,
+            ),
+        ]));
+    }
     if mode == TripMode::Bike {
         rows.push(Widget::row(vec![
             "Bike lane penalty:".text_widget(ctx).margin_right(20),
@@ -285,6 +299,7 @@ fn controls_to_params(panel: &Panel) -> (TripMode, RoutingParams) {
     let mut params = RoutingParams::default();
     if !panel.is_button_enabled("cars") {
         params.unprotected_turn_penalty = panel.spinner("unprotected_turn_penalty");
+        params.value_of_time = panel.spinner::<RoundedF64>("value_of_time").0;
         return (TripMode::Drive, params);
     }
     if !panel.is_button_enabled("pedestrians") {
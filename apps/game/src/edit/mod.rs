@@ -784,6 +784,12 @@ pub fn apply_map_edits(ctx: &mut EventCtx, app: &mut App, edits: MapEdits) {
             app.primary.draw_map.get_pl(pl).clear_rendering();
         }
 
+        for ts in effects.changed_transit_stops {
+            app.primary
+                .draw_map
+                .recreate_transit_stop(ctx, ts, &app.primary.map, &app.cs);
+        }
+
         if app.primary.layer.as_ref().and_then(|l| l.name()) == Some("map edits") {
             app.primary.layer = Some(Box::new(crate::layer::map::Static::edits(ctx, app)));
         }
@@ -940,7 +946,11 @@ fn cmd_to_id(cmd: &EditCmd) -> Option<ID> {
         EditCmd::ChangeRoad { r, .. } => Some(ID::Road(*r)),
         EditCmd::ChangeIntersection { i, .. } => Some(ID::Intersection(*i)),
         EditCmd::ChangeCrosswalks { i, .. } => Some(ID::Intersection(*i)),
-        EditCmd::ChangeRouteSchedule { .. } => None,
+        EditCmd::ChangeTransitStop { id, new, .. } => new.as_ref().map(|_| ID::TransitStop(*id)),
+        EditCmd::ChangeRouteSchedule { .. }
+        | EditCmd::ChangeTransitRoute { .. }
+        | EditCmd::ChangeRoadPricing { .. }
+        | EditCmd::ChangeParkingPolicy { .. } => None,
     }
 }
 
@@ -6,7 +6,7 @@ use map_model::{
 use widgetry::tools::{ChooseSomething, PopupMsg};
 use widgetry::{
     Choice, DrawBaselayer, EventCtx, Key, Line, Panel, SimpleState, Spinner, State, Text, TextExt,
-    Widget,
+    Toggle, Widget,
 };
 
 use crate::app::{App, Transition};
@@ -14,6 +14,8 @@ use crate::edit::traffic_signals::{BundleEdits, TrafficSignalEditor};
 use crate::edit::{apply_map_edits, check_sidewalk_connectivity, StopSignEditor};
 use crate::sandbox::GameplayMode;
 
+// Actuated stages reuse the spinners: "additional" is the time between min and max green, and
+// "delay" is the passage time.
 pub struct ChangeDuration {
     idx: usize,
 }
@@ -47,18 +49,13 @@ impl ChangeDuration {
                 .secondary()
                 .into_widget(ctx),
             Widget::col(vec![
-                Text::from_all(match signal.stages[idx].stage_type {
-                    StageType::Fixed(_) => vec![
-                        Line("Fixed timing").small_heading(),
-                        Line(" (Adjust both values below to enable variable timing)"),
-                    ],
-                    StageType::Variable(_, _, _) => vec![
-                        Line("Variable timing").small_heading(),
-                        Line(" (Set either values below to 0 to use fixed timing."),
-                    ],
-                })
-                .into_widget(ctx)
-                .named("timing type"),
+                timing_type_label(ctx, &signal.stages[idx].stage_type),
+                Toggle::checkbox(
+                    ctx,
+                    "actuated by vehicle detectors",
+                    None,
+                    matches!(signal.stages[idx].stage_type, StageType::Actuated(_, _, _)),
+                ),
                 Widget::row(vec![
                     "How much additional time can this stage last?"
                         .text_widget(ctx)
@@ -70,6 +67,7 @@ impl ChangeDuration {
                         match signal.stages[idx].stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, _, additional) => additional,
+                            StageType::Actuated(min_green, max_green, _) => max_green - min_green,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -85,6 +83,7 @@ impl ChangeDuration {
                         match signal.stages[idx].stage_type {
                             StageType::Fixed(_) => Duration::ZERO,
                             StageType::Variable(_, delay, _) => delay,
+                            StageType::Actuated(_, _, passage) => passage,
                         },
                         Duration::seconds(1.0),
                     ),
@@ -104,6 +103,38 @@ impl ChangeDuration {
     }
 }
 
+fn stage_type_from_panel(panel: &Panel) -> StageType {
+    let dt = panel.spinner("duration");
+    let delay = panel.spinner("delay");
+    let additional = panel.spinner("additional");
+    if delay == Duration::ZERO || additional == Duration::ZERO {
+        StageType::Fixed(dt)
+    } else if panel.is_checked("actuated by vehicle detectors") {
+        StageType::Actuated(dt, dt + additional, delay)
+    } else {
+        StageType::Variable(dt, delay, additional)
+    }
+}
+
+fn timing_type_label(ctx: &mut EventCtx, stage_type: &StageType) -> Widget {
+    Text::from_all(match stage_type {
+        StageType::Fixed(_) => vec![
+            Line("Fixed timing").small_heading(),
+            Line(" (Adjust both values below to enable variable or actuated timing)"),
+        ],
+        StageType::Variable(_, _, _) => vec![
+            Line("Variable timing").small_heading(),
+            Line(" (Set either values below to 0 to use fixed timing."),
+        ],
+        StageType::Actuated(_, _, _) => vec![
+            Line("Actuated timing").small_heading(),
+            Line(" (Set either values below to 0 to use fixed timing."),
+        ],
+    })
+    .into_widget(ctx)
+    .named("timing type")
+}
+
 impl SimpleState<App> for ChangeDuration {
     fn on_click(
         &mut self,
@@ -115,14 +146,7 @@ impl SimpleState<App> for ChangeDuration {
         match x {
             "close" => Transition::Pop,
             "Apply" => {
-                let dt = panel.spinner("duration");
-                let delay = panel.spinner("delay");
-                let additional = panel.spinner("additional");
-                let new_type = if delay == Duration::ZERO || additional == Duration::ZERO {
-                    StageType::Fixed(dt)
-                } else {
-                    StageType::Variable(dt, delay, additional)
-                };
+                let new_type = stage_type_from_panel(panel);
                 let idx = self.idx;
                 Transition::Multi(vec![
                     Transition::Pop,
@@ -144,22 +168,7 @@ impl SimpleState<App> for ChangeDuration {
         _: &mut App,
         panel: &mut Panel,
     ) -> Option<Transition> {
-        let new_label = Text::from_all(
-            if panel.spinner::<Duration>("delay") == Duration::ZERO
-                || panel.spinner::<Duration>("additional") == Duration::ZERO
-            {
-                vec![
-                    Line("Fixed timing").small_heading(),
-                    Line(" (Adjust both values below to enable variable timing)"),
-                ]
-            } else {
-                vec![
-                    Line("Variable timing").small_heading(),
-                    Line(" (Set either values below to 0 to use fixed timing."),
-                ]
-            },
-        )
-        .into_widget(ctx);
+        let new_label = timing_type_label(ctx, &stage_type_from_panel(panel));
         panel.replace(ctx, "timing type", new_label);
         None
     }
@@ -680,6 +680,10 @@ fn make_side_panel(
                     "Stage duration: {}, {}, {} (variable)",
                     min, delay, additional
                 ),
+                StageType::Actuated(min, max, passage) => format!(
+                    "Stage duration: {} to {}, {} passage (actuated)",
+                    min, max, passage
+                ),
             }
             .text_widget(ctx)
             .centered_vert(),
@@ -721,6 +725,7 @@ fn make_side_panel(
                 match canonical_signal.stages[idx].stage_type {
                     StageType::Fixed(d) => format!("{}", d),
                     StageType::Variable(min, _, _) => format!("{} (v)", min),
+                    StageType::Actuated(min, _, _) => format!("{} (a)", min),
                 },
             )))
             .render(ctx),
@@ -1,7 +1,4 @@
-use std::collections::BTreeSet;
-
-use abstutil::Timer;
-use map_model::{connectivity, Direction, DrivingSide, EditCmd, Map, PathConstraints};
+use map_model::{Direction, DrivingSide, EditCmd, Map};
 use widgetry::tools::PopupMsg;
 use widgetry::{EventCtx, State};
 
@@ -16,25 +13,9 @@ pub fn check_sidewalk_connectivity(
     app: &mut App,
     cmd: EditCmd,
 ) -> Option<Box<dyn State<App>>> {
-    let orig_edits = app.primary.map.get_edits().clone();
-    let (_, disconnected_before) =
-        connectivity::find_scc(&app.primary.map, PathConstraints::Pedestrian);
-
-    let mut edits = orig_edits.clone();
+    let mut edits = app.primary.map.get_edits().clone();
     edits.commands.push(cmd);
-    app.primary
-        .map
-        .try_apply_edits(edits, &mut Timer::throwaway());
-
-    let (_, disconnected_after) =
-        connectivity::find_scc(&app.primary.map, PathConstraints::Pedestrian);
-    app.primary
-        .map
-        .must_apply_edits(orig_edits, &mut Timer::throwaway());
-
-    let newly_disconnected = disconnected_after
-        .difference(&disconnected_before)
-        .collect::<Vec<_>>();
+    let newly_disconnected = app.primary.map.find_newly_disconnected_sidewalks(edits);
     if newly_disconnected.is_empty() {
         return None;
     }
@@ -59,39 +40,9 @@ pub fn check_blackholes(
     app: &mut App,
     cmd: EditCmd,
 ) -> Option<Box<dyn State<App>>> {
-    let orig_edits = app.primary.map.get_edits().clone();
-    let mut driving_ok_originally = BTreeSet::new();
-    let mut biking_ok_originally = BTreeSet::new();
-    for l in app.primary.map.all_lanes() {
-        if !l.driving_blackhole {
-            driving_ok_originally.insert(l.id);
-        }
-        if !l.biking_blackhole {
-            biking_ok_originally.insert(l.id);
-        }
-    }
-
-    let mut edits = orig_edits.clone();
+    let mut edits = app.primary.map.get_edits().clone();
     edits.commands.push(cmd);
-    app.primary
-        .map
-        .try_apply_edits(edits, &mut Timer::throwaway());
-
-    let mut newly_disconnected = BTreeSet::new();
-    for l in connectivity::find_scc(&app.primary.map, PathConstraints::Car).1 {
-        if driving_ok_originally.contains(&l) {
-            newly_disconnected.insert(l);
-        }
-    }
-    for l in connectivity::find_scc(&app.primary.map, PathConstraints::Bike).1 {
-        if biking_ok_originally.contains(&l) {
-            newly_disconnected.insert(l);
-        }
-    }
-    app.primary
-        .map
-        .must_apply_edits(orig_edits, &mut Timer::throwaway());
-
+    let newly_disconnected = app.primary.map.find_newly_disconnected_lanes(edits);
     if newly_disconnected.is_empty() {
         return None;
     }
@@ -596,6 +596,18 @@ fn parked_car_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
         rows.push("No longer parked".text_widget(ctx));
     }
 
+    if let Some((charge, capacity)) = app.primary.sim.get_battery_charge(id) {
+        rows.push(
+            format!(
+                "Battery at {}% ({:.1} of {:.0} kWh)",
+                (100.0 * charge / capacity).round(),
+                charge,
+                capacity
+            )
+            .text_widget(ctx),
+        );
+    }
+
     Widget::col(rows)
 }
 
@@ -631,6 +643,9 @@ fn header(
                         VehicleType::Bike => ("biking", Some("system/assets/meters/bike.svg")),
                         VehicleType::Bus | VehicleType::Train => unreachable!(),
                     },
+                    AgentID::CarPassenger(_, _) => {
+                        ("riding in a car", Some("system/assets/meters/car.svg"))
+                    }
                     AgentID::BusPassenger(_, _) => {
                         ("riding a bus", Some("system/assets/meters/bus.svg"))
                     }
@@ -131,8 +131,9 @@ fn bus_status_body(ctx: &mut EventCtx, app: &App, details: &mut Details, id: Car
 
     rows.push(
         Line(format!(
-            "Currently has {} passengers",
+            "Currently has {} / {} passengers",
             app.primary.sim.num_transit_passengers(id),
+            app.primary.sim.transit_capacity(id),
         ))
         .into_widget(ctx),
     );
@@ -265,6 +265,7 @@ fn export_raw_problems(app: &App) -> Result<String> {
                     Traversable::Turn(t) => map.get_i(t.parent).orig_id.to_string(),
                 },
                 Problem::ArterialIntersectionCrossing(t) => map.get_i(t.parent).orig_id.to_string(),
+                Problem::TransitBoardingDenied(ts, _) => map.get_r(ts.road).orig_id.to_string(),
             };
             writeln!(
                 out,
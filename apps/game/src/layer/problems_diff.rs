@@ -135,6 +135,9 @@ impl RelativeProblemMap {
                     Problem::ArterialIntersectionCrossing(t) => {
                         intersections.inc(t.parent);
                     }
+                    Problem::TransitBoardingDenied(ts, _) => {
+                        roads.inc(ts.road);
+                    }
                 }
             };
 
@@ -0,0 +1,160 @@
+use std::collections::BTreeSet;
+
+use geom::{Duration, Time};
+use sim::{Analytics, Emissions, TripID};
+use widgetry::{
+    EventCtx, GfxCtx, Line, LinePlot, Outcome, Panel, PlotOptions, Series, State, Text, TextExt,
+    Widget,
+};
+
+use crate::app::{App, Transition};
+use crate::sandbox::dashboards::DashTab;
+
+pub struct EmissionsSummary {
+    panel: Panel,
+}
+
+impl EmissionsSummary {
+    pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
+        let after = app.primary.sim.get_analytics();
+        let mut col = vec![
+            DashTab::Emissions.picker(ctx, app),
+            Text::from_multiline(vec![
+                Line("Energy and pollution from cars and transit vehicles so far"),
+                Line("Emissions from generating electricity aren't included.").secondary(),
+            ])
+            .into_widget(ctx),
+            describe(&after.emissions.total()).text_widget(ctx),
+        ];
+
+        if app.has_prebaked().is_some() {
+            let before = app.prebaked();
+            let (sum_before, sum_after) = compare_finished_trips(before, after);
+            col.push(Widget::horiz_separator(ctx, 1.0));
+            col.push(
+                Text::from_multiline(vec![
+                    Line(format!(
+                        "For trips that finished before and after \"{}\"",
+                        app.primary.map.get_edits().edits_name
+                    )),
+                    Line(format!("Before: {}", describe(&sum_before))),
+                    Line(format!("After: {}", describe(&sum_after))),
+                    Line(format!(
+                        "CO2 {}, NOx {}",
+                        pct_change(sum_before.co2_grams, sum_after.co2_grams),
+                        pct_change(sum_before.nox_grams, sum_after.nox_grams)
+                    )),
+                ])
+                .into_widget(ctx),
+            );
+        }
+
+        let mut series = vec![Series {
+            label: format!("After \"{}\"", app.primary.map.get_edits().edits_name),
+            color: app.cs.after_changes,
+            pts: co2_per_hour(after, app.primary.sim.time()),
+        }];
+        if app.has_prebaked().is_some() {
+            series.push(Series {
+                label: format!("Before \"{}\"", app.primary.map.get_edits().edits_name),
+                color: app.cs.before_changes.alpha(0.5),
+                pts: co2_per_hour(app.prebaked(), app.primary.sim.get_end_of_day()),
+            });
+        }
+        col.push(Line("CO2 per hour (kg)").small_heading().into_widget(ctx));
+        col.push(LinePlot::new_widget(
+            ctx,
+            "co2 per hour",
+            series,
+            PlotOptions::fixed(),
+            app.opts.units,
+        ));
+
+        Box::new(EmissionsSummary {
+            panel: Panel::new_builder(Widget::col(col))
+                .exact_size_percent(90, 90)
+                .build(ctx),
+        })
+    }
+}
+
+impl State<App> for EmissionsSummary {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        match self.panel.event(ctx) {
+            Outcome::Clicked(x) => match x.as_ref() {
+                "close" => Transition::Pop,
+                _ => unreachable!(),
+            },
+            Outcome::Changed(_) => DashTab::Emissions
+                .transition(ctx, app, &self.panel)
+                .unwrap(),
+            _ => Transition::Keep,
+        }
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _app: &App) {
+        self.panel.draw(g);
+    }
+}
+
+fn describe(x: &Emissions) -> String {
+    format!(
+        "{:.1} kWh of energy, {:.1} L of fuel, {:.1} kg of CO2, {:.1} g of NOx",
+        x.energy_kwh,
+        x.fuel_liters,
+        x.co2_grams / 1000.0,
+        x.nox_grams
+    )
+}
+
+fn pct_change(before: f64, after: f64) -> String {
+    if before == 0.0 {
+        return "unchanged".to_string();
+    }
+    let pct = 100.0 * (after - before) / before;
+    if pct >= 0.0 {
+        format!("+{:.1}%", pct)
+    } else {
+        format!("{:.1}%", pct)
+    }
+}
+
+/// Sums emissions for the trips that finished in both runs
+fn compare_finished_trips(before: &Analytics, after: &Analytics) -> (Emissions, Emissions) {
+    let finished = |analytics: &Analytics| -> BTreeSet<TripID> {
+        analytics
+            .finished_trips
+            .iter()
+            .filter(|(_, _, _, dt)| dt.is_some())
+            .map(|(_, id, _, _)| *id)
+            .collect()
+    };
+    let mut sum_before = Emissions::default();
+    let mut sum_after = Emissions::default();
+    for id in finished(before).intersection(&finished(after)) {
+        if let Some(x) = before.emissions.per_trip.get(id) {
+            sum_before += *x;
+        }
+        if let Some(x) = after.emissions.per_trip.get(id) {
+            sum_after += *x;
+        }
+    }
+    (sum_before, sum_after)
+}
+
+fn co2_per_hour(analytics: &Analytics, until: Time) -> Vec<(Time, usize)> {
+    let mut pts = vec![(Time::START_OF_DAY, 0)];
+    for hour in 0..until.get_hours() {
+        let kg = analytics
+            .emissions
+            .per_hour
+            .get(&hour)
+            .map(|x| x.co2_grams / 1000.0)
+            .unwrap_or(0.0);
+        pts.push((
+            Time::START_OF_DAY + Duration::hours(hour + 1),
+            kg.round() as usize,
+        ));
+    }
+    pts
+}
@@ -7,6 +7,7 @@ use crate::app::App;
 use crate::app::Transition;
 
 mod commuter;
+mod emissions;
 mod generic_trip_table;
 mod misc;
 mod mode_shift;
@@ -30,6 +31,7 @@ pub enum DashTab {
     CommuterPatterns,
     TrafficSignals,
     ModeShift,
+    Emissions,
 }
 
 impl DashTab {
@@ -44,6 +46,7 @@ impl DashTab {
             Choice::new("Commuter Patterns", DashTab::CommuterPatterns),
             Choice::new("Traffic Signal Demand", DashTab::TrafficSignals),
             Choice::new("Mode shift (experimental)", DashTab::ModeShift),
+            Choice::new("Emissions", DashTab::Emissions),
         ];
         if app.has_prebaked().is_none() {
             choices.remove(1);
@@ -73,6 +76,7 @@ impl DashTab {
             DashTab::CommuterPatterns => CommuterPatterns::new_state(ctx, app),
             DashTab::TrafficSignals => TrafficSignalDemand::new_state(ctx, app),
             DashTab::ModeShift => mode_shift::ModeShift::new_state(ctx, app),
+            DashTab::Emissions => emissions::EmissionsSummary::new_state(ctx, app),
         }
     }
 
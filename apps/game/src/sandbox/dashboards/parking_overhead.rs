@@ -21,6 +21,16 @@ pub struct ParkingOverhead {
 impl ParkingOverhead {
     pub fn new_state(ctx: &mut EventCtx, app: &App) -> Box<dyn State<App>> {
         let table = make_table(app);
+        let (total_parking, total_walking, total_fees) = produce_raw_data(app).into_iter().fold(
+            (Duration::ZERO, Duration::ZERO, 0.0),
+            |(parking, walking, fees), x| {
+                (
+                    parking + x.parking_duration,
+                    walking + x.walking_duration,
+                    fees + x.parking_fee,
+                )
+            },
+        );
         let col = Widget::col(vec![
             DashTab::ParkingOverhead.picker(ctx, app),
             Widget::col(vec![
@@ -49,6 +59,13 @@ impl ParkingOverhead {
                              high overhead,",
                         ),
                         Line("since the time spent driving off-map isn't shown here."),
+                        Line(""),
+                        Line(format!(
+                            "In total: {} looking for parking, {} walking, {:.2} paid to park",
+                            total_parking.to_string(&app.opts.units),
+                            total_walking.to_string(&app.opts.units),
+                            total_fees
+                        )),
                     ])
                     .into_widget(ctx),
                     Filler::square_width(ctx, 0.15).named("preview"),
@@ -111,6 +128,7 @@ struct Entry {
     driving_duration: Duration,
     parking_duration: Duration,
     walking_duration: Duration,
+    parking_fee: f64,
     percent_overhead: usize,
     starts_off_map: bool,
     ends_off_map: bool,
@@ -124,7 +142,8 @@ struct Filters {
 fn produce_raw_data(app: &App) -> Vec<Entry> {
     // Gather raw data
     let mut data = Vec::new();
-    for (id, phases) in app.primary.sim.get_analytics().get_all_trip_phases() {
+    let analytics = app.primary.sim.get_analytics();
+    for (id, phases) in analytics.get_all_trip_phases() {
         let trip = app.primary.sim.trip_info(id);
         let starts_off_map = matches!(trip.start, TripEndpoint::Border(_));
         let ends_off_map = matches!(trip.end, TripEndpoint::Border(_));
@@ -165,6 +184,7 @@ fn produce_raw_data(app: &App) -> Vec<Entry> {
             driving_duration,
             parking_duration,
             walking_duration,
+            parking_fee: analytics.parking_fees.get(&id).cloned().unwrap_or(0.0),
             percent_overhead: (100.0 * (1.0 - (driving_duration / total_duration))) as usize,
             starts_off_map,
             ends_off_map,
@@ -234,6 +254,13 @@ fn make_table(app: &App) -> Table<App, Entry, Filters> {
         }),
         Col::Sortable(Box::new(|rows| rows.sort_by_key(|x| x.walking_duration))),
     );
+    table.column(
+        "Parking fee",
+        Box::new(|ctx, _, x| Text::from(format!("{:.2}", x.parking_fee)).render(ctx)),
+        Col::Sortable(Box::new(|rows| {
+            rows.sort_by(|a, b| a.parking_fee.partial_cmp(&b.parking_fee).unwrap())
+        })),
+    );
     table.column(
         "Percent overhead",
         Box::new(|ctx, _, x| Text::from(format!("{}%", x.percent_overhead)).render(ctx)),
@@ -197,7 +197,11 @@ impl GameplayMode {
                         return false;
                     }
                 }
-                EditCmd::ChangeRouteSchedule { .. } => {}
+                EditCmd::ChangeRouteSchedule { .. }
+                | EditCmd::ChangeTransitStop { .. }
+                | EditCmd::ChangeTransitRoute { .. }
+                | EditCmd::ChangeRoadPricing { .. }
+                | EditCmd::ChangeParkingPolicy { .. } => {}
             }
         }
         true
@@ -3,14 +3,14 @@ use std::collections::{BTreeMap, BTreeSet};
 use maplit::btreeset;
 
 use abstutil::prettyprint_usize;
-use geom::{Duration, Time};
-use map_gui::tools::{checkbox_per_mode, grey_out_map, CityPicker};
+use geom::{Distance, Duration, LonLat, Pt2D, Ring, Time};
+use map_gui::tools::{checkbox_per_mode, grey_out_map, CityPicker, EditPolygon};
 use sim::SlidingWindow;
 use synthpop::{ScenarioModifier, TripMode};
 use widgetry::tools::{ChooseSomething, PopupMsg, URLManager};
 use widgetry::{
     lctrl, Choice, Color, EventCtx, GfxCtx, HorizontalAlignment, Key, Line, LinePlot, Outcome,
-    Panel, PlotOptions, Series, SimpleState, Slider, Spinner, State, Text, TextExt,
+    Panel, PlotOptions, Series, SimpleState, Slider, Spinner, State, Text, TextExt, Toggle,
     VerticalAlignment, Widget,
 };
 
@@ -272,6 +272,32 @@ impl EditScenarioModifiers {
                 .text("Repeat schedule multiple days with +/- 10 minutes of noise")
                 .build_def(ctx),
         ]));
+        rows.push(Widget::row(vec![
+            Spinner::widget(ctx, "scale_demand", (10, 500), 100_usize, 10),
+            ctx.style()
+                .btn_outline
+                .text("Scale the number of people (%)")
+                .build_def(ctx),
+        ]));
+        rows.push(Widget::row(vec![
+            Spinner::widget(
+                ctx,
+                "shift_departures",
+                (-Duration::hours(3), Duration::hours(3)),
+                Duration::minutes(30),
+                Duration::minutes(15),
+            ),
+            ctx.style()
+                .btn_outline
+                .text("Shift all departure times")
+                .build_def(ctx),
+        ]));
+        rows.push(
+            ctx.style()
+                .btn_outline
+                .text("Only keep trips starting or ending in an area")
+                .build_def(ctx),
+        );
         rows.push(Widget::horiz_separator(ctx, 1.0));
         rows.push(
             Widget::row(vec![
@@ -379,6 +405,46 @@ impl State<App> for EditScenarioModifiers {
                         self.modifiers.clone(),
                     ));
                 }
+                "Scale the number of people (%)" => {
+                    self.modifiers.push(ScenarioModifier::ScaleDemand(
+                        self.panel.spinner("scale_demand"),
+                    ));
+                    return Transition::Replace(EditScenarioModifiers::new_state(
+                        ctx,
+                        self.scenario_name.clone(),
+                        self.modifiers.clone(),
+                    ));
+                }
+                "Shift all departure times" => {
+                    self.modifiers.push(ScenarioModifier::ShiftDepartures(
+                        self.panel.spinner("shift_departures"),
+                    ));
+                    return Transition::Replace(EditScenarioModifiers::new_state(
+                        ctx,
+                        self.scenario_name.clone(),
+                        self.modifiers.clone(),
+                    ));
+                }
+                "Only keep trips starting or ending in an area" => {
+                    let scenario_name = self.scenario_name.clone();
+                    let modifiers = self.modifiers.clone();
+                    return Transition::Push(DrawArea::new_state(
+                        ctx,
+                        "Only keep trips starting or ending in this area",
+                        Box::new(move |ctx, area| {
+                            let mut modifiers = modifiers.clone();
+                            modifiers.push(ScenarioModifier::FilterArea(area));
+                            Transition::Multi(vec![
+                                Transition::Pop,
+                                Transition::Replace(EditScenarioModifiers::new_state(
+                                    ctx,
+                                    scenario_name.clone(),
+                                    modifiers,
+                                )),
+                            ])
+                        }),
+                    ));
+                }
                 x => {
                     if let Some(x) = x.strip_prefix("delete modifier ") {
                         self.modifiers.remove(x.parse::<usize>().unwrap() - 1);
@@ -451,6 +517,8 @@ impl ChangeMode {
                         choices
                     }),
                 ]),
+                Toggle::checkbox(ctx, "share rides with people already driving", None, false),
+                Toggle::checkbox(ctx, "only trips starting or ending in an area", None, false),
                 Widget::row(vec![
                     ctx.style()
                         .btn_solid_primary
@@ -523,6 +591,9 @@ impl State<App> for ChangeMode {
                     let (from_modes, departure_filter) = self.get_filters(app);
                     let to_mode = self.panel.dropdown_value::<Option<TripMode>, _>("to_mode");
                     let pct_ppl = self.panel.spinner("pct_ppl");
+                    let carpool = self
+                        .panel
+                        .is_checked("share rides with people already driving");
                     if from_modes.is_empty() {
                         return Transition::Push(PopupMsg::new_state(
                             ctx,
@@ -539,11 +610,44 @@ impl State<App> for ChangeMode {
                     }
 
                     let mut mods = self.modifiers.clone();
+                    if self
+                        .panel
+                        .is_checked("only trips starting or ending in an area")
+                    {
+                        let scenario_name = self.scenario_name.clone();
+                        return Transition::Push(DrawArea::new_state(
+                            ctx,
+                            "Only change trips starting or ending in this area",
+                            Box::new(move |ctx, area| {
+                                let mut mods = mods.clone();
+                                mods.push(ScenarioModifier::ChangeMode {
+                                    to_mode,
+                                    pct_ppl,
+                                    departure_filter,
+                                    from_modes: from_modes.clone(),
+                                    carpool,
+                                    area: Some(area),
+                                });
+                                // Pop this and the ChangeMode state
+                                Transition::Multi(vec![
+                                    Transition::Pop,
+                                    Transition::Pop,
+                                    Transition::Replace(EditScenarioModifiers::new_state(
+                                        ctx,
+                                        scenario_name.clone(),
+                                        mods,
+                                    )),
+                                ])
+                            }),
+                        ));
+                    }
                     mods.push(ScenarioModifier::ChangeMode {
                         to_mode,
                         pct_ppl,
                         departure_filter,
                         from_modes,
+                        carpool,
+                        area: None,
                     });
                     Transition::Multi(vec![
                         Transition::Pop,
@@ -570,6 +674,95 @@ impl State<App> for ChangeMode {
     }
 }
 
+/// Lets the player draw a polygon on the map, for modifiers that only apply to some area.
+struct DrawArea {
+    panel: Panel,
+    edit: EditPolygon,
+    on_done: Box<dyn Fn(&mut EventCtx, Vec<LonLat>) -> Transition>,
+}
+
+impl DrawArea {
+    fn new_state(
+        ctx: &mut EventCtx,
+        title: &str,
+        on_done: Box<dyn Fn(&mut EventCtx, Vec<LonLat>) -> Transition>,
+    ) -> Box<dyn State<App>> {
+        // Start with a square in the middle of the screen
+        let center = ctx.canvas.center_to_map_pt();
+        let radius = Distance::meters(500.0).inner_meters();
+        let pts = vec![
+            center.offset(-radius, -radius),
+            center.offset(radius, -radius),
+            center.offset(radius, radius),
+            center.offset(-radius, radius),
+        ];
+        Box::new(DrawArea {
+            panel: Panel::new_builder(Widget::col(vec![
+                Line(title).small_heading().into_widget(ctx),
+                "Drag the points to change the area. Click the map to add points.".text_widget(ctx),
+                Widget::row(vec![
+                    ctx.style()
+                        .btn_solid_primary
+                        .text("Done")
+                        .hotkey(Key::Enter)
+                        .build_def(ctx),
+                    ctx.style()
+                        .btn_solid_destructive
+                        .text("Cancel")
+                        .hotkey(Key::Escape)
+                        .build_def(ctx),
+                ]),
+            ]))
+            .aligned(HorizontalAlignment::Center, VerticalAlignment::Top)
+            .build(ctx),
+            edit: EditPolygon::new(pts),
+            on_done,
+        })
+    }
+
+    fn get_area(&self, app: &App) -> Option<Vec<LonLat>> {
+        let mut pts: Vec<Pt2D> = self.edit.get_points().to_vec();
+        if pts.len() < 3 {
+            return None;
+        }
+        pts.push(pts[0]);
+        Ring::new(pts.clone()).ok()?;
+        Some(app.primary.map.get_gps_bounds().convert_back(&pts))
+    }
+}
+
+impl State<App> for DrawArea {
+    fn event(&mut self, ctx: &mut EventCtx, app: &mut App) -> Transition {
+        self.edit.event(ctx, app);
+
+        if let Outcome::Clicked(x) = self.panel.event(ctx) {
+            match x.as_ref() {
+                "Cancel" => {
+                    return Transition::Pop;
+                }
+                "Done" => {
+                    return match self.get_area(app) {
+                        Some(area) => (self.on_done)(ctx, area),
+                        None => Transition::Push(PopupMsg::new_state(
+                            ctx,
+                            "Error",
+                            vec!["The area must have at least 3 points and can't cross itself"],
+                        )),
+                    };
+                }
+                _ => unreachable!(),
+            }
+        }
+
+        Transition::Keep
+    }
+
+    fn draw(&self, g: &mut GfxCtx, _: &App) {
+        self.panel.draw(g);
+        self.edit.draw(g);
+    }
+}
+
 pub struct DepartureSummary {
     first_trip: Time,
 }
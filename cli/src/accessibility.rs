@@ -0,0 +1,112 @@
+use anyhow::{bail, Result};
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+use map_model::connectivity::{
+    AccessMode, Accessibility, AccessibilityOptions, Opportunity, WalkingOptions,
+};
+use map_model::{Map, MapEdits};
+
+pub fn run(
+    map_path: String,
+    edits: Option<String>,
+    modes: Vec<AccessMode>,
+    opportunities: Vec<Opportunity>,
+    minutes: usize,
+    half_life_minutes: usize,
+    departure: Option<Time>,
+    output: String,
+) -> Result<()> {
+    if !output.ends_with(".csv") && !output.ends_with(".geojson") {
+        bail!("--output must end in .csv or .geojson");
+    }
+    let mut timer = Timer::new("calculate accessibility");
+    let mut map = Map::load_synchronously(map_path, &mut timer);
+    let opts = AccessibilityOptions {
+        modes,
+        opportunities,
+        time_limit: Duration::minutes(minutes),
+        half_life: Duration::minutes(half_life_minutes),
+        walking: WalkingOptions::default(),
+        transit_departure: departure,
+    };
+
+    let mut before = None;
+    if let Some(path) = edits {
+        timer.start("before edits");
+        before = Some(Accessibility::new(&map, opts.clone(), &mut timer));
+        timer.stop("before edits");
+
+        let edits = MapEdits::load_from_file(&map, path, &mut timer)?;
+        map.must_apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+    }
+    let after = Accessibility::new(&map, opts, &mut timer);
+
+    if output.ends_with(".csv") {
+        write_csv(&after, before.as_ref(), output)
+    } else {
+        write_geojson(&map, &after, before.as_ref(), output);
+        Ok(())
+    }
+}
+
+/// One row per home, mode, and opportunity. With edits, scores from before the edits get extra
+/// columns.
+fn write_csv(after: &Accessibility, before: Option<&Accessibility>, output: String) -> Result<()> {
+    let mut out = csv::Writer::from_writer(fs_err::File::create(output)?);
+    let mut header = vec!["building", "mode", "opportunity", "count", "gravity"];
+    if before.is_some() {
+        header.extend(["count_before_edits", "gravity_before_edits"]);
+    }
+    out.write_record(&header)?;
+
+    for (b, per_mode) in &after.scores {
+        for (mode, scores) in per_mode {
+            for (opportunity, score) in after.opts.opportunities.iter().zip(scores) {
+                let mut row = vec![
+                    b.0.to_string(),
+                    mode.to_string(),
+                    opportunity.to_string(),
+                    score.cumulative.to_string(),
+                    score.gravity.to_string(),
+                ];
+                if let Some(before) = before {
+                    // Edits don't change buildings, so this should always exist
+                    let old = before.get(*b, *mode, *opportunity).unwrap_or_default();
+                    row.push(old.cumulative.to_string());
+                    row.push(old.gravity.to_string());
+                }
+                out.write_record(&row)?;
+            }
+        }
+    }
+    out.flush()?;
+    Ok(())
+}
+
+/// One polygon per home, with a property for each score, like "walk jobs count"
+fn write_geojson(map: &Map, after: &Accessibility, before: Option<&Accessibility>, output: String) {
+    let mut pairs = Vec::new();
+    for (b, per_mode) in &after.scores {
+        let mut props = serde_json::Map::new();
+        props.insert("building".to_string(), b.0.into());
+        for (mode, scores) in per_mode {
+            for (opportunity, score) in after.opts.opportunities.iter().zip(scores) {
+                let key = format!("{} {}", mode, opportunity);
+                props.insert(format!("{} count", key), score.cumulative.into());
+                props.insert(format!("{} gravity", key), score.gravity.into());
+                if let Some(before) = before {
+                    let old = before.get(*b, *mode, *opportunity).unwrap_or_default();
+                    props.insert(format!("{} count before edits", key), old.cumulative.into());
+                    props.insert(format!("{} gravity before edits", key), old.gravity.into());
+                }
+            }
+        }
+        pairs.push((
+            map.get_b(*b).polygon.to_geojson(Some(map.get_gps_bounds())),
+            props,
+        ));
+    }
+    abstio::write_json(output, &geom::geometries_with_properties_to_geojson(pairs));
+}
@@ -0,0 +1,323 @@
+//! Repeatedly simulates the same day of a scenario. Between days, people adjust their routes (and
+//! optionally their modes) based on how the previous day went, until trip times stop changing.
+//!
+//! Mode choice only switches people between driving and biking. Anybody walking or taking transit
+//! for any of their trips keeps their modes, and nobody switches to walking or transit.
+
+use std::collections::BTreeMap;
+
+use anyhow::{bail, Result};
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::Serialize;
+
+use abstutil::{prettyprint_usize, Timer};
+use geom::{Duration, Time};
+use map_model::{Map, MapEdits, MAX_BIKE_SPEED};
+use sim::{ObservedTravelTimes, Sim, SimOptions};
+use synthpop::{Scenario, TripEndpoint, TripMode};
+
+/// Only switch modes when the alternative looks at least this much faster.
+const MODE_SWITCH_MARGIN: f64 = 0.1;
+
+/// Per person, the duration of each of their trips. None means the trip was cancelled or didn't
+/// finish by the end of the simulation.
+type TripDurations = Vec<Vec<Option<Duration>>>;
+/// Per person, the road pricing fees paid on each of their trips
+type TripFees = Vec<Vec<f64>>;
+
+#[derive(Serialize)]
+struct DayMetrics {
+    day: usize,
+    finished_trips: usize,
+    unfinished_trips: usize,
+    // Use f64 seconds, since a serialized Duration has a low cap.
+    total_trip_duration_seconds: f64,
+    total_road_pricing_fees: f64,
+    trips_per_mode: BTreeMap<TripMode, usize>,
+    /// How many people changed modes after this day
+    people_switching_modes: usize,
+    /// The total change in trip durations from the previous day, relative to the previous day's
+    /// total. Only trips finishing on both days count. None for the first day, or if no trip
+    /// finished on both days.
+    gap: Option<f64>,
+}
+
+pub fn run(
+    scenario_path: String,
+    max_days: usize,
+    gap_threshold: f64,
+    mode_choice: bool,
+    edits: Option<String>,
+    output: String,
+    mut opts: SimOptions,
+    rng_seed: u64,
+) -> Result<()> {
+    if opts.skip_analytics {
+        bail!("Each day is judged by its analytics, so --skip-analytics can't be used");
+    }
+    // Routes only adapt if they're chosen using travel times from previous days
+    opts.congestion_aware_routing = true;
+
+    let mut timer = Timer::new("run scenario until equilibrium");
+    let mut scenario: Scenario = abstio::must_read_object(scenario_path, &mut timer);
+    let mut map = Map::load_synchronously(scenario.map_name.path(), &mut timer);
+    if let Some(path) = edits {
+        let edits = MapEdits::load_from_file(&map, path, &mut timer)?;
+        map.must_apply_edits(edits, &mut timer);
+        map.recalculate_pathfinding_after_edits(&mut timer);
+    }
+
+    let mut choice_rng = XorShiftRng::seed_from_u64(rng_seed);
+    let mut travel_times = ObservedTravelTimes::new();
+    let mut prev_durations: Option<TripDurations> = None;
+    let mut all_metrics = Vec::new();
+
+    for day in 1..=max_days {
+        timer.start(format!("simulate day {}", day));
+        let mut sim = Sim::new(&map, opts.clone());
+        sim.set_prior_travel_times(travel_times.clone());
+        // Every day uses the same seed, so only people's choices differ
+        sim.instantiate(
+            &scenario,
+            &map,
+            &mut XorShiftRng::seed_from_u64(rng_seed),
+            &mut timer,
+        );
+        // Run a few hours past the end of the day, so late trips can finish
+        sim.timed_step(
+            &map,
+            sim.get_end_of_day() - Time::START_OF_DAY + Duration::hours(3),
+            &mut None,
+            &mut timer,
+        );
+        timer.stop(format!("simulate day {}", day));
+
+        let durations = get_trip_durations(&sim, &scenario);
+        let fees = get_trip_fees(&sim);
+        let gap = prev_durations
+            .as_ref()
+            .and_then(|prev| relative_gap(prev, &durations));
+        // Method of successive averages: each day has a bit less influence on routing
+        travel_times = travel_times.blend(sim.get_observed_travel_times(), 1.0 / (day as f64));
+
+        let mut metrics = DayMetrics::new(day, &scenario, &durations, gap);
+        metrics.total_road_pricing_fees = fees.iter().flatten().sum();
+        if mode_choice {
+            metrics.people_switching_modes =
+                choose_modes(&mut scenario, &durations, &fees, &map, day, &mut choice_rng);
+        }
+        info!(
+            "Day {}: {} trips finished, {} didn't, gap {}. {} people switching modes",
+            day,
+            prettyprint_usize(metrics.finished_trips),
+            prettyprint_usize(metrics.unfinished_trips),
+            gap.map(|x| format!("{:.4}", x))
+                .unwrap_or_else(|| "unknown".to_string()),
+            prettyprint_usize(metrics.people_switching_modes)
+        );
+        all_metrics.push(metrics);
+        // Write after every day, so results are available while this runs
+        abstio::write_json(output.clone(), &all_metrics);
+
+        if gap.map(|x| x < gap_threshold).unwrap_or(false) {
+            info!("Converged after {} days", day);
+            break;
+        }
+        prev_durations = Some(durations);
+    }
+
+    if mode_choice {
+        scenario.scenario_name = format!("{}_equilibrium", scenario.scenario_name);
+        scenario.save();
+        info!(
+            "Wrote {}",
+            abstio::path_scenario(&scenario.map_name, &scenario.scenario_name)
+        );
+    }
+    Ok(())
+}
+
+impl DayMetrics {
+    fn new(day: usize, scenario: &Scenario, durations: &TripDurations, gap: Option<f64>) -> Self {
+        let mut metrics = DayMetrics {
+            day,
+            finished_trips: 0,
+            unfinished_trips: 0,
+            total_trip_duration_seconds: 0.0,
+            total_road_pricing_fees: 0.0,
+            trips_per_mode: BTreeMap::new(),
+            people_switching_modes: 0,
+            gap,
+        };
+        for (person, trip_durations) in scenario.people.iter().zip(durations) {
+            for (trip, maybe_duration) in person.trips.iter().zip(trip_durations) {
+                *metrics.trips_per_mode.entry(trip.mode).or_insert(0) += 1;
+                if let Some(dt) = maybe_duration {
+                    metrics.finished_trips += 1;
+                    metrics.total_trip_duration_seconds += dt.inner_seconds();
+                } else {
+                    metrics.unfinished_trips += 1;
+                }
+            }
+        }
+        metrics
+    }
+}
+
+fn get_trip_durations(sim: &Sim, scenario: &Scenario) -> TripDurations {
+    let mut finished = BTreeMap::new();
+    for (_, trip, _, maybe_duration) in &sim.get_analytics().finished_trips {
+        finished.insert(*trip, *maybe_duration);
+    }
+    // People are created in the same order as the scenario, and so are their trips
+    assert_eq!(sim.get_all_people().len(), scenario.people.len());
+    sim.get_all_people()
+        .iter()
+        .map(|person| {
+            person
+                .trips
+                .iter()
+                .map(|trip| finished.get(trip).cloned().flatten())
+                .collect()
+        })
+        .collect()
+}
+
+fn get_trip_fees(sim: &Sim) -> TripFees {
+    let fees = &sim.get_analytics().road_pricing_fees;
+    sim.get_all_people()
+        .iter()
+        .map(|person| {
+            person
+                .trips
+                .iter()
+                .map(|trip| fees.get(trip).cloned().unwrap_or(0.0))
+                .collect()
+        })
+        .collect()
+}
+
+// None if no trip finished on both days, so a gridlocked or empty scenario never looks converged.
+fn relative_gap(prev: &TripDurations, current: &TripDurations) -> Option<f64> {
+    let mut total_change = Duration::ZERO;
+    let mut total_prev = Duration::ZERO;
+    for (before, after) in prev.iter().flatten().zip(current.iter().flatten()) {
+        if let (Some(before), Some(after)) = (before, after) {
+            total_change += (*after - *before).abs();
+            total_prev += *before;
+        }
+    }
+    if total_prev == Duration::ZERO {
+        return None;
+    }
+    Some(total_change / total_prev)
+}
+
+/// Let people who only drive or only bike switch to the other mode if it looks faster. The
+/// alternative is estimated from the free-flow time, scaled by how much slower than free-flow
+/// everybody using that mode actually was. Road pricing fees count as extra time, using the map's
+/// value of time. Only some of the people who'd benefit switch each day, fewer as the days go on,
+/// to avoid everybody oscillating between modes together. Returns the number of people who
+/// switched.
+fn choose_modes(
+    scenario: &mut Scenario,
+    durations: &TripDurations,
+    fees: &TripFees,
+    map: &Map,
+    day: usize,
+    rng: &mut XorShiftRng,
+) -> usize {
+    let fee_to_time = |fee: f64| {
+        let value_of_time = map.routing_params().value_of_time;
+        if fee > 0.0 && value_of_time > 0.0 {
+            Duration::seconds(3600.0 * fee / value_of_time)
+        } else {
+            Duration::ZERO
+        }
+    };
+
+    // For each eligible person, their current mode, their actual duration, and per mode, the
+    // free-flow estimate and the fees (as time) of their trips
+    let mut candidates = Vec::new();
+    // Per mode, the total (actual duration, free-flow estimate) of everybody using it
+    let mut totals: BTreeMap<TripMode, (Duration, Duration)> = BTreeMap::new();
+    'PERSON: for (idx, ((person, trip_durations), trip_fees)) in
+        scenario.people.iter().zip(durations).zip(fees).enumerate()
+    {
+        let mode = person.trips[0].mode;
+        if !matches!(mode, TripMode::Drive | TripMode::Bike) {
+            continue;
+        }
+        let mut actual = Duration::ZERO;
+        let mut estimates: BTreeMap<TripMode, Duration> = BTreeMap::new();
+        let mut fees_as_time: BTreeMap<TripMode, Duration> = BTreeMap::new();
+        for ((trip, maybe_duration), fee) in person.trips.iter().zip(trip_durations).zip(trip_fees)
+        {
+            if trip.mode != mode || trip.carpool.is_some() || trip.cancelled {
+                continue 'PERSON;
+            }
+            let dt = match maybe_duration {
+                Some(dt) => *dt,
+                None => {
+                    continue 'PERSON;
+                }
+            };
+            actual += dt;
+            if mode == TripMode::Drive {
+                *fees_as_time.entry(mode).or_insert(Duration::ZERO) += fee_to_time(*fee);
+            }
+            for (m, max_speed) in [
+                (TripMode::Drive, None),
+                (TripMode::Bike, Some(MAX_BIKE_SPEED)),
+            ] {
+                let path = match TripEndpoint::path_req(trip.origin, trip.destination, m, map)
+                    .and_then(|req| map.pathfind(req).ok())
+                {
+                    Some(path) => path,
+                    None => {
+                        continue 'PERSON;
+                    }
+                };
+                *estimates.entry(m).or_insert(Duration::ZERO) +=
+                    path.estimate_duration(map, max_speed);
+                // People driving already know what they paid
+                if m == TripMode::Drive && mode != TripMode::Drive {
+                    let fee = map.get_road_pricing().path_fee(&path, trip.depart, map);
+                    *fees_as_time.entry(m).or_insert(Duration::ZERO) += fee_to_time(fee);
+                }
+            }
+        }
+        let total = totals
+            .entry(mode)
+            .or_insert((Duration::ZERO, Duration::ZERO));
+        total.0 += actual;
+        total.1 += estimates[&mode];
+        candidates.push((idx, mode, actual, estimates, fees_as_time));
+    }
+
+    let slowdown = |mode: TripMode| match totals.get(&mode) {
+        Some((actual, free_flow)) if *free_flow > Duration::ZERO => (*actual / *free_flow).max(1.0),
+        _ => 1.0,
+    };
+    let switch_probability = 1.0 / ((day + 1) as f64);
+    let mut num_switched = 0;
+    for (idx, mode, actual, estimates, fees_as_time) in candidates {
+        let other_mode = if mode == TripMode::Drive {
+            TripMode::Bike
+        } else {
+            TripMode::Drive
+        };
+        let fee = |m: TripMode| fees_as_time.get(&m).cloned().unwrap_or(Duration::ZERO);
+        let current = actual + fee(mode);
+        let alternative = slowdown(other_mode) * estimates[&other_mode] + fee(other_mode);
+        if alternative < (1.0 - MODE_SWITCH_MARGIN) * current && rng.gen_bool(switch_probability) {
+            for trip in &mut scenario.people[idx].trips {
+                trip.mode = other_mode;
+                trip.modified = true;
+            }
+            num_switched += 1;
+        }
+    }
+    num_switched
+}
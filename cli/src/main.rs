@@ -4,8 +4,9 @@
 #[macro_use]
 extern crate log;
 
+mod accessibility;
 mod augment_scenario;
-mod clip_osm;
+mod equilibrium;
 mod generate_houses;
 mod import_grid2demand;
 mod import_scenario;
@@ -69,7 +70,7 @@ enum Command {
     /// Clips an OSM file to a boundary. This is a simple Rust port of `osmium extract large_map.osm
     /// -p clipping.poly -o smaller_map.osm`.
     ClipOSM {
-        /// The path to the input .osm.pbf file
+        /// The path to the input .osm.pbf or .osm file
         #[structopt(long)]
         pbf_path: String,
         /// The path to a GeoJSON file with one boundary polygon
@@ -212,6 +213,95 @@ enum Command {
         /// The path to a scenario file
         #[structopt()]
         scenario_path: String,
+        /// A JSON list of modifiers to transform the scenario before simulating it. These can be
+        /// generated with the GUI.
+        #[structopt(long, parse(try_from_str = parse_modifiers), default_value = "[]")]
+        scenario_modifiers: ModifierList,
+    },
+    /// Simulate the same day of a scenario repeatedly. After each day, people pick routes using
+    /// the travel times seen so far, and optionally switch between driving and biking. Stops once
+    /// trip times barely change from one day to the next.
+    RunEquilibrium {
+        /// The path to a scenario file
+        #[structopt()]
+        scenario_path: String,
+        /// Stop after this many days, even if trip times haven't converged
+        #[structopt(long, default_value = "10")]
+        max_days: usize,
+        /// Stop once the total change in trip times from the previous day, relative to that
+        /// day's total, drops below this
+        #[structopt(long, default_value = "0.01")]
+        gap_threshold: f64,
+        /// Let people who only drive or only bike switch between those two modes between days.
+        /// Walking and transit trips never change. The final scenario is saved with an
+        /// "_equilibrium" suffix.
+        #[structopt(long)]
+        mode_choice: bool,
+        /// The path to map edits to apply first, like road pricing
+        #[structopt(long)]
+        edits: Option<String>,
+        /// The path to write per-day metrics as JSON
+        #[structopt(long, default_value = "equilibrium.json")]
+        output: String,
+        /// A seed for generating random numbers
+        #[structopt(long, default_value = "42")]
+        rng_seed: u64,
+        #[structopt(flatten)]
+        opts: sim::SimOptions,
+    },
+    /// For every home in a map, count the jobs or amenities reachable within some time by
+    /// walking, biking, transit, and driving, along with gravity-weighted scores. Pass edits to
+    /// compare scores before and after them.
+    Accessibility {
+        /// The path to a map
+        #[structopt()]
+        map: String,
+        /// The path to map edits. If specified, scores from before and after the edits are both
+        /// written.
+        #[structopt(long)]
+        edits: Option<String>,
+        /// A comma-separated list of walk, bike, transit, and car
+        #[structopt(long, use_delimiter = true, default_value = "walk,bike,transit,car")]
+        modes: Vec<map_model::connectivity::AccessMode>,
+        /// A comma-separated list of jobs or amenity types, like Supermarket or School
+        #[structopt(long, use_delimiter = true, default_value = "jobs")]
+        opportunities: Vec<map_model::connectivity::Opportunity>,
+        /// Count opportunities reachable within this many minutes
+        #[structopt(long, default_value = "15")]
+        minutes: usize,
+        /// For gravity scores, an opportunity counts half as much for every additional this many
+        /// minutes it takes to reach
+        #[structopt(long, default_value = "10")]
+        half_life_minutes: usize,
+        /// Leave at this time, like 08:00:00, and wait for scheduled transit vehicles. If omitted,
+        /// transit trips wait the average time between vehicles.
+        #[structopt(long, parse(try_from_str = geom::Time::parse))]
+        departure: Option<geom::Time>,
+        /// The path to write, ending in .csv or .geojson
+        #[structopt(long, default_value = "accessibility.csv")]
+        output: String,
+    },
+    /// Adds a congestion charge for driving into an area to some map edits. Every road with its
+    /// center inside the boundary is part of the area.
+    AddCordon {
+        /// The path to a map
+        #[structopt()]
+        map: String,
+        /// The path to a GeoJSON file with one boundary polygon
+        #[structopt(long)]
+        boundary: String,
+        /// What to call the charged area
+        #[structopt(long)]
+        name: String,
+        /// The fee for driving into the area, in the local currency
+        #[structopt(long)]
+        fee: f64,
+        /// The path to existing map edits to add the cordon to
+        #[structopt(long)]
+        edits: Option<String>,
+        /// The path to write the new edits
+        #[structopt(long, default_value = "cordon.json")]
+        output: String,
     },
 }
 
@@ -262,7 +352,7 @@ async fn main() -> Result<()> {
             pbf_path,
             clip_path,
             out_path,
-        } => clip_osm::run(pbf_path, clip_path, out_path)?,
+        } => importer::clip_osm(&pbf_path, &clip_path, &out_path)?,
         Command::ImportGrid2Demand { input, map } => import_grid2demand::run(input, map)?,
         Command::ImportScenario {
             input,
@@ -322,7 +412,56 @@ async fn main() -> Result<()> {
         } => importer::regenerate_everything(shard_num, num_shards).await,
         Command::RegenerateEverythingExternally => regenerate_everything_externally()?,
         Command::Import { job } => job.run(&mut Timer::new("import one city")).await,
-        Command::PrebakeScenario { scenario_path } => prebake_scenario(scenario_path),
+        Command::PrebakeScenario {
+            scenario_path,
+            scenario_modifiers,
+        } => prebake_scenario(scenario_path, scenario_modifiers),
+        Command::RunEquilibrium {
+            scenario_path,
+            max_days,
+            gap_threshold,
+            mode_choice,
+            edits,
+            output,
+            rng_seed,
+            opts,
+        } => equilibrium::run(
+            scenario_path,
+            max_days,
+            gap_threshold,
+            mode_choice,
+            edits,
+            output,
+            opts,
+            rng_seed,
+        )?,
+        Command::Accessibility {
+            map,
+            edits,
+            modes,
+            opportunities,
+            minutes,
+            half_life_minutes,
+            departure,
+            output,
+        } => accessibility::run(
+            map,
+            edits,
+            modes,
+            opportunities,
+            minutes,
+            half_life_minutes,
+            departure,
+            output,
+        )?,
+        Command::AddCordon {
+            map,
+            boundary,
+            name,
+            fee,
+            edits,
+            output,
+        } => add_cordon(map, boundary, name, fee, edits, output)?,
     }
     Ok(())
 }
@@ -423,11 +562,51 @@ fn regenerate_everything_externally() -> Result<()> {
     Ok(())
 }
 
-fn prebake_scenario(path: String) {
+fn prebake_scenario(path: String, modifiers: ModifierList) {
     let mut timer = Timer::new("prebake scenario");
     let scenario: synthpop::Scenario = abstio::must_read_object(path, &mut timer);
     let map = map_model::Map::load_synchronously(scenario.map_name.path(), &mut timer);
-    sim::prebake::prebake(&map, scenario, &mut timer);
+    sim::prebake::prebake_with_modifiers(&map, scenario, &modifiers, &mut timer);
+}
+
+fn add_cordon(
+    map_path: String,
+    boundary: String,
+    name: String,
+    fee: f64,
+    edits_path: Option<String>,
+    output: String,
+) -> Result<()> {
+    let mut timer = Timer::new("add cordon");
+    let mut map = map_model::Map::load_synchronously(map_path, &mut timer);
+    let mut edits = if let Some(path) = edits_path {
+        map_model::MapEdits::load_from_file(&map, path, &mut timer)?
+    } else {
+        let mut edits = map.new_edits();
+        edits.edits_name = abstutil::basename(&output);
+        edits
+    };
+    map.must_apply_edits(edits.clone(), &mut timer);
+
+    let pts = geom::LonLat::read_geojson_polygon(&boundary)?;
+    let polygon = geom::Ring::new(map.get_gps_bounds().convert(&pts))?.into_polygon();
+    let cordon =
+        map_model::Cordon::from_polygon(name, &polygon, map_model::Charge::flat(fee), &map);
+    if cordon.roads.is_empty() {
+        anyhow::bail!("No roads are inside {}", boundary);
+    }
+    info!("{} covers {} roads", cordon.name, cordon.roads.len());
+
+    let mut pricing = map.get_road_pricing().clone();
+    pricing.cordons.push(cordon);
+    edits.commands.push(map_model::EditCmd::ChangeRoadPricing {
+        old: map.get_road_pricing().clone(),
+        new: pricing,
+    });
+    map.must_apply_edits(edits, &mut timer);
+    abstio::write_json(output.clone(), &map.get_edits().to_permanent(&map));
+    info!("Wrote {}", output);
+    Ok(())
 }
 
 fn driving_side(drive_on_left: bool) -> map_model::DrivingSide {
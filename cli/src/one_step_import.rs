@@ -57,7 +57,7 @@ pub async fn run(
 
         // Clip it
         println!("Clipping osm.pbf file to your boundary");
-        crate::clip_osm::run(pbf, geojson_path.clone(), osm.clone())?;
+        importer::clip_osm(&pbf, &geojson_path, &osm)?;
     }
 
     // Import!
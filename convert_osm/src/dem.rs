@@ -0,0 +1,306 @@
+//! Reads digital elevation models (DEMs) from local raster files, so elevation data can be added
+//! without running anything external. Two formats are supported:
+//!
+//! - SRTM `.hgt` tiles, named by their southwest corner, like `N47W123.hgt`
+//! - GeoTIFFs using longitude and latitude (like EPSG:4326), with one band of elevations in meters
+//!
+//! Rasters in a projected coordinate system are skipped.
+
+use std::io::{BufReader, Read};
+
+use anyhow::Result;
+use fs_err::File;
+
+use geom::{GPSBounds, LonLat};
+
+/// Every local raster covering part of the map
+pub struct Rasters {
+    rasters: Vec<Raster>,
+}
+
+impl Rasters {
+    /// Load every raster in a directory that overlaps the bounds. Returns None if there are none.
+    pub fn load(dir: &str, bounds: &GPSBounds) -> Result<Option<Rasters>> {
+        let mut rasters = Vec::new();
+        if !fs_err::metadata(dir).map(|m| m.is_dir()).unwrap_or(false) {
+            return Ok(None);
+        }
+        let mut paths = Vec::new();
+        for entry in fs_err::read_dir(dir)? {
+            paths.push(entry?.path());
+        }
+        // Be deterministic about which raster wins when several overlap
+        paths.sort();
+        for path in paths {
+            let ext = path
+                .extension()
+                .and_then(|x| x.to_str())
+                .map(|x| x.to_lowercase());
+            let result = match ext.as_deref() {
+                Some("hgt") => Raster::load_hgt(&path.display().to_string(), bounds),
+                Some("tif") | Some("tiff") => {
+                    Raster::load_geotiff(&path.display().to_string(), bounds)
+                }
+                _ => continue,
+            };
+            match result {
+                Ok(Some(raster)) => rasters.push(raster),
+                Ok(None) => {}
+                Err(err) => {
+                    warn!("Skipping {}: {}", path.display(), err);
+                }
+            }
+        }
+        if rasters.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(Rasters { rasters }))
+    }
+
+    /// The elevation in meters at some point, from the first raster that covers it
+    pub fn get(&self, pt: LonLat) -> Option<f64> {
+        self.rasters.iter().find_map(|r| r.get(pt))
+    }
+}
+
+/// A grid of elevation samples, evenly spaced in longitude and latitude
+struct Raster {
+    width: usize,
+    height: usize,
+    /// Row-major, starting from the north-west. NaN means no data.
+    values: Vec<f32>,
+    /// The position of the sample in the first row and column
+    west: f64,
+    north: f64,
+    /// Degrees between samples
+    dx: f64,
+    dy: f64,
+}
+
+impl Raster {
+    fn load_hgt(path: &str, bounds: &GPSBounds) -> Result<Option<Raster>> {
+        let (south, west) = parse_hgt_name(path)?;
+        if !overlaps(bounds, west, south, west + 1.0, south + 1.0) {
+            return Ok(None);
+        }
+        let mut bytes = Vec::new();
+        BufReader::new(File::open(path)?).read_to_end(&mut bytes)?;
+        Raster::from_hgt(&bytes, south, west).map(Some)
+    }
+
+    /// The tile is a square of big-endian 16-bit samples. The edges overlap with neighboring
+    /// tiles, so a 1 arc-second tile has 3601 samples per side.
+    fn from_hgt(bytes: &[u8], south: f64, west: f64) -> Result<Raster> {
+        let size = ((bytes.len() / 2) as f64).sqrt() as usize;
+        if size < 2 || size * size * 2 != bytes.len() {
+            bail!("{} bytes isn't a square HGT tile", bytes.len());
+        }
+        let values = bytes
+            .chunks_exact(2)
+            .map(|pair| {
+                let x = i16::from_be_bytes([pair[0], pair[1]]);
+                // Voids
+                if x == i16::MIN {
+                    f32::NAN
+                } else {
+                    x as f32
+                }
+            })
+            .collect();
+        let step = 1.0 / (size - 1) as f64;
+        Ok(Raster {
+            width: size,
+            height: size,
+            values,
+            west,
+            north: south + 1.0,
+            dx: step,
+            dy: step,
+        })
+    }
+
+    fn load_geotiff(path: &str, bounds: &GPSBounds) -> Result<Option<Raster>> {
+        use tiff::decoder::{Decoder, DecodingResult};
+        use tiff::tags::Tag;
+
+        let mut decoder = Decoder::new(BufReader::new(File::open(path)?))?;
+        let (width, height) = decoder.dimensions()?;
+        let (width, height) = (width as usize, height as usize);
+
+        // See http://docs.opengeospatial.org/is/19-008r4/19-008r4.html for these tags
+        let mut pixel_is_point = false;
+        if let Ok(keys) = decoder.get_tag_u16_vec(Tag::GeoKeyDirectoryTag) {
+            // A header of 4 numbers, then (key, location, count, value) for each key
+            for key in keys.chunks_exact(4).skip(1) {
+                match (key[0], key[1], key[3]) {
+                    // GTModelTypeGeoKey, with a value of ModelTypeProjected
+                    (1024, 0, 1) => {
+                        bail!("it uses a projected coordinate system, not longitude and latitude");
+                    }
+                    // GTRasterTypeGeoKey, with a value of RasterPixelIsPoint
+                    (1025, 0, 2) => {
+                        pixel_is_point = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+        let scale = decoder.get_tag_f64_vec(Tag::ModelPixelScaleTag)?;
+        let tiepoint = decoder.get_tag_f64_vec(Tag::ModelTiepointTag)?;
+        if scale.len() < 2 || tiepoint.len() < 6 {
+            bail!("it's missing georeferencing");
+        }
+        let (dx, dy) = (scale[0], scale[1]);
+        // The tiepoint links a raster position (i, j) to a longitude and latitude. By default,
+        // that position is the corner of a pixel, but we want the center.
+        let offset = if pixel_is_point { 0.0 } else { 0.5 };
+        let west = tiepoint[3] + (offset - tiepoint[0]) * dx;
+        let north = tiepoint[4] - (offset - tiepoint[1]) * dy;
+        let east = west + (width - 1) as f64 * dx;
+        let south = north - (height - 1) as f64 * dy;
+        if !overlaps(bounds, west, south, east, north) {
+            return Ok(None);
+        }
+
+        let nodata = decoder
+            .get_tag_ascii_string(Tag::GdalNodata)
+            .ok()
+            .and_then(|x| x.trim_matches(char::from(0)).trim().parse::<f32>().ok());
+        let values: Vec<f32> = match decoder.read_image()? {
+            DecodingResult::U8(x) => x.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::U16(x) => x.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::U32(x) => x.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I8(x) => x.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I16(x) => x.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::I32(x) => x.into_iter().map(|x| x as f32).collect(),
+            DecodingResult::F32(x) => x,
+            DecodingResult::F64(x) => x.into_iter().map(|x| x as f32).collect(),
+            _ => bail!("it has an unsupported sample type"),
+        };
+        if values.len() != width * height {
+            bail!("it has more than one band");
+        }
+        let values = values
+            .into_iter()
+            .map(|x| if Some(x) == nodata { f32::NAN } else { x })
+            .collect();
+
+        Ok(Some(Raster {
+            width,
+            height,
+            values,
+            west,
+            north,
+            dx,
+            dy,
+        }))
+    }
+
+    /// Bilinearly interpolates between the four nearest samples. None if the point is outside the
+    /// raster or any of those samples are missing.
+    fn get(&self, pt: LonLat) -> Option<f64> {
+        let col = (pt.x() - self.west) / self.dx;
+        let row = (self.north - pt.y()) / self.dy;
+        if col < 0.0 || row < 0.0 {
+            return None;
+        }
+        let (col0, row0) = (col.floor() as usize, row.floor() as usize);
+        if col0 >= self.width || row0 >= self.height {
+            return None;
+        }
+        // On the last row or column, there's nothing further to interpolate with
+        let col1 = (col0 + 1).min(self.width - 1);
+        let row1 = (row0 + 1).min(self.height - 1);
+        let (fx, fy) = (col - col0 as f64, row - row0 as f64);
+
+        let mut value = 0.0;
+        for (c, r, weight) in [
+            (col0, row0, (1.0 - fx) * (1.0 - fy)),
+            (col1, row0, fx * (1.0 - fy)),
+            (col0, row1, (1.0 - fx) * fy),
+            (col1, row1, fx * fy),
+        ] {
+            // Exactly on a sample, a missing neighbor doesn't matter
+            if weight > 0.0 {
+                value += weight * self.values[r * self.width + c] as f64;
+            }
+        }
+        if value.is_finite() {
+            Some(value)
+        } else {
+            None
+        }
+    }
+}
+
+/// Returns the (latitude, longitude) of the southwest corner
+fn parse_hgt_name(path: &str) -> Result<(f64, f64)> {
+    let name = std::path::Path::new(path)
+        .file_stem()
+        .and_then(|x| x.to_str())
+        .unwrap_or("")
+        .to_uppercase();
+    // Like N47W123
+    if name.len() != 7 {
+        bail!("{} isn't named like N47W123.hgt", path);
+    }
+    let lat_sign = match &name[0..1] {
+        "N" => 1.0,
+        "S" => -1.0,
+        _ => bail!("{} isn't named like N47W123.hgt", path),
+    };
+    let lon_sign = match &name[3..4] {
+        "E" => 1.0,
+        "W" => -1.0,
+        _ => bail!("{} isn't named like N47W123.hgt", path),
+    };
+    let lat = name[1..3].parse::<f64>()?;
+    let lon = name[4..7].parse::<f64>()?;
+    Ok((lat_sign * lat, lon_sign * lon))
+}
+
+fn overlaps(bounds: &GPSBounds, west: f64, south: f64, east: f64, north: f64) -> bool {
+    west <= bounds.max_lon
+        && east >= bounds.min_lon
+        && south <= bounds.max_lat
+        && north >= bounds.min_lat
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hgt_interpolation() {
+        // A 3x3 tile, rising by 10 meters per sample to the east, with a void in one corner
+        let mut bytes = Vec::new();
+        for row in 0..3 {
+            for col in 0..3 {
+                let x: i16 = if row == 2 && col == 2 {
+                    i16::MIN
+                } else {
+                    10 * col
+                };
+                bytes.extend(x.to_be_bytes());
+            }
+        }
+        let raster = Raster::from_hgt(&bytes, 47.0, -123.0).unwrap();
+
+        // Exactly on samples
+        assert_eq!(raster.get(LonLat::new(-123.0, 48.0)), Some(0.0));
+        assert_eq!(raster.get(LonLat::new(-122.0, 48.0)), Some(20.0));
+        // Between samples
+        assert_eq!(raster.get(LonLat::new(-122.75, 47.75)), Some(5.0));
+        // Next to the void
+        assert_eq!(raster.get(LonLat::new(-122.25, 47.25)), None);
+        // Off the tile
+        assert_eq!(raster.get(LonLat::new(-123.5, 47.5)), None);
+    }
+
+    #[test]
+    fn hgt_names() {
+        assert_eq!(parse_hgt_name("data/N47W123.hgt").unwrap(), (47.0, -123.0));
+        assert_eq!(parse_hgt_name("s34e151.HGT").unwrap(), (-34.0, 151.0));
+        assert!(parse_hgt_name("elevation.hgt").is_err());
+    }
+}
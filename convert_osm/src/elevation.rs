@@ -4,11 +4,66 @@ use std::process::Command;
 use anyhow::Result;
 use fs_err::File;
 
-use geom::{Distance, PolyLine};
+use geom::{Distance, LonLat, PolyLine, Pt2D};
 use osm2streets::OriginalRoad;
 use raw_map::RawMap;
 
+use crate::dem::Rasters;
+
+/// Look up the elevation of every intersection, then calculate the incline of every road. Local
+/// DEMs in `data/input/shared/elevation` are used if they cover the whole map. Otherwise, this
+/// falls back to the `elevation_lookups` Docker image, which downloads data sources itself.
 pub fn add_data(map: &mut RawMap) -> Result<()> {
+    match Rasters::load(
+        &abstio::path_shared_input("elevation"),
+        &map.streets.gps_bounds,
+    ) {
+        Ok(Some(rasters)) => match add_data_from_rasters(map, &rasters) {
+            Ok(()) => {
+                return Ok(());
+            }
+            Err(err) => {
+                warn!("{}, so using Docker for elevation data", err);
+            }
+        },
+        Ok(None) => {
+            info!("No local DEM covers the map, so using Docker for elevation data");
+        }
+        Err(err) => {
+            warn!("Couldn't read local DEMs ({}), so using Docker", err);
+        }
+    }
+    add_data_with_docker(map)
+}
+
+fn add_data_from_rasters(map: &mut RawMap, rasters: &Rasters) -> Result<()> {
+    let mut missing = 0;
+    let mut elevations = Vec::new();
+    for (id, r) in &map.streets.roads {
+        if let Some(pts) = sample_points(map, &r.osm_center_points) {
+            let values: Vec<f64> = pts.into_iter().filter_map(|pt| rasters.get(pt)).collect();
+            // If the ends aren't covered, use the closest sample along the road
+            if let (Some(first), Some(last)) = (values.first(), values.last()) {
+                elevations.push((*id, *first, *last));
+            } else {
+                missing += 1;
+            }
+        }
+    }
+    // Don't mix in roads with unknown elevation; they'd get very wrong inclines
+    if missing > 0 {
+        bail!("Local DEMs don't cover {} roads", missing);
+    }
+
+    for (id, start, end) in elevations {
+        map.streets.intersections.get_mut(&id.i1).unwrap().elevation = Distance::meters(start);
+        map.streets.intersections.get_mut(&id.i2).unwrap().elevation = Distance::meters(end);
+    }
+    calculate_inclines(map);
+    Ok(())
+}
+
+fn add_data_with_docker(map: &mut RawMap) -> Result<()> {
     let input = format!("elevation_input_{}", map.name.as_filename());
     let output = format!("elevation_output_{}", map.name.as_filename());
 
@@ -73,25 +128,9 @@ fn generate_input(input: &str, map: &RawMap) -> Result<Vec<OriginalRoad>> {
     let mut f = BufWriter::new(File::create(format!("{input}/query"))?);
     let mut ids = Vec::new();
     for (id, r) in &map.streets.roads {
-        // TODO Handle cul-de-sacs
-        if let Ok(pl) = PolyLine::new(r.osm_center_points.clone()) {
+        if let Some(pts) = sample_points(map, &r.osm_center_points) {
             ids.push(*id);
-            // Sample points along the road. Smaller step size gives more detail, but is slower.
-            let mut pts = Vec::new();
-            for (pt, _) in pl.step_along(Distance::meters(5.0), Distance::ZERO) {
-                pts.push(pt);
-            }
-            // Always ask for the intersection
-            if *pts.last().unwrap() != pl.last_pt() {
-                pts.push(pl.last_pt());
-            }
-            for (idx, gps) in map
-                .streets
-                .gps_bounds
-                .convert_back(&pts)
-                .into_iter()
-                .enumerate()
-            {
+            for (idx, gps) in pts.iter().enumerate() {
                 write!(f, "{},{}", gps.x(), gps.y())?;
                 if idx != pts.len() - 1 {
                     write!(f, " ")?;
@@ -103,6 +142,22 @@ fn generate_input(input: &str, map: &RawMap) -> Result<Vec<OriginalRoad>> {
     Ok(ids)
 }
 
+/// Points along a road's center line to look up, always including both ends
+fn sample_points(map: &RawMap, center_pts: &[Pt2D]) -> Option<Vec<LonLat>> {
+    // TODO Handle cul-de-sacs
+    let pl = PolyLine::new(center_pts.to_vec()).ok()?;
+    // Smaller step size gives more detail, but is slower.
+    let mut pts = Vec::new();
+    for (pt, _) in pl.step_along(Distance::meters(5.0), Distance::ZERO) {
+        pts.push(pt);
+    }
+    // Always ask for the intersection
+    if *pts.last().unwrap() != pl.last_pt() {
+        pts.push(pl.last_pt());
+    }
+    Some(map.streets.gps_bounds.convert_back(&pts))
+}
+
 fn scrape_output(output: &str, map: &mut RawMap, ids: Vec<OriginalRoad>) -> Result<()> {
     let num_ids = ids.len();
     let mut cnt = 0;
@@ -141,6 +196,11 @@ fn scrape_output(output: &str, map: &mut RawMap, ids: Vec<OriginalRoad>) -> Resu
         bail!("Output had {} lines, but we made {} queries", cnt, num_ids);
     }
 
+    calculate_inclines(map);
+    Ok(())
+}
+
+fn calculate_inclines(map: &mut RawMap) {
     // Calculate the incline for each road here, before the road gets trimmed for intersection
     // geometry. If we did this after trimming, we'd miss some of the horizontal distance.
     for (id, road) in &mut map.streets.roads {
@@ -163,6 +223,4 @@ fn scrape_output(output: &str, map: &mut RawMap, ids: Vec<OriginalRoad>) -> Resu
             );
         }
     }
-
-    Ok(())
 }
@@ -1,15 +1,41 @@
 use std::collections::{BTreeMap, HashMap, HashSet};
 
-use anyhow::Result;
+use anyhow::{bail, Result};
+use chrono::{Datelike, NaiveDate, Weekday};
 use fs_err::File;
 use serde::Deserialize;
 
 use abstutil::MultiMap;
-use geom::{LonLat, PolyLine, Pt2D};
+use geom::{Duration, LonLat, PolyLine, Pt2D, Time};
 use kml::{ExtraShape, ExtraShapes};
 use raw_map::{RawMap, RawTransitRoute, RawTransitStop, RawTransitType};
 
-pub fn import(map: &mut RawMap) -> Result<()> {
+/// Which day's schedule to import from a GTFS feed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ServiceDay {
+    /// Services running on exactly this date, including exceptions from calendar_dates.txt.
+    Date(NaiveDate),
+    /// A representative day of the week: the first date with this weekday covered by the feed.
+    Weekday(Weekday),
+}
+
+impl std::str::FromStr for ServiceDay {
+    type Err = anyhow::Error;
+
+    /// Parses a date like `2021-06-02` or a day of the week like `wed`.
+    fn from_str(x: &str) -> Result<ServiceDay> {
+        if let Ok(date) = NaiveDate::parse_from_str(x, "%Y-%m-%d") {
+            return Ok(ServiceDay::Date(date));
+        }
+        match x.parse::<Weekday>() {
+            Ok(weekday) => Ok(ServiceDay::Weekday(weekday)),
+            Err(_) => bail!("{} isn't a YYYY-MM-DD date or a day of the week", x),
+        }
+    }
+}
+
+/// Imports transit routes and stops, along with the schedule of every trip running on one day.
+pub fn import(map: &mut RawMap, service_day: ServiceDay) -> Result<()> {
     // Collect metadata about routes
     for rec in csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/routes.txt"))?)
         .deserialize()
@@ -37,14 +63,25 @@ pub fn import(map: &mut RawMap) -> Result<()> {
         });
     }
 
+    // Which services run on the chosen day? If there's no calendar, assume all of them.
+    let active_services = read_active_services(map, service_day)?;
+
     // Map route_id to shape_id
     let mut route_to_shapes = MultiMap::new();
     // Map (route_id, shape_id) to trip_id
     let mut route_and_shape_to_trips = MultiMap::new();
+    let mut active_trips = HashSet::new();
     for rec in csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/trips.txt"))?)
         .deserialize()
     {
         let rec: Trip = rec?;
+        if active_services
+            .as_ref()
+            .map(|services| services.contains(&rec.service_id))
+            .unwrap_or(true)
+        {
+            active_trips.insert(rec.trip_id.clone());
+        }
         route_to_shapes.insert(rec.route_id.clone(), rec.shape_id.clone());
         route_and_shape_to_trips.insert((rec.route_id, rec.shape_id), rec.trip_id);
     }
@@ -105,38 +142,86 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     }
     map.transit_routes = transit_routes;
 
-    // For now, every route uses exactly one trip ID, and there's no schedule. Just pick an
-    // arbitrary trip per route.
+    // Every route uses exactly one sequence of stops. Pick an arbitrary trip per route to
+    // define it, preferring one that runs on the service day.
     let mut route_to_trip = HashMap::new();
     for (route_id, shape_id) in &route_to_shape {
         let trips = route_and_shape_to_trips.get((route_id.clone(), shape_id.clone()));
-        if let Some(trip_id) = trips.into_iter().next() {
+        if let Some(trip_id) = trips
+            .iter()
+            .find(|trip_id| active_trips.contains(*trip_id))
+            .or_else(|| trips.iter().next())
+        {
             route_to_trip.insert(route_id.clone(), trip_id);
         }
     }
 
-    // Scrape the trip ID -> (stop ID, sequence number)
-    let mut trip_to_stops: HashMap<TripID, Vec<(StopID, usize)>> = HashMap::new();
+    // Scrape the trip ID -> (stop ID, sequence number, arrival time)
+    let mut trip_to_stops: HashMap<TripID, Vec<(StopID, usize, Option<Time>)>> = HashMap::new();
     for rec in
         csv::Reader::from_reader(File::open(map.name.city.input_path("gtfs/stop_times.txt"))?)
             .deserialize()
     {
         let rec: StopTime = rec?;
+        // Stops that aren't timepoints may leave both of these blank
+        let time = if !rec.arrival_time.is_empty() {
+            Some(Time::parse(&rec.arrival_time)?)
+        } else if !rec.departure_time.is_empty() {
+            Some(Time::parse(&rec.departure_time)?)
+        } else {
+            None
+        };
         trip_to_stops
             .entry(rec.trip_id)
             .or_insert_with(Vec::new)
-            .push((rec.stop_id, rec.stop_sequence));
+            .push((rec.stop_id, rec.stop_sequence, time));
+    }
+    for stops in trip_to_stops.values_mut() {
+        stops.sort_by_key(|(_, seq, _)| *seq);
     }
 
-    // Assign the stops for every route
+    // Assign the stops and schedule for every route
     let mut stop_ids = HashSet::new();
     for route in &mut map.transit_routes {
-        let trip_id = route_to_trip[&RouteID(route.gtfs_id.clone())];
-        let mut stops = trip_to_stops.remove(&trip_id).unwrap_or_else(Vec::new);
-        stops.sort_by_key(|(_, seq)| *seq);
-        for (stop_id, _) in stops {
+        let route_id = RouteID(route.gtfs_id.clone());
+        let trip_id = route_to_trip[&route_id];
+        for (stop_id, _, _) in trip_to_stops.get(trip_id).unwrap_or(&Vec::new()) {
             route.stops.push(stop_id.0.clone());
-            stop_ids.insert(stop_id);
+            stop_ids.insert(stop_id.clone());
+        }
+
+        // Only trips following exactly the same stops contribute to the schedule
+        let mut stop_times = Vec::new();
+        for trip_id in
+            route_and_shape_to_trips.get((route_id.clone(), route_to_shape[&route_id].clone()))
+        {
+            if !active_trips.contains(trip_id) {
+                continue;
+            }
+            let stops = if let Some(stops) = trip_to_stops.get(trip_id) {
+                stops
+            } else {
+                continue;
+            };
+            if stops.len() != route.stops.len()
+                || stops
+                    .iter()
+                    .zip(route.stops.iter())
+                    .any(|((stop_id, _, _), expected)| stop_id.0 != *expected)
+            {
+                continue;
+            }
+            if let Some(times) = interpolate_times(stops.iter().map(|(_, _, t)| *t).collect()) {
+                // Trips starting after midnight belong to the next service day
+                if times[0] < Time::START_OF_DAY + Duration::hours(24) {
+                    stop_times.push(times);
+                }
+            }
+        }
+        if !stop_times.is_empty() {
+            stop_times.sort();
+            map.transit_stop_times
+                .insert(route.gtfs_id.clone(), stop_times);
         }
     }
 
@@ -163,12 +248,32 @@ pub fn import(map: &mut RawMap) -> Result<()> {
     // Make sure all of the stops are valid and used by some route
     let mut used_stops = HashSet::new();
     for route in &mut map.transit_routes {
-        route.stops.retain(|stop_id| {
-            used_stops.insert(stop_id.clone());
-            map.transit_stops.contains_key(stop_id)
-        });
+        let keep: Vec<bool> = route
+            .stops
+            .iter()
+            .map(|stop_id| {
+                used_stops.insert(stop_id.clone());
+                map.transit_stops.contains_key(stop_id)
+            })
+            .collect();
+        retain_by_idx(&mut route.stops, &keep);
+        for times in map
+            .transit_stop_times
+            .get_mut(&route.gtfs_id)
+            .into_iter()
+            .flatten()
+        {
+            retain_by_idx(times, &keep);
+        }
     }
     map.transit_routes.retain(|route| !route.stops.is_empty());
+    let route_ids: HashSet<String> = map
+        .transit_routes
+        .iter()
+        .map(|route| route.gtfs_id.clone())
+        .collect();
+    map.transit_stop_times
+        .retain(|route_id, _| route_ids.contains(route_id));
     map.transit_stops
         .retain(|stop_id, _| used_stops.contains(stop_id));
 
@@ -187,6 +292,8 @@ struct TripID(String);
 struct StopID(String);
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
 struct RouteID(String);
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize)]
+struct ServiceID(String);
 
 #[derive(Deserialize)]
 struct Route {
@@ -204,6 +311,7 @@ struct Trip {
     route_id: RouteID,
     shape_id: ShapeID,
     trip_id: TripID,
+    service_id: ServiceID,
 }
 
 #[derive(Deserialize)]
@@ -227,6 +335,152 @@ struct StopTime {
     trip_id: TripID,
     stop_id: StopID,
     stop_sequence: usize,
+    // These may exceed 24 hours for trips continuing past midnight
+    arrival_time: String,
+    departure_time: String,
+}
+
+#[derive(Deserialize)]
+struct Calendar {
+    service_id: ServiceID,
+    monday: u8,
+    tuesday: u8,
+    wednesday: u8,
+    thursday: u8,
+    friday: u8,
+    saturday: u8,
+    sunday: u8,
+    start_date: String,
+    end_date: String,
+}
+
+impl Calendar {
+    fn runs_on(&self, date: NaiveDate) -> Result<bool> {
+        if date < parse_date(&self.start_date)? || date > parse_date(&self.end_date)? {
+            return Ok(false);
+        }
+        let flag = match date.weekday() {
+            Weekday::Mon => self.monday,
+            Weekday::Tue => self.tuesday,
+            Weekday::Wed => self.wednesday,
+            Weekday::Thu => self.thursday,
+            Weekday::Fri => self.friday,
+            Weekday::Sat => self.saturday,
+            Weekday::Sun => self.sunday,
+        };
+        Ok(flag == 1)
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarDate {
+    service_id: ServiceID,
+    date: String,
+    // 1 means the service was added for this date, 2 means it was removed
+    exception_type: u8,
+}
+
+fn parse_date(x: &str) -> Result<NaiveDate> {
+    Ok(NaiveDate::parse_from_str(x, "%Y%m%d")?)
+}
+
+/// Returns None if the feed has neither calendar.txt nor calendar_dates.txt.
+fn read_active_services(map: &RawMap, day: ServiceDay) -> Result<Option<HashSet<ServiceID>>> {
+    let mut calendar = Vec::new();
+    let mut exceptions = Vec::new();
+    let calendar_path = map.name.city.input_path("gtfs/calendar.txt");
+    let dates_path = map.name.city.input_path("gtfs/calendar_dates.txt");
+    if abstio::file_exists(&calendar_path) {
+        for rec in csv::Reader::from_reader(File::open(calendar_path)?).deserialize() {
+            let rec: Calendar = rec?;
+            calendar.push(rec);
+        }
+    }
+    if abstio::file_exists(&dates_path) {
+        for rec in csv::Reader::from_reader(File::open(dates_path)?).deserialize() {
+            let rec: CalendarDate = rec?;
+            exceptions.push(rec);
+        }
+    }
+    if calendar.is_empty() && exceptions.is_empty() {
+        warn!("GTFS calendar missing, so assuming every service runs");
+        return Ok(None);
+    }
+
+    let date = match day {
+        ServiceDay::Date(date) => date,
+        ServiceDay::Weekday(weekday) => {
+            let mut first = None;
+            for date in calendar
+                .iter()
+                .map(|rec| &rec.start_date)
+                .chain(exceptions.iter().map(|rec| &rec.date))
+            {
+                let date = parse_date(date)?;
+                if first.map(|x| date < x).unwrap_or(true) {
+                    first = Some(date);
+                }
+            }
+            let mut date = first.unwrap();
+            while date.weekday() != weekday {
+                date = date.succ_opt().unwrap();
+            }
+            date
+        }
+    };
+    info!("Importing the GTFS schedule for {}", date);
+
+    let mut services = HashSet::new();
+    for rec in calendar {
+        if rec.runs_on(date)? {
+            services.insert(rec.service_id);
+        }
+    }
+    for rec in exceptions {
+        if parse_date(&rec.date)? != date {
+            continue;
+        }
+        if rec.exception_type == 1 {
+            services.insert(rec.service_id);
+        } else if rec.exception_type == 2 {
+            services.remove(&rec.service_id);
+        }
+    }
+    Ok(Some(services))
+}
+
+/// Stops that aren't timepoints may be missing a time. Linearly interpolate between the known
+/// times around them. Returns None if the first or last time is missing.
+fn interpolate_times(times: Vec<Option<Time>>) -> Option<Vec<Time>> {
+    if times.first()?.is_none() || times.last()?.is_none() {
+        return None;
+    }
+    let mut result: Vec<Time> = Vec::new();
+    let mut idx = 0;
+    while idx < times.len() {
+        if let Some(t) = times[idx] {
+            result.push(t);
+            idx += 1;
+            continue;
+        }
+        let prev = *result.last().unwrap();
+        let next_idx = (idx..times.len()).find(|i| times[*i].is_some()).unwrap();
+        let next = times[next_idx].unwrap();
+        let gaps = (next_idx - idx + 1) as f64;
+        for n in 0..(next_idx - idx) {
+            result.push(prev + (next - prev) * ((n + 1) as f64 / gaps));
+        }
+        idx = next_idx;
+    }
+    Some(result)
+}
+
+fn retain_by_idx<T>(list: &mut Vec<T>, keep: &[bool]) {
+    let mut idx = 0;
+    list.retain(|_| {
+        idx += 1;
+        keep[idx - 1]
+    });
 }
 
 fn dump_kml(map: &RawMap) {
@@ -260,3 +514,41 @@ fn dump_kml(map: &RawMap) {
         &ExtraShapes { shapes },
     );
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_interpolate_times() {
+        let t = |x: &str| Time::parse(x).unwrap();
+        assert_eq!(
+            interpolate_times(vec![
+                Some(t("08:00:00")),
+                None,
+                None,
+                Some(t("08:03:00")),
+                Some(t("08:05:00")),
+                None,
+                Some(t("08:09:00")),
+            ]),
+            Some(vec![
+                t("08:00:00"),
+                t("08:01:00"),
+                t("08:02:00"),
+                t("08:03:00"),
+                t("08:05:00"),
+                t("08:07:00"),
+                t("08:09:00"),
+            ])
+        );
+        assert_eq!(
+            interpolate_times(vec![Some(t("08:00:00")), Some(t("25:30:00"))]),
+            Some(vec![t("08:00:00"), t("25:30:00")])
+        );
+
+        assert_eq!(interpolate_times(vec![None, Some(t("08:00:00"))]), None);
+        assert_eq!(interpolate_times(vec![Some(t("08:00:00")), None]), None);
+        assert_eq!(interpolate_times(Vec::new()), None);
+    }
+}
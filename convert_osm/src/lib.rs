@@ -15,6 +15,9 @@ pub use streets_reader::{
     OnstreetParking, Options, PrivateOffstreetParking, PublicOffstreetParking,
 };
 
+pub use self::gtfs::ServiceDay;
+
+mod dem;
 mod elevation;
 mod extract;
 mod gtfs;
@@ -84,10 +87,6 @@ pub fn convert(
         add_extra_buildings(&mut map, path).unwrap();
     }
 
-    if opts.gtfs_url.is_some() {
-        gtfs::import(&mut map).unwrap();
-    }
-
     if map.name == MapName::new("gb", "bristol", "east") {
         bristol_hack(&mut map);
     }
@@ -95,6 +94,13 @@ pub fn convert(
     map
 }
 
+/// Add transit routes and stops from the GTFS feed in the city's input directory, along with the
+/// schedule of trips running on one day. Only call this when `Options::gtfs_url` is set and the
+/// feed has been downloaded.
+pub fn import_gtfs(map: &mut RawMap, service_day: ServiceDay) -> Result<()> {
+    gtfs::import(map, service_day)
+}
+
 fn add_extra_buildings(map: &mut RawMap, path: &str) -> Result<()> {
     let require_in_bounds = true;
     let mut id = -1;
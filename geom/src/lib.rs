@@ -37,6 +37,7 @@ mod polygon;
 mod polyline;
 mod pt;
 mod ring;
+mod schema;
 mod speed;
 mod stats;
 mod tessellation;
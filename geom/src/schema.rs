@@ -0,0 +1,100 @@
+//! Describes how some types appear in JSON, so APIs can publish a schema. The units are trimmed
+//! to 4 decimal places and serialized as integers, so the schemas have to be written by hand.
+
+use schemars::gen::SchemaGenerator;
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject};
+use schemars::JsonSchema;
+
+use crate::{Distance, Duration, LonLat, Speed, Time};
+
+fn trimmed_f64(description: &str) -> Schema {
+    SchemaObject {
+        instance_type: Some(InstanceType::Integer.into()),
+        format: Some("int32".to_string()),
+        metadata: Some(Box::new(Metadata {
+            description: Some(description.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    }
+    .into()
+}
+
+impl JsonSchema for Time {
+    fn schema_name() -> String {
+        "Time".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        trimmed_f64("Ten-thousandths of a second since midnight, so 1:00 AM is 36000000")
+    }
+}
+
+impl JsonSchema for Duration {
+    fn schema_name() -> String {
+        "Duration".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        trimmed_f64("Ten-thousandths of a second, so one minute is 600000")
+    }
+}
+
+impl JsonSchema for Distance {
+    fn schema_name() -> String {
+        "Distance".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        trimmed_f64("Ten-thousandths of a meter, so one meter is 10000")
+    }
+}
+
+impl JsonSchema for Speed {
+    fn schema_name() -> String {
+        "Speed".to_string()
+    }
+
+    fn json_schema(_: &mut SchemaGenerator) -> Schema {
+        trimmed_f64("Ten-thousandths of a meter per second, so 1 m/s is 10000")
+    }
+}
+
+/// Plain numbers, not trimmed like the units
+#[derive(JsonSchema)]
+#[schemars(rename = "LonLat")]
+#[allow(dead_code)]
+struct LonLatSchema {
+    longitude: f64,
+    latitude: f64,
+}
+
+impl JsonSchema for LonLat {
+    fn schema_name() -> String {
+        "LonLat".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        LonLatSchema::json_schema(gen)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schema_matches_json() {
+        // Make sure the description matches what's actually serialized
+        let t = Time::START_OF_DAY + Duration::hours(1);
+        assert_eq!(serde_json::to_string(&t).unwrap(), "36000000");
+        assert_eq!(
+            serde_json::to_string(&Duration::minutes(1)).unwrap(),
+            "600000"
+        );
+        assert_eq!(
+            serde_json::to_string(&Distance::meters(1.0)).unwrap(),
+            "10000"
+        );
+    }
+}
@@ -0,0 +1,79 @@
+#!/usr/bin/python3
+# This example drives a few cars from outside the simulation. Each car wanders randomly, picking a
+# new turn whenever it reaches the end of the lanes it's been told about, and leaves after a fixed
+# number of turns. The rest of the traffic is simulated as usual. Before running this script, start
+# the API server:
+#
+# > cargo run --release --bin headless -- --port=1234
+
+import random
+# You may need to install https://requests.readthedocs.io
+import requests
+
+
+api = 'http://localhost:1234'
+start_lanes = [100, 200, 300]
+turns_per_car = 10
+dt = 5
+max_steps = 1000
+
+
+def main():
+    random.seed(42)
+    turns_left = {}
+    for lane in start_lanes:
+        # Distances in JSON are ten-thousandths of a meter
+        resp = requests.post(api + '/cosim/spawn', json={
+            'vehicle': {
+                'vehicle_type': 'Car',
+                'length': 45000,
+                'max_speed': None,
+            },
+            'start': {
+                'lane': lane,
+                'dist_along': 50000,
+            },
+        })
+        if resp.status_code != requests.codes.ok:
+            print('Couldn\'t start on lane {}: {}'.format(lane, resp.text))
+            continue
+        car = resp.json()
+        turns_left[car['id']] = turns_per_car
+        print('Spawned car {} on lane {}'.format(car['id'], lane))
+
+    for _ in range(max_steps):
+        cars = requests.get(api + '/cosim/get-state').json()
+        if not cars:
+            break
+        for car in cars:
+            if not car['waiting']:
+                continue
+            cid = car['id']['id']
+            if turns_left[cid] == 0 or not car['next_turns']:
+                decide(car['id'], 'Vanish')
+            else:
+                turns_left[cid] -= 1
+                decide(car['id'], {'Turn': random.choice(car['next_turns'])})
+        step()
+
+    print('Done at {}'.format(requests.get(api + '/sim/get-time').text))
+
+
+def decide(car, decision):
+    requests.post(api + '/cosim/decide', json={
+        'car': car,
+        'decision': decision,
+    }).raise_for_status()
+
+
+def step():
+    now = requests.get(api + '/sim/get-time').text
+    h, m, s = now.split(':')
+    t = int(h) * 3600 + int(m) * 60 + float(s) + dt
+    # The API also accepts a plain number of seconds since midnight
+    requests.get(api + '/sim/goto-time',
+                 params={'t': str(t)}).raise_for_status()
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,63 @@
+#!/usr/bin/python3
+# This example runs the same scenario with different random seeds in parallel, all inside one API
+# server that only loads the map once. Before running this script, start the API server:
+#
+# > cargo run --release --bin headless -- --port=1234
+
+from concurrent.futures import ThreadPoolExecutor
+# You may need to install https://requests.readthedocs.io
+import requests
+
+
+api = 'http://localhost:1234'
+scenario = 'data/system/us/seattle/scenarios/montlake/weekday.bin'
+hours_to_sim = '03:00:00'
+seeds = [1, 2, 3, 4]
+
+
+def main():
+    sessions = []
+    for seed in seeds:
+        resp = requests.post(api + '/sessions/new', params={'rng_seed': seed}, json={
+            'scenario': scenario,
+            'modifiers': [],
+            'edits': None,
+        })
+        resp.raise_for_status()
+        sessions.append(resp.text)
+
+    # Each session steps independently, so these run at the same time
+    with ThreadPoolExecutor(max_workers=len(sessions)) as pool:
+        results = list(pool.map(run_sim, sessions))
+
+    for seed, (finished, seconds) in zip(seeds, results):
+        print('Seed {}: {} finished trips, total of {} seconds'.format(
+            seed, finished, seconds))
+
+    # Fork the first session and keep going, without disturbing the original
+    fork = requests.get(api + '/sessions/fork',
+                        params={'session': sessions[0]}).text
+    requests.get(api + '/sim/goto-time',
+                 params={'session': fork, 't': '04:00:00'}).raise_for_status()
+    print('The fork of session {} is at {}, the original is still at {}'.format(
+        sessions[0],
+        requests.get(api + '/sim/get-time', params={'session': fork}).text,
+        requests.get(api + '/sim/get-time', params={'session': sessions[0]}).text))
+
+    for session in sessions + [fork]:
+        requests.get(api + '/sessions/delete',
+                     params={'session': session}).raise_for_status()
+
+
+# Returns (number of finished trips, total seconds spent on them)
+def run_sim(session):
+    requests.get(api + '/sim/goto-time',
+                 params={'session': session, 't': hours_to_sim}).raise_for_status()
+    trips = [trip for trip in requests.get(api + '/data/get-finished-trips',
+                                           params={'session': session}).json() if trip['duration'] is not None]
+    # Durations in JSON are ten-thousandths of a second
+    return len(trips), sum([trip['duration'] for trip in trips]) / 10000
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,71 @@
+#!/usr/bin/python3
+# This example tries turning the parking lanes on a few roads into bike lanes, one road at a time,
+# and measures how each design affects trip times. Before running this script, start the API
+# server:
+#
+# > cargo run --release --bin headless -- --port=1234
+
+# You may need to install https://requests.readthedocs.io
+import requests
+
+
+api = 'http://localhost:1234'
+hours_to_sim = '03:00:00'
+candidate_roads = [10, 20, 30, 40]
+
+
+def main():
+    baseline = run_sim()
+    print('Baseline: {} finished trips, total of {} seconds'.format(
+        len(baseline), sum(baseline.values())))
+
+    for r in candidate_roads:
+        cmd = requests.get(api + '/map/get-edit-road-command',
+                           params={'id': r}).json()
+        new = cmd['ChangeRoad']['new']
+        changed = False
+        for lane in new['lanes_ltr']:
+            if lane['lt'] == 'Parking':
+                lane['lt'] = 'Biking'
+                changed = True
+        if not changed:
+            print('Road {} has no parking lanes'.format(r))
+            continue
+
+        # The server rejects edits that would disconnect part of the map
+        resp = requests.post(api + '/map/apply-edit', json=cmd)
+        if resp.status_code != requests.codes.ok:
+            print('Road {} rejected: {}'.format(r, resp.text))
+            continue
+
+        # /sim/reset keeps the edits
+        experiment = run_sim()
+        print('Bike lanes on road {}: {} finished trips, {} seconds saved over the baseline'.format(
+            r, len(experiment), compare(baseline, experiment)))
+
+        # Go back to the original design before trying the next road
+        requests.get(api + '/map/undo-edit').raise_for_status()
+
+    print('Final edits:', requests.get(api + '/map/get-edits').json()['commands'])
+
+
+# Returns a map from trip ID to duration in seconds. Cancelled trips are skipped.
+def run_sim():
+    requests.get(api + '/sim/reset').raise_for_status()
+    requests.get(api + '/sim/goto-time',
+                 params={'t': hours_to_sim}).raise_for_status()
+    trips = {}
+    for trip in requests.get(api + '/data/get-finished-trips').json():
+        if trip['duration'] is not None:
+            # Durations in JSON are ten-thousandths of a second
+            trips[trip['id']] = trip['duration'] / 10000
+    return trips
+
+
+# Sums the time saved by trips that finished in both simulations
+def compare(before, after):
+    return sum([before[trip] - dt for trip, dt in after.items() if trip in before])
+
+
+if __name__ == '__main__':
+    main()
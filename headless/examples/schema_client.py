@@ -0,0 +1,66 @@
+#!/usr/bin/python3
+# This example builds a client from the API server's own description of itself, so it can't drift
+# out of sync with the server. Parameters are checked before sending anything. Before running this
+# script, start the API server:
+#
+# > cargo run --release --bin headless -- --port=1234
+
+# You may need to install https://requests.readthedocs.io
+import requests
+
+
+class Client:
+    def __init__(self, api):
+        self.api = api
+        self.endpoints = requests.get(api + '/api/schema').json()['paths']
+
+    # Calls an endpoint like '/sim/goto-time', passing keyword arguments as query parameters. If the
+    # endpoint takes a JSON body, pass it as `body`.
+    def call(self, path, body=None, **params):
+        if path not in self.endpoints:
+            raise Exception('{} isn\'t an endpoint'.format(path))
+        method, spec = next(iter(self.endpoints[path].items()))
+        known = {p['name']: p for p in spec['parameters']}
+        for name in params:
+            if name not in known:
+                raise Exception('{} doesn\'t take a {} parameter. Options: {}'.format(
+                    path, name, ', '.join(known)))
+        for p in known.values():
+            if p['required'] and p['name'] not in params:
+                raise Exception('{} needs a {} parameter: {}'.format(
+                    path, p['name'], p['description']))
+        if 'requestBody' in spec and body is None:
+            raise Exception('{} needs a JSON body'.format(path))
+
+        resp = requests.request(method, self.api + path, params=params, json=body)
+        if resp.status_code != requests.codes.ok:
+            err = resp.json()
+            raise Exception('{}: {}'.format(err['path'], err['error']))
+        if resp.headers.get('Content-Type') == 'application/json':
+            return resp.json()
+        return resp.text
+
+
+def main():
+    client = Client('http://localhost:1234')
+    for path, methods in client.endpoints.items():
+        for method, spec in methods.items():
+            print('{} {}: {}'.format(method.upper(), path, spec['summary']))
+    print()
+
+    print(client.call('/sim/reset'))
+    print(client.call('/sim/goto-time', t='01:00:00'))
+    trips = client.call('/data/get-finished-trips')
+    print('{} trips finished by {}'.format(
+        len(trips), client.call('/sim/get-time')))
+
+    # Mistakes are caught by the server too
+    try:
+        requests.get('http://localhost:1234/sim/goto-time',
+                     params={'time': '02:00:00'}).raise_for_status()
+    except requests.HTTPError as err:
+        print('The server rejected a typo:', err.response.json()['error'])
+
+
+if __name__ == '__main__':
+    main()
@@ -0,0 +1,52 @@
+#!/usr/bin/python3
+# This example subscribes to events while the simulation runs, instead of polling.
+# Before running this script, start the API server:
+#
+# > cargo run --release --bin headless -- --port=1234
+
+import json
+# You may need to install https://requests.readthedocs.io
+import requests
+
+
+api = 'http://localhost:1234'
+
+
+def main():
+    print('Reset the simulation:', requests.get(api + '/sim/reset').text)
+
+    finished_trips = 0
+    resp = requests.get(api + '/stream/goto-time',
+                        params={'t': '03:00:00', 'positions_every': '00:10:00'}, stream=True)
+    resp.raise_for_status()
+    for name, data in read_events(resp):
+        if name == 'trip-finished':
+            finished_trips += 1
+        elif name == 'problem':
+            print('Trip {} had a problem: {}'.format(data['trip'], data['problem']))
+        elif name == 'alert':
+            print('Alert at {}: {}'.format(seconds(data['time']), data['message']))
+        elif name == 'positions':
+            print('{} agents moving, {} trips finished so far'.format(
+                len(data['agents']), finished_trips))
+        elif name == 'done':
+            print('Simulation reached {} seconds'.format(seconds(data)))
+
+
+# Times in JSON are ten-thousandths of a second since midnight
+def seconds(time):
+    return time / 10000
+
+
+# Parses a stream of server-sent events, yielding (event name, JSON data)
+def read_events(resp):
+    name = None
+    for line in resp.iter_lines(decode_unicode=True):
+        if line.startswith('event: '):
+            name = line[len('event: '):]
+        elif line.startswith('data: '):
+            yield name, json.loads(line[len('data: '):])
+
+
+if __name__ == '__main__':
+    main()
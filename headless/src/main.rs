@@ -8,19 +8,57 @@
 //! it's now 01:01:00.0
 //! > curl http://localhost:1234/data/get-road-thruput
 //! ... huge JSON blob
+//!
+//! Instead of polling, clients can also subscribe to server-sent events while the simulation runs:
+//!
+//! > curl -N http://localhost:1234/stream/goto-time?t=02:00:00&positions_every=00:05:00
+//!
+//! One process can run many independent simulations. Every request takes an optional `session`
+//! parameter, defaulting to the session created at startup. Sessions on the same map share it
+//! until they edit it.
+//!
+//! > curl -X POST http://localhost:1234/sessions/new -d '{"scenario": "...", "modifiers": [], "edits": null}'
+//! 1
+//! > curl http://localhost:1234/sessions/fork?session=1
+//! 2
+//! > curl http://localhost:1234/sim/goto-time?session=2&t=01:00:00
+//!
+//! Incidents can block lanes, slow down a road, or close an intersection for a while, without
+//! editing the map:
+//!
+//! > curl -X POST http://localhost:1234/sim/add-incident -d '{"start": 288000000, "end": 306000000, "disruption": {"CloseIntersection": 42}}'
+//! 0
+//!
+//! Times, durations, and distances in JSON are integers counting ten-thousandths of a second or
+//! meter, so that incident lasts from 8am to 8:30am.
+//!
+//! An external model can drive its own cars and bikes through the `/cosim` endpoints, deciding
+//! which turn to take each time one of them reaches the end of its planned lanes. See
+//! `examples/cosim_client.py`.
+//!
+//! Every endpoint, parameter, and response is described by an OpenAPI document, and requests that
+//! don't match it get a JSON error. Typed clients can be generated from it, and
+//! `examples/schema_client.py` reads it at runtime instead:
+//!
+//! > curl http://localhost:1234/api/schema > headless.json
+//! > openapi-generator-cli generate -i headless.json -g go -o go_client
 
 #[macro_use]
 extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+mod schema;
+
 use std::collections::{BTreeMap, BTreeSet, HashMap};
-use std::sync::RwLock;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
 
 use anyhow::Result;
-use hyper::{Body, Request, Response, Server, StatusCode};
+use hyper::{Body, Request, Response, Server};
 use rand::SeedableRng;
 use rand_xorshift::XorShiftRng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use structopt::StructOpt;
 
@@ -29,16 +67,23 @@ use abstutil::{serialize_btreemap, Timer};
 use geom::{Distance, Duration, FindClosest, LonLat, Time};
 use map_model::{
     CompressedMovementID, ControlTrafficSignal, EditCmd, EditIntersection, IntersectionID, Map,
-    MovementID, PermanentMapEdits, RoadID, TurnID,
+    MapEdits, MovementID, PermanentEditCmd, PermanentMapEdits, Position, RoadID, TransitRouteID,
+    TurnID,
 };
 use sim::{
-    AgentID, AgentType, DelayCause, PersonID, Sim, SimFlags, SimOptions, TripID, VehicleType,
+    AgentID, AgentType, AlertLocation, CarID, DelayCause, Event, ExternalDecision, Incident,
+    IncidentID, PersonID, Problem, Sim, SimFlags, SimOptions, TripID, VehicleSpec, VehicleType,
 };
 use synthpop::{ExternalPerson, Scenario, ScenarioModifier, TripMode};
 
+use crate::schema::{ApiError, ResponseType};
+
 lazy_static::lazy_static! {
-    static ref MAP: RwLock<Map> = RwLock::new(Map::blank());
-    static ref SIM: RwLock<Sim> = RwLock::new(Sim::new(&Map::blank(), SimOptions::new("tmp")));
+    /// Every loaded map, keyed by path. Sessions share these until they edit the map.
+    static ref MAPS: RwLock<BTreeMap<String, Arc<Map>>> = RwLock::new(BTreeMap::new());
+    static ref SESSIONS: RwLock<BTreeMap<SessionID, Arc<Mutex<Session>>>> =
+        RwLock::new(BTreeMap::new());
+    /// New sessions start from this
     static ref LOAD: RwLock<LoadSim> = RwLock::new({
         LoadSim {
             scenario: abstio::path_scenario(&MapName::seattle("montlake"), "weekday"),
@@ -50,6 +95,20 @@ lazy_static::lazy_static! {
     });
 }
 
+static NEXT_SESSION: AtomicUsize = AtomicUsize::new(0);
+
+type SessionID = usize;
+
+/// Requests without a `session` parameter use the one created at startup
+const DEFAULT_SESSION: SessionID = 0;
+
+/// One independent simulation
+struct Session {
+    map: Arc<Map>,
+    sim: Sim,
+    load: LoadSim,
+}
+
 #[derive(StructOpt)]
 #[structopt(
     name = "headless",
@@ -77,11 +136,11 @@ async fn main() {
         let mut load = LOAD.write().unwrap();
         load.rng_seed = args.rng_seed;
         load.opts = args.opts;
-
-        let (map, sim) = load.setup(&mut Timer::new("setup headless"));
-        *MAP.write().unwrap() = map;
-        *SIM.write().unwrap() = sim;
     }
+    let load = LOAD.read().unwrap().clone();
+    let (map, sim) = load.setup(&mut Timer::new("setup headless"));
+    let id = insert_session(Session { map, sim, load });
+    assert_eq!(id, DEFAULT_SESSION);
 
     let addr = std::net::SocketAddr::from(([127, 0, 0, 1], args.port));
     info!("Listening on http://{}", addr);
@@ -104,40 +163,241 @@ async fn serve_req(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
             .collect();
     let body = hyper::body::to_bytes(req).await?.to_vec();
     info!("Handling {}", path);
-    Ok(
-        match handle_command(
-            &path,
-            &params,
-            &body,
-            &mut SIM.write().unwrap(),
-            &mut MAP.write().unwrap(),
-            &mut LOAD.write().unwrap(),
-        ) {
-            Ok(resp) => Response::new(Body::from(resp)),
+    let endpoint = match schema::check_request(&path, &params, &body) {
+        Ok(endpoint) => endpoint,
+        Err(err) => {
+            error!("{}: {}", path, err.error);
+            return Ok(err.into_response());
+        }
+    };
+    if path == "/api/schema" {
+        return Ok(json_response(abstutil::to_json(&schema::openapi())));
+    }
+    if path == "/stream/goto-time" {
+        return Ok(match stream_goto_time(&params) {
+            Ok(resp) => resp,
             Err(err) => {
                 error!("{}: {}", path, err);
-                Response::builder()
-                    .status(StatusCode::BAD_REQUEST)
-                    .body(Body::from(format!("Bad command {}: {}", path, err)))
-                    .unwrap()
+                ApiError::new(&path, err.to_string()).into_response()
             }
+        });
+    }
+    // Commands can step the simulation or load files for a while, so don't block the async
+    // runtime and stall requests for other sessions
+    let cmd_path = path.clone();
+    let result = tokio::task::spawn_blocking(move || {
+        if cmd_path.starts_with("/sessions/") {
+            handle_session_command(&cmd_path, &params, &body)
+        } else {
+            get_session(&params).and_then(|session| {
+                handle_command(&cmd_path, &params, &body, &mut session.lock().unwrap())
+            })
+        }
+    })
+    .await
+    .unwrap_or_else(|err| Err(anyhow!("command stopped: {}", err)));
+    Ok(match result {
+        Ok(resp) => match endpoint.response {
+            ResponseType::Json(_) => json_response(resp),
+            _ => Response::new(Body::from(resp)),
         },
-    )
+        Err(err) => {
+            error!("{}: {}", path, err);
+            ApiError::new(&path, err.to_string()).into_response()
+        }
+    })
+}
+
+fn json_response(json: String) -> Response<Body> {
+    Response::builder()
+        .header("Content-Type", "application/json")
+        .body(Body::from(json))
+        .unwrap()
+}
+
+/// Advances the simulation like /sim/goto-time, but responds with a stream of server-sent events
+/// as it goes. Each message is JSON, with the event name indicating the type:
+///
+/// - "trip-finished", "trip-cancelled", "problem", "alert", and "signal-stage" as they happen
+/// - "positions" with the same format as /data/get-agent-positions, every `positions_every` of
+///   simulation time
+/// - "done" once the simulation reaches the target time
+///
+/// The simulation advances in steps of `positions_every`, so other requests made while streaming
+/// are handled in between. Many clients can stream the same session at once; each one gets every
+/// event, and all of them advance the simulation.
+fn stream_goto_time(params: &HashMap<String, String>) -> Result<Response<Body>> {
+    let end_time = Time::parse(
+        params
+            .get("t")
+            .ok_or_else(|| anyhow!("missing GET parameter t"))?,
+    )?;
+    let positions_every = match params.get("positions_every") {
+        Some(x) => Duration::parse(x)?,
+        None => Duration::minutes(1),
+    };
+    if positions_every <= Duration::ZERO {
+        bail!("positions_every must be positive");
+    }
+    let session = get_session(params)?;
+    let subscriber = {
+        let mut session = session.lock().unwrap();
+        if end_time <= session.sim.time() {
+            bail!("{} is in the past. call /sim/reset first?", end_time);
+        }
+        session.sim.subscribe_events()
+    };
+
+    let (mut sender, body) = Body::channel();
+    tokio::spawn(async move {
+        loop {
+            // Stepping can take a while, so don't block the async runtime
+            let step_session = session.clone();
+            let result = tokio::task::spawn_blocking(move || {
+                let mut session = step_session.lock().unwrap();
+                let Session { sim, map, .. } = &mut *session;
+                // Another stream of the same session might've already gone past the end
+                if sim.time() < end_time {
+                    let dt = positions_every.min(end_time - sim.time());
+                    sim.timed_step(map, dt, &mut None, &mut Timer::throwaway());
+                }
+
+                let mut messages = Vec::new();
+                for (time, ev) in sim.take_events(subscriber) {
+                    if let Some(msg) = StreamedEvent::new(time, ev) {
+                        messages.push(sse_message(msg.name(), &abstutil::to_json_terse(&msg)));
+                    }
+                }
+                messages.push(sse_message(
+                    "positions",
+                    &abstutil::to_json_terse(&get_agent_positions(sim, map)),
+                ));
+                let done = sim.time() >= end_time;
+                if done {
+                    messages.push(sse_message("done", &abstutil::to_json_terse(&sim.time())));
+                    sim.unsubscribe_events(subscriber);
+                }
+                (messages, done)
+            })
+            .await;
+            let (messages, done) = match result {
+                Ok(x) => x,
+                Err(err) => {
+                    error!("Stream stopped: {}", err);
+                    return;
+                }
+            };
+
+            for msg in messages {
+                if sender.send_data(msg.into()).await.is_err() {
+                    info!("Stream client disconnected");
+                    let _ = tokio::task::spawn_blocking(move || {
+                        session.lock().unwrap().sim.unsubscribe_events(subscriber);
+                    })
+                    .await;
+                    return;
+                }
+            }
+            if done {
+                return;
+            }
+        }
+    });
+
+    Ok(Response::builder()
+        .header("Content-Type", "text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .body(body)?)
+}
+
+fn sse_message(name: &str, json: &str) -> String {
+    format!("event: {}\ndata: {}\n\n", name, json)
+}
+
+/// Only savestates of the session's map can be loaded, so a request can't read arbitrary files.
+fn savestate_path(sim: &Sim, path: &str) -> Result<String> {
+    // Savestates for every edit and run of a map are grouped in one directory
+    let save_dir = sim.save_dir();
+    let map_dir = std::path::Path::new(&save_dir)
+        .parent()
+        .ok_or_else(|| anyhow!("no savestate directory for this map"))?;
+    let map_dir = std::fs::canonicalize(map_dir)
+        .map_err(|err| anyhow!("no savestates for this map: {}", err))?;
+    let resolved = std::fs::canonicalize(path)
+        .map_err(|err| anyhow!("can't find savestate {}: {}", path, err))?;
+    if !resolved.starts_with(&map_dir) {
+        bail!("{} isn't in this map's savestate directory", path);
+    }
+    Ok(resolved.to_string_lossy().to_string())
+}
+
+fn handle_session_command(
+    path: &str,
+    params: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<String> {
+    match path {
+        "/sessions/list" => Ok(abstutil::to_json(
+            &SESSIONS.read().unwrap().keys().collect::<Vec<_>>(),
+        )),
+        "/sessions/new" => {
+            let args: LoadSim = abstutil::from_json(body)?;
+            let mut load = LOAD.read().unwrap().clone();
+            load.scenario = args.scenario;
+            load.modifiers = args.modifiers;
+            load.edits = args.edits;
+            if let Some(seed) = params.get("rng_seed") {
+                load.rng_seed = seed.parse::<u64>()?;
+            }
+
+            let (map, sim) = load.setup(&mut Timer::new("new session"));
+            Ok(insert_session(Session { map, sim, load }).to_string())
+        }
+        "/sessions/fork" => {
+            let session = get_session(params)?;
+            let session = session.lock().unwrap();
+            // The copy shares the map until either session edits it. A savestate has to be from
+            // the same map and edits as the session being forked.
+            let sim = match params.get("savestate") {
+                Some(path) => Sim::load_savestate(
+                    savestate_path(&session.sim, path)?,
+                    &mut Timer::throwaway(),
+                )?,
+                None => session.sim.clone(),
+            };
+            Ok(insert_session(Session {
+                map: session.map.clone(),
+                sim,
+                load: session.load.clone(),
+            })
+            .to_string())
+        }
+        "/sessions/delete" => {
+            let id = params
+                .get("session")
+                .ok_or_else(|| anyhow!("missing GET parameter session"))?
+                .parse::<SessionID>()?;
+            match SESSIONS.write().unwrap().remove(&id) {
+                Some(_) => Ok(format!("session {} deleted", id)),
+                None => bail!("session {} doesn't exist", id),
+            }
+        }
+        _ => Err(anyhow!("Unknown command")),
+    }
 }
 
 fn handle_command(
     path: &str,
     params: &HashMap<String, String>,
     body: &[u8],
-    sim: &mut Sim,
-    map: &mut Map,
-    load: &mut LoadSim,
+    session: &mut Session,
 ) -> Result<String> {
     let get = |key: &str| {
         params
             .get(key)
             .ok_or_else(|| anyhow!("missing GET parameter {}", key))
     };
+    let Session { map, sim, load } = session;
 
     match path {
         // Controlling the simulation
@@ -162,12 +422,12 @@ fn handle_command(
             Ok("flags changed and sim reloaded".to_string())
         }
         "/sim/load-blank" => {
-            *map =
-                Map::load_synchronously(get("map")?.to_string(), &mut Timer::new("load new map"));
-            *sim = Sim::new(&map, SimOptions::default());
+            *map = load_map(get("map")?.to_string(), &mut Timer::new("load new map"));
+            *sim = Sim::new(map, SimOptions::default());
             Ok("map changed, blank simulation".to_string())
         }
         "/sim/get-time" => Ok(sim.time().to_string()),
+        "/sim/save" => Ok(sim.save()),
         "/sim/goto-time" => {
             let t = Time::parse(get("t")?)?;
             if t <= sim.time() {
@@ -199,6 +459,47 @@ fn handle_command(
                 sim.get_all_people().last().unwrap().id
             ))
         }
+        "/sim/add-incident" => {
+            let incident: Incident = abstutil::from_json(body)?;
+            let id = sim.add_incident(incident)?;
+            Ok(abstutil::to_json(&id))
+        }
+        "/sim/get-incidents" => Ok(abstutil::to_json(
+            &sim.get_incidents()
+                .into_iter()
+                .map(|(id, incident, active)| IncidentStatus {
+                    id,
+                    incident,
+                    active,
+                })
+                .collect::<Vec<_>>(),
+        )),
+        // Externally controlled vehicles
+        "/cosim/spawn" => {
+            let req: SpawnExternalCar = abstutil::from_json(body)?;
+            let id = sim.spawn_external_car(req.vehicle, req.start, req.emergency, map)?;
+            Ok(abstutil::to_json(&id))
+        }
+        "/cosim/make-emergency-vehicle" => {
+            let id = CarID {
+                id: get("id")?.parse::<usize>()?,
+                vehicle_type: VehicleType::Car,
+            };
+            sim.make_emergency_vehicle(id)?;
+            Ok(format!("{} is now an emergency vehicle", id))
+        }
+        "/cosim/decide" => {
+            let req: ExternalCarDecision = abstutil::from_json(body)?;
+            let msg = format!("{} will {:?}", req.car, req.decision);
+            sim.external_decision(req.car, req.decision, map)?;
+            Ok(msg)
+        }
+        "/cosim/get-state" => Ok(abstutil::to_json(
+            &sim.get_all_external_cars()
+                .into_iter()
+                .filter_map(|id| sim.external_car_state(id, map))
+                .collect::<Vec<_>>(),
+        )),
         // Traffic signals
         "/traffic-signals/get" => {
             let i = IntersectionID(get("id")?.parse::<usize>()?);
@@ -220,6 +521,7 @@ fn handle_command(
                 old: map.get_i_edit(id),
                 new: EditIntersection::TrafficSignal(ts.export(map)),
             });
+            let map = Arc::make_mut(map);
             map.must_apply_edits(edits, &mut Timer::throwaway());
             map.recalculate_pathfinding_after_edits(&mut Timer::throwaway());
 
@@ -318,21 +620,7 @@ fn handle_command(
             }
             Ok(abstutil::to_json(&trips))
         }
-        "/data/get-agent-positions" => Ok(abstutil::to_json(&AgentPositions {
-            agents: sim
-                .get_unzoomed_agents(map)
-                .into_iter()
-                .chain(sim.get_unzoomed_transit_riders(map))
-                .map(|a| AgentPosition {
-                    id: a.id,
-                    trip: sim.agent_to_trip(a.id),
-                    person: a.person,
-                    vehicle_type: a.id.to_vehicle_type(),
-                    pos: a.pos.to_gps(map.get_gps_bounds()),
-                    distance_crossed: sim.agent_properties(map, a.id).dist_crossed,
-                })
-                .collect(),
-        })),
+        "/data/get-agent-positions" => Ok(abstutil::to_json(&get_agent_positions(sim, map))),
         "/data/get-road-thruput" => Ok(abstutil::to_json(&RoadThroughput {
             counts: sim
                 .get_analytics()
@@ -388,6 +676,72 @@ fn handle_command(
                 &map.edit_road_cmd(r, |_| {}).to_perma(map),
             ))
         }
+        "/map/get-edit-intersection-command" => {
+            let i = IntersectionID(get("id")?.parse::<usize>()?);
+            if map.get_i(i).is_border() {
+                bail!("{} is a border and can't be edited", i);
+            }
+            let current = map.get_i_edit(i);
+            Ok(abstutil::to_json(
+                &EditCmd::ChangeIntersection {
+                    i,
+                    old: current.clone(),
+                    new: current,
+                }
+                .to_perma(map),
+            ))
+        }
+        "/map/get-edit-crosswalks-command" => {
+            let i = IntersectionID(get("id")?.parse::<usize>()?);
+            let current = map.get_i_crosswalks_edit(i);
+            Ok(abstutil::to_json(
+                &EditCmd::ChangeCrosswalks {
+                    i,
+                    old: current.clone(),
+                    new: current,
+                }
+                .to_perma(map),
+            ))
+        }
+        "/map/get-edit-route-schedule-command" => {
+            let id = TransitRouteID(get("id")?.parse::<usize>()?);
+            let tr = map
+                .maybe_get_tr(id)
+                .ok_or_else(|| anyhow!("{} doesn't exist", id))?;
+            Ok(abstutil::to_json(
+                &EditCmd::ChangeRouteSchedule {
+                    id,
+                    old: tr.spawn_times.clone(),
+                    new: tr.spawn_times.clone(),
+                }
+                .to_perma(map),
+            ))
+        }
+        "/map/apply-edit" => {
+            let cmd: PermanentEditCmd = abstutil::from_json(body)?;
+            let mut edits = map.get_edits().clone();
+            edits.commands.push(cmd.into_cmd(map)?);
+            apply_edits(map, sim, load, edits)
+        }
+        "/map/undo-edit" => {
+            let mut edits = map.get_edits().clone();
+            if edits.commands.pop().is_none() {
+                bail!("There are no edits to undo");
+            }
+            apply_edits(map, sim, load, edits)
+        }
+        "/map/set-edits" => {
+            let perma: PermanentMapEdits = abstutil::from_json(body)?;
+            if perma.map_name != *map.get_name() {
+                bail!(
+                    "Edits are for {}, but this map is {}",
+                    perma.map_name.describe(),
+                    map.get_name().describe()
+                );
+            }
+            let edits = perma.into_edits(map)?;
+            apply_edits(map, sim, load, edits)
+        }
         "/map/get-intersection-geometry" => {
             let i = IntersectionID(get("id")?.parse::<usize>()?);
             Ok(abstutil::to_json(&export_geometry(map, i)))
@@ -409,9 +763,7 @@ fn handle_command(
     }
 }
 
-// TODO I think specifying the API with protobufs or similar will be a better idea.
-
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct FinishedTrip {
     id: TripID,
     person: PersonID,
@@ -420,24 +772,26 @@ struct FinishedTrip {
     mode: TripMode,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct Delays {
     #[serde(serialize_with = "serialize_btreemap")]
+    #[schemars(with = "Vec<(MovementID, Vec<Duration>)>")]
     per_direction: BTreeMap<MovementID, Vec<Duration>>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct Throughput {
     #[serde(serialize_with = "serialize_btreemap")]
+    #[schemars(with = "Vec<(MovementID, usize)>")]
     per_direction: BTreeMap<MovementID, usize>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct AgentPositions {
     agents: Vec<AgentPosition>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct AgentPosition {
     /// The agent's ID
     id: AgentID,
@@ -450,7 +804,7 @@ struct AgentPosition {
     /// The agent's current position. For pedestrians, this is their center. For vehicles, this
     /// represents the front of the vehicle.
     pos: LonLat,
-    /// The distance crossed so far by the agent, in meters. There are some caveats to this value:
+    /// The distance crossed so far by the agent. There are some caveats to this value:
     /// - The distance along driveways between buildings/parking lots and the road doesn't count
     ///   here.
     /// - The distance only represents the current leg of the trip. If somebody walks to a car, the
@@ -463,13 +817,112 @@ struct AgentPosition {
     distance_crossed: Distance,
 }
 
-#[derive(Serialize)]
+fn get_agent_positions(sim: &Sim, map: &Map) -> AgentPositions {
+    AgentPositions {
+        agents: sim
+            .get_unzoomed_agents(map)
+            .into_iter()
+            .chain(sim.get_unzoomed_transit_riders(map))
+            .map(|a| AgentPosition {
+                id: a.id,
+                trip: sim.agent_to_trip(a.id),
+                person: a.person,
+                vehicle_type: a.id.to_vehicle_type(),
+                pos: a.pos.to_gps(map.get_gps_bounds()),
+                distance_crossed: sim.agent_properties(map, a.id).dist_crossed,
+            })
+            .collect(),
+    }
+}
+
+/// The subset of simulation events sent to streaming clients
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged)]
+enum StreamedEvent {
+    TripFinished {
+        time: Time,
+        trip: TripID,
+        mode: TripMode,
+        total_time: Duration,
+        blocked_time: Duration,
+    },
+    TripCancelled {
+        time: Time,
+        trip: TripID,
+        mode: TripMode,
+    },
+    Problem {
+        time: Time,
+        trip: TripID,
+        problem: Problem,
+    },
+    Alert {
+        time: Time,
+        location: AlertLocation,
+        message: String,
+    },
+    SignalStage {
+        time: Time,
+        intersection: IntersectionID,
+        stage: usize,
+    },
+}
+
+impl StreamedEvent {
+    fn new(time: Time, ev: Event) -> Option<StreamedEvent> {
+        match ev {
+            Event::TripFinished {
+                trip,
+                mode,
+                total_time,
+                blocked_time,
+            } => Some(StreamedEvent::TripFinished {
+                time,
+                trip,
+                mode,
+                total_time,
+                blocked_time,
+            }),
+            Event::TripCancelled(trip, mode) => {
+                Some(StreamedEvent::TripCancelled { time, trip, mode })
+            }
+            Event::ProblemEncountered(trip, problem) => Some(StreamedEvent::Problem {
+                time,
+                trip,
+                problem,
+            }),
+            Event::Alert(location, message) => Some(StreamedEvent::Alert {
+                time,
+                location,
+                message,
+            }),
+            Event::SignalStageChanged(intersection, stage) => Some(StreamedEvent::SignalStage {
+                time,
+                intersection,
+                stage,
+            }),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            StreamedEvent::TripFinished { .. } => "trip-finished",
+            StreamedEvent::TripCancelled { .. } => "trip-cancelled",
+            StreamedEvent::Problem { .. } => "problem",
+            StreamedEvent::Alert { .. } => "alert",
+            StreamedEvent::SignalStage { .. } => "signal-stage",
+        }
+    }
+}
+
+#[derive(Serialize, JsonSchema)]
 struct RoadThroughput {
     // (road, agent type, hour since midnight, throughput for that one hour period)
     counts: Vec<(RoadID, AgentType, usize, usize)>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct TrafficSignalState {
     current_stage_idx: usize,
     remaining_time: Duration,
@@ -478,34 +931,64 @@ struct TrafficSignalState {
     waiting: Vec<(AgentID, TurnID, Time)>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, JsonSchema)]
 struct BlockedByGraph {
     /// Each entry indicates that some agent has been stuck in one place for some amount of time,
     /// due to being blocked by another agent or because they're waiting at an intersection. Unless
     /// the agent is a bus, then the TripID and PersonID will also be filled out.
     #[serde(serialize_with = "serialize_btreemap")]
+    #[schemars(with = "Vec<(AgentID, (Duration, DelayCause, Option<TripID>, Option<PersonID>))>")]
     blocked_by: BTreeMap<AgentID, (Duration, DelayCause, Option<TripID>, Option<PersonID>)>,
 }
 
-#[derive(Deserialize)]
+#[derive(Serialize, JsonSchema)]
+struct IncidentStatus<'a> {
+    id: IncidentID,
+    incident: &'a Incident,
+    /// Is it disrupting traffic right now?
+    active: bool,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct SpawnExternalCar {
+    vehicle: VehicleSpec,
+    /// Where the front of the vehicle starts
+    start: Position,
+    /// Emergency vehicles preempt traffic signals and make everyone else yield
+    #[serde(default)]
+    emergency: bool,
+}
+
+#[derive(Deserialize, JsonSchema)]
+struct ExternalCarDecision {
+    car: CarID,
+    decision: ExternalDecision,
+}
+
+#[derive(Clone, Deserialize, JsonSchema)]
 struct LoadSim {
+    /// Path to a scenario file
     scenario: String,
     modifiers: Vec<ScenarioModifier>,
+    #[schemars(with = "Option<schema::MapEditsSchema>")]
     edits: Option<PermanentMapEdits>,
     // These are fixed from the initial command line flags
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
     rng_seed: u64,
     #[serde(skip_deserializing)]
+    #[schemars(skip)]
     opts: SimOptions,
 }
 
 impl LoadSim {
-    fn setup(&self, timer: &mut Timer) -> (Map, Sim) {
+    fn setup(&self, timer: &mut Timer) -> (Arc<Map>, Sim) {
         let mut scenario: Scenario = abstio::must_read_object(self.scenario.clone(), timer);
 
-        let mut map = Map::load_synchronously(scenario.map_name.path(), timer);
+        let mut map = load_map(scenario.map_name.path(), timer);
         if let Some(perma) = self.edits.clone() {
             let edits = perma.into_edits(&map).unwrap();
+            let map = Arc::make_mut(&mut map);
             map.must_apply_edits(edits, timer);
             map.recalculate_pathfinding_after_edits(timer);
         }
@@ -522,6 +1005,81 @@ impl LoadSim {
     }
 }
 
+/// Rejects edits that break transit, disconnect sidewalks, or create new blackholes. Otherwise
+/// applies them, updates the running simulation, and remembers them for /sim/reset.
+fn apply_edits(
+    map: &mut Arc<Map>,
+    sim: &mut Sim,
+    load: &mut LoadSim,
+    edits: MapEdits,
+) -> Result<String> {
+    // Check before copying a map shared with other sessions
+    map.check_transit_edits(&edits)?;
+    // Other sessions sharing this map keep the original
+    let map = Arc::make_mut(map);
+    let sidewalks = map.find_newly_disconnected_sidewalks(edits.clone());
+    if !sidewalks.is_empty() {
+        bail!(
+            "These edits would disconnect {} sidewalks: {:?}",
+            sidewalks.len(),
+            sidewalks
+        );
+    }
+    let lanes = map.find_newly_disconnected_lanes(edits.clone());
+    if !lanes.is_empty() {
+        bail!(
+            "These edits would disconnect {} driving or biking lanes: {:?}",
+            lanes.len(),
+            lanes
+        );
+    }
+
+    let mut timer = Timer::throwaway();
+    map.must_apply_edits(edits, &mut timer);
+    map.recalculate_pathfinding_after_edits(&mut timer);
+    sim.handle_live_edited_traffic_signals(map);
+    let (trips, parked_cars) = sim.handle_live_edits(map, &mut timer);
+    load.edits = Some(map.get_edits().to_permanent(map));
+
+    Ok(format!(
+        "{} edits now in effect. {} trips cancelled, {} parked cars displaced",
+        map.get_edits().commands.len(),
+        trips,
+        parked_cars
+    ))
+}
+
+/// Loads a map, or reuses it if another session already has
+fn load_map(path: String, timer: &mut Timer) -> Arc<Map> {
+    MAPS.write()
+        .unwrap()
+        .entry(path.clone())
+        .or_insert_with(|| Arc::new(Map::load_synchronously(path, timer)))
+        .clone()
+}
+
+fn insert_session(session: Session) -> SessionID {
+    let id = NEXT_SESSION.fetch_add(1, Ordering::SeqCst);
+    SESSIONS
+        .write()
+        .unwrap()
+        .insert(id, Arc::new(Mutex::new(session)));
+    id
+}
+
+fn get_session(params: &HashMap<String, String>) -> Result<Arc<Mutex<Session>>> {
+    let id = match params.get("session") {
+        Some(x) => x.parse::<SessionID>()?,
+        None => DEFAULT_SESSION,
+    };
+    SESSIONS
+        .read()
+        .unwrap()
+        .get(&id)
+        .cloned()
+        .ok_or_else(|| anyhow!("session {} doesn't exist", id))
+}
+
 fn export_geometry(map: &Map, i: IntersectionID) -> geojson::GeoJson {
     let mut pairs = Vec::new();
 
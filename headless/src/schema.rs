@@ -0,0 +1,818 @@
+//! Every endpoint the API server handles, along with its parameters, request body, and response.
+//! This is served as an OpenAPI document from /api/schema, so clients can be generated from it,
+//! and it's also used to check requests before handling them. The schemas for request and response
+//! bodies are derived from the Rust types, so they can't drift out of sync.
+
+use std::collections::{BTreeMap, HashMap};
+
+use hyper::{Body, Response, StatusCode};
+use schemars::gen::{SchemaGenerator, SchemaSettings};
+use schemars::schema::{InstanceType, Metadata, Schema, SchemaObject};
+use schemars::JsonSchema;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use geom::{Duration, Time};
+use map_model::{ControlTrafficSignal, IntersectionID, RoadID};
+use sim::{CarID, ExternalCarState, Incident, IncidentID, TripID};
+use synthpop::ExternalPerson;
+
+use crate::{
+    AgentPositions, BlockedByGraph, Delays, ExternalCarDecision, FinishedTrip, IncidentStatus,
+    LoadSim, RoadThroughput, SessionID, SpawnExternalCar, StreamedEvent, Throughput,
+    TrafficSignalState,
+};
+
+/// Describes a request or response body, adding any named types it uses to the generator.
+pub type SchemaFn = fn(&mut SchemaGenerator) -> Schema;
+
+fn of<T: JsonSchema>(gen: &mut SchemaGenerator) -> Schema {
+    gen.subschema_for::<T>()
+}
+
+pub struct Endpoint {
+    pub path: &'static str,
+    pub summary: &'static str,
+    pub params: &'static [Param],
+    /// Describes the JSON request body, if the endpoint takes one. Endpoints with a body expect
+    /// POST; everything else accepts GET or POST.
+    pub body: Option<SchemaFn>,
+    pub response: ResponseType,
+}
+
+pub struct Param {
+    pub name: &'static str,
+    pub kind: ParamKind,
+    pub required: bool,
+    pub description: &'static str,
+}
+
+#[derive(Clone, Copy)]
+pub enum ParamKind {
+    /// A non-negative integer, usually an ID
+    Integer,
+    Number,
+    /// Like 01:30:00 or 5400 (seconds since midnight)
+    Time,
+    /// Like 00:05:00 or 300 (seconds)
+    Duration,
+    String,
+}
+
+pub enum ResponseType {
+    /// Plain text, usually a confirmation message
+    Text,
+    Json(SchemaFn),
+    /// Server-sent events, each with JSON data
+    EventStream,
+}
+
+/// A request that couldn't be handled. This is sent as JSON.
+#[derive(Serialize, JsonSchema)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    pub error: String,
+    /// The endpoint that was requested
+    pub path: String,
+    /// The parameter that's wrong, if the problem is with one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub param: Option<String>,
+}
+
+impl ApiError {
+    pub fn new(path: &str, error: String) -> ApiError {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            error,
+            path: path.to_string(),
+            param: None,
+        }
+    }
+
+    fn param(path: &str, param: &str, error: String) -> ApiError {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            error,
+            path: path.to_string(),
+            param: Some(param.to_string()),
+        }
+    }
+
+    pub fn into_response(self) -> Response<Body> {
+        Response::builder()
+            .status(self.status)
+            .header("Content-Type", "application/json")
+            .body(Body::from(abstutil::to_json(&self)))
+            .unwrap()
+    }
+}
+
+/// Makes sure the endpoint exists, all required parameters are present, every parameter is known
+/// and parses, and a JSON body is present if needed. The handler still checks the body matches
+/// its schema and that IDs refer to something.
+pub fn check_request(
+    path: &str,
+    params: &HashMap<String, String>,
+    body: &[u8],
+) -> Result<&'static Endpoint, ApiError> {
+    let endpoint = ENDPOINTS
+        .iter()
+        .find(|e| e.path == path)
+        .ok_or_else(|| ApiError {
+            status: StatusCode::NOT_FOUND,
+            error: "Unknown endpoint; see /api/schema".to_string(),
+            path: path.to_string(),
+            param: None,
+        })?;
+
+    for name in params.keys() {
+        if !endpoint.params.iter().any(|p| p.name == name) {
+            return Err(ApiError::param(
+                path,
+                name,
+                format!("{} doesn't take a {} parameter", path, name),
+            ));
+        }
+    }
+    for p in endpoint.params {
+        let value = match params.get(p.name) {
+            Some(x) => x,
+            None => {
+                if p.required {
+                    return Err(ApiError::param(
+                        path,
+                        p.name,
+                        format!("missing GET parameter {}", p.name),
+                    ));
+                }
+                continue;
+            }
+        };
+        let ok = match p.kind {
+            ParamKind::Integer => value.parse::<usize>().is_ok(),
+            ParamKind::Number => value.parse::<f64>().is_ok(),
+            ParamKind::Time => Time::parse(value).is_ok(),
+            ParamKind::Duration => Duration::parse(value).is_ok(),
+            ParamKind::String => true,
+        };
+        if !ok {
+            return Err(ApiError::param(
+                path,
+                p.name,
+                format!("{} isn't a valid {}", value, p.kind.describe()),
+            ));
+        }
+    }
+
+    if endpoint.body.is_some() {
+        if body.is_empty() {
+            return Err(ApiError::new(
+                path,
+                format!("{} needs a JSON body; see /api/schema", path),
+            ));
+        }
+        if let Err(err) = serde_json::from_slice::<Value>(body) {
+            return Err(ApiError::new(
+                path,
+                format!("The body isn't valid JSON: {}", err),
+            ));
+        }
+    }
+
+    Ok(endpoint)
+}
+
+impl ParamKind {
+    fn describe(self) -> &'static str {
+        match self {
+            ParamKind::Integer => "non-negative integer",
+            ParamKind::Number => "number",
+            ParamKind::Time => "time, like 01:30:00",
+            ParamKind::Duration => "duration, like 00:05:00",
+            ParamKind::String => "string",
+        }
+    }
+
+    fn to_schema(self) -> Value {
+        match self {
+            ParamKind::Integer => json!({"type": "integer", "minimum": 0}),
+            ParamKind::Number => json!({"type": "number"}),
+            ParamKind::Time => json!({"type": "string", "example": "01:30:00"}),
+            ParamKind::Duration => json!({"type": "string", "example": "00:05:00"}),
+            ParamKind::String => json!({"type": "string"}),
+        }
+    }
+}
+
+/// Produces an OpenAPI 3.0 document describing the whole API.
+pub fn openapi() -> Value {
+    let mut gen = SchemaSettings::openapi3().into_generator();
+    let api_error = gen.subschema_for::<ApiError>();
+
+    let mut paths = serde_json::Map::new();
+    for e in ENDPOINTS {
+        let parameters: Vec<Value> = e
+            .params
+            .iter()
+            .map(|p| {
+                json!({
+                    "name": p.name,
+                    "in": "query",
+                    "required": p.required,
+                    "description": p.description,
+                    "schema": p.kind.to_schema(),
+                })
+            })
+            .collect();
+        let response = match e.response {
+            ResponseType::Text => json!({
+                "description": "A message",
+                "content": {"text/plain": {"schema": {"type": "string"}}},
+            }),
+            ResponseType::Json(schema) => json!({
+                "description": e.summary,
+                "content": {"application/json": {"schema": schema(&mut gen)}},
+            }),
+            ResponseType::EventStream => {
+                // Make sure everything the events contain is described
+                gen.subschema_for::<StreamedEvent>();
+                gen.subschema_for::<AgentPositions>();
+                gen.subschema_for::<Time>();
+                json!({
+                    "description": "Server-sent events. The event name is the type, and the data \
+                                    is JSON matching the StreamedEvent, AgentPositions, or Time \
+                                    schema.",
+                    "content": {"text/event-stream": {"schema": {"type": "string"}}},
+                })
+            }
+        };
+        let mut operation = json!({
+            "operationId": e.path.trim_start_matches('/').replace(['/', '-'], "_"),
+            "summary": e.summary,
+            "tags": [e.path.split('/').nth(1).unwrap()],
+            "parameters": parameters,
+            "responses": {
+                "200": response,
+                "400": {
+                    "description": "The request was invalid or couldn't be handled",
+                    "content": {"application/json": {"schema": api_error}},
+                },
+            },
+        });
+        let method = if let Some(schema) = e.body {
+            operation["requestBody"] = json!({
+                "required": true,
+                "content": {"application/json": {"schema": schema(&mut gen)}},
+            });
+            "post"
+        } else {
+            "get"
+        };
+        let mut item = serde_json::Map::new();
+        item.insert(method.to_string(), operation);
+        paths.insert(e.path.to_string(), Value::Object(item));
+    }
+
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "A/B Street headless API",
+            "version": env!("CARGO_PKG_VERSION"),
+            "description": "Run a traffic simulation without graphics. Times, durations, \
+                            distances, and speeds in JSON are integers, counting ten-thousandths \
+                            of a second or meter. 1:00 AM is 36000000, and one meter is 10000. \
+                            Query parameters take times and durations like 01:30:00 instead.",
+        },
+        "paths": paths,
+        "components": {"schemas": gen.take_definitions()},
+    })
+}
+
+/// Types from other crates that are large or change often aren't spelled out. Clients can treat
+/// them as opaque JSON, usually taken from another endpoint and modified.
+macro_rules! opaque_schema {
+    ($type:ident, $name:expr, $description:expr) => {
+        pub struct $type;
+
+        impl JsonSchema for $type {
+            fn schema_name() -> String {
+                $name.to_string()
+            }
+
+            fn json_schema(_: &mut SchemaGenerator) -> Schema {
+                SchemaObject {
+                    instance_type: Some(InstanceType::Object.into()),
+                    metadata: Some(Box::new(Metadata {
+                        description: Some($description.to_string()),
+                        ..Default::default()
+                    })),
+                    ..Default::default()
+                }
+                .into()
+            }
+        }
+    };
+}
+
+opaque_schema!(OpenApiSchema, "OpenAPI", "This document");
+opaque_schema!(
+    MapEditsSchema,
+    "PermanentMapEdits",
+    "All map edits, as returned by /map/get-edits"
+);
+opaque_schema!(
+    EditCmdSchema,
+    "PermanentEditCmd",
+    "One map edit, as returned by the /map/get-edit-*-command endpoints"
+);
+opaque_schema!(
+    GeoJsonSchema,
+    "FeatureCollection",
+    "A GeoJSON FeatureCollection"
+);
+
+const fn param(
+    name: &'static str,
+    kind: ParamKind,
+    required: bool,
+    description: &'static str,
+) -> Param {
+    Param {
+        name,
+        kind,
+        required,
+        description,
+    }
+}
+
+const SESSION: Param = param(
+    "session",
+    ParamKind::Integer,
+    false,
+    "The session to use. Defaults to the one created at startup.",
+);
+const SESSION_ONLY: &[Param] = &[SESSION];
+
+const fn endpoint(
+    path: &'static str,
+    summary: &'static str,
+    params: &'static [Param],
+    body: Option<SchemaFn>,
+    response: ResponseType,
+) -> Endpoint {
+    Endpoint {
+        path,
+        summary,
+        params,
+        body,
+        response,
+    }
+}
+
+pub const ENDPOINTS: &[Endpoint] = &[
+    endpoint(
+        "/api/schema",
+        "Describe the whole API as an OpenAPI document",
+        &[],
+        None,
+        ResponseType::Json(of::<OpenApiSchema>),
+    ),
+    // Sessions
+    endpoint(
+        "/sessions/list",
+        "List all sessions",
+        &[],
+        None,
+        ResponseType::Json(of::<Vec<SessionID>>),
+    ),
+    endpoint(
+        "/sessions/new",
+        "Start a new session, returning its ID",
+        &[param(
+            "rng_seed",
+            ParamKind::Integer,
+            false,
+            "Seeds the random number generator. Defaults to the command line flag.",
+        )],
+        Some(of::<LoadSim>),
+        ResponseType::Json(of::<SessionID>),
+    ),
+    endpoint(
+        "/sessions/fork",
+        "Copy a session, returning the new ID",
+        &[
+            SESSION,
+            param(
+                "savestate",
+                ParamKind::String,
+                false,
+                "Start the copy from this savestate, made on the same map and edits. It must be in \
+                 the map's savestate directory, like the paths /sim/save returns.",
+            ),
+        ],
+        None,
+        ResponseType::Json(of::<SessionID>),
+    ),
+    endpoint(
+        "/sessions/delete",
+        "Delete a session",
+        &[param(
+            "session",
+            ParamKind::Integer,
+            true,
+            "The session to delete",
+        )],
+        None,
+        ResponseType::Text,
+    ),
+    // Controlling the simulation
+    endpoint(
+        "/sim/reset",
+        "Restart the simulation, keeping map edits made through /map",
+        SESSION_ONLY,
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/sim/load",
+        "Change the scenario, modifiers, and edits, then restart",
+        SESSION_ONLY,
+        Some(of::<LoadSim>),
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/sim/load-blank",
+        "Switch to a different map with no trips",
+        &[
+            SESSION,
+            param("map", ParamKind::String, true, "Path to a map file"),
+        ],
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/sim/get-time",
+        "The current simulation time",
+        SESSION_ONLY,
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/sim/save",
+        "Write a savestate, returning its path",
+        SESSION_ONLY,
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/sim/goto-time",
+        "Run the simulation until some time",
+        &[
+            SESSION,
+            param("t", ParamKind::Time, true, "Must be in the future"),
+        ],
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/sim/new-person",
+        "Add a person with some trips starting in the future",
+        SESSION_ONLY,
+        Some(of::<ExternalPerson>),
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/stream/goto-time",
+        "Run the simulation until some time, streaming events and agent positions",
+        &[
+            SESSION,
+            param("t", ParamKind::Time, true, "Must be in the future"),
+            param(
+                "positions_every",
+                ParamKind::Duration,
+                false,
+                "How often to send agent positions. Defaults to 1 minute.",
+            ),
+        ],
+        None,
+        ResponseType::EventStream,
+    ),
+    endpoint(
+        "/sim/add-incident",
+        "Schedule an incident that temporarily disrupts traffic, returning its ID",
+        SESSION_ONLY,
+        Some(of::<Incident>),
+        ResponseType::Json(of::<IncidentID>),
+    ),
+    endpoint(
+        "/sim/get-incidents",
+        "Describe every incident added so far",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<Vec<IncidentStatus<'static>>>),
+    ),
+    // Externally controlled vehicles
+    endpoint(
+        "/cosim/spawn",
+        "Start an externally controlled car or bike, returning its ID",
+        SESSION_ONLY,
+        Some(of::<SpawnExternalCar>),
+        ResponseType::Json(of::<CarID>),
+    ),
+    endpoint(
+        "/cosim/make-emergency-vehicle",
+        "Make a car already driving, externally controlled or not, preempt traffic signals",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "The car's ID"),
+        ],
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/cosim/decide",
+        "Tell an externally controlled vehicle what to do next",
+        SESSION_ONLY,
+        Some(of::<ExternalCarDecision>),
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/cosim/get-state",
+        "Describe every externally controlled vehicle",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<Vec<ExternalCarState>>),
+    ),
+    // Traffic signals
+    endpoint(
+        "/traffic-signals/get",
+        "Get a traffic signal's configuration",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "IntersectionID"),
+        ],
+        None,
+        ResponseType::Json(of::<ControlTrafficSignal>),
+    ),
+    endpoint(
+        "/traffic-signals/set",
+        "Change a traffic signal's configuration",
+        SESSION_ONLY,
+        Some(of::<ControlTrafficSignal>),
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/traffic-signals/get-delays",
+        "Delays per movement through a traffic signal between two times",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "IntersectionID"),
+            param("t1", ParamKind::Time, true, "Start of the time range"),
+            param("t2", ParamKind::Time, true, "End of the time range"),
+        ],
+        None,
+        ResponseType::Json(of::<Delays>),
+    ),
+    endpoint(
+        "/traffic-signals/get-cumulative-thruput",
+        "The number of agents through each movement of a traffic signal so far",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "IntersectionID"),
+        ],
+        None,
+        ResponseType::Json(of::<Throughput>),
+    ),
+    endpoint(
+        "/traffic-signals/get-all-current-state",
+        "The current stage and waiting agents at every traffic signal",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<BTreeMap<IntersectionID, TrafficSignalState>>),
+    ),
+    // Querying data
+    endpoint(
+        "/data/get-finished-trips",
+        "Every trip finished or cancelled so far",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<Vec<FinishedTrip>>),
+    ),
+    endpoint(
+        "/data/get-agent-positions",
+        "Where every agent is right now",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<AgentPositions>),
+    ),
+    endpoint(
+        "/data/get-road-thruput",
+        "The number of agents crossing each road per hour",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<RoadThroughput>),
+    ),
+    endpoint(
+        "/data/get-blocked-by-graph",
+        "Which agents are stuck, and why",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<BlockedByGraph>),
+    ),
+    endpoint(
+        "/data/trip-time-lower-bound",
+        "The fastest a trip could possibly finish, in seconds",
+        &[SESSION, param("id", ParamKind::Integer, true, "TripID")],
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/data/all-trip-time-lower-bounds",
+        "The fastest every trip could possibly finish",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<BTreeMap<TripID, Duration>>),
+    ),
+    // Controlling the map
+    endpoint(
+        "/map/get-edits",
+        "The current map edits",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<MapEditsSchema>),
+    ),
+    endpoint(
+        "/map/get-edit-road-command",
+        "An edit command for a road, with the current state filled out to modify",
+        &[SESSION, param("id", ParamKind::Integer, true, "RoadID")],
+        None,
+        ResponseType::Json(of::<EditCmdSchema>),
+    ),
+    endpoint(
+        "/map/get-edit-intersection-command",
+        "An edit command for an intersection, with the current state filled out to modify",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "IntersectionID"),
+        ],
+        None,
+        ResponseType::Json(of::<EditCmdSchema>),
+    ),
+    endpoint(
+        "/map/get-edit-crosswalks-command",
+        "An edit command for an intersection's crosswalks, with the current state filled out",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "IntersectionID"),
+        ],
+        None,
+        ResponseType::Json(of::<EditCmdSchema>),
+    ),
+    endpoint(
+        "/map/get-edit-route-schedule-command",
+        "An edit command for a transit route's schedule, with the current state filled out",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "TransitRouteID"),
+        ],
+        None,
+        ResponseType::Json(of::<EditCmdSchema>),
+    ),
+    endpoint(
+        "/map/apply-edit",
+        "Apply one more edit command, unless it disconnects part of the map",
+        SESSION_ONLY,
+        Some(of::<EditCmdSchema>),
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/map/undo-edit",
+        "Undo the most recent edit command",
+        SESSION_ONLY,
+        None,
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/map/set-edits",
+        "Replace all map edits, unless they disconnect part of the map",
+        SESSION_ONLY,
+        Some(of::<MapEditsSchema>),
+        ResponseType::Text,
+    ),
+    endpoint(
+        "/map/get-intersection-geometry",
+        "The geometry of one intersection and its roads",
+        &[
+            SESSION,
+            param("id", ParamKind::Integer, true, "IntersectionID"),
+        ],
+        None,
+        ResponseType::Json(of::<GeoJsonSchema>),
+    ),
+    endpoint(
+        "/map/get-all-geometry",
+        "The geometry of the whole map",
+        SESSION_ONLY,
+        None,
+        ResponseType::Json(of::<GeoJsonSchema>),
+    ),
+    endpoint(
+        "/map/get-nearest-road",
+        "The road closest to a point, within some distance",
+        &[
+            SESSION,
+            param("lon", ParamKind::Number, true, "Longitude"),
+            param("lat", ParamKind::Number, true, "Latitude"),
+            param(
+                "threshold_meters",
+                ParamKind::Number,
+                true,
+                "How far to search",
+            ),
+        ],
+        None,
+        ResponseType::Json(of::<RoadID>),
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use super::*;
+
+    #[test]
+    fn schema_refs_exist() {
+        let doc = openapi();
+        let schemas = doc["components"]["schemas"].as_object().unwrap();
+        let mut refs = Vec::new();
+        find_refs(&doc, &mut refs);
+        for r in refs {
+            let name = r.trim_start_matches("#/components/schemas/");
+            assert!(schemas.contains_key(name), "{} isn't defined", r);
+        }
+    }
+
+    #[test]
+    fn units_match_json() {
+        let doc = openapi();
+        let schemas = &doc["components"]["schemas"];
+        for name in ["Time", "Duration", "Distance"] {
+            assert_eq!(schemas[name]["type"], "integer", "{}", name);
+        }
+        assert_eq!(
+            schemas["Incident"]["properties"]["start"]["$ref"],
+            "#/components/schemas/Time"
+        );
+    }
+
+    #[test]
+    fn handled_routes_match_endpoints() {
+        // Every route main.rs handles is either a match arm or a special case in serve_req
+        let mut handled = BTreeSet::new();
+        for line in include_str!("main.rs").lines() {
+            let line = line.trim().trim_start_matches("if path == ");
+            if let Some((route, rest)) =
+                line.strip_prefix('"').and_then(|line| line.split_once('"'))
+            {
+                if route.starts_with('/') && (rest.starts_with(" =>") || rest.starts_with(" {")) {
+                    handled.insert(route);
+                }
+            }
+        }
+        let documented: BTreeSet<&str> = ENDPOINTS.iter().map(|e| e.path).collect();
+        assert_eq!(handled, documented);
+    }
+
+    #[test]
+    fn traffic_signals_and_modifiers_have_schemas() {
+        let doc = openapi();
+        let schemas = &doc["components"]["schemas"];
+        assert!(schemas["ControlTrafficSignal"]["properties"]["stages"].is_object());
+        assert!(schemas["ControlTrafficSignal"]["properties"]
+            .get("coordinated")
+            .is_none());
+        assert!(schemas["ScenarioModifier"]["oneOf"].is_array());
+    }
+
+    fn find_refs(value: &Value, refs: &mut Vec<String>) {
+        match value {
+            Value::Object(map) => {
+                for (k, v) in map {
+                    if k == "$ref" {
+                        refs.push(v.as_str().unwrap().to_string());
+                    } else {
+                        find_refs(v, refs);
+                    }
+                }
+            }
+            Value::Array(list) => {
+                for v in list {
+                    find_refs(v, refs);
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -9,14 +9,9 @@ use kml::ExtraShapes;
 use map_model::BuildingType;
 use raw_map::RawMap;
 
-use crate::configuration::ImporterConfiguration;
 use crate::utils::{download, download_kml};
 
-pub async fn import_extra_data(
-    map: &RawMap,
-    config: &ImporterConfiguration,
-    timer: &mut Timer<'_>,
-) {
+pub async fn import_extra_data(map: &RawMap, timer: &mut Timer<'_>) {
     // From https://data.technologiestiftung-berlin.de/dataset/lor_planungsgraeume/en
     download_kml(
         map.get_city_name().input_path("planning_areas.bin"),
@@ -30,7 +25,6 @@ pub async fn import_extra_data(
     // From
     // https://daten.berlin.de/datensaetze/einwohnerinnen-und-einwohner-berlin-lor-planungsr%C3%A4umen-am-31122018
     download(
-        config,
         map.get_city_name().input_path("EWR201812E_Matrix.csv"),
         "https://www.statistik-berlin-brandenburg.de/opendata/EWR201812E_Matrix.csv",
     )
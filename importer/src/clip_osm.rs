@@ -5,30 +5,56 @@ use anyhow::Result;
 use fs_err::File;
 use geo::prelude::Contains;
 use geo::{LineString, Point, Polygon};
-use osmio::obj_types::ArcOSMObj;
 use osmio::{Node, OSMObj, OSMObjBase, OSMObjectType, OSMReader, OSMWriter, Relation, Way};
 
 use geom::LonLat;
 
-pub fn run(pbf_path: String, clip_path: String, out_path: String) -> Result<()> {
-    let boundary_pts = LonLat::read_geojson_polygon(&clip_path)?;
+/// Clips an .osm.pbf or .osm file to a boundary, writing the result as XML. This is a simple Rust
+/// port of `osmium extract large_map.osm -p clipping.geojson -o smaller_map.osm`, using the
+/// default `complete_ways` strategy.
+pub fn clip_osm(input: &str, clip_path: &str, output: &str) -> Result<()> {
+    let boundary_pts = LonLat::read_geojson_polygon(clip_path)?;
     let raw_pts: Vec<(f64, f64)> = boundary_pts
         .into_iter()
         .map(|pt| (pt.x(), pt.y()))
         .collect();
     let boundary = Polygon::new(LineString::from(raw_pts), Vec::new());
-    clip(&pbf_path, &boundary, &out_path)
+    if input.ends_with(".pbf") {
+        clip(
+            || {
+                Ok(osmio::pbf::PBFReader::new(BufReader::new(File::open(
+                    input,
+                )?)))
+            },
+            &boundary,
+            output,
+        )
+    } else {
+        clip(
+            || {
+                Ok(osmio::xml::XMLReader::new(BufReader::new(File::open(
+                    input,
+                )?)))
+            },
+            &boundary,
+            output,
+        )
+    }
 }
 
-fn clip(pbf_path: &str, boundary: &Polygon, out_path: &str) -> Result<()> {
-    // TODO Maybe just have a single map with RcOSMObj. But then the order we write will be wrong.
+/// Reads the input twice, so takes a way to open it.
+fn clip<R: OSMReader, F: Fn() -> Result<R>>(
+    open: F,
+    boundary: &Polygon,
+    out_path: &str,
+) -> Result<()> {
+    let mut node_ids_within_boundary: HashSet<i64> = HashSet::new();
     let mut way_node_ids: HashSet<i64> = HashSet::new();
     let mut way_ids: HashSet<i64> = HashSet::new();
     let mut relation_ids: HashSet<i64> = HashSet::new();
     {
         // First Pass: accumulate the IDs we want to include in the output
-        let mut reader = osmio::pbf::PBFReader::new(BufReader::new(File::open(pbf_path)?));
-        let mut node_ids_within_boundary: HashSet<i64> = HashSet::new();
+        let mut reader = open()?;
         for obj in reader.objects() {
             match obj.object_type() {
                 OSMObjectType::Node => {
@@ -70,24 +96,19 @@ fn clip(pbf_path: &str, boundary: &Polygon, out_path: &str) -> Result<()> {
 
     let mut writer = osmio::xml::XMLWriter::new(BufWriter::new(File::create(out_path)?));
     // Second Pass: write the feature for each ID accumulated in the first pass
-    let mut reader = osmio::pbf::PBFReader::new(BufReader::new(File::open(pbf_path)?));
+    let mut reader = open()?;
     for obj in reader.objects() {
-        match &obj {
-            ArcOSMObj::Node(node) => {
-                if way_node_ids.contains(&node.id()) {
-                    writer.write_obj(&obj)?;
-                }
-            }
-            ArcOSMObj::Way(way) => {
-                if way_ids.contains(&way.id()) {
-                    writer.write_obj(&obj)?;
-                }
-            }
-            ArcOSMObj::Relation(relation) => {
-                if relation_ids.contains(&relation.id()) {
-                    writer.write_obj(&obj)?;
-                }
+        let keep = match obj.object_type() {
+            // Like osmium, keep standalone nodes inside the boundary too, since they describe
+            // amenities, bus stops, and so on
+            OSMObjectType::Node => {
+                node_ids_within_boundary.contains(&obj.id()) || way_node_ids.contains(&obj.id())
             }
+            OSMObjectType::Way => way_ids.contains(&obj.id()),
+            OSMObjectType::Relation => relation_ids.contains(&obj.id()),
+        };
+        if keep {
+            writer.write_obj(&obj)?;
         }
     }
 
@@ -13,14 +13,15 @@ use structopt::StructOpt;
 
 use abstio::{CityName, MapName};
 use abstutil::Timer;
+use convert_osm::ServiceDay;
 use map_model::RawToMapOptions;
 
-use self::configuration::{load_configuration, ImporterConfiguration};
+pub use self::clip_osm::clip_osm;
 pub use self::pick_geofabrik::pick_geofabrik;
 
 mod basemap;
 mod berlin;
-mod configuration;
+mod clip_osm;
 mod map_config;
 mod pick_geofabrik;
 mod seattle;
@@ -75,9 +76,7 @@ pub async fn oneshot(
 
     if create_uk_travel_demand_model {
         timer.start("generating UK travel demand model");
-        uk::generate_scenario(&map, &load_configuration(), &mut timer)
-            .await
-            .unwrap();
+        uk::generate_scenario(&map, &mut timer).await.unwrap();
         timer.stop("generating UK travel demand model");
     }
 
@@ -106,6 +105,10 @@ pub struct Job {
     /// importer/config/$city/.
     #[structopt()]
     pub only_map: Option<String>,
+    /// Which day's schedule to import for maps with a GTFS feed: a date like 2021-06-02, or a day
+    /// of the week like wed.
+    #[structopt(long, default_value = "wed")]
+    pub gtfs_service_day: ServiceDay,
 
     #[structopt(flatten)]
     pub opts: RawToMapOptions,
@@ -120,6 +123,7 @@ impl Job {
             scenario: false,
             city_overview: false,
             only_map: None,
+            gtfs_service_day: ServiceDay::Weekday(chrono::Weekday::Wed),
             opts: RawToMapOptions::default(),
         };
         // Only some maps run extra tasks
@@ -174,8 +178,6 @@ impl Job {
             std::process::exit(1);
         }
 
-        let config: ImporterConfiguration = load_configuration();
-
         timer.start(format!("import {}", self.city.describe()));
         let names = if let Some(n) = self.only_map {
             println!("- Just working on {}", n);
@@ -194,8 +196,8 @@ impl Job {
         {
             timer.start("ensure_popdat_exists");
             let (popdat, huge_map) = seattle::ensure_popdat_exists(
+                self.gtfs_service_day,
                 timer,
-                &config,
                 &mut built_raw_huge_seattle,
                 &mut built_map_huge_seattle,
             )
@@ -214,16 +216,16 @@ impl Job {
             if self.osm_to_raw
                 && (!built_raw_huge_seattle || name != MapName::seattle("huge_seattle"))
             {
-                let raw = utils::osm_to_raw(name.clone(), timer, &config).await;
+                let raw = utils::osm_to_raw(name.clone(), self.gtfs_service_day, timer).await;
 
                 // The collision data will only cover one part of London, since we don't have a
                 // region-wide map there yet
                 if name.city == CityName::new("de", "berlin") {
-                    berlin::import_extra_data(&raw, &config, timer).await;
+                    berlin::import_extra_data(&raw, timer).await;
                 } else if name == MapName::new("gb", "leeds", "huge") {
-                    uk::import_collision_data(&raw, &config, timer).await;
+                    uk::import_collision_data(&raw, timer).await;
                 } else if name == MapName::new("gb", "london", "camden") {
-                    uk::import_collision_data(&raw, &config, timer).await;
+                    uk::import_collision_data(&raw, timer).await;
                 }
             }
 
@@ -320,7 +322,7 @@ impl Job {
                         map.minify_buildings(timer);
                         map.save();
                     } else {
-                        uk::generate_scenario(maybe_map.as_ref().unwrap(), &config, timer)
+                        uk::generate_scenario(maybe_map.as_ref().unwrap(), timer)
                             .await
                             .unwrap();
                     }
@@ -10,10 +10,9 @@ use map_model::{BuildingID, BuildingType, Map};
 use sim::count_parked_cars_per_bldg;
 use synthpop::Scenario;
 
-use crate::configuration::ImporterConfiguration;
 use crate::utils::{download, download_kml};
 
-pub async fn input(config: &ImporterConfiguration, timer: &mut Timer<'_>) {
+pub async fn input(timer: &mut Timer<'_>) {
     let city = CityName::seattle();
 
     // Soundcast data was originally retrieved from staff at PSRC via a download link that didn't
@@ -23,13 +22,11 @@ pub async fn input(config: &ImporterConfiguration, timer: &mut Timer<'_>) {
     // the importer pipeline to depend on something in data/input in S3, but this should let
     // anybody run the full pipeline.
     download(
-        config,
         city.input_path("parcels_urbansim.txt"),
         "http://abstreet.s3-website.us-east-2.amazonaws.com/dev/data/input/us/seattle/parcels_urbansim.txt.gz",
     )
     .await;
     download(
-        config,
         city.input_path("trips_2014.csv"),
         "http://abstreet.s3-website.us-east-2.amazonaws.com/dev/data/input/us/seattle/trips_2014.csv.gz",
     )
@@ -60,7 +57,6 @@ pub async fn input(config: &ImporterConfiguration, timer: &mut Timer<'_>) {
     // From
     // https://data-seattlecitygis.opendata.arcgis.com/datasets/5b5c745e0f1f48e7a53acec63a0022ab_0
     download(
-        config,
         city.input_path("collisions.kml"),
         "https://opendata.arcgis.com/datasets/5b5c745e0f1f48e7a53acec63a0022ab_0.kml",
     )
@@ -100,8 +96,8 @@ pub async fn input(config: &ImporterConfiguration, timer: &mut Timer<'_>) {
 
 /// Download and pre-process data needed to generate Seattle scenarios.
 pub async fn ensure_popdat_exists(
+    gtfs_service_day: convert_osm::ServiceDay,
     timer: &mut Timer<'_>,
-    config: &ImporterConfiguration,
     built_raw_huge_seattle: &mut bool,
     built_map_huge_seattle: &mut bool,
 ) -> (crate::soundcast::PopDat, map_model::Map) {
@@ -116,7 +112,7 @@ pub async fn ensure_popdat_exists(
     }
 
     if !abstio::file_exists(abstio::path_raw_map(&huge_name)) {
-        crate::utils::osm_to_raw(MapName::seattle("huge_seattle"), timer, config).await;
+        crate::utils::osm_to_raw(MapName::seattle("huge_seattle"), gtfs_service_day, timer).await;
         *built_raw_huge_seattle = true;
     }
     let huge_map = if abstio::file_exists(huge_name.path()) {
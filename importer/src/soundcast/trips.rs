@@ -1,4 +1,4 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
 use abstutil::{prettyprint_usize, MultiMap, Timer};
 use geom::PolyLine;
@@ -293,6 +293,7 @@ pub fn make_scenario(
         map_name: map.get_name().clone(),
         people,
         only_seed_buses: None,
+        transit_capacities: BTreeMap::new(),
     }
     .remove_weird_schedules(true)
 }
@@ -14,16 +14,10 @@ use popdat::od::DesireLine;
 use raw_map::RawMap;
 use synthpop::{Scenario, TrafficCounts, TripEndpoint, TripMode};
 
-use crate::configuration::ImporterConfiguration;
 use crate::utils::download;
 
-pub async fn import_collision_data(
-    map: &RawMap,
-    config: &ImporterConfiguration,
-    timer: &mut Timer<'_>,
-) {
+pub async fn import_collision_data(map: &RawMap, timer: &mut Timer<'_>) {
     download(
-        config,
         path_shared_input("Road Safety Data - Accidents 2019.csv"),
         "http://data.dft.gov.uk.s3.amazonaws.com/road-accidents-safety-data/DfTRoadSafety_Accidents_2019.zip").await;
 
@@ -43,20 +37,14 @@ pub async fn import_collision_data(
     );
 }
 
-pub async fn generate_scenario(
-    map: &Map,
-    config: &ImporterConfiguration,
-    timer: &mut Timer<'_>,
-) -> Result<()> {
+pub async fn generate_scenario(map: &Map, timer: &mut Timer<'_>) -> Result<()> {
     timer.start("prepare input");
     download(
-        config,
         path_shared_input("wu03ew_v2.csv"),
         "https://s3-eu-west-1.amazonaws.com/statistics.digitalresources.jisc.ac.uk/dkan/files/FLOW/wu03ew_v2/wu03ew_v2.csv").await;
     // https://mapit.mysociety.org/area/45350.html (for geocode) E02004277 is an example place to
     // debug where these zones are.
     download(
-        config,
         path_shared_input("zones_core.geojson"),
         "https://github.com/cyipt/actdev/releases/download/0.1.13/zones_core.geojson",
     )
@@ -1,16 +1,16 @@
+use std::io::{BufReader, BufWriter};
 use std::path::Path;
-use std::process::Command;
+
+use fs_err::File;
 
 use abstio::{CityName, MapName};
-use abstutil::{must_run_cmd, Timer};
+use abstutil::Timer;
 use map_model::RawToMapOptions;
 use raw_map::RawMap;
 
-use crate::configuration::ImporterConfiguration;
-
 /// If the output file doesn't already exist, downloads the URL into that location. Automatically
 /// uncompresses .zip and .gz files. Assumes a proper path is passed in (including data/).
-pub async fn download(config: &ImporterConfiguration, output: String, url: &str) {
+pub async fn download(output: String, url: &str) {
     if Path::new(&output).exists() {
         println!("- {} already exists", output);
         return;
@@ -32,17 +32,16 @@ pub async fn download(config: &ImporterConfiguration, output: String, url: &str)
             Path::new(&output).parent().unwrap().display().to_string()
         };
         println!("- Unzipping into {}", unzip_to);
-        must_run_cmd(Command::new(&config.unzip).arg(tmp).arg("-d").arg(unzip_to));
+        let mut archive = zip::ZipArchive::new(BufReader::new(File::open(tmp).unwrap())).unwrap();
+        archive.extract(unzip_to).unwrap();
         fs_err::remove_file(tmp).unwrap();
     } else if url.contains(".gz") {
         println!("- Gunzipping");
-        fs_err::rename(tmp, format!("{}.gz", output)).unwrap();
-
-        let mut gunzip_cmd = Command::new(&config.gunzip);
-        for arg in config.gunzip_args.split_ascii_whitespace() {
-            gunzip_cmd.arg(arg);
-        }
-        must_run_cmd(gunzip_cmd.arg(format!("{}.gz", output)));
+        let mut decoder =
+            flate2::read::MultiGzDecoder::new(BufReader::new(File::open(tmp).unwrap()));
+        let mut out = BufWriter::new(File::create(&output).unwrap());
+        std::io::copy(&mut decoder, &mut out).unwrap();
+        fs_err::remove_file(tmp).unwrap();
     } else {
         fs_err::rename(tmp, output).unwrap();
     }
@@ -83,9 +82,9 @@ pub async fn download_kml(
     fs_err::rename(tmp, output.replace(".bin", ".kml")).unwrap();
 }
 
-/// Uses osmium to clip the input .osm (or .pbf) against a polygon and produce some output.  Skips
-/// if the output exists.
-fn osmium(input: String, clipping_polygon: String, output: String, config: &ImporterConfiguration) {
+/// Clips the input .osm (or .pbf) against a polygon and produces some output. Skips if the
+/// output exists.
+fn clip(input: String, clipping_polygon: String, output: String) {
     if Path::new(&output).exists() {
         println!("- {} already exists", output);
         return;
@@ -95,34 +94,22 @@ fn osmium(input: String, clipping_polygon: String, output: String, config: &Impo
         .expect("Creating parent dir failed");
 
     println!("- Clipping {} to {}", input, clipping_polygon);
-
-    // --strategy complete_ways is default
-    must_run_cmd(
-        Command::new(&config.osmium)
-            .arg("extract")
-            .arg("-p")
-            .arg(clipping_polygon)
-            .arg(input)
-            .arg("-o")
-            .arg(output)
-            .arg("-f")
-            // Smaller files without author, timestamp, version
-            .arg("osm,add_metadata=false"),
-    );
+    crate::clip_osm(&input, &clipping_polygon, &output).unwrap();
 }
 
 /// Creates a RawMap from OSM and other input data.
 pub async fn osm_to_raw(
     name: MapName,
+    gtfs_service_day: convert_osm::ServiceDay,
     timer: &mut abstutil::Timer<'_>,
-    config: &ImporterConfiguration,
 ) -> RawMap {
     if name.city == CityName::seattle() {
-        crate::seattle::input(config, timer).await;
+        crate::seattle::input(timer).await;
     }
     let opts = crate::map_config::config_for_map(&name);
+    let has_gtfs = opts.gtfs_url.is_some();
     if let Some(ref url) = opts.gtfs_url {
-        download(config, name.city.input_path("gtfs/"), url).await;
+        download(name.city.input_path("gtfs/"), url).await;
     }
 
     let boundary_polygon = format!(
@@ -142,22 +129,24 @@ pub async fn osm_to_raw(
             .into_string()
             .unwrap()
     ));
-    download(config, local_osm_file.clone(), &osm_url).await;
+    download(local_osm_file.clone(), &osm_url).await;
 
-    osmium(
+    clip(
         local_osm_file,
         boundary_polygon.clone(),
         name.city.input_path(format!("osm/{}.osm", name.map)),
-        config,
     );
 
-    let map = convert_osm::convert(
+    let mut map = convert_osm::convert(
         name.city.input_path(format!("osm/{}.osm", name.map)),
         name.clone(),
         Some(boundary_polygon),
         opts,
         timer,
     );
+    if has_gtfs {
+        convert_osm::import_gtfs(&mut map, gtfs_service_day).unwrap();
+    }
     map.save();
     map
 }
@@ -570,6 +570,22 @@ impl DrawMap {
         self.roads[road.id.0] = draw;
     }
 
+    /// The stop may have been created, moved, or deleted.
+    pub fn recreate_transit_stop(
+        &mut self,
+        ctx: &EventCtx,
+        id: TransitStopID,
+        map: &Map,
+        cs: &ColorScheme,
+    ) {
+        if let Some(stop) = map.maybe_get_ts(id) {
+            self.bus_stops
+                .insert(id, DrawTransitStop::new(ctx, stop, map, cs));
+        } else {
+            self.bus_stops.remove(&id);
+        }
+    }
+
     pub fn free_memory(&mut self) {
         // Clear the lazily evaluated zoomed-in details
         for r in &mut self.roads {
@@ -0,0 +1,252 @@
+//! Batch accessibility analysis: from every home, how many jobs or amenities can somebody reach
+//! by different modes? Comparing the results before and after map edits shows who gains or loses
+//! access.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::Timer;
+use geom::{Duration, Time};
+
+use crate::connectivity::{
+    all_transit_costs_from, all_vehicle_costs_from, all_walking_costs_from, Spot, TransitRides,
+    WalkingOptions,
+};
+use crate::{AmenityType, Building, BuildingID, BuildingType, Map, PathConstraints};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum AccessMode {
+    Walk,
+    Bike,
+    /// Walking and riding public transit
+    Transit,
+    /// Driving, ignoring traffic and the time to find parking
+    Car,
+}
+
+impl AccessMode {
+    pub fn all() -> Vec<AccessMode> {
+        vec![
+            AccessMode::Walk,
+            AccessMode::Bike,
+            AccessMode::Transit,
+            AccessMode::Car,
+        ]
+    }
+}
+
+impl fmt::Display for AccessMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessMode::Walk => write!(f, "walk"),
+            AccessMode::Bike => write!(f, "bike"),
+            AccessMode::Transit => write!(f, "transit"),
+            AccessMode::Car => write!(f, "car"),
+        }
+    }
+}
+
+impl FromStr for AccessMode {
+    type Err = anyhow::Error;
+
+    fn from_str(x: &str) -> Result<AccessMode> {
+        for mode in AccessMode::all() {
+            if mode.to_string() == x {
+                return Ok(mode);
+            }
+        }
+        bail!("Unknown mode {}; try walk, bike, transit, or car", x)
+    }
+}
+
+/// Something people want to reach
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Opportunity {
+    Jobs,
+    Amenity(AmenityType),
+}
+
+impl Opportunity {
+    /// How many of these are in a building?
+    pub fn count(self, b: &Building) -> usize {
+        match self {
+            Opportunity::Jobs => match b.bldg_type {
+                BuildingType::ResidentialCommercial(_, workers)
+                | BuildingType::Commercial(workers) => workers,
+                BuildingType::Residential { .. } | BuildingType::Empty => 0,
+            },
+            Opportunity::Amenity(category) => b
+                .amenities
+                .iter()
+                .filter(|a| AmenityType::categorize(&a.amenity_type) == Some(category))
+                .count(),
+        }
+    }
+}
+
+impl fmt::Display for Opportunity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Opportunity::Jobs => write!(f, "jobs"),
+            Opportunity::Amenity(category) => write!(f, "{}", category),
+        }
+    }
+}
+
+impl FromStr for Opportunity {
+    type Err = anyhow::Error;
+
+    /// Either "jobs" or an `AmenityType`, like "Supermarket"
+    fn from_str(x: &str) -> Result<Opportunity> {
+        if x == "jobs" {
+            return Ok(Opportunity::Jobs);
+        }
+        match AmenityType::from_str(x) {
+            Ok(category) => Ok(Opportunity::Amenity(category)),
+            Err(_) => bail!("Unknown opportunity {}; try jobs or an amenity type", x),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct AccessibilityOptions {
+    pub modes: Vec<AccessMode>,
+    pub opportunities: Vec<Opportunity>,
+    /// Only count opportunities reachable within this long
+    pub time_limit: Duration,
+    /// For gravity scores, an opportunity counts half as much for every additional half_life it
+    /// takes to reach
+    pub half_life: Duration,
+    pub walking: WalkingOptions,
+    /// If specified, transit trips leave at this time and wait for scheduled vehicles. Otherwise,
+    /// they wait the average time between vehicles.
+    pub transit_departure: Option<Time>,
+}
+
+#[derive(Clone, Copy, Default, Debug, PartialEq)]
+pub struct Score {
+    /// How many opportunities are reachable within the time limit
+    pub cumulative: usize,
+    /// Like `cumulative`, but opportunities further away count less
+    pub gravity: f64,
+}
+
+pub struct Accessibility {
+    pub opts: AccessibilityOptions,
+    /// For every building with residents and each mode, one score per opportunity, in the same
+    /// order as `opts.opportunities`
+    pub scores: BTreeMap<BuildingID, BTreeMap<AccessMode, Vec<Score>>>,
+}
+
+impl Accessibility {
+    pub fn new(map: &Map, opts: AccessibilityOptions, timer: &mut Timer) -> Accessibility {
+        // Only remember buildings with something to reach
+        let mut opportunities: HashMap<BuildingID, Vec<usize>> = HashMap::new();
+        for b in map.all_buildings() {
+            let counts: Vec<usize> = opts.opportunities.iter().map(|o| o.count(b)).collect();
+            if counts.iter().any(|x| *x > 0) {
+                opportunities.insert(b.id, counts);
+            }
+        }
+        let homes: Vec<BuildingID> = map
+            .all_buildings()
+            .iter()
+            .filter(|b| b.bldg_type.has_residents())
+            .map(|b| b.id)
+            .collect();
+        let transit = if opts.modes.contains(&AccessMode::Transit) {
+            timer.start("calculate transit rides");
+            let rides = TransitRides::new(map);
+            timer.stop("calculate transit rides");
+            Some(rides)
+        } else {
+            None
+        };
+
+        let mut scores: BTreeMap<BuildingID, BTreeMap<AccessMode, Vec<Score>>> = BTreeMap::new();
+        for mode in opts.modes.clone() {
+            let opts = &opts;
+            let opportunities = &opportunities;
+            let transit = transit.as_ref();
+            for (b, per_opportunity) in timer.parallelize(
+                &format!("calculate {} accessibility", mode),
+                homes.clone(),
+                |b| {
+                    let starts = vec![Spot::Building(b)];
+                    let costs = match mode {
+                        AccessMode::Walk => all_walking_costs_from(
+                            map,
+                            starts,
+                            opts.time_limit,
+                            opts.walking.clone(),
+                        ),
+                        AccessMode::Bike => all_vehicle_costs_from(
+                            map,
+                            starts,
+                            opts.time_limit,
+                            PathConstraints::Bike,
+                        ),
+                        AccessMode::Transit => all_transit_costs_from(
+                            map,
+                            starts,
+                            opts.time_limit,
+                            opts.walking.clone(),
+                            transit.unwrap(),
+                            opts.transit_departure,
+                        ),
+                        AccessMode::Car => all_vehicle_costs_from(
+                            map,
+                            starts,
+                            opts.time_limit,
+                            PathConstraints::Car,
+                        ),
+                    };
+                    (b, opts.score(&costs, opportunities))
+                },
+            ) {
+                scores.entry(b).or_default().insert(mode, per_opportunity);
+            }
+        }
+
+        Accessibility { opts, scores }
+    }
+
+    pub fn get(&self, b: BuildingID, mode: AccessMode, opportunity: Opportunity) -> Option<Score> {
+        let idx = self
+            .opts
+            .opportunities
+            .iter()
+            .position(|o| *o == opportunity)?;
+        Some(self.scores.get(&b)?.get(&mode)?[idx])
+    }
+}
+
+impl AccessibilityOptions {
+    fn score(
+        &self,
+        costs: &HashMap<BuildingID, Duration>,
+        opportunities: &HashMap<BuildingID, Vec<usize>>,
+    ) -> Vec<Score> {
+        // Sum in a fixed order, so gravity scores are deterministic
+        let mut reached: Vec<(BuildingID, Duration)> = costs
+            .iter()
+            .filter(|(b, _)| opportunities.contains_key(b))
+            .map(|(b, cost)| (*b, *cost))
+            .collect();
+        reached.sort_by_key(|(b, _)| *b);
+
+        let mut scores = vec![Score::default(); self.opportunities.len()];
+        for (b, cost) in reached {
+            let weight = 0.5_f64.powf(cost / self.half_life);
+            for (score, count) in scores.iter_mut().zip(&opportunities[&b]) {
+                score.cumulative += *count;
+                score.gravity += (*count as f64) * weight;
+            }
+        }
+        scores
+    }
+}
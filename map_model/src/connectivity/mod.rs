@@ -8,10 +8,16 @@ use petgraph::graphmap::DiGraphMap;
 use abstutil::PriorityQueueItem;
 use geom::Duration;
 
+pub use self::accessibility::{
+    AccessMode, Accessibility, AccessibilityOptions, Opportunity, Score,
+};
+pub use self::transit::{all_transit_costs_from, TransitRides};
 pub use self::walking::{all_walking_costs_from, WalkingOptions};
 pub use crate::pathfind::{vehicle_cost, WalkingNode};
 use crate::{BuildingID, DirectedRoadID, IntersectionID, LaneID, Map, PathConstraints};
 
+mod accessibility;
+mod transit;
 mod walking;
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
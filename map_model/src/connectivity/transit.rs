@@ -0,0 +1,283 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use abstutil::PriorityQueueItem;
+use geom::{Duration, Time};
+
+use crate::connectivity::walking::{start_walking, walking_costs};
+use crate::connectivity::{Spot, WalkingOptions};
+use crate::pathfind::WalkingNode;
+use crate::{BuildingID, Map, PathConstraints, PathStep, TransitStopID};
+
+/// Nobody transfers more than this many times.
+const MAX_RIDES: usize = 3;
+
+/// When transit vehicles reach each stop. This is calculated once and shared by many searches.
+#[derive(Clone)]
+pub struct TransitRides {
+    routes: Vec<RouteRides>,
+}
+
+#[derive(Clone)]
+struct RouteRides {
+    stops: Vec<TransitStopID>,
+    /// For every vehicle in the order they spawn, when it reaches each stop
+    arrivals: Vec<Vec<Time>>,
+    /// How long after spawning a typical vehicle reaches each stop
+    offsets: Vec<Duration>,
+    /// On average, how long somebody arriving at a stop waits for the next vehicle. None if only
+    /// one vehicle runs all day.
+    expected_wait: Option<Duration>,
+}
+
+impl TransitRides {
+    pub fn new(map: &Map) -> TransitRides {
+        let mut routes = Vec::new();
+        for route in map.all_transit_routes() {
+            // Edits can leave spawn times out of order, but vehicles are looked up by time
+            let mut spawn_times = route.spawn_times.clone();
+            spawn_times.sort();
+            let scheduled: Option<Vec<Vec<Time>>> = spawn_times
+                .iter()
+                .map(|spawned| {
+                    (0..route.stops.len())
+                        .map(|idx| route.scheduled_arrival(map, *spawned, idx))
+                        .collect()
+                })
+                .collect();
+            let arrivals = if let Some(arrivals) = scheduled {
+                arrivals
+            } else {
+                // Without a schedule, assume the vehicle goes the speed limit and doesn't wait
+                // at stops
+                match route.all_paths(map) {
+                    Ok(paths) => {
+                        // Path i leads to stop i
+                        let mut offsets = Vec::new();
+                        let mut total = Duration::ZERO;
+                        for path in &paths[0..route.stops.len()] {
+                            total += path.estimate_duration(map, None);
+                            offsets.push(total);
+                        }
+                        spawn_times
+                            .iter()
+                            .map(|spawned| offsets.iter().map(|x| *spawned + *x).collect())
+                            .collect()
+                    }
+                    Err(err) => {
+                        warn!("Skipping {} for transit rides: {}", route.long_name, err);
+                        continue;
+                    }
+                }
+            };
+            let offsets = if arrivals.is_empty() {
+                Vec::new()
+            } else {
+                let typical = arrivals.len() / 2;
+                arrivals[typical]
+                    .iter()
+                    .map(|t| *t - spawn_times[typical])
+                    .collect()
+            };
+            routes.push(RouteRides {
+                stops: route.stops.clone(),
+                arrivals,
+                offsets,
+                expected_wait: expected_wait(&spawn_times),
+            });
+        }
+        TransitRides { routes }
+    }
+}
+
+impl RouteRides {
+    /// Given the cost to reach some stops, board this route and return the cost to reach later
+    /// stops. If the departure time is known, wait for the next scheduled vehicle. Otherwise,
+    /// assume the average wait.
+    fn ride(
+        &self,
+        frontier: &HashMap<TransitStopID, Duration>,
+        departure: Option<Time>,
+    ) -> Vec<(TransitStopID, Duration)> {
+        let mut results = Vec::new();
+        if let Some(departure) = departure {
+            // Vehicles don't pass each other, so the earliest one boarded so far is the best
+            let mut boarded: Option<usize> = None;
+            for (idx, stop) in self.stops.iter().enumerate() {
+                if let Some(vehicle) = boarded {
+                    results.push((*stop, self.arrivals[vehicle][idx] - departure));
+                }
+                if let Some(cost) = frontier.get(stop) {
+                    let ready = departure + *cost;
+                    let vehicle = self.arrivals.partition_point(|times| times[idx] < ready);
+                    if vehicle < self.arrivals.len() && boarded.map(|x| vehicle < x).unwrap_or(true)
+                    {
+                        boarded = Some(vehicle);
+                    }
+                }
+            }
+        } else if let Some(wait) = self.expected_wait {
+            // When somebody boarding the best vehicle so far got on, and where
+            let mut boarded: Option<(Duration, Duration)> = None;
+            for (stop, offset) in self.stops.iter().zip(self.offsets.iter()) {
+                if let Some((board_time, board_offset)) = boarded {
+                    results.push((*stop, board_time + (*offset - board_offset)));
+                }
+                if let Some(cost) = frontier.get(stop) {
+                    let board_time = *cost + wait;
+                    // Would somebody boarding here get further than the vehicle boarded before?
+                    let better = match boarded {
+                        Some((best_time, best_offset)) => {
+                            board_time + best_offset < best_time + *offset
+                        }
+                        None => true,
+                    };
+                    if better {
+                        boarded = Some((board_time, *offset));
+                    }
+                }
+            }
+        }
+        results
+    }
+}
+
+/// Half of the average time between vehicles
+fn expected_wait(spawn_times: &[Time]) -> Option<Duration> {
+    if spawn_times.len() < 2 {
+        return None;
+    }
+    let span = *spawn_times.last().unwrap() - spawn_times[0];
+    Some(span / ((spawn_times.len() - 1) as f64) / 2.0)
+}
+
+/// Starting from some initial spots, calculate the cost to all buildings by walking and riding
+/// public transit. If a destination isn't reachable, it won't be included in the results. Ignore
+/// results greater than the time_limit away.
+///
+/// When leaving at `departure`, boarding a route means waiting for its next scheduled vehicle.
+/// Without a departure time, boarding costs the average wait over the day.
+pub fn all_transit_costs_from(
+    map: &Map,
+    starts: Vec<Spot>,
+    time_limit: Duration,
+    opts: WalkingOptions,
+    rides: &TransitRides,
+    departure: Option<Time>,
+) -> HashMap<BuildingID, Duration> {
+    let (mut results, mut stop_costs) =
+        walking_costs(map, start_walking(map, starts, &opts), time_limit, &opts);
+    // Stops reached more quickly in the last round, where somebody might board something new
+    let mut frontier = stop_costs.clone();
+
+    for _ in 0..MAX_RIDES {
+        let mut alight: HashMap<TransitStopID, Duration> = HashMap::new();
+        for route in &rides.routes {
+            for (stop, arrival) in route.ride(&frontier, departure) {
+                if arrival <= time_limit
+                    && stop_costs.get(&stop).map(|x| arrival < *x).unwrap_or(true)
+                    && alight.get(&stop).map(|x| arrival < *x).unwrap_or(true)
+                {
+                    alight.insert(stop, arrival);
+                }
+            }
+        }
+        if alight.is_empty() {
+            break;
+        }
+
+        // Walk from every stop where somebody got off
+        let mut queue: BinaryHeap<PriorityQueueItem<Duration, WalkingNode>> = BinaryHeap::new();
+        for (stop, cost) in &alight {
+            stop_costs.insert(*stop, *cost);
+            let pos = map.get_ts(*stop).sidewalk_pos;
+            let lane = map.get_l(pos.lane());
+            for (endpt, step, dist) in [
+                (false, PathStep::ContraflowLane(lane.id), pos.dist_along()),
+                (
+                    true,
+                    PathStep::Lane(lane.id),
+                    lane.length() - pos.dist_along(),
+                ),
+            ] {
+                let speed = step.max_speed_along(
+                    Some(opts.walking_speed),
+                    PathConstraints::Pedestrian,
+                    map,
+                );
+                queue.push(PriorityQueueItem {
+                    cost: *cost + dist / speed,
+                    value: WalkingNode::SidewalkEndpoint(lane.get_directed_parent(), endpt),
+                });
+            }
+        }
+        let (bldgs, stops) = walking_costs(map, queue, time_limit, &opts);
+        for (b, cost) in bldgs {
+            if results.get(&b).map(|x| cost < *x).unwrap_or(true) {
+                results.insert(b, cost);
+            }
+        }
+        frontier = alight;
+        for (stop, cost) in stops {
+            if stop_costs.get(&stop).map(|x| cost < *x).unwrap_or(true) {
+                stop_costs.insert(stop, cost);
+                frontier.insert(stop, cost);
+            }
+        }
+    }
+
+    results
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expected_wait() {
+        let start = Time::START_OF_DAY + Duration::hours(7);
+        // Every 20 minutes
+        let spawn_times: Vec<Time> = (0..4).map(|i| start + Duration::minutes(20 * i)).collect();
+        assert_eq!(expected_wait(&spawn_times), Some(Duration::minutes(10)));
+        assert_eq!(expected_wait(&[start]), None);
+    }
+
+    #[test]
+    fn test_scheduled_ride() {
+        let stop = |idx| TransitStopID {
+            road: crate::RoadID(0),
+            idx,
+        };
+        let start = Time::START_OF_DAY + Duration::hours(7);
+        let offsets = vec![
+            Duration::minutes(0),
+            Duration::minutes(5),
+            Duration::minutes(10),
+        ];
+        let route = RouteRides {
+            stops: vec![stop(0), stop(1), stop(2)],
+            // Every 20 minutes
+            arrivals: (0..4)
+                .map(|i| {
+                    let spawned = start + Duration::minutes(20 * i);
+                    offsets.iter().map(|x| spawned + *x).collect()
+                })
+                .collect(),
+            offsets,
+            expected_wait: Some(Duration::minutes(10)),
+        };
+        // Reach the middle stop 7 minutes after leaving at 7
+        let mut frontier = HashMap::new();
+        frontier.insert(stop(1), Duration::minutes(7));
+
+        // The 7:20 vehicle reaches the middle stop at 7:25 and the last stop at 7:30
+        assert_eq!(
+            route.ride(&frontier, Some(start)),
+            vec![(stop(2), Duration::minutes(30))]
+        );
+        // Without a departure time, wait an average of 10 minutes
+        assert_eq!(
+            route.ride(&frontier, None),
+            vec![(stop(2), Duration::minutes(22))]
+        );
+    }
+}
@@ -5,7 +5,7 @@ use geom::{Duration, Speed};
 
 use crate::connectivity::Spot;
 use crate::pathfind::{zone_cost, WalkingNode};
-use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep};
+use crate::{BuildingID, Lane, LaneType, Map, PathConstraints, PathStep, TransitStopID};
 
 #[derive(Clone)]
 pub struct WalkingOptions {
@@ -47,6 +47,17 @@ pub fn all_walking_costs_from(
     time_limit: Duration,
     opts: WalkingOptions,
 ) -> HashMap<BuildingID, Duration> {
+    let queue = start_walking(map, starts, &opts);
+    walking_costs(map, queue, time_limit, &opts).0
+}
+
+/// The sidewalk endpoints to start searching from. Empty if all of the start buildings are on the
+/// shoulder of a road and `!opts.allow_shoulders`.
+pub(crate) fn start_walking(
+    map: &Map,
+    starts: Vec<Spot>,
+    opts: &WalkingOptions,
+) -> BinaryHeap<PriorityQueueItem<Duration, WalkingNode>> {
     let mut queue: BinaryHeap<PriorityQueueItem<Duration, WalkingNode>> = BinaryHeap::new();
 
     for spot in starts {
@@ -102,16 +113,35 @@ pub fn all_walking_costs_from(
             }
         }
         if shoulder_endpoint.into_iter().all(|x| x) {
-            return HashMap::new();
+            return BinaryHeap::new();
         }
     }
 
+    queue
+}
+
+/// Continue a search from some sidewalk endpoints that were already reached. Returns the cost to
+/// all buildings and transit stops within the time_limit.
+pub(crate) fn walking_costs(
+    map: &Map,
+    mut queue: BinaryHeap<PriorityQueueItem<Duration, WalkingNode>>,
+    time_limit: Duration,
+    opts: &WalkingOptions,
+) -> (
+    HashMap<BuildingID, Duration>,
+    HashMap<TransitStopID, Duration>,
+) {
     let mut sidewalk_to_bldgs = MultiMap::new();
     for b in map.all_buildings() {
         sidewalk_to_bldgs.insert(b.sidewalk(), b.id);
     }
+    let mut sidewalk_to_stops = MultiMap::new();
+    for ts in map.all_transit_stops().values() {
+        sidewalk_to_stops.insert(ts.sidewalk_pos.lane(), ts.id);
+    }
 
     let mut results = HashMap::new();
+    let mut stop_results = HashMap::new();
 
     let mut visited_nodes = HashSet::new();
     while let Some(current) = queue.pop() {
@@ -159,6 +189,18 @@ pub fn all_walking_costs_from(
                         results.insert(*b, bldg_cost);
                     }
                 }
+                for ts in sidewalk_to_stops.get(lane.id) {
+                    let stop_dist_along = map.get_ts(*ts).sidewalk_pos.dist_along();
+                    let dist_to_stop = if is_dst_i {
+                        sidewalk_len - stop_dist_along
+                    } else {
+                        stop_dist_along
+                    };
+                    let stop_cost = current.cost + dist_to_stop / speed;
+                    if stop_cost <= time_limit {
+                        stop_results.insert(*ts, stop_cost);
+                    }
+                }
 
                 queue.push(PriorityQueueItem {
                     cost: current.cost + sidewalk_len / speed,
@@ -188,5 +230,5 @@ pub fn all_walking_costs_from(
         }
     }
 
-    results
+    (results, stop_results)
 }
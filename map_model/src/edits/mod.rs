@@ -11,16 +11,18 @@ use abstutil::Timer;
 use geom::{Distance, HashablePt2D, Line, Speed, Time};
 use osm2streets::{get_lane_specs_ltr, InputRoad};
 
-pub use self::perma::PermanentMapEdits;
+pub use self::perma::{PermanentEditCmd, PermanentMapEdits};
 use crate::make::{match_points_to_lanes, snap_driveway, trim_path};
 use crate::{
     connectivity, AccessRestrictions, BuildingID, ControlStopSign, ControlTrafficSignal,
     IntersectionID, IntersectionType, LaneID, LaneSpec, Map, MapConfig, Movement, ParkingLotID,
-    PathConstraints, Pathfinder, Road, RoadID, TransitRouteID, TurnID, TurnType, Zone,
+    ParkingPolicy, PathConstraints, Pathfinder, Position, Road, RoadID, RoadPricing, TransitRoute,
+    TransitRouteID, TransitStop, TransitStopID, TurnID, TurnType, Zone,
 };
 
 mod compat;
 mod perma;
+mod validate;
 
 /// Represents changes to a map. Note this isn't serializable -- that's what `PermanentMapEdits`
 /// does.
@@ -40,6 +42,13 @@ pub struct MapEdits {
     pub original_intersections: BTreeMap<IntersectionID, EditIntersection>,
     pub original_crosswalks: BTreeMap<IntersectionID, EditCrosswalks>,
     pub changed_routes: BTreeSet<TransitRouteID>,
+    /// None means the stop or route didn't exist originally
+    pub original_transit_stops: BTreeMap<TransitStopID, Option<EditTransitStop>>,
+    pub original_transit_routes: BTreeMap<TransitRouteID, Option<EditTransitRoute>>,
+    /// None if road pricing hasn't changed
+    pub original_road_pricing: Option<RoadPricing>,
+    /// None if the parking policy hasn't changed
+    pub original_parking_policy: Option<ParkingPolicy>,
 
     /// Some edits are included in the game by default, in data/system/proposals, as "community
     /// proposals." They require a description and may have a link to a write-up.
@@ -68,6 +77,34 @@ pub struct EditRoad {
 #[derive(Debug, Clone, PartialEq)]
 pub struct EditCrosswalks(pub BTreeMap<TurnID, TurnType>);
 
+/// The stop has to stay on the road in its `TransitStopID`. To move it to another road, delete it
+/// and create a new stop.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditTransitStop {
+    pub name: String,
+    pub gtfs_id: String,
+    pub driving_pos: Position,
+    pub sidewalk_pos: Position,
+    pub is_train_stop: bool,
+}
+
+/// Everything about a route besides its ID. The schedule can also be changed by itself with
+/// `EditCmd::ChangeRouteSchedule`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EditTransitRoute {
+    pub long_name: String,
+    pub short_name: String,
+    pub gtfs_id: String,
+    pub stops: Vec<TransitStopID>,
+    pub start: LaneID,
+    pub end_border: Option<LaneID>,
+    pub route_type: PathConstraints,
+    pub spawn_times: Vec<Time>,
+    pub orig_spawn_times: Vec<Time>,
+    /// See `Map::get_transit_stop_times`. Empty if the schedule is unknown.
+    pub stop_times: Vec<Vec<Time>>,
+}
+
 impl EditRoad {
     pub fn get_orig_from_osm(r: &Road, cfg: &MapConfig) -> EditRoad {
         EditRoad {
@@ -142,6 +179,25 @@ pub enum EditCmd {
         old: EditCrosswalks,
         new: EditCrosswalks,
     },
+    /// None for old creates the stop; None for new deletes it.
+    ChangeTransitStop {
+        id: TransitStopID,
+        old: Option<EditTransitStop>,
+        new: Option<EditTransitStop>,
+    },
+    /// None for old creates the route; None for new deletes it.
+    ChangeTransitRoute {
+        id: TransitRouteID,
+        old: Option<EditTransitRoute>,
+        new: Option<EditTransitRoute>,
+    },
+    /// Replaces all tolls and cordon charges
+    ChangeRoadPricing { old: RoadPricing, new: RoadPricing },
+    /// Replaces all parking prices, time limits, permit zones, and chargers
+    ChangeParkingPolicy {
+        old: ParkingPolicy,
+        new: ParkingPolicy,
+    },
 }
 
 pub struct EditEffects {
@@ -152,6 +208,8 @@ pub struct EditEffects {
     pub added_turns: BTreeSet<TurnID>,
     pub deleted_turns: BTreeSet<TurnID>,
     pub changed_parking_lots: BTreeSet<ParkingLotID>,
+    /// Created, moved, or deleted
+    pub changed_transit_stops: BTreeSet<TransitStopID>,
     modified_lanes: BTreeSet<LaneID>,
 }
 
@@ -168,6 +226,10 @@ impl MapEdits {
             original_intersections: BTreeMap::new(),
             original_crosswalks: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_transit_stops: BTreeMap::new(),
+            original_transit_routes: BTreeMap::new(),
+            original_road_pricing: None,
+            original_parking_policy: None,
         }
     }
 
@@ -240,6 +302,10 @@ impl MapEdits {
         self.original_intersections.clear();
         self.original_crosswalks.clear();
         self.changed_routes.clear();
+        self.original_transit_stops.clear();
+        self.original_transit_routes.clear();
+        self.original_road_pricing = None;
+        self.original_parking_policy = None;
 
         for cmd in &self.commands {
             match cmd {
@@ -259,6 +325,32 @@ impl MapEdits {
                 EditCmd::ChangeRouteSchedule { id, .. } => {
                     self.changed_routes.insert(*id);
                 }
+                EditCmd::ChangeTransitStop { id, ref old, .. } => {
+                    if !self.original_transit_stops.contains_key(id) {
+                        self.original_transit_stops.insert(*id, old.clone());
+                    }
+                }
+                EditCmd::ChangeTransitRoute { id, ref old, .. } => {
+                    if !self.original_transit_routes.contains_key(id) {
+                        // The schedule might've been changed before this command; the original
+                        // is still known.
+                        let orig = old.clone().map(|mut route| {
+                            route.spawn_times = route.orig_spawn_times.clone();
+                            route
+                        });
+                        self.original_transit_routes.insert(*id, orig);
+                    }
+                }
+                EditCmd::ChangeRoadPricing { ref old, .. } => {
+                    if self.original_road_pricing.is_none() {
+                        self.original_road_pricing = Some(old.clone());
+                    }
+                }
+                EditCmd::ChangeParkingPolicy { ref old, .. } => {
+                    if self.original_parking_policy.is_none() {
+                        self.original_parking_policy = Some(old.clone());
+                    }
+                }
             }
         }
 
@@ -269,9 +361,25 @@ impl MapEdits {
             .retain(|i, orig| map.get_i_edit(*i) != orig.clone());
         self.original_crosswalks
             .retain(|i, orig| map.get_i_crosswalks_edit(*i) != orig.clone());
+        self.original_transit_stops
+            .retain(|id, orig| map.get_ts_edit(*id) != *orig);
+        self.original_transit_routes
+            .retain(|id, orig| map.get_tr_edit(*id) != *orig);
+        if self.original_road_pricing.as_ref() == Some(map.get_road_pricing()) {
+            self.original_road_pricing = None;
+        }
+        if self.original_parking_policy.as_ref() == Some(map.get_parking_policy()) {
+            self.original_parking_policy = None;
+        }
+        // If the whole route was edited, that covers the schedule too
+        let original_transit_routes = &self.original_transit_routes;
         self.changed_routes.retain(|br| {
-            let r = map.get_tr(*br);
-            r.spawn_times != r.orig_spawn_times
+            if original_transit_routes.contains_key(br) {
+                return false;
+            }
+            map.maybe_get_tr(*br)
+                .map(|r| r.spawn_times != r.orig_spawn_times)
+                .unwrap_or(false)
         });
     }
 
@@ -298,6 +406,32 @@ impl MapEdits {
                 new: map.get_i_crosswalks_edit(*i),
             });
         }
+        for (id, old) in &self.original_transit_stops {
+            self.commands.push(EditCmd::ChangeTransitStop {
+                id: *id,
+                old: old.clone(),
+                new: map.get_ts_edit(*id),
+            });
+        }
+        for (id, old) in &self.original_transit_routes {
+            self.commands.push(EditCmd::ChangeTransitRoute {
+                id: *id,
+                old: old.clone(),
+                new: map.get_tr_edit(*id),
+            });
+        }
+        if let Some(ref old) = self.original_road_pricing {
+            self.commands.push(EditCmd::ChangeRoadPricing {
+                old: old.clone(),
+                new: map.get_road_pricing().clone(),
+            });
+        }
+        if let Some(ref old) = self.original_parking_policy {
+            self.commands.push(EditCmd::ChangeParkingPolicy {
+                old: old.clone(),
+                new: map.get_parking_policy().clone(),
+            });
+        }
         for r in &self.changed_routes {
             let r = map.get_tr(*r);
             self.commands.push(EditCmd::ChangeRouteSchedule {
@@ -376,8 +510,56 @@ impl EditCmd {
                 EditIntersection::Closed => format!("close {}", i),
             },
             EditCmd::ChangeCrosswalks { i, .. } => format!("crosswalks at {}", i),
-            EditCmd::ChangeRouteSchedule { id, .. } => {
-                format!("reschedule route {}", map.get_tr(*id).short_name)
+            EditCmd::ChangeRouteSchedule { id, .. } => match map.maybe_get_tr(*id) {
+                Some(r) => format!("reschedule route {}", r.short_name),
+                None => format!("reschedule deleted {}", id),
+            },
+            EditCmd::ChangeTransitStop { old, new, .. } => match (old, new) {
+                (None, Some(new)) => format!("add stop {}", new.name),
+                (Some(old), None) => format!("delete stop {}", old.name),
+                (Some(_), Some(new)) => format!("move stop {}", new.name),
+                (None, None) => unreachable!(),
+            },
+            EditCmd::ChangeTransitRoute { old, new, .. } => match (old, new) {
+                (None, Some(new)) => {
+                    details.push(format!("{} stops", new.stops.len()));
+                    format!("add route {}", new.short_name)
+                }
+                (Some(old), None) => format!("delete route {}", old.short_name),
+                (Some(old), Some(new)) => {
+                    if old.stops != new.stops {
+                        details.push(format!(
+                            "{} stops, previously {}",
+                            new.stops.len(),
+                            old.stops.len()
+                        ));
+                    }
+                    format!("reroute {}", new.short_name)
+                }
+                (None, None) => unreachable!(),
+            },
+            EditCmd::ChangeRoadPricing { new, .. } => {
+                details.push(format!("{} tolled roads", new.tolls.len()));
+                for cordon in &new.cordons {
+                    details.push(format!(
+                        "cordon {} around {} roads",
+                        cordon.name,
+                        cordon.roads.len()
+                    ));
+                }
+                "road pricing".to_string()
+            }
+            EditCmd::ChangeParkingPolicy { new, .. } => {
+                details.push(format!("{} priced parking lanes", new.onstreet.len()));
+                details.push(format!("{} priced parking lots", new.lots.len()));
+                for zone in &new.permit_zones {
+                    details.push(format!(
+                        "permit zone {} covering {} lanes",
+                        zone.name,
+                        zone.lanes.len()
+                    ));
+                }
+                "parking policy".to_string()
             }
         };
         (summary, details)
@@ -462,7 +644,76 @@ impl EditCmd {
                 }
             }
             EditCmd::ChangeRouteSchedule { id, new, .. } => {
-                map.transit_routes[id.0].spawn_times = new.clone();
+                // The route might be deleted later in the stack
+                if let Some(route) = map.transit_routes.get_mut(id) {
+                    route.spawn_times = new.clone();
+                }
+            }
+            EditCmd::ChangeTransitStop { id, new, .. } => {
+                if map.get_ts_edit(*id) == *new {
+                    return;
+                }
+
+                effects.changed_transit_stops.insert(*id);
+                if let Some(new) = new {
+                    map.mut_road(id.road).transit_stops.insert(*id);
+                    map.transit_stops.insert(
+                        *id,
+                        TransitStop {
+                            id: *id,
+                            name: new.name.clone(),
+                            gtfs_id: new.gtfs_id.clone(),
+                            driving_pos: new.driving_pos,
+                            sidewalk_pos: new.sidewalk_pos,
+                            is_train_stop: new.is_train_stop,
+                        },
+                    );
+                } else {
+                    map.mut_road(id.road).transit_stops.remove(id);
+                    map.transit_stops.remove(id);
+                }
+            }
+            EditCmd::ChangeTransitRoute { id, new, .. } => {
+                let current = map.get_tr_edit(*id);
+                if current == *new {
+                    return;
+                }
+
+                // Routes may refer to stops created later in the stack, so validation happens
+                // after all commands are applied.
+                if let Some(new) = new {
+                    map.transit_routes.insert(
+                        *id,
+                        TransitRoute {
+                            id: *id,
+                            long_name: new.long_name.clone(),
+                            short_name: new.short_name.clone(),
+                            gtfs_id: new.gtfs_id.clone(),
+                            stops: new.stops.clone(),
+                            start: new.start,
+                            end_border: new.end_border,
+                            route_type: new.route_type,
+                            spawn_times: new.spawn_times.clone(),
+                            orig_spawn_times: new.orig_spawn_times.clone(),
+                        },
+                    );
+                    if let Some(stop_times) = current_stop_times(current.as_ref(), new) {
+                        map.transit_stop_times.insert(*id, stop_times);
+                    } else {
+                        map.transit_stop_times.remove(id);
+                    }
+                } else {
+                    map.transit_routes.remove(id);
+                    map.transit_stop_times.remove(id);
+                }
+            }
+            EditCmd::ChangeRoadPricing { new, .. } => {
+                // Only routing changes, and the pathfinder is always rebuilt after edits
+                map.road_pricing = new.clone();
+            }
+            EditCmd::ChangeParkingPolicy { new, .. } => {
+                // Only the simulation cares
+                map.parking_policy = new.clone();
             }
         }
     }
@@ -489,6 +740,22 @@ impl EditCmd {
                 old: new,
                 new: old,
             },
+            EditCmd::ChangeTransitStop { id, old, new } => EditCmd::ChangeTransitStop {
+                id,
+                old: new,
+                new: old,
+            },
+            EditCmd::ChangeTransitRoute { id, old, new } => EditCmd::ChangeTransitRoute {
+                id,
+                old: new,
+                new: old,
+            },
+            EditCmd::ChangeRoadPricing { old, new } => {
+                EditCmd::ChangeRoadPricing { old: new, new: old }
+            }
+            EditCmd::ChangeParkingPolicy { old, new } => {
+                EditCmd::ChangeParkingPolicy { old: new, new: old }
+            }
         }
     }
 }
@@ -739,6 +1006,30 @@ fn fix_parking_lot_driveways(map: &mut Map, input: Vec<ParkingLotID>) {
     }
 }
 
+/// The schedule's stop times are only meaningful for the stops and trips they were published
+/// for. If a route's stops change from what's currently in the map without new times, forget the
+/// stale ones. Undoing that change restores the original times.
+fn current_stop_times(
+    current: Option<&EditTransitRoute>,
+    new: &EditTransitRoute,
+) -> Option<Vec<Vec<Time>>> {
+    if new.stop_times.is_empty()
+        || new.stop_times.len() != new.orig_spawn_times.len()
+        || new
+            .stop_times
+            .iter()
+            .any(|times| times.len() != new.stops.len())
+    {
+        return None;
+    }
+    if let Some(current) = current {
+        if current.stops != new.stops && current.stop_times == new.stop_times {
+            return None;
+        }
+    }
+    Some(new.stop_times.clone())
+}
+
 impl Map {
     pub fn new_edits(&self) -> MapEdits {
         let mut edits = MapEdits::new();
@@ -803,6 +1094,80 @@ impl Map {
         EditCrosswalks(turns)
     }
 
+    /// None if the stop doesn't exist
+    pub fn get_ts_edit(&self, id: TransitStopID) -> Option<EditTransitStop> {
+        let ts = self.maybe_get_ts(id)?;
+        Some(EditTransitStop {
+            name: ts.name.clone(),
+            gtfs_id: ts.gtfs_id.clone(),
+            driving_pos: ts.driving_pos,
+            sidewalk_pos: ts.sidewalk_pos,
+            is_train_stop: ts.is_train_stop,
+        })
+    }
+
+    /// None if the route doesn't exist
+    pub fn get_tr_edit(&self, id: TransitRouteID) -> Option<EditTransitRoute> {
+        let tr = self.maybe_get_tr(id)?;
+        Some(EditTransitRoute {
+            long_name: tr.long_name.clone(),
+            short_name: tr.short_name.clone(),
+            gtfs_id: tr.gtfs_id.clone(),
+            stops: tr.stops.clone(),
+            start: tr.start,
+            end_border: tr.end_border,
+            route_type: tr.route_type,
+            spawn_times: tr.spawn_times.clone(),
+            orig_spawn_times: tr.orig_spawn_times.clone(),
+            stop_times: self
+                .get_transit_stop_times(id)
+                .cloned()
+                .unwrap_or_else(Vec::new),
+        })
+    }
+
+    /// Pick an unused ID for a new stop along this road. IDs of stops deleted by the current
+    /// edits are never reused, so commands referring to them stay unambiguous.
+    pub fn new_transit_stop_id(&self, road: RoadID) -> TransitStopID {
+        let mut used: Vec<TransitStopID> = self.get_r(road).transit_stops.iter().cloned().collect();
+        for cmd in &self.edits.commands {
+            match cmd {
+                EditCmd::ChangeTransitStop { id, .. } => {
+                    used.push(*id);
+                }
+                EditCmd::ChangeTransitRoute { old, new, .. } => {
+                    for route in old.iter().chain(new.iter()) {
+                        used.extend(route.stops.iter().cloned());
+                    }
+                }
+                _ => {}
+            }
+        }
+        TransitStopID {
+            road,
+            idx: used
+                .into_iter()
+                .filter(|ts| ts.road == road)
+                .map(|ts| ts.idx + 1)
+                .max()
+                .unwrap_or(0),
+        }
+    }
+
+    /// Pick an unused ID for a new route. IDs of routes deleted by the current edits are never
+    /// reused.
+    pub fn new_transit_route_id(&self) -> TransitRouteID {
+        let mut max = self.transit_routes.keys().last().map(|id| id.0 + 1);
+        for cmd in &self.edits.commands {
+            if let EditCmd::ChangeTransitRoute { id, .. }
+            | EditCmd::ChangeRouteSchedule { id, .. } = cmd
+            {
+                max = max.max(Some(id.0 + 1));
+            }
+        }
+        TransitRouteID(max.unwrap_or(0))
+    }
+
     pub fn save_edits(&self) {
         // Don't overwrite the current edits with the compressed first. Otherwise, undo/redo order
         // in the UI gets messed up.
@@ -844,6 +1209,7 @@ impl Map {
             added_turns: BTreeSet::new(),
             deleted_turns: BTreeSet::new(),
             changed_parking_lots: BTreeSet::new(),
+            changed_transit_stops: BTreeSet::new(),
             modified_lanes: BTreeSet::new(),
         };
 
@@ -910,16 +1276,35 @@ impl Map {
             for id in &effects.changed_roads {
                 let stops = self.get_r(*id).transit_stops.clone();
                 for s in stops {
-                    let sidewalk_pos = self.get_ts(s).sidewalk_pos;
-                    // Must exist, because we aren't allowed to orphan a bus stop.
-                    let driving_lane = self
+                    let ts = self.get_ts(s);
+                    let sidewalk_pos = ts.sidewalk_pos;
+                    let constraints = if ts.is_train_stop {
+                        PathConstraints::Train
+                    } else {
+                        PathConstraints::Bus
+                    };
+                    // check_transit_edits prevents orphaning a stop, but don't crash if somebody
+                    // skipped it
+                    if let Some(driving_lane) = self
                         .get_r(*id)
-                        .find_closest_lane(sidewalk_pos.lane(), |l| {
-                            PathConstraints::Bus.can_use(l, self)
-                        })
-                        .unwrap();
-                    let driving_pos = sidewalk_pos.equiv_pos(driving_lane, self);
-                    self.transit_stops.get_mut(&s).unwrap().driving_pos = driving_pos;
+                        .find_closest_lane(sidewalk_pos.lane(), |l| constraints.can_use(l, self))
+                    {
+                        let driving_pos = sidewalk_pos.equiv_pos(driving_lane, self);
+                        self.transit_stops.get_mut(&s).unwrap().driving_pos = driving_pos;
+                    } else {
+                        error!("{} has no lane for vehicles to stop along", s);
+                    }
+                }
+            }
+
+            for route in self.transit_routes.values() {
+                for ts in &route.stops {
+                    if !self.transit_stops.contains_key(ts) {
+                        error!(
+                            "{} ({}) stops at {}, which doesn't exist",
+                            route.id, route.short_name, ts
+                        );
+                    }
                 }
             }
         }
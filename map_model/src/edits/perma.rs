@@ -5,10 +5,17 @@ use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
 use abstutil::{deserialize_btreemap, serialize_btreemap};
-use geom::Time;
+use geom::{Distance, Time};
 
-use crate::edits::{EditCmd, EditCrosswalks, EditIntersection, EditRoad, MapEdits};
-use crate::{osm, ControlStopSign, IntersectionID, Map, MovementID, OriginalRoad, TurnType};
+use crate::edits::{
+    EditCmd, EditCrosswalks, EditIntersection, EditRoad, EditTransitRoute, EditTransitStop,
+    MapEdits,
+};
+use crate::{
+    osm, BuildingID, Charge, Charger, ChargerLocation, ControlStopSign, Cordon, IntersectionID,
+    LaneID, Map, MovementID, OriginalRoad, ParkingLotID, ParkingPolicy, ParkingRules,
+    PathConstraints, PermitZone, Position, RoadPricing, TransitRouteID, TransitStopID, TurnType,
+};
 
 // Manually change this to attempt to preserve edits after major OSM updates.
 const IGNORE_OLD_LANES: bool = false;
@@ -55,6 +62,86 @@ pub struct PermanentEditCrosswalks {
     turns: BTreeMap<traffic_signal_data::Turn, TurnType>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PermanentLaneID {
+    road: OriginalRoad,
+    offset: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PermanentPosition {
+    lane: PermanentLaneID,
+    dist_along: Distance,
+}
+
+/// Stops created by edits don't exist in the basemap, so they're identified by their position
+/// along a road, not a GTFS ID.
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct PermanentTransitStopID {
+    road: OriginalRoad,
+    idx: usize,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentEditTransitStop {
+    name: String,
+    gtfs_id: String,
+    driving_pos: PermanentPosition,
+    sidewalk_pos: PermanentPosition,
+    is_train_stop: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentEditTransitRoute {
+    long_name: String,
+    short_name: String,
+    gtfs_id: String,
+    stops: Vec<PermanentTransitStopID>,
+    start: PermanentLaneID,
+    end_border: Option<PermanentLaneID>,
+    route_type: PathConstraints,
+    spawn_times: Vec<Time>,
+    orig_spawn_times: Vec<Time>,
+    #[serde(default)]
+    stop_times: Vec<Vec<Time>>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentRoadPricing {
+    tolls: Vec<(OriginalRoad, Charge)>,
+    cordons: Vec<PermanentCordon>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentCordon {
+    name: String,
+    roads: Vec<OriginalRoad>,
+    charge: Charge,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentParkingPolicy {
+    onstreet: Vec<(PermanentLaneID, ParkingRules)>,
+    lots: Vec<(osm::OsmID, ParkingRules)>,
+    permit_zones: Vec<PermanentPermitZone>,
+    #[serde(default)]
+    chargers: Vec<(PermanentChargerLocation, Charger)>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub enum PermanentChargerLocation {
+    Onstreet(PermanentLaneID),
+    Building(osm::OsmID),
+    Lot(osm::OsmID),
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct PermanentPermitZone {
+    name: String,
+    lanes: Vec<PermanentLaneID>,
+    residents: Vec<osm::OsmID>,
+}
+
 #[allow(clippy::enum_variant_names)]
 #[derive(Serialize, Deserialize, Clone)]
 pub enum PermanentEditCmd {
@@ -78,6 +165,25 @@ pub enum PermanentEditCmd {
         old: Vec<Time>,
         new: Vec<Time>,
     },
+    ChangeTransitStop {
+        id: PermanentTransitStopID,
+        old: Option<PermanentEditTransitStop>,
+        new: Option<PermanentEditTransitStop>,
+    },
+    /// Existing routes are matched by GTFS ID. The ID is only used for routes created by edits.
+    ChangeTransitRoute {
+        id: TransitRouteID,
+        old: Option<PermanentEditTransitRoute>,
+        new: Option<PermanentEditTransitRoute>,
+    },
+    ChangeRoadPricing {
+        old: PermanentRoadPricing,
+        new: PermanentRoadPricing,
+    },
+    ChangeParkingPolicy {
+        old: PermanentParkingPolicy,
+        new: PermanentParkingPolicy,
+    },
 }
 
 impl EditCmd {
@@ -105,6 +211,24 @@ impl EditCmd {
                     new: new.clone(),
                 }
             }
+            EditCmd::ChangeTransitStop { id, old, new } => PermanentEditCmd::ChangeTransitStop {
+                id: PermanentTransitStopID::new(*id, map),
+                old: old.as_ref().map(|x| x.to_permanent(map)),
+                new: new.as_ref().map(|x| x.to_permanent(map)),
+            },
+            EditCmd::ChangeTransitRoute { id, old, new } => PermanentEditCmd::ChangeTransitRoute {
+                id: *id,
+                old: old.as_ref().map(|x| x.to_permanent(map)),
+                new: new.as_ref().map(|x| x.to_permanent(map)),
+            },
+            EditCmd::ChangeRoadPricing { old, new } => PermanentEditCmd::ChangeRoadPricing {
+                old: PermanentRoadPricing::new(old, map),
+                new: PermanentRoadPricing::new(new, map),
+            },
+            EditCmd::ChangeParkingPolicy { old, new } => PermanentEditCmd::ChangeParkingPolicy {
+                old: PermanentParkingPolicy::new(old, map),
+                new: PermanentParkingPolicy::new(new, map),
+            },
         }
     }
 }
@@ -166,6 +290,54 @@ impl PermanentEditCmd {
                     .ok_or_else(|| anyhow!("can't find {}", gtfs_id))?;
                 Ok(EditCmd::ChangeRouteSchedule { id, old, new })
             }
+            PermanentEditCmd::ChangeTransitStop { id, old, new } => {
+                let id = id.with_permanent(map)?;
+                // The stop might've been created by an earlier command, but if it's in the
+                // basemap, make sure it's the same one.
+                match (&old, map.maybe_get_ts(id)) {
+                    (Some(old), Some(ts)) if old.gtfs_id != ts.gtfs_id => {
+                        bail!(
+                            "{} is {} now, but {} in the edits",
+                            id,
+                            ts.gtfs_id,
+                            old.gtfs_id
+                        );
+                    }
+                    (None, Some(_)) => bail!("can't create {}; it already exists", id),
+                    _ => {}
+                }
+                Ok(EditCmd::ChangeTransitStop {
+                    id,
+                    old: old.map(|x| x.with_permanent(map)).transpose()?,
+                    new: new.map(|x| x.with_permanent(map)).transpose()?,
+                })
+            }
+            PermanentEditCmd::ChangeTransitRoute { id, old, new } => {
+                let id = match old {
+                    Some(ref old) => map.find_tr_by_gtfs(&old.gtfs_id).unwrap_or(id),
+                    None => {
+                        if map.maybe_get_tr(id).is_some() {
+                            bail!("can't create {}; it already exists", id);
+                        }
+                        id
+                    }
+                };
+                Ok(EditCmd::ChangeTransitRoute {
+                    id,
+                    old: old.map(|x| x.with_permanent(map)).transpose()?,
+                    new: new.map(|x| x.with_permanent(map)).transpose()?,
+                })
+            }
+            PermanentEditCmd::ChangeRoadPricing { old, new } => Ok(EditCmd::ChangeRoadPricing {
+                old: old.with_permanent(map)?,
+                new: new.with_permanent(map)?,
+            }),
+            PermanentEditCmd::ChangeParkingPolicy { old, new } => {
+                Ok(EditCmd::ChangeParkingPolicy {
+                    old: old.with_permanent(map)?,
+                    new: new.with_permanent(map)?,
+                })
+            }
         }
     }
 }
@@ -177,7 +349,7 @@ impl MapEdits {
             map_name: map.get_name().clone(),
             edits_name: self.edits_name.clone(),
             // Increase this every time there's a schema change
-            version: 11,
+            version: 13,
             proposal_description: self.proposal_description.clone(),
             proposal_link: self.proposal_link.clone(),
             commands: self.commands.iter().map(|cmd| cmd.to_perma(map)).collect(),
@@ -205,7 +377,12 @@ impl PermanentMapEdits {
             original_intersections: BTreeMap::new(),
             original_crosswalks: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_transit_stops: BTreeMap::new(),
+            original_transit_routes: BTreeMap::new(),
+            original_road_pricing: None,
+            original_parking_policy: None,
         };
+        map.check_transit_edits(&edits)?;
         edits.update_derived(map);
         Ok(edits)
     }
@@ -227,13 +404,24 @@ impl PermanentMapEdits {
                         None
                     }
                 })
-                .collect(),
+                .fold(Vec::new(), |mut commands, cmd| {
+                    commands.push(cmd);
+                    if let Err(err) = map.check_transit_commands(&commands) {
+                        warn!("Skipping command that breaks transit: {}", err);
+                        commands.pop();
+                    }
+                    commands
+                }),
             merge_zones: self.merge_zones,
 
             changed_roads: BTreeSet::new(),
             original_intersections: BTreeMap::new(),
             original_crosswalks: BTreeMap::new(),
             changed_routes: BTreeSet::new(),
+            original_transit_stops: BTreeMap::new(),
+            original_transit_routes: BTreeMap::new(),
+            original_road_pricing: None,
+            original_parking_policy: None,
         };
         edits.update_derived(map);
         edits
@@ -338,3 +526,279 @@ impl PermanentEditCrosswalks {
         Ok(EditCrosswalks(turns))
     }
 }
+
+impl PermanentLaneID {
+    fn new(l: LaneID, map: &Map) -> PermanentLaneID {
+        PermanentLaneID {
+            road: map.get_r(l.road).orig_id,
+            offset: l.offset,
+        }
+    }
+
+    fn with_permanent(self, map: &Map) -> Result<LaneID> {
+        let road = map.find_r_by_osm_id(self.road)?;
+        if self.offset >= map.get_r(road).lanes.len() {
+            bail!("{} doesn't have lane {} anymore", self.road, self.offset);
+        }
+        Ok(LaneID {
+            road,
+            offset: self.offset,
+        })
+    }
+}
+
+impl PermanentPosition {
+    fn new(pos: Position, map: &Map) -> PermanentPosition {
+        PermanentPosition {
+            lane: PermanentLaneID::new(pos.lane(), map),
+            dist_along: pos.dist_along(),
+        }
+    }
+
+    fn with_permanent(self, map: &Map) -> Result<Position> {
+        let lane = self.lane.with_permanent(map)?;
+        if self.dist_along > map.get_l(lane).length() {
+            bail!("{} is shorter than {} now", lane, self.dist_along);
+        }
+        Ok(Position::new(lane, self.dist_along))
+    }
+}
+
+impl PermanentTransitStopID {
+    fn new(id: TransitStopID, map: &Map) -> PermanentTransitStopID {
+        PermanentTransitStopID {
+            road: map.get_r(id.road).orig_id,
+            idx: id.idx,
+        }
+    }
+
+    fn with_permanent(self, map: &Map) -> Result<TransitStopID> {
+        Ok(TransitStopID {
+            road: map.find_r_by_osm_id(self.road)?,
+            idx: self.idx,
+        })
+    }
+}
+
+impl EditTransitStop {
+    fn to_permanent(&self, map: &Map) -> PermanentEditTransitStop {
+        PermanentEditTransitStop {
+            name: self.name.clone(),
+            gtfs_id: self.gtfs_id.clone(),
+            driving_pos: PermanentPosition::new(self.driving_pos, map),
+            sidewalk_pos: PermanentPosition::new(self.sidewalk_pos, map),
+            is_train_stop: self.is_train_stop,
+        }
+    }
+}
+
+impl PermanentEditTransitStop {
+    fn with_permanent(self, map: &Map) -> Result<EditTransitStop> {
+        Ok(EditTransitStop {
+            name: self.name,
+            gtfs_id: self.gtfs_id,
+            driving_pos: self.driving_pos.with_permanent(map)?,
+            sidewalk_pos: self.sidewalk_pos.with_permanent(map)?,
+            is_train_stop: self.is_train_stop,
+        })
+    }
+}
+
+impl EditTransitRoute {
+    fn to_permanent(&self, map: &Map) -> PermanentEditTransitRoute {
+        PermanentEditTransitRoute {
+            long_name: self.long_name.clone(),
+            short_name: self.short_name.clone(),
+            gtfs_id: self.gtfs_id.clone(),
+            stops: self
+                .stops
+                .iter()
+                .map(|ts| PermanentTransitStopID::new(*ts, map))
+                .collect(),
+            start: PermanentLaneID::new(self.start, map),
+            end_border: self.end_border.map(|l| PermanentLaneID::new(l, map)),
+            route_type: self.route_type,
+            spawn_times: self.spawn_times.clone(),
+            orig_spawn_times: self.orig_spawn_times.clone(),
+            stop_times: self.stop_times.clone(),
+        }
+    }
+}
+
+impl PermanentEditTransitRoute {
+    fn with_permanent(self, map: &Map) -> Result<EditTransitRoute> {
+        Ok(EditTransitRoute {
+            long_name: self.long_name,
+            short_name: self.short_name,
+            gtfs_id: self.gtfs_id,
+            stops: self
+                .stops
+                .into_iter()
+                .map(|ts| ts.with_permanent(map))
+                .collect::<Result<Vec<_>>>()?,
+            start: self.start.with_permanent(map)?,
+            end_border: self.end_border.map(|l| l.with_permanent(map)).transpose()?,
+            route_type: self.route_type,
+            spawn_times: self.spawn_times,
+            orig_spawn_times: self.orig_spawn_times,
+            stop_times: self.stop_times,
+        })
+    }
+}
+
+impl PermanentRoadPricing {
+    fn new(pricing: &RoadPricing, map: &Map) -> PermanentRoadPricing {
+        PermanentRoadPricing {
+            tolls: pricing
+                .tolls
+                .iter()
+                .map(|(r, charge)| (map.get_r(*r).orig_id, charge.clone()))
+                .collect(),
+            cordons: pricing
+                .cordons
+                .iter()
+                .map(|cordon| PermanentCordon {
+                    name: cordon.name.clone(),
+                    roads: cordon.roads.iter().map(|r| map.get_r(*r).orig_id).collect(),
+                    charge: cordon.charge.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    fn with_permanent(self, map: &Map) -> Result<RoadPricing> {
+        let mut tolls = BTreeMap::new();
+        for (r, charge) in self.tolls {
+            tolls.insert(map.find_r_by_osm_id(r)?, charge);
+        }
+        let mut cordons = Vec::new();
+        for cordon in self.cordons {
+            cordons.push(Cordon {
+                name: cordon.name,
+                roads: cordon
+                    .roads
+                    .into_iter()
+                    .map(|r| map.find_r_by_osm_id(r))
+                    .collect::<Result<BTreeSet<_>>>()?,
+                charge: cordon.charge,
+            });
+        }
+        Ok(RoadPricing { tolls, cordons })
+    }
+}
+
+impl PermanentParkingPolicy {
+    fn new(policy: &ParkingPolicy, map: &Map) -> PermanentParkingPolicy {
+        PermanentParkingPolicy {
+            onstreet: policy
+                .onstreet
+                .iter()
+                .map(|(l, rules)| (PermanentLaneID::new(*l, map), rules.clone()))
+                .collect(),
+            lots: policy
+                .lots
+                .iter()
+                .map(|(pl, rules)| (map.get_pl(*pl).osm_id, rules.clone()))
+                .collect(),
+            permit_zones: policy
+                .permit_zones
+                .iter()
+                .map(|zone| PermanentPermitZone {
+                    name: zone.name.clone(),
+                    lanes: zone
+                        .lanes
+                        .iter()
+                        .map(|l| PermanentLaneID::new(*l, map))
+                        .collect(),
+                    residents: zone
+                        .residents
+                        .iter()
+                        .map(|b| map.get_b(*b).orig_id)
+                        .collect(),
+                })
+                .collect(),
+            chargers: policy
+                .chargers
+                .iter()
+                .map(|(loc, charger)| {
+                    let loc = match loc {
+                        ChargerLocation::Onstreet(l) => {
+                            PermanentChargerLocation::Onstreet(PermanentLaneID::new(*l, map))
+                        }
+                        ChargerLocation::Building(b) => {
+                            PermanentChargerLocation::Building(map.get_b(*b).orig_id)
+                        }
+                        ChargerLocation::Lot(pl) => {
+                            PermanentChargerLocation::Lot(map.get_pl(*pl).osm_id)
+                        }
+                    };
+                    (loc, charger.clone())
+                })
+                .collect(),
+        }
+    }
+
+    fn with_permanent(self, map: &Map) -> Result<ParkingPolicy> {
+        // Zones can cover many buildings, so don't search for each one
+        let bldgs: BTreeMap<osm::OsmID, BuildingID> = map
+            .all_buildings()
+            .iter()
+            .map(|b| (b.orig_id, b.id))
+            .collect();
+        let lots: BTreeMap<osm::OsmID, ParkingLotID> = map
+            .all_parking_lots()
+            .iter()
+            .map(|pl| (pl.osm_id, pl.id))
+            .collect();
+
+        let mut policy = ParkingPolicy::default();
+        for (l, rules) in self.onstreet {
+            policy.onstreet.insert(l.with_permanent(map)?, rules);
+        }
+        for (id, rules) in self.lots {
+            match lots.get(&id) {
+                Some(pl) => {
+                    policy.lots.insert(*pl, rules);
+                }
+                None => bail!("Can't find parking lot {}", id),
+            }
+        }
+        for zone in self.permit_zones {
+            let mut residents = BTreeSet::new();
+            for id in zone.residents {
+                match bldgs.get(&id) {
+                    Some(b) => {
+                        residents.insert(*b);
+                    }
+                    None => bail!("Can't find building {}", id),
+                }
+            }
+            policy.permit_zones.push(PermitZone {
+                name: zone.name,
+                lanes: zone
+                    .lanes
+                    .into_iter()
+                    .map(|l| l.with_permanent(map))
+                    .collect::<Result<BTreeSet<_>>>()?,
+                residents,
+            });
+        }
+        for (loc, charger) in self.chargers {
+            let loc = match loc {
+                PermanentChargerLocation::Onstreet(l) => {
+                    ChargerLocation::Onstreet(l.with_permanent(map)?)
+                }
+                PermanentChargerLocation::Building(id) => match bldgs.get(&id) {
+                    Some(b) => ChargerLocation::Building(*b),
+                    None => bail!("Can't find building {}", id),
+                },
+                PermanentChargerLocation::Lot(id) => match lots.get(&id) {
+                    Some(pl) => ChargerLocation::Lot(*pl),
+                    None => bail!("Can't find parking lot {}", id),
+                },
+            };
+            policy.chargers.insert(loc, charger);
+        }
+        Ok(policy)
+    }
+}
@@ -0,0 +1,163 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+
+use abstutil::Timer;
+
+use crate::edits::{EditCmd, EditTransitRoute, EditTransitStop};
+use crate::{
+    connectivity, LaneID, LaneSpec, LaneType, Map, MapEdits, PathConstraints, RoadID,
+    TransitRouteID, TransitStopID,
+};
+
+// These speculatively apply some new edits to see if they're valid. They always leave the map in
+// the original state.
+
+impl Map {
+    /// Applying edits that delete a stop some route still uses, or remove every lane a stop's
+    /// vehicles could use, would break transit. This checks for both without applying anything.
+    pub fn check_transit_edits(&self, new_edits: &MapEdits) -> Result<()> {
+        self.check_transit_commands(&new_edits.commands)
+    }
+
+    pub(crate) fn check_transit_commands(&self, commands: &[EditCmd]) -> Result<()> {
+        // The state after applying the commands to the unedited map. Start with the unedited
+        // state of anything the current edits touch -- the first `old` value is the original.
+        let mut stops: BTreeMap<TransitStopID, Option<EditTransitStop>> = BTreeMap::new();
+        let mut routes: BTreeMap<TransitRouteID, Option<EditTransitRoute>> = BTreeMap::new();
+        let mut roads: BTreeMap<RoadID, Vec<LaneSpec>> = BTreeMap::new();
+        for cmd in &self.get_edits().commands {
+            match cmd {
+                EditCmd::ChangeTransitStop { id, old, .. } => {
+                    stops.entry(*id).or_insert_with(|| old.clone());
+                }
+                EditCmd::ChangeTransitRoute { id, old, .. } => {
+                    routes.entry(*id).or_insert_with(|| old.clone());
+                }
+                EditCmd::ChangeRoad { r, old, .. } => {
+                    roads.entry(*r).or_insert_with(|| old.lanes_ltr.clone());
+                }
+                _ => {}
+            }
+        }
+        for cmd in commands {
+            match cmd {
+                EditCmd::ChangeTransitStop { id, new, .. } => {
+                    stops.insert(*id, new.clone());
+                }
+                EditCmd::ChangeTransitRoute { id, new, .. } => {
+                    routes.insert(*id, new.clone());
+                }
+                EditCmd::ChangeRoad { r, new, .. } => {
+                    roads.insert(*r, new.lanes_ltr.clone());
+                }
+                _ => {}
+            }
+        }
+
+        let stop_exists = |ts: &TransitStopID| match stops.get(ts) {
+            Some(stop) => stop.is_some(),
+            None => self.maybe_get_ts(*ts).is_some(),
+        };
+        for r in self.all_transit_routes() {
+            routes.entry(r.id).or_insert_with(|| self.get_tr_edit(r.id));
+        }
+        for (id, route) in &routes {
+            if let Some(route) = route {
+                for ts in &route.stops {
+                    if !stop_exists(ts) {
+                        bail!(
+                            "{} ({}) stops at {}, which doesn't exist",
+                            id,
+                            route.short_name,
+                            ts
+                        );
+                    }
+                }
+            }
+        }
+
+        // Only check roads with changed lanes or stops
+        let mut check_roads: BTreeSet<RoadID> = roads.keys().cloned().collect();
+        check_roads.extend(stops.keys().map(|ts| ts.road));
+        for r in check_roads {
+            let is_train_stops: Vec<bool> = self
+                .get_r(r)
+                .transit_stops
+                .iter()
+                .chain(stops.keys().filter(|ts| ts.road == r))
+                .filter_map(|ts| match stops.get(ts) {
+                    Some(stop) => stop.as_ref().map(|stop| stop.is_train_stop),
+                    None => Some(self.get_ts(*ts).is_train_stop),
+                })
+                .collect();
+            let lanes: Vec<LaneType> = match roads.get(&r) {
+                Some(specs) => specs.iter().map(|spec| spec.lt).collect(),
+                None => self.get_r(r).lanes.iter().map(|l| l.lane_type).collect(),
+            };
+            for is_train_stop in is_train_stops {
+                let ok = if is_train_stop {
+                    lanes.contains(&LaneType::LightRail)
+                } else {
+                    lanes.contains(&LaneType::Driving) || lanes.contains(&LaneType::Bus)
+                };
+                if !ok {
+                    bail!(
+                        "{} would have a transit stop, but no lane for vehicles to stop along",
+                        r
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Closing intersections could disconnect sidewalks. Returns the sidewalks that aren't
+    /// reachable with `new_edits`, but are with the current edits.
+    pub fn find_newly_disconnected_sidewalks(&mut self, new_edits: MapEdits) -> BTreeSet<LaneID> {
+        let orig_edits = self.get_edits().clone();
+        let (_, disconnected_before) = connectivity::find_scc(self, PathConstraints::Pedestrian);
+
+        self.try_apply_edits(new_edits, &mut Timer::throwaway());
+        let (_, disconnected_after) = connectivity::find_scc(self, PathConstraints::Pedestrian);
+        self.must_apply_edits(orig_edits, &mut Timer::throwaway());
+
+        disconnected_after
+            .difference(&disconnected_before)
+            .cloned()
+            .collect()
+    }
+
+    /// Closing intersections, changing lane types, or reversing lanes could create new
+    /// blackholes. Returns the driving and biking lanes that'd be disconnected with `new_edits`.
+    pub fn find_newly_disconnected_lanes(&mut self, new_edits: MapEdits) -> BTreeSet<LaneID> {
+        let orig_edits = self.get_edits().clone();
+        let mut driving_ok_originally = BTreeSet::new();
+        let mut biking_ok_originally = BTreeSet::new();
+        for l in self.all_lanes() {
+            if !l.driving_blackhole {
+                driving_ok_originally.insert(l.id);
+            }
+            if !l.biking_blackhole {
+                biking_ok_originally.insert(l.id);
+            }
+        }
+
+        self.try_apply_edits(new_edits, &mut Timer::throwaway());
+        let mut newly_disconnected = BTreeSet::new();
+        for l in connectivity::find_scc(self, PathConstraints::Car).1 {
+            if driving_ok_originally.contains(&l) {
+                newly_disconnected.insert(l);
+            }
+        }
+        for l in connectivity::find_scc(self, PathConstraints::Bike).1 {
+            if biking_ok_originally.contains(&l) {
+                newly_disconnected.insert(l);
+            }
+        }
+        self.must_apply_edits(orig_edits, &mut Timer::throwaway());
+
+        newly_disconnected
+    }
+}
@@ -28,15 +28,16 @@ extern crate anyhow;
 #[macro_use]
 extern crate log;
 
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, BTreeSet};
 
 use serde::{Deserialize, Serialize};
 
 use abstio::MapName;
 use abstutil::{
-    deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
+    deserialize_appended, deserialize_btreemap, deserialize_multimap, serialize_btreemap,
+    serialize_multimap, MultiMap,
 };
-use geom::{Bounds, GPSBounds, Polygon};
+use geom::{Bounds, GPSBounds, Polygon, Time};
 // Re-export a bunch of things for convenience
 pub use osm2streets::ControlType as IntersectionType;
 pub use osm2streets::{
@@ -47,7 +48,8 @@ pub use raw_map::{Amenity, AmenityType, AreaType};
 
 pub use crate::city::City;
 pub use crate::edits::{
-    EditCmd, EditEffects, EditIntersection, EditRoad, MapEdits, PermanentMapEdits,
+    EditCmd, EditEffects, EditIntersection, EditRoad, EditTransitRoute, EditTransitStop, MapEdits,
+    PermanentEditCmd, PermanentMapEdits,
 };
 pub use crate::make::RawToMapOptions;
 pub use crate::objects::area::{Area, AreaID};
@@ -57,6 +59,10 @@ pub use crate::objects::intersection::{Intersection, IntersectionID};
 pub use crate::objects::lane::{CommonEndpoint, Lane, LaneID, PARKING_LOT_SPOT_LENGTH};
 pub use crate::objects::movement::{CompressedMovementID, Movement, MovementID};
 pub use crate::objects::parking_lot::{ParkingLot, ParkingLotID};
+pub use crate::objects::parking_policy::{
+    Charger, ChargerLocation, ParkingPolicy, ParkingRules, PermitZone,
+};
+pub use crate::objects::pricing::{Charge, Cordon, RoadPricing};
 pub use crate::objects::road::{DirectedRoadID, Road, RoadID, RoadSideID, SideOfRoad};
 pub use crate::objects::stop_signs::{ControlStopSign, RoadWithStopSign};
 pub use crate::objects::traffic_signals::{ControlTrafficSignal, Stage, StageType};
@@ -65,8 +71,8 @@ pub use crate::objects::turn::{Turn, TurnID, TurnPriority, TurnType};
 pub use crate::objects::zone::{AccessRestrictions, Zone};
 pub use crate::pathfind::uber_turns::{IntersectionCluster, UberTurn};
 pub use crate::pathfind::{
-    Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2, Pathfinder, PathfinderCache,
-    PathfinderCaching, RoutingParams,
+    AlternativeRoutes, Path, PathConstraints, PathRequest, PathStep, PathStepV2, PathV2,
+    Pathfinder, PathfinderCache, PathfinderCaching, RoutingParams,
 };
 pub use crate::traversable::{Position, Traversable, MAX_BIKE_SPEED, MAX_WALKING_SPEED};
 
@@ -91,7 +97,11 @@ pub struct Map {
         deserialize_with = "deserialize_btreemap"
     )]
     transit_stops: BTreeMap<TransitStopID, TransitStop>,
-    transit_routes: Vec<TransitRoute>,
+    #[serde(
+        serialize_with = "crate::objects::transit::serialize_transit_routes",
+        deserialize_with = "crate::objects::transit::deserialize_transit_routes"
+    )]
+    transit_routes: BTreeMap<TransitRouteID, TransitRoute>,
     areas: Vec<Area>,
     parking_lots: Vec<ParkingLot>,
     boundary_polygon: Polygon,
@@ -117,9 +127,24 @@ pub struct Map {
     zones: Vec<Zone>,
 
     name: MapName,
+    /// See `get_transit_stop_times`. Kept apart from `TransitRoute` and appended last, so that
+    /// maps imported before schedules existed still load.
+    #[serde(default, deserialize_with = "deserialize_appended")]
+    transit_stop_times: BTreeMap<TransitRouteID, Vec<Vec<Time>>>,
+    /// Which traffic signals were coordinated when the map was built. `ControlTrafficSignal`
+    /// doesn't serialize this itself, so that maps imported before coordination existed still
+    /// load.
+    #[serde(default, deserialize_with = "deserialize_appended")]
+    coordinated_signals: BTreeSet<IntersectionID>,
 
     #[serde(skip_serializing, skip_deserializing)]
     edits: MapEdits,
+    /// Only set by edits
+    #[serde(skip_serializing, skip_deserializing)]
+    road_pricing: RoadPricing,
+    /// Only set by edits
+    #[serde(skip_serializing, skip_deserializing)]
+    parking_policy: ParkingPolicy,
     #[serde(skip_serializing, skip_deserializing)]
     edits_generation: usize,
     #[serde(skip_serializing, skip_deserializing)]
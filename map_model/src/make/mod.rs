@@ -17,7 +17,7 @@ use crate::pathfind::{CreateEngine, Pathfinder};
 use crate::{
     connectivity, osm, AccessRestrictions, Area, AreaID, ControlStopSign, ControlTrafficSignal,
     Intersection, IntersectionID, IntersectionType, Lane, LaneID, Map, MapEdits, OriginalRoad,
-    PathConstraints, Position, Road, RoadID, RoutingParams, Zone,
+    ParkingPolicy, PathConstraints, Position, Road, RoadID, RoadPricing, RoutingParams, Zone,
 };
 
 mod bridges;
@@ -54,7 +54,9 @@ impl Map {
             intersections: Vec::new(),
             buildings: Vec::new(),
             transit_stops: BTreeMap::new(),
-            transit_routes: Vec::new(),
+            transit_routes: BTreeMap::new(),
+            transit_stop_times: BTreeMap::new(),
+            coordinated_signals: BTreeSet::new(),
             areas: Vec::new(),
             parking_lots: Vec::new(),
             zones: Vec::new(),
@@ -70,6 +72,8 @@ impl Map {
             routing_params: RoutingParams::default(),
             name: raw.name.clone(),
             edits: MapEdits::new(),
+            road_pricing: RoadPricing::default(),
+            parking_policy: ParkingPolicy::default(),
             edits_generation: 0,
             road_to_buildings: MultiMap::new(),
         };
@@ -291,6 +295,12 @@ impl Map {
         }
 
         traffic_signals::synchronize(&mut map);
+        map.coordinated_signals = map
+            .traffic_signals
+            .values()
+            .filter(|ts| ts.coordinated)
+            .map(|ts| ts.id)
+            .collect();
 
         timer.start("setup pathfinding");
         let engine = if opts.skip_ch {
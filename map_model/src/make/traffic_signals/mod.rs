@@ -107,6 +107,7 @@ fn new(id: IntersectionID) -> ControlTrafficSignal {
         id,
         stages: Vec::new(),
         offset: Duration::ZERO,
+        coordinated: false,
     }
 }
 
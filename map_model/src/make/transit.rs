@@ -39,7 +39,8 @@ pub fn finalize_transit(map: &mut Map, raw: &RawMap, timer: &mut Timer) {
 
     let snapper = BorderSnapper::new(map);
     for route in &raw.transit_routes {
-        if let Err(err) = create_route(route, map, &gtfs_to_stop_id, &snapper) {
+        let stop_times = raw.transit_stop_times.get(&route.gtfs_id);
+        if let Err(err) = create_route(route, stop_times, map, &gtfs_to_stop_id, &snapper) {
             warn!(
                 "Couldn't snap route {} ({}): {}",
                 route.gtfs_id, route.short_name, err
@@ -143,16 +144,20 @@ impl BorderSnapper {
 
 fn create_route(
     route: &RawTransitRoute,
+    stop_times: Option<&Vec<Vec<Time>>>,
     map: &mut Map,
     gtfs_to_stop_id: &HashMap<String, TransitStopID>,
     snapper: &BorderSnapper,
 ) -> Result<()> {
     // TODO At least warn about stops that failed to snap
-    let stops: Vec<TransitStopID> = route
-        .stops
-        .iter()
-        .filter_map(|gtfs_id| gtfs_to_stop_id.get(gtfs_id).cloned())
-        .collect();
+    let mut stops = Vec::new();
+    let mut stop_indices = Vec::new();
+    for (idx, gtfs_id) in route.stops.iter().enumerate() {
+        if let Some(id) = gtfs_to_stop_id.get(gtfs_id) {
+            stops.push(*id);
+            stop_indices.push(idx);
+        }
+    }
     if stops.is_empty() {
         bail!("No valid stops");
     }
@@ -219,12 +224,11 @@ fn create_route(
         }
     };
 
-    // TODO This'll come from the RawTransitRoute eventually. For now, every 30 minutes.
-    let spawn_times: Vec<Time> = (0..48)
+    // Without a schedule, just run every 30 minutes.
+    let default_spawn_times: Vec<Time> = (0..48)
         .map(|i| Time::START_OF_DAY + (i as f64) * Duration::minutes(30))
         .collect();
-
-    let result = TransitRoute {
+    let mut result = TransitRoute {
         id: TransitRouteID(map.transit_routes.len()),
         long_name: route.long_name.clone(),
         short_name: route.short_name.clone(),
@@ -236,13 +240,31 @@ fn create_route(
             RawTransitType::Bus => PathConstraints::Bus,
             RawTransitType::Train => PathConstraints::Train,
         },
-        spawn_times: spawn_times.clone(),
-        orig_spawn_times: spawn_times,
+        spawn_times: default_spawn_times.clone(),
+        orig_spawn_times: default_spawn_times,
     };
 
     // Check that the paths are valid
-    result.all_paths(map)?;
+    let paths = result.all_paths(map)?;
+
+    // Only keep the times for stops that snapped successfully. Vehicles spawn early enough to
+    // drive from the start to the first stop by the published time, or at midnight if that's
+    // not possible.
+    let trips: Vec<Vec<Time>> = stop_times
+        .into_iter()
+        .flatten()
+        .map(|times| stop_indices.iter().map(|idx| times[*idx]).collect())
+        .collect();
+    if !trips.is_empty() {
+        let lead_time = paths[0].estimate_duration(map, None);
+        result.spawn_times = trips
+            .iter()
+            .map(|times| times[0].clamped_sub(lead_time))
+            .collect();
+        result.orig_spawn_times = result.spawn_times.clone();
+        map.transit_stop_times.insert(result.id, trips);
+    }
 
-    map.transit_routes.push(result);
+    map.transit_routes.insert(result.id, result);
     Ok(())
 }
@@ -11,13 +11,13 @@ use geom::{Bounds, Distance, Duration, GPSBounds, Polygon, Pt2D, Ring, Time};
 use raw_map::RawMap;
 
 use crate::{
-    osm, Area, AreaID, AreaType, Building, BuildingID, BuildingType, CommonEndpoint,
-    CompressedMovementID, ControlStopSign, ControlTrafficSignal, DirectedRoadID, Direction,
-    DrivingSide, Intersection, IntersectionID, Lane, LaneID, LaneType, Map, MapConfig, MapEdits,
-    Movement, MovementID, OffstreetParking, OriginalRoad, ParkingLot, ParkingLotID, Path,
-    PathConstraints, PathRequest, PathV2, Pathfinder, PathfinderCaching, Position, Road, RoadID,
-    RoutingParams, TransitRoute, TransitRouteID, TransitStop, TransitStopID, Turn, TurnID,
-    TurnType, Zone,
+    osm, AlternativeRoutes, Area, AreaID, AreaType, Building, BuildingID, BuildingType,
+    CommonEndpoint, CompressedMovementID, ControlStopSign, ControlTrafficSignal, DirectedRoadID,
+    Direction, DrivingSide, Intersection, IntersectionID, Lane, LaneID, LaneType, Map, MapConfig,
+    MapEdits, Movement, MovementID, OffstreetParking, OriginalRoad, ParkingLot, ParkingLotID,
+    ParkingPolicy, Path, PathConstraints, PathRequest, PathV2, Pathfinder, PathfinderCaching,
+    Position, Road, RoadID, RoadPricing, RoutingParams, TransitRoute, TransitRouteID, TransitStop,
+    TransitStopID, Turn, TurnID, TurnType, Zone,
 };
 
 impl Map {
@@ -67,6 +67,11 @@ impl Map {
 
         self.edits = self.new_edits();
         self.recalculate_road_to_buildings();
+        for i in &self.coordinated_signals {
+            if let Some(ts) = self.traffic_signals.get_mut(i) {
+                ts.coordinated = true;
+            }
+        }
         self.recalculate_all_movements(timer);
 
         // Enable to work on shrinking map file sizes. Never run this on the web though --
@@ -131,7 +136,9 @@ impl Map {
             intersections: Vec::new(),
             buildings: Vec::new(),
             transit_stops: BTreeMap::new(),
-            transit_routes: Vec::new(),
+            transit_routes: BTreeMap::new(),
+            transit_stop_times: BTreeMap::new(),
+            coordinated_signals: BTreeSet::new(),
             areas: Vec::new(),
             parking_lots: Vec::new(),
             zones: Vec::new(),
@@ -153,6 +160,8 @@ impl Map {
             routing_params: RoutingParams::default(),
             name: MapName::blank(),
             edits: MapEdits::new(),
+            road_pricing: RoadPricing::default(),
+            parking_policy: ParkingPolicy::default(),
             edits_generation: 0,
             road_to_buildings: MultiMap::new(),
         }
@@ -310,7 +319,7 @@ impl Map {
     }
 
     pub fn maybe_get_tr(&self, route: TransitRouteID) -> Option<&TransitRoute> {
-        self.transit_routes.get(route.0)
+        self.transit_routes.get(&route)
     }
 
     pub fn get_r(&self, id: RoadID) -> &Road {
@@ -508,20 +517,26 @@ impl Map {
     }
 
     pub fn get_tr(&self, route: TransitRouteID) -> &TransitRoute {
-        &self.transit_routes[route.0]
+        &self.transit_routes[&route]
+    }
+
+    pub fn all_transit_routes(&self) -> impl Iterator<Item = &TransitRoute> {
+        self.transit_routes.values()
     }
 
-    pub fn all_transit_routes(&self) -> &Vec<TransitRoute> {
-        &self.transit_routes
+    /// When does every trip of a route arrive at each stop, according to its published schedule?
+    /// Trips line up with the route's `orig_spawn_times`, and each trip's times with its `stops`.
+    pub fn get_transit_stop_times(&self, route: TransitRouteID) -> Option<&Vec<Vec<Time>>> {
+        self.transit_stop_times.get(&route)
     }
 
     pub fn get_transit_route(&self, name: &str) -> Option<&TransitRoute> {
-        self.transit_routes.iter().find(|r| r.long_name == name)
+        self.transit_routes.values().find(|r| r.long_name == name)
     }
 
     pub fn get_routes_serving_stop(&self, stop: TransitStopID) -> Vec<&TransitRoute> {
         let mut routes = Vec::new();
-        for r in &self.transit_routes {
+        for r in self.transit_routes.values() {
             if r.stops.contains(&stop) {
                 routes.push(r);
             }
@@ -652,6 +667,21 @@ impl Map {
             .pathfind_with_params(req.clone(), params, cache_custom, self)
             .ok_or_else(|| anyhow!("can't fulfill {}", req))
     }
+    /// Finds up to `opts.max_routes` meaningfully different paths, starting with the best one.
+    /// Returns nothing if there's no path at all.
+    pub fn pathfind_alternatives(&self, req: PathRequest, opts: &AlternativeRoutes) -> Vec<PathV2> {
+        self.pathfind_alternatives_with_params(req, self.routing_params(), opts)
+    }
+    pub fn pathfind_alternatives_with_params(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        opts: &AlternativeRoutes,
+    ) -> Vec<PathV2> {
+        assert!(!self.pathfinder_dirty);
+        self.pathfinder
+            .pathfind_alternatives_with_params(req, params, opts, self)
+    }
     pub fn should_use_transit(
         &self,
         start: Position,
@@ -740,8 +770,9 @@ impl Map {
     }
 
     pub fn hack_override_orig_spawn_times(&mut self, br: TransitRouteID, times: Vec<Time>) {
-        self.transit_routes[br.0].orig_spawn_times = times.clone();
-        self.transit_routes[br.0].spawn_times = times;
+        let route = self.transit_routes.get_mut(&br).unwrap();
+        route.orig_spawn_times = times.clone();
+        route.spawn_times = times;
     }
 
     pub fn hack_add_area(&mut self, area_type: AreaType, polygon: Polygon, osm_tags: Tags) {
@@ -864,6 +895,16 @@ impl Map {
         &self.routing_params
     }
 
+    /// Tolls and cordon charges, from map edits
+    pub fn get_road_pricing(&self) -> &RoadPricing {
+        &self.road_pricing
+    }
+
+    /// Parking prices, time limits, and permit zones, from map edits
+    pub fn get_parking_policy(&self) -> &ParkingPolicy {
+        &self.parking_policy
+    }
+
     pub fn road_to_buildings(&self, r: RoadID) -> &BTreeSet<BuildingID> {
         self.road_to_buildings.get(r)
     }
@@ -1,6 +1,9 @@
 use std::collections::BTreeSet;
 use std::fmt;
 
+use schemars::gen::SchemaGenerator;
+use schemars::schema::Schema;
+use schemars::JsonSchema;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use geom::{Distance, Line, PolyLine, Polygon, Pt2D};
@@ -72,6 +75,21 @@ impl<'de> Deserialize<'de> for LaneID {
     }
 }
 
+impl JsonSchema for LaneID {
+    fn schema_name() -> String {
+        "LaneID".to_string()
+    }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+        let mut schema = u32::json_schema(gen).into_object();
+        schema.metadata().description = Some(
+            "The RoadID in the upper 27 bits, and the lane's offset from the left in the lower 5"
+                .to_string(),
+        );
+        schema.into()
+    }
+}
+
 /// A road segment is broken down into individual lanes, which have a LaneType.
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct Lane {
@@ -5,6 +5,8 @@ pub mod intersection;
 pub mod lane;
 pub mod movement;
 pub mod parking_lot;
+pub mod parking_policy;
+pub mod pricing;
 pub mod road;
 pub mod stop_signs;
 pub mod traffic_signals;
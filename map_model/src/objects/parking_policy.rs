@@ -0,0 +1,119 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Duration, Time};
+
+use crate::{BuildingID, Charge, LaneID, Map, ParkingLotID};
+
+/// Prices, time limits, and permit zones for public parking, and chargers for electric cars. These
+/// only come from map edits. Private offstreet parking is only affected by chargers.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct ParkingPolicy {
+    /// Keyed by parking lane
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub onstreet: BTreeMap<LaneID, ParkingRules>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub lots: BTreeMap<ParkingLotID, ParkingRules>,
+    pub permit_zones: Vec<PermitZone>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub chargers: BTreeMap<ChargerLocation, Charger>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ParkingRules {
+    /// Charged per hour. Drivers pay up-front for their whole stay, using the rate when they
+    /// arrive.
+    pub hourly_rate: Charge,
+    /// Drivers planning to stay longer than this won't park here
+    pub max_stay: Option<Duration>,
+}
+
+/// Only residents may park on-street in this zone.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PermitZone {
+    pub name: String,
+    /// Parking lanes
+    pub lanes: BTreeSet<LaneID>,
+    /// Cars belonging to people living here have a permit
+    pub residents: BTreeSet<BuildingID>,
+}
+
+/// Where a charger is. Chargers turn the first few parking spots there into charging spots.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ChargerLocation {
+    /// A parking lane
+    Onstreet(LaneID),
+    Building(BuildingID),
+    Lot(ParkingLotID),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Charger {
+    /// How many parking spots have a charger
+    pub num_spots: usize,
+    /// How quickly each spot charges a battery
+    pub power_kw: f64,
+}
+
+impl ParkingRules {
+    /// The fee for parking from `arrival` for `stay`
+    pub fn fee(&self, arrival: Time, stay: Duration) -> f64 {
+        self.hourly_rate.at(arrival) * stay.inner_seconds() / 3600.0
+    }
+
+    /// Can somebody park here for this long? None means indefinitely.
+    pub fn allows_stay(&self, stay: Option<Duration>) -> bool {
+        match (self.max_stay, stay) {
+            (Some(max), Some(stay)) => stay <= max,
+            (Some(_), None) => false,
+            (None, _) => true,
+        }
+    }
+}
+
+impl PermitZone {
+    /// Covers every parking lane and building within some distance of a building
+    pub fn around_building(name: String, b: BuildingID, radius: Distance, map: &Map) -> PermitZone {
+        let center = map.get_b(b).polygon.center();
+        PermitZone {
+            name,
+            lanes: map
+                .all_lanes()
+                .filter(|l| l.is_parking() && l.lane_center_pts.middle().dist_to(center) <= radius)
+                .map(|l| l.id)
+                .collect(),
+            residents: map
+                .all_buildings()
+                .iter()
+                .filter(|bldg| bldg.polygon.center().dist_to(center) <= radius)
+                .map(|bldg| bldg.id)
+                .collect(),
+        }
+    }
+}
+
+impl ParkingPolicy {
+    /// True if nothing affects where cars choose to park. Chargers only matter to electric cars
+    /// low on charge, so they don't count.
+    pub fn is_empty(&self) -> bool {
+        self.onstreet.is_empty() && self.lots.is_empty() && self.permit_zones.is_empty()
+    }
+
+    /// Can a car whose owner lives at `home` park on this lane?
+    pub fn has_permit(&self, l: LaneID, home: Option<BuildingID>) -> bool {
+        self.permit_zones.iter().all(|zone| {
+            !zone.lanes.contains(&l) || home.map(|b| zone.residents.contains(&b)).unwrap_or(false)
+        })
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Duration, Polygon, Time};
+
+use crate::{Map, MovementID, Path, PathStep, RoadID, RoutingParams};
+
+/// Fees for driving certain places, like tolls on roads and congestion charges for entering an
+/// area. These only come from map edits. Fees are in whatever the local currency is, and only
+/// private cars pay them.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct RoadPricing {
+    /// Charged every time a car enters one of these roads, in either direction
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pub tolls: BTreeMap<RoadID, Charge>,
+    pub cordons: Vec<Cordon>,
+}
+
+/// An area that costs something to drive into. Driving around inside or leaving is free.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cordon {
+    pub name: String,
+    /// The roads inside the area. Entering one of these from any other road is charged.
+    pub roads: BTreeSet<RoadID>,
+    pub charge: Charge,
+}
+
+/// A fee that might depend on the time of day
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Charge {
+    /// Charged outside of all of the periods
+    pub base_fee: f64,
+    /// (start, end, fee). If periods overlap, the first one wins.
+    pub periods: Vec<(Time, Time, f64)>,
+}
+
+impl Charge {
+    pub fn flat(fee: f64) -> Charge {
+        Charge {
+            base_fee: fee,
+            periods: Vec::new(),
+        }
+    }
+
+    pub fn at(&self, time: Time) -> f64 {
+        for (start, end, fee) in &self.periods {
+            if time >= *start && time < *end {
+                return *fee;
+            }
+        }
+        self.base_fee
+    }
+
+    /// The most this ever costs, at any time of day
+    pub fn max(&self) -> f64 {
+        self.periods
+            .iter()
+            .map(|(_, _, fee)| *fee)
+            .fold(self.base_fee, f64::max)
+    }
+
+    pub fn varies_by_time(&self) -> bool {
+        self.periods.iter().any(|(_, _, fee)| *fee != self.base_fee)
+    }
+}
+
+impl Cordon {
+    /// A cordon around every road with its center inside the polygon
+    pub fn from_polygon(name: String, polygon: &Polygon, charge: Charge, map: &Map) -> Cordon {
+        Cordon {
+            name,
+            roads: map
+                .all_roads()
+                .iter()
+                .filter(|r| polygon.contains_pt(r.center_pts.middle()))
+                .map(|r| r.id)
+                .collect(),
+            charge,
+        }
+    }
+}
+
+impl RoadPricing {
+    pub fn is_empty(&self) -> bool {
+        self.tolls.is_empty() && self.cordons.is_empty()
+    }
+
+    /// If false, the same fees apply all day.
+    pub fn varies_by_time(&self) -> bool {
+        self.tolls.values().any(|charge| charge.varies_by_time())
+            || self.cordons.iter().any(|c| c.charge.varies_by_time())
+    }
+
+    /// The fee for a car making this movement, entering the road at the end. When the time isn't
+    /// known, assume the highest charge of the day.
+    pub fn movement_fee(&self, mvmnt: MovementID, time: Option<Time>) -> f64 {
+        let fee = |charge: &Charge| match time {
+            Some(t) => charge.at(t),
+            None => charge.max(),
+        };
+        let mut total = self.tolls.get(&mvmnt.to.road).map(fee).unwrap_or(0.0);
+        for cordon in &self.cordons {
+            if !cordon.roads.contains(&mvmnt.from.road) && cordon.roads.contains(&mvmnt.to.road) {
+                total += fee(&cordon.charge);
+            }
+        }
+        total
+    }
+
+    /// The fee for a car making this movement as extra time, using the value of time. Drivers who
+    /// don't care about money ignore fees.
+    pub fn movement_cost(&self, mvmnt: MovementID, params: &RoutingParams) -> Duration {
+        if params.value_of_time > 0.0 {
+            let fee = self.movement_fee(mvmnt, params.pricing_time);
+            if fee > 0.0 {
+                return Duration::seconds(3600.0 * fee / params.value_of_time);
+            }
+        }
+        Duration::ZERO
+    }
+
+    /// Estimates the fees for driving along a path, assuming everything along it is charged
+    /// at the departure time.
+    pub fn path_fee(&self, path: &Path, departure: Time, map: &Map) -> f64 {
+        if self.is_empty() {
+            return 0.0;
+        }
+        path.get_steps()
+            .iter()
+            .filter_map(|step| match step {
+                PathStep::Turn(t) => Some(self.movement_fee(t.to_movement(map), Some(departure))),
+                _ => None,
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DirectedRoadID, Direction, IntersectionID};
+
+    fn mvmnt(from: usize, to: usize) -> MovementID {
+        MovementID {
+            from: DirectedRoadID {
+                road: RoadID(from),
+                dir: Direction::Fwd,
+            },
+            to: DirectedRoadID {
+                road: RoadID(to),
+                dir: Direction::Fwd,
+            },
+            parent: IntersectionID(0),
+            crosswalk: false,
+        }
+    }
+
+    fn hour(h: usize) -> Time {
+        Time::START_OF_DAY + Duration::hours(h)
+    }
+
+    fn peak_charge() -> Charge {
+        Charge {
+            base_fee: 2.0,
+            periods: vec![(hour(7), hour(9), 5.0)],
+        }
+    }
+
+    #[test]
+    fn movement_fees() {
+        let mut pricing = RoadPricing::default();
+        pricing.tolls.insert(RoadID(3), Charge::flat(1.0));
+        pricing.cordons.push(Cordon {
+            name: "downtown".to_string(),
+            roads: vec![RoadID(1), RoadID(2), RoadID(3)].into_iter().collect(),
+            charge: peak_charge(),
+        });
+
+        // Only entering the cordon is charged
+        assert_eq!(pricing.movement_fee(mvmnt(0, 1), Some(hour(12))), 2.0);
+        assert_eq!(pricing.movement_fee(mvmnt(0, 1), Some(hour(8))), 5.0);
+        assert_eq!(pricing.movement_fee(mvmnt(1, 2), Some(hour(8))), 0.0);
+        assert_eq!(pricing.movement_fee(mvmnt(2, 0), Some(hour(8))), 0.0);
+        // Tolls apply inside the cordon too
+        assert_eq!(pricing.movement_fee(mvmnt(2, 3), Some(hour(8))), 1.0);
+        assert_eq!(pricing.movement_fee(mvmnt(0, 3), Some(hour(8))), 6.0);
+        // Without a time, assume the worst
+        assert_eq!(pricing.movement_fee(mvmnt(0, 1), None), 5.0);
+        assert!(pricing.varies_by_time());
+    }
+
+    #[test]
+    fn fees_in_routing_costs() {
+        let mut pricing = RoadPricing::default();
+        pricing.tolls.insert(RoadID(1), peak_charge());
+        let mut params = RoutingParams::default();
+
+        // Fees become time, using the value of time
+        let as_time = |params: &RoutingParams, fee: f64| {
+            Duration::seconds(3600.0 * fee / params.value_of_time)
+        };
+        assert_eq!(
+            pricing.movement_cost(mvmnt(0, 1), &params),
+            as_time(&params, 5.0)
+        );
+        params.pricing_time = Some(hour(12));
+        assert_eq!(
+            pricing.movement_cost(mvmnt(0, 1), &params),
+            as_time(&params, 2.0)
+        );
+        assert_eq!(pricing.movement_cost(mvmnt(1, 0), &params), Duration::ZERO);
+        // Drivers who don't care about money ignore fees
+        params.value_of_time = 0.0;
+        assert_eq!(pricing.movement_cost(mvmnt(0, 1), &params), Duration::ZERO);
+    }
+}
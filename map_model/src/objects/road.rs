@@ -3,6 +3,7 @@ use std::fmt;
 
 use anyhow::Result;
 use enumset::EnumSet;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize, Tags};
@@ -14,7 +15,9 @@ use crate::{
     LaneSpec, LaneType, Map, OriginalRoad, PathConstraints, RestrictionType, TransitStopID, Zone,
 };
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct RoadID(
     #[serde(
         serialize_with = "serialize_usize",
@@ -57,12 +60,24 @@ impl RoadID {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct DirectedRoadID {
     pub road: RoadID,
+    #[schemars(with = "DirectionSchema")]
     pub dir: Direction,
 }
 
+/// Direction is defined in osm2streets, so describe how it's serialized here.
+#[derive(JsonSchema)]
+#[schemars(rename = "Direction")]
+#[allow(dead_code)]
+enum DirectionSchema {
+    Fwd,
+    Back,
+}
+
 impl fmt::Display for DirectedRoadID {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "DirectedRoadID({}, {})", self.road.0, self.dir,)
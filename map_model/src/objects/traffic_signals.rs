@@ -1,6 +1,7 @@
 use std::collections::{BTreeMap, BTreeSet};
 
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use geom::{Distance, Duration, Speed};
@@ -17,14 +18,20 @@ const CROSSWALK_PACE: Speed = Speed::const_meters_per_second(1.4);
 /// A traffic signal consists of a sequence of Stages that repeat in a cycle. Most Stages last for a
 /// fixed duration. During a single Stage, some movements are protected (can proceed with the
 /// highest priority), while others are permitted (have to yield before proceeding).
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, JsonSchema)]
 pub struct ControlTrafficSignal {
     pub id: IntersectionID,
     pub stages: Vec<Stage>,
     pub offset: Duration,
+    /// Coordinated signals keep a fixed cycle length, and the first stage absorbs any time unused
+    /// by actuated stages. See `traffic_signal_data::Plan`.
+    ///
+    /// The map's binary format stores this separately, in `Map::coordinated_signals`.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub coordinated: bool,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub struct Stage {
     pub protected_movements: BTreeSet<MovementID>,
     pub yield_movements: BTreeSet<MovementID>,
@@ -33,21 +40,27 @@ pub struct Stage {
     pub stage_type: StageType,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, JsonSchema)]
 pub enum StageType {
     Fixed(Duration),
     /// Minimum is the minimum duration, 0 allows cycle to be skipped if no demand.
     /// Delay is the elapsed time with no demand that ends a cycle.
     /// Additional is the additional duration for an extended cycle.
     Variable(Duration, Duration, Duration),
+    /// Minimum green, maximum green, passage time. The stage gaps out when no vehicle reaches the
+    /// detectors on its protected approaches within the passage time, or maxes out.
+    Actuated(Duration, Duration, Duration),
 }
 
 impl StageType {
     // TODO Maybe don't have this; force callers to acknowledge different policies
+    /// For actuated stages, this is the maximum green, which is also the stage's share of the
+    /// cycle when the signal is coordinated.
     pub fn simple_duration(&self) -> Duration {
         match self {
             StageType::Fixed(d) => *d,
             StageType::Variable(duration, _, _) => *duration,
+            StageType::Actuated(_, max_green, _) => *max_green,
         }
     }
 }
@@ -135,16 +148,35 @@ impl ControlTrafficSignal {
                 // TODO Maybe make UnmarkedCrossing yield
                 assert!(!m.turn_type.pedestrian_crossing())
             }
+            if let StageType::Actuated(min_green, max_green, passage) = stage.stage_type {
+                if min_green > max_green {
+                    bail!(
+                        "Stage {} has a minimum green of {}, longer than the maximum {}",
+                        stage_index,
+                        min_green,
+                        max_green
+                    );
+                }
+                if passage <= Duration::ZERO {
+                    bail!("Stage {} needs a positive passage time", stage_index);
+                }
+            }
+
             // Is there enough time in each stage to walk across the crosswalk
             let min_crossing_time = self.get_min_crossing_time(stage_index, i);
-            if stage.stage_type.simple_duration() < min_crossing_time {
+            // An actuated stage might only last for the minimum green
+            let time_given = match stage.stage_type {
+                StageType::Actuated(min_green, _, _) => min_green,
+                _ => stage.stage_type.simple_duration(),
+            };
+            if time_given < min_crossing_time {
                 bail!(
                     "Traffic signal does not allow enough time in stage to complete the \
                      crosswalk\nStage Index{}\nStage : {:?}\nTime Required: {}\nTime Given: {}",
                     stage_index,
                     stage,
                     min_crossing_time,
-                    stage.stage_type.simple_duration()
+                    time_given
                 );
             }
         }
@@ -345,12 +377,19 @@ impl Stage {
                 .inner_seconds()
                 .ceil(),
         );
+        if let StageType::Actuated(min_green, max_green, passage) = self.stage_type {
+            if time > min_green {
+                self.stage_type = StageType::Actuated(time, max_green.max(time), passage);
+            }
+            return;
+        }
         if time > self.stage_type.simple_duration() {
             self.stage_type = match self.stage_type {
                 StageType::Fixed(_) => StageType::Fixed(time),
                 StageType::Variable(_, delay, additional) => {
                     StageType::Variable(time, delay, additional)
                 }
+                StageType::Actuated(_, _, _) => unreachable!(),
             };
         }
     }
@@ -407,10 +446,18 @@ impl ControlTrafficSignal {
                                     additional.inner_seconds() as usize,
                                 )
                             }
+                            StageType::Actuated(min_green, max_green, passage) => {
+                                traffic_signal_data::StageType::Actuated(
+                                    min_green.inner_seconds() as usize,
+                                    max_green.inner_seconds() as usize,
+                                    passage.inner_seconds() as usize,
+                                )
+                            }
                         },
                     })
                     .collect(),
                 offset_seconds: self.offset.inner_seconds() as usize,
+                coordinated: self.coordinated,
             }],
         }
     }
@@ -462,6 +509,13 @@ impl ControlTrafficSignal {
                                 Duration::seconds(additional as f64),
                             )
                         }
+                        traffic_signal_data::StageType::Actuated(min_green, max_green, passage) => {
+                            StageType::Actuated(
+                                Duration::seconds(min_green as f64),
+                                Duration::seconds(max_green as f64),
+                                Duration::seconds(passage as f64),
+                            )
+                        }
                     },
                 });
             } else {
@@ -472,6 +526,7 @@ impl ControlTrafficSignal {
             id,
             stages,
             offset: Duration::seconds(plan.offset_seconds as f64),
+            coordinated: plan.coordinated,
         };
         ts.validate(map.get_i(id))?;
         Ok(ts)
@@ -1,16 +1,36 @@
 //! Public transit stops and routes.
 
+use std::collections::BTreeMap;
 use std::fmt;
 
 use anyhow::Result;
-use serde::{Deserialize, Serialize};
+use schemars::JsonSchema;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 use abstutil::{deserialize_usize, serialize_usize};
 use geom::Time;
 
 use crate::{LaneID, Map, Path, PathConstraints, PathRequest, Position, RoadID};
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+/// Routes used to be stored in a list indexed by their ID. Routes can now be deleted, leaving
+/// gaps, but keep the same format so existing maps still load.
+pub(crate) fn serialize_transit_routes<S: Serializer>(
+    map: &BTreeMap<TransitRouteID, TransitRoute>,
+    s: S,
+) -> Result<S::Ok, S::Error> {
+    map.values().collect::<Vec<_>>().serialize(s)
+}
+
+pub(crate) fn deserialize_transit_routes<'de, D: Deserializer<'de>>(
+    d: D,
+) -> Result<BTreeMap<TransitRouteID, TransitRoute>, D::Error> {
+    let routes = <Vec<TransitRoute>>::deserialize(d)?;
+    Ok(routes.into_iter().map(|r| (r.id, r)).collect())
+}
+
+#[derive(
+    Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
 pub struct TransitStopID {
     pub road: RoadID,
     /// As long as this is unique per road, this value is otherwise meaningless. Not contiguous or
@@ -24,7 +44,9 @@ impl fmt::Display for TransitStopID {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct TransitRouteID(
     #[serde(
         serialize_with = "serialize_usize",
@@ -138,6 +160,22 @@ impl TransitRoute {
         Ok(paths)
     }
 
+    /// When should a vehicle that spawned at `spawned_at` arrive at the stop with this index?
+    /// None if the schedule is unknown.
+    ///
+    /// Vehicles spawning at one of the `orig_spawn_times` follow that trip's published times. If
+    /// the spawn times were edited, follow the trip that originally spawned closest, shifted to
+    /// the new spawn time.
+    pub fn scheduled_arrival(&self, map: &Map, spawned_at: Time, stop_idx: usize) -> Option<Time> {
+        let trips = map.get_transit_stop_times(self.id)?;
+        if trips.len() != self.orig_spawn_times.len() {
+            return None;
+        }
+        let trip =
+            (0..trips.len()).min_by_key(|idx| (self.orig_spawn_times[*idx] - spawned_at).abs())?;
+        Some(spawned_at + (*trips[trip].get(stop_idx)? - self.orig_spawn_times[trip]))
+    }
+
     pub fn plural_noun(&self) -> &'static str {
         if self.route_type == PathConstraints::Bus {
             "buses"
@@ -2,7 +2,7 @@ use std::cell::RefCell;
 use std::collections::HashMap;
 
 use fast_paths::{deserialize_32, serialize_32, FastGraph, InputGraph, PathCalculator};
-use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::graph::{DiGraph, EdgeIndex, NodeIndex};
 use serde::{Deserialize, Serialize};
 use thread_local::ThreadLocal;
 
@@ -99,6 +99,34 @@ impl PathfindEngine {
         }
     }
 
+    /// Builds a Dijkstra engine directly from (from node, to node, weight) edges. Unlike going
+    /// through an `InputGraph`, edge IDs match the order of the input, for `set_edge_weight`.
+    pub fn dijkstra_from_edges<I: Iterator<Item = (usize, usize, usize)>>(
+        num_nodes: usize,
+        edges: I,
+    ) -> PathfindEngine {
+        let mut graph = DiGraph::new();
+        let dummy_weight = 42;
+        for node in 0..num_nodes {
+            assert_eq!(graph.add_node(dummy_weight).index(), node);
+        }
+        for (from, to, weight) in edges {
+            graph.add_edge(NodeIndex::new(from), NodeIndex::new(to), weight);
+        }
+        PathfindEngine::Dijkstra { graph }
+    }
+
+    /// Changes the weight of one edge in a Dijkstra engine. Contraction hierarchies can't be
+    /// changed after they're prepared.
+    pub fn set_edge_weight(&mut self, edge: usize, weight: usize) {
+        match self {
+            PathfindEngine::Dijkstra { ref mut graph } => {
+                graph[EdgeIndex::new(edge)] = weight;
+            }
+            PathfindEngine::Empty | PathfindEngine::CH { .. } => unreachable!(),
+        }
+    }
+
     pub fn reuse_ordering(&self) -> CreateEngine {
         match self {
             PathfindEngine::Empty => unreachable!(),
@@ -135,21 +163,13 @@ pub enum CreateEngine<'a> {
 impl<'a> CreateEngine<'a> {
     pub fn create(&self, input_graph: InputGraph) -> PathfindEngine {
         match self {
-            CreateEngine::Dijkstra => {
-                let mut graph = DiGraph::new();
-                let dummy_weight = 42;
-                for node in 0..input_graph.get_num_nodes() {
-                    assert_eq!(graph.add_node(dummy_weight).index(), node);
-                }
-                for edge in input_graph.get_edges() {
-                    graph.add_edge(
-                        NodeIndex::new(edge.from),
-                        NodeIndex::new(edge.to),
-                        edge.weight,
-                    );
-                }
-                PathfindEngine::Dijkstra { graph }
-            }
+            CreateEngine::Dijkstra => PathfindEngine::dijkstra_from_edges(
+                input_graph.get_num_nodes(),
+                input_graph
+                    .get_edges()
+                    .iter()
+                    .map(|edge| (edge.from, edge.to, edge.weight)),
+            ),
             CreateEngine::CH => {
                 info!(
                     "Contraction hierarchy input graph has {} nodes",
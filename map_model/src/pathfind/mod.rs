@@ -1,11 +1,11 @@
 //! Everything related to pathfinding through a map for different types of agents.
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use enumset::EnumSetType;
 use serde::{Deserialize, Serialize};
 
-use geom::Duration;
+use geom::{Duration, Time};
 
 pub use self::engine::CreateEngine;
 pub use self::pathfinder::{Pathfinder, PathfinderCache, PathfinderCaching};
@@ -196,6 +196,33 @@ pub struct RoutingParams {
     /// Don't allow movements between these roads at all. Only affects vehicle routing, not
     /// pedestrian.
     pub avoid_movements_between: BTreeSet<(RoadID, RoadID)>,
+
+    /// How long vehicles were actually observed to take crossing a road and making it through
+    /// the movement at the end, including any time spent waiting at the intersection. When
+    /// present, this replaces the free-flow estimate and `unprotected_turn_penalty` for that
+    /// movement. Only affects vehicle routing.
+    ///
+    /// These come from a running simulation, so they're never saved with the map.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub observed_movement_times: BTreeMap<MovementID, Duration>,
+
+    /// How much money a driver would pay to save an hour of travel. Road pricing fees are turned
+    /// into an equivalent delay using this. Only affects car routing. Not saved with the map, so
+    /// existing maps still load.
+    #[serde(
+        skip_serializing,
+        skip_deserializing,
+        default = "default_value_of_time"
+    )]
+    pub value_of_time: f64,
+    /// When to evaluate road pricing that changes through the day. If None, assume the highest
+    /// charge of the day applies.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub pricing_time: Option<Time>,
+}
+
+fn default_value_of_time() -> f64 {
+    15.0
 }
 
 impl Default for RoutingParams {
@@ -216,6 +243,36 @@ impl Default for RoutingParams {
 
             avoid_roads: BTreeSet::new(),
             avoid_movements_between: BTreeSet::new(),
+
+            observed_movement_times: BTreeMap::new(),
+
+            value_of_time: default_value_of_time(),
+            pricing_time: None,
+        }
+    }
+}
+
+/// How to find several different routes between the same start and end.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AlternativeRoutes {
+    /// Return at most this many routes, including the best one
+    pub max_routes: usize,
+    /// After finding a route, multiply the cost of crossing each of its roads by this. Higher
+    /// values find routes that are more different, but maybe much slower.
+    pub penalty: f64,
+    /// Skip routes sharing more than this fraction of their length with a previous route
+    pub max_overlap: f64,
+    /// Skip routes costing more than this multiple of the best route
+    pub max_cost_ratio: f64,
+}
+
+impl Default for AlternativeRoutes {
+    fn default() -> Self {
+        Self {
+            max_routes: 3,
+            penalty: 1.5,
+            max_overlap: 0.7,
+            max_cost_ratio: 1.5,
         }
     }
 }
@@ -11,8 +11,8 @@ use crate::pathfind::engine::CreateEngine;
 use crate::pathfind::vehicles::VehiclePathfinder;
 use crate::pathfind::walking::SidewalkPathfinder;
 use crate::{
-    DirectedRoadID, Map, PathConstraints, PathRequest, PathV2, Position, RoutingParams,
-    TransitRouteID, TransitStopID,
+    AlternativeRoutes, DirectedRoadID, Map, PathConstraints, PathRequest, PathV2, Position,
+    RoutingParams, TransitRouteID, TransitStopID,
 };
 
 #[derive(Serialize, Deserialize)]
@@ -271,6 +271,44 @@ impl Pathfinder {
         result
     }
 
+    /// Finds up to `opts.max_routes` meaningfully different paths for a vehicle, starting with
+    /// the best one. Uses the RoutingParams built into this Pathfinder. Pedestrians only get the
+    /// one best path.
+    pub fn pathfind_alternatives(
+        &self,
+        req: PathRequest,
+        opts: &AlternativeRoutes,
+        map: &Map,
+    ) -> Vec<PathV2> {
+        self.pathfind_alternatives_with_params(req, &self.params, opts, map)
+    }
+
+    /// Like `pathfind_alternatives`, but may use custom routing parameters. Nothing is cached;
+    /// the search for alternatives builds its own graph either way.
+    pub fn pathfind_alternatives_with_params(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        opts: &AlternativeRoutes,
+        map: &Map,
+    ) -> Vec<PathV2> {
+        match req.constraints {
+            PathConstraints::Pedestrian => self
+                .pathfind_with_params(req, params, PathfinderCaching::NoCache, map)
+                .into_iter()
+                .take(opts.max_routes)
+                .collect(),
+            PathConstraints::Car => self.car_graph.pathfind_alternatives(req, params, opts, map),
+            PathConstraints::Bike => self
+                .bike_graph
+                .pathfind_alternatives(req, params, opts, map),
+            PathConstraints::Bus => self.bus_graph.pathfind_alternatives(req, params, opts, map),
+            PathConstraints::Train => self
+                .train_graph
+                .pathfind_alternatives(req, params, opts, map),
+        }
+    }
+
     pub fn all_costs_from(
         &self,
         req: PathRequest,
@@ -198,6 +198,18 @@ impl Path {
         path
     }
 
+    /// A path along just one lane, without pathfinding. The caller can extend it with `add`.
+    pub fn one_step(req: PathRequest, map: &Map) -> Path {
+        assert_eq!(req.start.lane(), req.end.lane());
+        Path::new(
+            map,
+            vec![PathStep::Lane(req.start.lane())],
+            req,
+            Vec::new(),
+            Vec::new(),
+        )
+    }
+
     /// Once we finish this PathStep, how much distance will be crossed? If the step is at the
     /// beginning or end of our path, then the full length may not be used.
     pub fn dist_crossed_from_step(&self, map: &Map, step: &PathStep) -> Distance {
@@ -350,6 +362,24 @@ impl Path {
         }
     }
 
+    /// Replace everything after the current step with a different path, which must start with
+    /// the current step and end at the same place as the original request. Can't be used in the
+    /// middle of an uber-turn.
+    pub fn reroute(&mut self, new_path: Path, map: &Map) {
+        assert!(self.currently_inside_ut.is_none());
+        assert_eq!(self.steps[0], new_path.steps[0]);
+        assert_eq!(self.orig_req.end, new_path.orig_req.end);
+
+        for step in self.steps.iter().skip(1) {
+            self.total_length -= self.dist_crossed_from_step(map, step);
+        }
+        self.steps = new_path.steps;
+        self.uber_turns = new_path.uber_turns;
+        for step in self.steps.iter().skip(1) {
+            self.total_length += self.dist_crossed_from_step(map, step);
+        }
+    }
+
     pub fn current_step(&self) -> PathStep {
         self.steps[0]
     }
@@ -1,6 +1,6 @@
 //! Pathfinding for cars, bikes, buses, and trains using contraction hierarchies
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
 use fast_paths::InputGraph;
 use serde::{Deserialize, Serialize};
@@ -14,8 +14,8 @@ use crate::pathfind::uber_turns::{IntersectionCluster, UberTurnV2};
 use crate::pathfind::zone_cost;
 use crate::pathfind::{round, unround};
 use crate::{
-    osm, DirectedRoadID, Direction, LaneType, Map, MovementID, PathConstraints, PathRequest,
-    PathV2, Position, RoutingParams, Traversable,
+    osm, AlternativeRoutes, DirectedRoadID, Direction, LaneType, Map, MovementID, PathConstraints,
+    PathRequest, PathV2, Position, RoutingParams, Traversable,
 };
 
 #[derive(Clone, Serialize, Deserialize)]
@@ -91,7 +91,120 @@ impl VehiclePathfinder {
         if matches!(self.engine, PathfindEngine::Empty) {
             return None;
         }
+        let (raw_weight, road_steps, uber_turns) = self.pathfind_roads(&self.engine, &req, map)?;
+        let cost = unround(raw_weight);
+        Some(PathV2::from_roads(road_steps, req, cost, uber_turns, map))
+    }
+
+    /// Returns up to `opts.max_routes` meaningfully different paths, starting with the best one.
+    /// This uses the penalty method: after finding each route, the roads it crosses become more
+    /// expensive, and the search repeats. Routes overlapping too much with a previous one or
+    /// costing too much more than the best are skipped.
+    ///
+    /// The penalized searches use one temporary Dijkstra graph built with `params`, so this works
+    /// no matter what engine this pathfinder uses, and with any RoutingParams. Only the weights of
+    /// penalized edges change between searches. The cost of every returned path is recalculated
+    /// without penalties, so the costs are comparable to each other.
+    pub fn pathfind_alternatives(
+        &self,
+        req: PathRequest,
+        params: &RoutingParams,
+        opts: &AlternativeRoutes,
+        map: &Map,
+    ) -> Vec<PathV2> {
+        if matches!(self.engine, PathfindEngine::Empty) || opts.max_routes == 0 {
+            return Vec::new();
+        }
+
+        let edges = make_edges(self.constraints, &self.nodes, &self.uber_turns, params, map);
+        let mut penalties: HashMap<DirectedRoadID, f64> = HashMap::new();
+        let mut edges_per_road: MultiMap<DirectedRoadID, usize> = MultiMap::new();
+        for (idx, edge) in edges.iter().enumerate() {
+            for (dr, _) in &edge.costs {
+                edges_per_road.insert(*dr, idx);
+            }
+        }
+        let mut engine = PathfindEngine::dijkstra_from_edges(
+            self.nodes.num_nodes(),
+            edges
+                .iter()
+                .map(|edge| (edge.from, edge.to, edge.weight(&penalties))),
+        );
+
+        // The best route doesn't need any penalties, so use the faster engine if possible
+        let best = if params == &self.params {
+            self.pathfind_roads(&self.engine, &req, map)
+        } else {
+            self.pathfind_roads(&engine, &req, map)
+        };
+        let (_, roads, uber_turns) = match best {
+            Some(result) => result,
+            None => {
+                return Vec::new();
+            }
+        };
+        let best_cost = match route_cost(self.constraints, params, &req, &roads, map) {
+            Some(cost) => cost,
+            None => {
+                return Vec::new();
+            }
+        };
 
+        let mut accepted: Vec<BTreeSet<DirectedRoadID>> = Vec::new();
+        let mut results = Vec::new();
+        let mut candidate = Some((roads, uber_turns, best_cost));
+        // Some searches will just find a variation of a previous route, so give up eventually
+        for _ in 0..3 * opts.max_routes {
+            let (roads, uber_turns, cost) = match candidate.take() {
+                Some(x) => x,
+                None => break,
+            };
+
+            let lengths: Vec<(DirectedRoadID, f64)> = roads
+                .iter()
+                .map(|dr| (*dr, map.get_r(dr.road).length().inner_meters()))
+                .collect();
+            let diverse = accepted
+                .iter()
+                .all(|prev| overlap(&lengths, prev) <= opts.max_overlap);
+            if diverse && cost <= opts.max_cost_ratio * best_cost {
+                accepted.push(roads.iter().cloned().collect());
+                results.push(PathV2::from_roads(
+                    roads.clone(),
+                    req.clone(),
+                    cost,
+                    uber_turns,
+                    map,
+                ));
+                if results.len() == opts.max_routes {
+                    break;
+                }
+            }
+
+            let mut changed_edges = BTreeSet::new();
+            for dr in roads {
+                *penalties.entry(dr).or_insert(1.0) *= opts.penalty;
+                changed_edges.extend(edges_per_road.get(dr).iter().cloned());
+            }
+            for idx in changed_edges {
+                engine.set_edge_weight(idx, edges[idx].weight(&penalties));
+            }
+            if let Some((_, roads, uber_turns)) = self.pathfind_roads(&engine, &req, map) {
+                if let Some(cost) = route_cost(self.constraints, params, &req, &roads, map) {
+                    candidate = Some((roads, uber_turns, cost));
+                }
+            }
+        }
+        results
+    }
+
+    /// Returns (raw cost, the roads crossed, the uber-turns used)
+    fn pathfind_roads(
+        &self,
+        engine: &PathfindEngine,
+        req: &PathRequest,
+        map: &Map,
+    ) -> Option<(usize, Vec<DirectedRoadID>, Vec<UberTurnV2>)> {
         assert!(!map.get_l(req.start.lane()).is_walkable());
         let mut starts = vec![(
             self.nodes.get(Node::Road(
@@ -106,7 +219,7 @@ impl VehiclePathfinder {
                 round(cost),
             ));
         }
-        let (raw_weight, raw_nodes) = self.engine.calculate_path_multiple_sources_and_targets(
+        let (raw_weight, raw_nodes) = engine.calculate_path_multiple_sources_and_targets(
             starts,
             vec![(
                 self.nodes
@@ -133,8 +246,7 @@ impl VehiclePathfinder {
                 }
             }
         }
-        let cost = unround(raw_weight);
-        Some(PathV2::from_roads(road_steps, req, cost, uber_turns, map))
+        Some((raw_weight, road_steps, uber_turns))
     }
 
     pub fn apply_edits(&mut self, map: &Map) {
@@ -201,6 +313,47 @@ fn make_input_graph(
     map: &Map,
 ) -> InputGraph {
     let mut input_graph = InputGraph::new();
+    let no_penalties = HashMap::new();
+    for edge in make_edges(constraints, nodes, uber_turns, params, map) {
+        input_graph.add_edge(edge.from, edge.to, edge.weight(&no_penalties));
+    }
+    nodes.guarantee_node_ordering(&mut input_graph);
+    input_graph.freeze();
+    input_graph
+}
+
+/// One edge in the graph, before its cost is rounded
+struct Edge {
+    from: usize,
+    to: usize,
+    /// The cost of crossing each road along this edge. Usually there's just one, but entering an
+    /// uber-turn crosses several roads, and leaving one crosses none.
+    costs: Vec<(DirectedRoadID, Duration)>,
+}
+
+impl Edge {
+    /// Multiply the cost of crossing some roads, to find alternative routes
+    fn weight(&self, penalties: &HashMap<DirectedRoadID, f64>) -> usize {
+        round(
+            self.costs
+                .iter()
+                .map(|(dr, cost)| match penalties.get(dr) {
+                    Some(penalty) => *penalty * *cost,
+                    None => *cost,
+                })
+                .sum(),
+        )
+    }
+}
+
+fn make_edges(
+    constraints: PathConstraints,
+    nodes: &NodeMap<Node>,
+    uber_turns: &[UberTurnV2],
+    params: &RoutingParams,
+    map: &Map,
+) -> Vec<Edge> {
+    let mut edges = Vec::new();
 
     // From some roads, instead of adding edges to movements, add edges to these (indexed)
     // uber-turns.
@@ -232,24 +385,24 @@ fn make_input_graph(
                         if let Some(cost) =
                             vehicle_cost(mvmnt.from, mvmnt, constraints, params, map)
                         {
-                            input_graph.add_edge(
+                            edges.push(Edge {
                                 from,
-                                nodes.get(Node::Road(mvmnt.to)),
-                                round(cost),
-                            );
+                                to: nodes.get(Node::Road(mvmnt.to)),
+                                costs: vec![(mvmnt.from, cost)],
+                            });
                         }
                     }
                 } else {
                     for idx in indices {
                         let ut = &uber_turns[*idx];
 
-                        let mut sum_cost = Duration::ZERO;
+                        let mut costs = Vec::new();
                         let mut ok = true;
                         for mvmnt in &ut.path {
                             if let Some(cost) =
                                 vehicle_cost(mvmnt.from, *mvmnt, constraints, params, map)
                             {
-                                sum_cost += cost;
+                                costs.push((mvmnt.from, cost));
                             } else {
                                 // A vehicle isn't allowed to do one of the steps inside the
                                 // uber-turn. So just toss out the entire uber-turn from the graph.
@@ -258,33 +411,73 @@ fn make_input_graph(
                             }
                         }
                         if ok {
-                            input_graph.add_edge(
+                            edges.push(Edge {
                                 from,
-                                nodes.get(Node::UberTurn(*idx)),
-                                round(sum_cost),
-                            );
-                            input_graph.add_edge(
-                                nodes.get(Node::UberTurn(*idx)),
-                                nodes.get(Node::Road(ut.exit())),
-                                // The cost is already captured for entering the uber-turn
-                                1,
-                            );
+                                to: nodes.get(Node::UberTurn(*idx)),
+                                costs,
+                            });
+                            edges.push(Edge {
+                                from: nodes.get(Node::UberTurn(*idx)),
+                                to: nodes.get(Node::Road(ut.exit())),
+                                // The cost is already captured for entering the uber-turn. With
+                                // nothing to cross, this rounds up to 1.
+                                costs: Vec::new(),
+                            });
                         }
                     }
                 }
             }
         }
     }
+    edges
+}
 
-    nodes.guarantee_node_ordering(&mut input_graph);
-    input_graph.freeze();
-    input_graph
+/// The cost of crossing a sequence of roads, ignoring any penalties used to find alternatives
+fn route_cost(
+    constraints: PathConstraints,
+    params: &RoutingParams,
+    req: &PathRequest,
+    roads: &[DirectedRoadID],
+    map: &Map,
+) -> Option<Duration> {
+    let mut cost = Duration::ZERO;
+    if let Some((pos, extra)) = req.alt_start {
+        let start = map.get_l(req.start.lane()).get_directed_parent();
+        let alt_start = map.get_l(pos.lane()).get_directed_parent();
+        if roads[0] == alt_start && alt_start != start {
+            cost += extra;
+        }
+    }
+    for pair in roads.windows(2) {
+        let mvmnt = MovementID {
+            from: pair[0],
+            to: pair[1],
+            parent: pair[0].dst_i(map),
+            crosswalk: false,
+        };
+        cost += vehicle_cost(pair[0], mvmnt, constraints, params, map)?;
+    }
+    Some(cost)
+}
+
+/// What fraction of a route's length, weighted by road, also appears in another route?
+fn overlap<T: Ord>(route: &[(T, f64)], other: &BTreeSet<T>) -> f64 {
+    let total: f64 = route.iter().map(|(_, len)| *len).sum();
+    if total == 0.0 {
+        return 1.0;
+    }
+    let shared: f64 = route
+        .iter()
+        .filter(|(x, _)| other.contains(x))
+        .map(|(_, len)| *len)
+        .sum();
+    shared / total
 }
 
 /// This returns the pathfinding cost of crossing one road and turn, in units of time. It factors
 /// in the ideal time to cross the space and penalties for entering an access-restricted zone,
-/// taking an unprotected turn, or going up a steep hill for some vehicle types. If this returns
-/// `None`, then the movement isn't actually allowed.
+/// taking an unprotected turn, going up a steep hill for some vehicle types, or paying a toll or
+/// cordon charge. If this returns `None`, then the movement isn't actually allowed.
 pub fn vehicle_cost(
     dr: DirectedRoadID,
     mvmnt: MovementID,
@@ -299,7 +492,14 @@ pub fn vehicle_cost(
         PathConstraints::Bike => Some(crate::MAX_BIKE_SPEED),
         PathConstraints::Pedestrian => unreachable!(),
     };
-    let t1 = road.length() / Traversable::max_speed_along_road(dr, max_speed, constraints, map).0;
+    let mut t1 =
+        road.length() / Traversable::max_speed_along_road(dr, max_speed, constraints, map).0;
+    // Observed times already include waiting at the intersection, so they cover the unprotected
+    // turn penalty too. Never assume vehicles can beat free-flow speeds, though.
+    let observed = params.observed_movement_times.get(&mvmnt);
+    if let Some(observed) = observed {
+        t1 = t1.max(*observed);
+    }
 
     let t2 = movement.geom.length()
         / Traversable::max_speed_along_movement(mvmnt, max_speed, constraints, map);
@@ -364,10 +564,14 @@ pub fn vehicle_cost(
 
     let mut extra = zone_cost(mvmnt, constraints, map);
     // Penalize unprotected turns at a stop sign from smaller to larger roads.
-    if map.is_unprotected_turn(dr.road, mvmnt.to.road, movement.turn_type) {
+    if observed.is_none() && map.is_unprotected_turn(dr.road, mvmnt.to.road, movement.turn_type) {
         extra += params.unprotected_turn_penalty
     }
 
+    if constraints == PathConstraints::Car {
+        extra += map.get_road_pricing().movement_cost(mvmnt, params);
+    }
+
     if (params.main_road_penalty - 1.0).abs() > f64::EPSILON
         && road.get_rank() != osm::RoadRank::Local
     {
@@ -376,3 +580,17 @@ pub fn vehicle_cost(
 
     Some(multiplier * base + extra)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overlap() {
+        let route = [(1, 100.0), (2, 300.0), (3, 100.0)];
+        let other: BTreeSet<usize> = [2, 3, 4].into_iter().collect();
+        assert_eq!(overlap(&route, &other), 0.8);
+        assert_eq!(overlap(&route, &BTreeSet::new()), 0.0);
+        assert_eq!(overlap(&route, &[1, 2, 3].into_iter().collect()), 1.0);
+    }
+}
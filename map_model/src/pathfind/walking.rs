@@ -108,8 +108,26 @@ impl SidewalkPathfinder {
             return;
         }
 
+        // Transit stops created by edits need new nodes, and then the old node ordering can't be
+        // reused.
+        let mut added_nodes = false;
+        if use_transit.is_some() {
+            for ts in map.all_transit_stops().keys() {
+                if !self.nodes.contains(WalkingNode::RideTransit(*ts)) {
+                    self.nodes.get_or_insert(WalkingNode::RideTransit(*ts));
+                    added_nodes = true;
+                }
+            }
+        }
+
         let input_graph = make_input_graph(&self.nodes, use_transit, map);
-        let engine = self.engine.reuse_ordering().create(input_graph);
+        let engine = if !added_nodes {
+            self.engine.reuse_ordering().create(input_graph)
+        } else if self.engine.is_dijkstra() {
+            CreateEngine::Dijkstra.create(input_graph)
+        } else {
+            CreateEngine::CH.create(input_graph)
+        };
         self.engine = engine;
     }
 
@@ -1,5 +1,6 @@
 use std::fmt;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use geom::{Angle, Distance, PolyLine, Pt2D, Speed};
@@ -7,7 +8,9 @@ use geom::{Angle, Distance, PolyLine, Pt2D, Speed};
 use crate::{DirectedRoadID, Direction, LaneID, Map, MovementID, PathConstraints, TurnID};
 
 /// Represents a specific point some distance along a lane.
-#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct Position {
     // Don't let callers construct a Position directly, so it's easy to find callers of new().
     lane: LaneID,
@@ -131,7 +134,9 @@ impl Position {
 
 /// Either a lane or a turn, where most movement happens.
 // TODO Consider adding building and parking lot driveways here.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub enum Traversable {
     Lane(LaneID),
     Turn(TurnID),
@@ -9,10 +9,10 @@ use serde::{Deserialize, Serialize};
 
 use abstio::{CityName, MapName};
 use abstutil::{
-    deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
-    Tags,
+    deserialize_appended, deserialize_btreemap, deserialize_multimap, serialize_btreemap,
+    serialize_multimap, MultiMap, Tags,
 };
-use geom::{PolyLine, Polygon, Pt2D};
+use geom::{PolyLine, Polygon, Pt2D, Time};
 
 pub use self::types::{Amenity, AmenityType, AreaType};
 
@@ -46,6 +46,11 @@ pub struct RawMap {
         deserialize_with = "deserialize_multimap"
     )]
     pub bus_routes_on_roads: MultiMap<osm::WayID, String>,
+    /// Per transit route's `gtfs_id`, when does every trip on the imported service day arrive at
+    /// each stop? Each inner list lines up with the route's `stops`, and trips are sorted by the
+    /// first arrival. Routes without a known schedule are missing.
+    #[serde(default, deserialize_with = "deserialize_appended")]
+    pub transit_stop_times: BTreeMap<String, Vec<Vec<Time>>>,
 }
 
 impl RawMap {
@@ -60,6 +65,7 @@ impl RawMap {
             transit_routes: Vec::new(),
             transit_stops: BTreeMap::new(),
             bus_routes_on_roads: MultiMap::new(),
+            transit_stop_times: BTreeMap::new(),
         }
     }
 
@@ -149,7 +155,6 @@ pub struct RawTransitRoute {
     /// Entries into transit_stops
     pub stops: Vec<String>,
     pub route_type: RawTransitType,
-    // TODO Schedule
 }
 
 #[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
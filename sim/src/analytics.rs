@@ -1,17 +1,21 @@
 use std::collections::{BTreeMap, BTreeSet, VecDeque};
 use std::fmt::Write;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Counter;
 use geom::{Duration, Pt2D, Time};
 use map_model::{
-    CompressedMovementID, IntersectionID, LaneID, Map, MovementID, ParkingLotID, Path, PathRequest,
-    RoadID, TransitRouteID, TransitStopID, Traversable, TurnID,
+    ChargerLocation, CompressedMovementID, IntersectionID, LaneID, Map, MovementID, ParkingLotID,
+    Path, PathRequest, RoadID, TransitRouteID, TransitStopID, Traversable, TurnID,
 };
 use synthpop::TripMode;
 
-use crate::{AgentID, AgentType, AlertLocation, CarID, Event, ParkingSpot, TripID, TripPhaseType};
+use crate::{
+    AgentID, AgentType, AlertLocation, CarID, Emissions, EmissionsSummary, Event, ParkingSpot,
+    SignalPriority, TripID, TripPhaseType, VehicleType,
+};
 
 /// As a simulation runs, different pieces emit Events. The Analytics object listens to these,
 /// organizing and storing some information from them. The UI queries Analytics to draw time-series
@@ -39,6 +43,15 @@ pub struct Analytics {
     /// For each passenger boarding, how long did they wait at the stop?
     pub passengers_boarding: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID, Duration)>>,
     pub passengers_alighting: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID)>>,
+    /// Each time a transit vehicle departs a stop, how full is it? The load factor is the number of
+    /// passengers on board divided by the vehicle's capacity.
+    pub transit_load_factors: BTreeMap<TransitRouteID, Vec<(Time, CarID, TransitStopID, f64)>>,
+    /// For each arrival at a stop, how late was the vehicle compared to its schedule? Negative
+    /// means early.
+    pub schedule_deviations: BTreeMap<TransitStopID, Vec<(Time, TransitRouteID, Duration)>>,
+    /// Each time somebody gets in or out of a shared car, how many people are inside, including
+    /// the driver? Cars driven alone are never recorded here.
+    pub car_occupancy: Vec<(Time, CarID, usize)>,
 
     pub started_trips: BTreeMap<TripID, Time>,
     /// Finish time, ID, mode, trip duration if successful (or None if cancelled)
@@ -57,6 +70,23 @@ pub struct Analytics {
     /// Per parking lane or lot, when does a spot become filled (true) or free (false)
     pub parking_lane_changes: BTreeMap<LaneID, Vec<(Time, bool)>>,
     pub parking_lot_changes: BTreeMap<ParkingLotID, Vec<(Time, bool)>>,
+    /// Per charger, when does an electric car plug in (true) or unplug (false)
+    pub charger_changes: BTreeMap<ChargerLocation, Vec<(Time, bool)>>,
+    /// Per charger, when each car unplugged, how long it was plugged in, and how many kWh it
+    /// charged
+    pub charging_sessions: BTreeMap<ChargerLocation, Vec<(Time, Duration, f64)>>,
+    /// Per charger, when a car headed there found every spot taken and had to park elsewhere
+    pub chargers_full: BTreeMap<ChargerLocation, Vec<Time>>,
+
+    /// Energy used and pollution emitted by motorized vehicles
+    pub emissions: EmissionsSummary,
+    /// Per trip, the total tolls and cordon charges paid
+    pub road_pricing_fees: BTreeMap<TripID, f64>,
+    /// Per trip, the fee paid to park at the end
+    pub parking_fees: BTreeMap<TripID, f64>,
+    /// Each time a transit or emergency vehicle changed a traffic signal's timing, and roughly
+    /// how much delay that saved the vehicle
+    pub signal_priority: Vec<(Time, IntersectionID, CarID, SignalPriority, Duration)>,
 
     pub(crate) alerts: Vec<(Time, AlertLocation, String)>,
 
@@ -64,7 +94,7 @@ pub struct Analytics {
     record_anything: bool,
 }
 
-#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
 pub enum Problem {
     /// A vehicle waited >30s, or a pedestrian waited >15s.
     IntersectionDelay(IntersectionID, Duration),
@@ -76,6 +106,8 @@ pub enum Problem {
     OvertakeDesired(Traversable),
     /// Too many people are crossing the same sidewalk or crosswalk at the same time.
     PedestrianOvercrowding(Traversable),
+    /// A transit rider couldn't board a vehicle on this route, because it was full.
+    TransitBoardingDenied(TransitStopID, TransitRouteID),
 }
 
 impl Problem {
@@ -90,6 +122,7 @@ impl Problem {
                 on.get_polyline(map).middle()
             }
             Problem::ArterialIntersectionCrossing(t) => map.get_t(*t).geom.middle(),
+            Problem::TransitBoardingDenied(ts, _) => map.get_ts(*ts).sidewalk_pos.pt(map),
         }
     }
 }
@@ -101,6 +134,7 @@ pub enum ProblemType {
     OvertakeDesired,
     ArterialIntersectionCrossing,
     PedestrianOvercrowding,
+    TransitBoardingDenied,
 }
 
 impl From<&Problem> for ProblemType {
@@ -111,6 +145,7 @@ impl From<&Problem> for ProblemType {
             Problem::OvertakeDesired(_) => Self::OvertakeDesired,
             Problem::ArterialIntersectionCrossing(_) => Self::ArterialIntersectionCrossing,
             Problem::PedestrianOvercrowding(_) => Self::PedestrianOvercrowding,
+            Problem::TransitBoardingDenied(_, _) => Self::TransitBoardingDenied,
         }
     }
 }
@@ -133,6 +168,7 @@ impl ProblemType {
             ProblemType::OvertakeDesired,
             ProblemType::ArterialIntersectionCrossing,
             ProblemType::PedestrianOvercrowding,
+            ProblemType::TransitBoardingDenied,
         ]
     }
 
@@ -147,6 +183,7 @@ impl ProblemType {
                 "where pedestrians cross arterial intersections"
             }
             ProblemType::PedestrianOvercrowding => "where pedestrians are over-crowded",
+            ProblemType::TransitBoardingDenied => "where transit riders are left behind",
         }
     }
 }
@@ -161,6 +198,9 @@ impl Analytics {
             bus_arrivals: Vec::new(),
             passengers_boarding: BTreeMap::new(),
             passengers_alighting: BTreeMap::new(),
+            transit_load_factors: BTreeMap::new(),
+            schedule_deviations: BTreeMap::new(),
+            car_occupancy: Vec::new(),
             started_trips: BTreeMap::new(),
             finished_trips: Vec::new(),
             problems_per_trip: BTreeMap::new(),
@@ -168,11 +208,30 @@ impl Analytics {
             intersection_delays: BTreeMap::new(),
             parking_lane_changes: BTreeMap::new(),
             parking_lot_changes: BTreeMap::new(),
+            charger_changes: BTreeMap::new(),
+            charging_sessions: BTreeMap::new(),
+            chargers_full: BTreeMap::new(),
+            emissions: EmissionsSummary::default(),
+            road_pricing_fees: BTreeMap::new(),
+            parking_fees: BTreeMap::new(),
+            signal_priority: Vec::new(),
             alerts: Vec::new(),
             record_anything,
         }
     }
 
+    pub(crate) fn record_emissions(
+        &mut self,
+        time: Time,
+        trip: Option<TripID>,
+        road: RoadID,
+        emissions: Emissions,
+    ) {
+        if self.record_anything {
+            self.emissions.record(time, trip, road, emissions);
+        }
+    }
+
     pub fn event(&mut self, ev: Event, time: Time, map: &Map) {
         if !self.record_anything {
             return;
@@ -231,6 +290,12 @@ impl Analytics {
         if let Event::BusArrivedAtStop(bus, route, stop) = ev {
             self.bus_arrivals.push((time, bus, route, stop));
         }
+        if let Event::TransitScheduleDeviation(_, route, stop, late) = ev {
+            self.schedule_deviations
+                .entry(stop)
+                .or_insert_with(Vec::new)
+                .push((time, route, late));
+        }
 
         // Passengers boarding/alighting
         if let Event::PassengerBoardsTransit(_, _, route, stop, waiting) = ev {
@@ -246,6 +311,19 @@ impl Analytics {
                 .push((time, route));
         }
 
+        // Transit crowding
+        if let Event::BusDepartedFromStop(bus, route, stop, passengers, capacity) = ev {
+            self.transit_load_factors
+                .entry(route)
+                .or_insert_with(Vec::new)
+                .push((time, bus, stop, passengers as f64 / capacity as f64));
+        }
+
+        // Shared rides
+        if let Event::CarOccupancyChanged(car, occupancy) = ev {
+            self.car_occupancy.push((time, car, occupancy));
+        }
+
         // Started trips
         if let Event::TripPhaseStarting(id, _, _, _) = ev {
             self.started_trips.entry(id).or_insert(time);
@@ -272,7 +350,7 @@ impl Analytics {
                 AgentID::Car(_) => Duration::seconds(30.0),
                 AgentID::Pedestrian(_) => Duration::seconds(15.0),
                 // Don't record for riders
-                AgentID::BusPassenger(_, _) => Duration::hours(24),
+                AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => Duration::hours(24),
             };
             if delay > threshold {
                 self.problems_per_trip
@@ -319,6 +397,58 @@ impl Analytics {
             }
         }
 
+        if let Event::SignalPriorityGranted(i, car, priority, saved) = ev {
+            self.signal_priority.push((time, i, car, priority, saved));
+        }
+
+        // Electric car charging
+        match ev {
+            Event::EVChargingStarted(_, loc) => {
+                self.charger_changes
+                    .entry(loc)
+                    .or_insert_with(Vec::new)
+                    .push((time, true));
+            }
+            Event::EVChargingStopped(_, loc, duration, energy) => {
+                self.charger_changes
+                    .entry(loc)
+                    .or_insert_with(Vec::new)
+                    .push((time, false));
+                self.charging_sessions
+                    .entry(loc)
+                    .or_insert_with(Vec::new)
+                    .push((time, duration, energy));
+            }
+            Event::EVChargerFull(_, loc) => {
+                self.chargers_full
+                    .entry(loc)
+                    .or_insert_with(Vec::new)
+                    .push(time);
+            }
+            _ => {}
+        }
+
+        // Road pricing. Only private cars pay.
+        if let Event::AgentEntersTraversable(
+            AgentID::Car(car),
+            Some(trip),
+            Traversable::Turn(t),
+            _,
+        ) = ev
+        {
+            if car.vehicle_type == VehicleType::Car && !map.get_road_pricing().is_empty() {
+                let fee = map
+                    .get_road_pricing()
+                    .movement_fee(t.to_movement(map), Some(time));
+                if fee > 0.0 {
+                    *self.road_pricing_fees.entry(trip).or_insert(0.0) += fee;
+                }
+            }
+        }
+        if let Event::ParkingFeePaid(_, trip, _, fee) = ev {
+            *self.parking_fees.entry(trip).or_insert(0.0) += fee;
+        }
+
         // Safety metrics
         if let Event::AgentEntersTraversable(a, Some(trip), Traversable::Turn(t), _) = ev {
             if a.to_type() == AgentType::Bike && map.get_i(t.parent).roads.len() > 4 {
@@ -519,6 +649,53 @@ impl Analytics {
         pts
     }
 
+    /// Returns the load factor of every vehicle departing any stop along the route, up to the
+    /// current time.
+    pub fn transit_load_factor(&self, now: Time, route: TransitRouteID) -> Vec<(Time, f64)> {
+        let mut pts = Vec::new();
+        let empty = Vec::new();
+        for (t, _, _, load) in self.transit_load_factors.get(&route).unwrap_or(&empty) {
+            if *t > now {
+                break;
+            }
+            pts.push((*t, *load));
+        }
+        pts
+    }
+
+    /// How many times was a rider left behind at this stop by a full vehicle, up to the current
+    /// time? A rider passed up by two full vehicles counts twice.
+    pub fn denied_boardings(&self, now: Time, stop: TransitStopID) -> usize {
+        let mut cnt = 0;
+        for problems in self.problems_per_trip.values() {
+            for (t, problem) in problems {
+                if *t > now {
+                    break;
+                }
+                if let Problem::TransitBoardingDenied(ts, _) = problem {
+                    if *ts == stop {
+                        cnt += 1;
+                    }
+                }
+            }
+        }
+        cnt
+    }
+
+    /// Returns how many people were inside a shared car over time, up to the current time.
+    pub fn car_occupancy(&self, now: Time, car: CarID) -> Vec<(Time, usize)> {
+        let mut pts = Vec::new();
+        for (t, c, occupancy) in &self.car_occupancy {
+            if *t > now {
+                break;
+            }
+            if *c == car {
+                pts.push((*t, *occupancy));
+            }
+        }
+        pts
+    }
+
     /// Returns the free spots over time
     pub fn parking_lane_availability(
         &self,
@@ -609,6 +786,9 @@ impl Analytics {
                         }
                     }
                     Problem::ArterialIntersectionCrossing(t) => t.parent,
+                    Problem::TransitBoardingDenied(_, _) => {
+                        continue;
+                    }
                 };
                 if id == i {
                     raw_per_type
@@ -0,0 +1,297 @@
+//! Electric cars have a battery that drains as they drive. When it gets low, drivers try to park
+//! at a charger near their destination, and the battery charges while they're parked there.
+
+use std::collections::BTreeMap;
+
+use rand::{Rng, SeedableRng};
+use rand_xorshift::XorShiftRng;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap};
+use geom::{Distance, Time};
+use map_model::{BuildingID, ChargerLocation, Map, OffstreetParking, ParkingPolicy, Position};
+
+use crate::{CarID, Event, ParkingSim, ParkingSimState, ParkingSpot, SimOptions, Vehicle};
+
+const BATTERY_CAPACITY_KWH: f64 = 60.0;
+/// Drivers look for a charger when their battery is below this fraction of its capacity.
+const LOW_CHARGE: f64 = 0.3;
+/// How far drivers are willing to walk between a charger and where they're going
+const MAX_WALK_FROM_CHARGER: Distance = Distance::const_meters(800.0);
+
+/// Where a parking spot is, and its index there. Chargers are placed on the first few spots.
+pub fn charger_location(spot: ParkingSpot) -> (ChargerLocation, usize) {
+    match spot {
+        ParkingSpot::Onstreet(l, idx) => (ChargerLocation::Onstreet(l), idx),
+        ParkingSpot::Offstreet(b, idx) => (ChargerLocation::Building(b), idx),
+        ParkingSpot::Lot(pl, idx) => (ChargerLocation::Lot(pl), idx),
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct Battery {
+    capacity_kwh: f64,
+    charge_kwh: f64,
+}
+
+/// Chargers are part of the map's `ParkingPolicy`.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct ChargingSimState {
+    pct_electric_cars: usize,
+    /// Every electric car
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    batteries: BTreeMap<CarID, Battery>,
+    /// Where each electric car is charging, since when, and the charger's power in kW
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    plugged_in: BTreeMap<CarID, (ChargerLocation, Time, f64)>,
+}
+
+impl ChargingSimState {
+    pub fn new(opts: &SimOptions) -> ChargingSimState {
+        ChargingSimState {
+            pct_electric_cars: opts.pct_electric_cars,
+            batteries: BTreeMap::new(),
+            plugged_in: BTreeMap::new(),
+        }
+    }
+
+    /// What percent of the cars in a scenario should be electric
+    pub fn pct_electric_cars(&self) -> usize {
+        self.pct_electric_cars
+    }
+
+    /// Start tracking the battery of a new electric vehicle.
+    pub fn add_vehicle(&mut self, vehicle: &Vehicle) {
+        if !vehicle.electric {
+            return;
+        }
+        // Deterministic per car, without disturbing any other source of randomness
+        let mut rng = XorShiftRng::seed_from_u64(vehicle.id.id as u64);
+        self.batteries.insert(
+            vehicle.id,
+            Battery {
+                capacity_kwh: BATTERY_CAPACITY_KWH,
+                charge_kwh: BATTERY_CAPACITY_KWH * rng.gen_range(0.2..1.0),
+            },
+        );
+    }
+
+    pub fn is_electric(&self, car: CarID) -> bool {
+        self.batteries.contains_key(&car)
+    }
+
+    /// The current charge and capacity of an electric car's battery, in kWh, counting any charging
+    /// in progress.
+    pub fn get_charge(&self, car: CarID, now: Time) -> Option<(f64, f64)> {
+        let battery = self.batteries.get(&car)?;
+        let mut charge = battery.charge_kwh;
+        if let Some((_, since, power_kw)) = self.plugged_in.get(&car) {
+            charge += energy_charged(*since, now, *power_kw, battery);
+        }
+        Some((charge, battery.capacity_kwh))
+    }
+
+    /// Called with the energy used to cross each lane or turn.
+    pub fn consume(&mut self, car: CarID, energy_kwh: f64) {
+        if let Some(battery) = self.batteries.get_mut(&car) {
+            // Running out doesn't stop anybody, but the battery can't go below empty.
+            battery.charge_kwh = (battery.charge_kwh - energy_kwh).max(0.0);
+        }
+    }
+
+    /// If this electric car needs charging, find a free charger close to where the driver is
+    /// going. Returns the spot and where to drive to park there.
+    pub fn choose_charger(
+        &self,
+        vehicle: &Vehicle,
+        target: BuildingID,
+        parking: &ParkingSimState,
+        map: &Map,
+    ) -> Option<(ParkingSpot, Position)> {
+        let battery = self.batteries.get(&vehicle.id)?;
+        if battery.charge_kwh >= LOW_CHARGE * battery.capacity_kwh {
+            return None;
+        }
+
+        let target_pt = map.get_b(target).sidewalk_pos.pt(map);
+        let mut best: Option<(Distance, ParkingSpot)> = None;
+        for (loc, charger) in &map.get_parking_policy().chargers {
+            let free = match *loc {
+                ChargerLocation::Onstreet(l) => parking.get_free_onstreet_spots(l),
+                ChargerLocation::Building(b) => {
+                    // Only the driver's own building, or a public garage
+                    if b != target
+                        && !matches!(map.get_b(b).parking, OffstreetParking::PublicGarage(_, _))
+                    {
+                        continue;
+                    }
+                    parking.get_free_offstreet_spots(b)
+                }
+                ChargerLocation::Lot(pl) => parking.get_free_lot_spots(pl),
+            };
+            if let Some(spot) = free
+                .into_iter()
+                .find(|spot| charger_location(*spot).1 < charger.num_spots)
+            {
+                let dist = parking
+                    .spot_to_sidewalk_pos(spot, map)
+                    .pt(map)
+                    .dist_to(target_pt);
+                if dist <= MAX_WALK_FROM_CHARGER
+                    && best.map(|(best_dist, _)| dist < best_dist).unwrap_or(true)
+                {
+                    best = Some((dist, spot));
+                }
+            }
+        }
+        let (_, spot) = best?;
+        Some((spot, parking.spot_to_driving_pos(spot, vehicle, map)))
+    }
+
+    /// Plugs in and unplugs electric cars as they park and leave. The chargers come from the
+    /// map's parking policy.
+    pub fn handle_event(&mut self, now: Time, ev: &Event, policy: &ParkingPolicy) -> Option<Event> {
+        match ev {
+            Event::CarReachedParkingSpot(car, spot) => {
+                if !self.is_electric(*car) {
+                    return None;
+                }
+                let (loc, idx) = charger_location(*spot);
+                let charger = policy.chargers.get(&loc)?;
+                if idx < charger.num_spots {
+                    self.plugged_in.insert(*car, (loc, now, charger.power_kw));
+                    return Some(Event::EVChargingStarted(*car, loc));
+                }
+                None
+            }
+            Event::CarLeftParkingSpot(car, _) => {
+                let (loc, since, power_kw) = self.plugged_in.remove(car)?;
+                let battery = self.batteries.get_mut(car)?;
+                let energy = energy_charged(since, now, power_kw, battery);
+                battery.charge_kwh += energy;
+                Some(Event::EVChargingStopped(*car, loc, now - since, energy))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn energy_charged(since: Time, now: Time, power_kw: f64, battery: &Battery) -> f64 {
+    let hours = (now - since).inner_seconds() / 3600.0;
+    (power_kw * hours).min(battery.capacity_kwh - battery.charge_kwh)
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Duration;
+    use map_model::{Charger, LaneID};
+
+    use super::*;
+    use crate::{VehicleSpec, VehicleType};
+
+    fn car(id: usize, electric: bool) -> Vehicle {
+        VehicleSpec {
+            vehicle_type: VehicleType::Car,
+            length: Distance::meters(4.5),
+            max_speed: None,
+            electric,
+        }
+        .make(
+            CarID {
+                id,
+                vehicle_type: VehicleType::Car,
+            },
+            None,
+        )
+    }
+
+    #[test]
+    fn only_electric_cars_have_batteries() {
+        let mut state = ChargingSimState::new(&SimOptions::default());
+        let ev = car(1, true);
+        let gas = car(2, false);
+        state.add_vehicle(&ev);
+        state.add_vehicle(&gas);
+
+        assert!(state.is_electric(ev.id));
+        assert!(!state.is_electric(gas.id));
+        assert_eq!(state.get_charge(gas.id, Time::START_OF_DAY), None);
+
+        let (charge, capacity) = state.get_charge(ev.id, Time::START_OF_DAY).unwrap();
+        assert!(charge > 0.0 && charge <= capacity);
+        state.consume(ev.id, capacity * 2.0);
+        assert_eq!(
+            state.get_charge(ev.id, Time::START_OF_DAY),
+            Some((0.0, capacity))
+        );
+    }
+
+    #[test]
+    fn charge_at_map_chargers() {
+        let lane = LaneID {
+            road: map_model::RoadID(0),
+            offset: 0,
+        };
+        let mut policy = ParkingPolicy::default();
+        policy.chargers.insert(
+            ChargerLocation::Onstreet(lane),
+            Charger {
+                num_spots: 1,
+                power_kw: 10.0,
+            },
+        );
+
+        let mut state = ChargingSimState::new(&SimOptions::default());
+        let ev = car(1, true);
+        state.add_vehicle(&ev);
+        state.consume(ev.id, BATTERY_CAPACITY_KWH);
+
+        // Spots past the charger's don't charge
+        let t1 = Time::START_OF_DAY + Duration::hours(1);
+        assert!(state
+            .handle_event(
+                t1,
+                &Event::CarReachedParkingSpot(ev.id, ParkingSpot::Onstreet(lane, 1)),
+                &policy
+            )
+            .is_none());
+        assert!(state
+            .handle_event(
+                t1,
+                &Event::CarReachedParkingSpot(ev.id, ParkingSpot::Onstreet(lane, 0)),
+                &policy
+            )
+            .is_some());
+
+        let t2 = t1 + Duration::hours(2);
+        assert_eq!(
+            state.get_charge(ev.id, t2),
+            Some((20.0, BATTERY_CAPACITY_KWH))
+        );
+        match state.handle_event(
+            t2,
+            &Event::CarLeftParkingSpot(ev.id, ParkingSpot::Onstreet(lane, 0)),
+            &policy,
+        ) {
+            Some(Event::EVChargingStopped(_, loc, duration, energy)) => {
+                assert_eq!(loc, ChargerLocation::Onstreet(lane));
+                assert_eq!(duration, Duration::hours(2));
+                assert_eq!(energy, 20.0);
+            }
+            x => panic!("unexpected {:?}", x),
+        }
+
+        // Charging stops once the battery is full
+        let battery = Battery {
+            capacity_kwh: BATTERY_CAPACITY_KWH,
+            charge_kwh: BATTERY_CAPACITY_KWH - 5.0,
+        };
+        assert_eq!(energy_charged(t1, t2, 10.0, &battery), 5.0);
+    }
+}
@@ -0,0 +1,46 @@
+//! Vehicles whose movement decisions come from outside the simulation, like a separate model of
+//! automated vehicles or micromobility. They still queue behind other vehicles and wait their turn
+//! at intersections; the external controller only decides where they go and when they stop.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use geom::Distance;
+use map_model::{LaneID, Traversable, TurnID};
+
+use crate::CarID;
+
+/// One decision from an external controller
+#[derive(Clone, Debug, Serialize, Deserialize, JsonSchema)]
+pub enum ExternalDecision {
+    /// Continue through this turn after the last lane planned so far. This replaces any earlier
+    /// `StopAt`.
+    Turn(TurnID),
+    /// Change to an adjacent lane heading the same direction on the current road. Fails if the
+    /// vehicle is in a turn or there's no room right now.
+    ChangeLanes(LaneID),
+    /// Stop at this distance along the last planned lane, until another decision. By default,
+    /// vehicles stop at the end of their last planned lane.
+    StopAt(Distance),
+    /// Leave the simulation once reaching the stopping point on the last planned lane
+    Vanish,
+}
+
+/// What an externally controlled vehicle is doing right now
+#[derive(Clone, Debug, Serialize, JsonSchema)]
+pub struct ExternalCarState {
+    pub id: CarID,
+    /// The current lane or turn
+    pub on: Traversable,
+    /// Where the front of the vehicle is along `on`
+    pub dist_along: Distance,
+    /// Every lane and turn decided so far, starting with the current one
+    pub planned: Vec<Traversable>,
+    /// Where the vehicle will stop along the last planned lane
+    pub stop_at: Distance,
+    /// The vehicle has stopped on its last planned lane, so it won't go anywhere until the next
+    /// decision.
+    pub waiting: bool,
+    /// The turns this vehicle could make after its last planned lane
+    pub next_turns: Vec<TurnID>,
+}
@@ -0,0 +1,535 @@
+//! Estimates the energy used and pollution emitted by vehicles as they move around.
+
+use std::collections::BTreeMap;
+use std::ops;
+
+use serde::{Deserialize, Serialize};
+
+use geom::{Distance, Duration, Speed, Time};
+use map_model::{Map, PathStep, RoadID, Traversable};
+
+use crate::{
+    AgentID, CarID, ChargingSimState, DrivingSimState, Event, SimOptions, TripID, VehicleType,
+};
+
+const GRAVITY: f64 = 9.81;
+/// In kg/m^3, at sea level
+const AIR_DENSITY: f64 = 1.2;
+const JOULES_PER_KWH: f64 = 3_600_000.0;
+
+/// What a vehicle consumes and emits over some stretch of driving.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct Emissions {
+    /// Energy drawn from fuel or a battery
+    pub energy_kwh: f64,
+    pub fuel_liters: f64,
+    pub co2_grams: f64,
+    pub nox_grams: f64,
+}
+
+impl ops::Add for Emissions {
+    type Output = Emissions;
+
+    fn add(self, other: Emissions) -> Emissions {
+        Emissions {
+            energy_kwh: self.energy_kwh + other.energy_kwh,
+            fuel_liters: self.fuel_liters + other.fuel_liters,
+            co2_grams: self.co2_grams + other.co2_grams,
+            nox_grams: self.nox_grams + other.nox_grams,
+        }
+    }
+}
+
+impl ops::AddAssign for Emissions {
+    fn add_assign(&mut self, other: Emissions) {
+        *self = *self + other;
+    }
+}
+
+/// One vehicle crossing one lane or turn. In the simulation, vehicles cross at a constant speed
+/// and then wait at the end if they're blocked, so this describes the entire speed profile. The
+/// speed is how fast the vehicle really crossed, so it accounts for the vehicle's own top speed,
+/// incidents slowing down the road, and starting from a stop.
+#[derive(Clone, Debug)]
+pub struct DrivingSegment {
+    pub vehicle_type: VehicleType,
+    /// A car running on a battery instead of fuel
+    pub electric: bool,
+    /// How fast the vehicle was going at the start. If it was stopped, the vehicle has to
+    /// accelerate to the cruising speed.
+    pub initial_speed: Speed,
+    pub cruising_speed: Speed,
+    pub distance: Distance,
+    /// Time spent stopped at the end, waiting in a queue or at an intersection
+    pub idle_time: Duration,
+    /// Positive is uphill. 0.1 is 10%
+    pub percent_incline: f64,
+}
+
+/// The energy needed at the wheels to overcome rolling resistance, air drag, and hills, and to
+/// speed up, plus auxiliary power drawn the entire time. Energy is converted to fuel and pollutants
+/// using fixed factors per vehicle type. Braking and going downhill don't recover anything.
+///
+/// Pass different parameters to `Sim::set_emission_model` to change how this works. The model is
+/// saved along with the simulation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PhysicalEmissionModel {
+    /// Vehicle types missing here don't emit anything.
+    pub vehicles: BTreeMap<VehicleType, VehicleEmissionParams>,
+    /// Used instead of the car's params for electric cars
+    pub electric_car: VehicleEmissionParams,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VehicleEmissionParams {
+    pub mass_kg: f64,
+    pub rolling_resistance_coefficient: f64,
+    /// The drag coefficient times the frontal area, in m^2
+    pub drag_area: f64,
+    /// Drawn the entire time the vehicle is running, even while stopped, in kW
+    pub auxiliary_power_kw: f64,
+    /// What fraction of the energy drawn reaches the wheels
+    pub drivetrain_efficiency: f64,
+    /// How much energy a liter of fuel holds. None for electric vehicles.
+    pub fuel_kwh_per_liter: Option<f64>,
+    pub co2_grams_per_liter: f64,
+    pub nox_grams_per_liter: f64,
+}
+
+impl Default for PhysicalEmissionModel {
+    fn default() -> PhysicalEmissionModel {
+        let mut vehicles = BTreeMap::new();
+        // A petrol passenger car
+        vehicles.insert(
+            VehicleType::Car,
+            VehicleEmissionParams {
+                mass_kg: 1500.0,
+                rolling_resistance_coefficient: 0.012,
+                drag_area: 0.7,
+                auxiliary_power_kw: 5.0,
+                drivetrain_efficiency: 0.25,
+                fuel_kwh_per_liter: Some(8.9),
+                co2_grams_per_liter: 2310.0,
+                nox_grams_per_liter: 0.9,
+            },
+        );
+        // A diesel bus with some passengers
+        vehicles.insert(
+            VehicleType::Bus,
+            VehicleEmissionParams {
+                mass_kg: 15000.0,
+                rolling_resistance_coefficient: 0.008,
+                drag_area: 6.0,
+                auxiliary_power_kw: 15.0,
+                drivetrain_efficiency: 0.3,
+                fuel_kwh_per_liter: Some(10.0),
+                co2_grams_per_liter: 2640.0,
+                nox_grams_per_liter: 10.0,
+            },
+        );
+        // Electric light rail. Emissions from generating the electricity aren't counted.
+        vehicles.insert(
+            VehicleType::Train,
+            VehicleEmissionParams {
+                mass_kg: 40000.0,
+                rolling_resistance_coefficient: 0.002,
+                drag_area: 10.0,
+                auxiliary_power_kw: 20.0,
+                drivetrain_efficiency: 0.85,
+                fuel_kwh_per_liter: None,
+                co2_grams_per_liter: 0.0,
+                nox_grams_per_liter: 0.0,
+            },
+        );
+        PhysicalEmissionModel {
+            vehicles,
+            // Heavier because of the battery, but much more efficient and barely drawing anything
+            // while stopped
+            electric_car: VehicleEmissionParams {
+                mass_kg: 1800.0,
+                rolling_resistance_coefficient: 0.010,
+                drag_area: 0.6,
+                auxiliary_power_kw: 1.0,
+                drivetrain_efficiency: 0.85,
+                fuel_kwh_per_liter: None,
+                co2_grams_per_liter: 0.0,
+                nox_grams_per_liter: 0.0,
+            },
+        }
+    }
+}
+
+impl PhysicalEmissionModel {
+    /// Calculates what a vehicle emits.
+    pub fn emissions(&self, segment: &DrivingSegment) -> Emissions {
+        let params = if segment.electric {
+            &self.electric_car
+        } else {
+            match self.vehicles.get(&segment.vehicle_type) {
+                Some(params) => params,
+                None => {
+                    return Emissions::default();
+                }
+            }
+        };
+        let v = segment.cruising_speed.inner_meters_per_second();
+        let v0 = segment.initial_speed.inner_meters_per_second();
+        let dist = segment.distance.inner_meters();
+        let angle = segment.percent_incline.atan();
+
+        let force = params.mass_kg
+            * GRAVITY
+            * (params.rolling_resistance_coefficient * angle.cos() + angle.sin())
+            + 0.5 * AIR_DENSITY * params.drag_area * v * v;
+        let mut work = (force * dist).max(0.0);
+        work += 0.5 * params.mass_kg * (v * v - v0 * v0).max(0.0);
+
+        let moving_time = if v > 0.0 { dist / v } else { 0.0 };
+        let running_hours = (moving_time + segment.idle_time.inner_seconds()) / 3600.0;
+        let energy_kwh = work / params.drivetrain_efficiency / JOULES_PER_KWH
+            + params.auxiliary_power_kw * running_hours;
+        let fuel_liters = params
+            .fuel_kwh_per_liter
+            .map(|x| energy_kwh / x)
+            .unwrap_or(0.0);
+        Emissions {
+            energy_kwh,
+            fuel_liters,
+            co2_grams: fuel_liters * params.co2_grams_per_liter,
+            nox_grams: fuel_liters * params.nox_grams_per_liter,
+        }
+    }
+}
+
+/// Everything emitted so far, grouped a few different ways.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct EmissionsSummary {
+    /// Turns count towards the road the vehicle is leaving.
+    pub per_road: BTreeMap<RoadID, Emissions>,
+    /// Transit vehicles aren't part of any trip, so they're only counted per road and hour.
+    pub per_trip: BTreeMap<TripID, Emissions>,
+    /// Keyed by the hour of the day
+    pub per_hour: BTreeMap<usize, Emissions>,
+}
+
+impl EmissionsSummary {
+    pub(crate) fn record(
+        &mut self,
+        time: Time,
+        trip: Option<TripID>,
+        road: RoadID,
+        emissions: Emissions,
+    ) {
+        *self.per_road.entry(road).or_insert_with(Emissions::default) += emissions;
+        if let Some(trip) = trip {
+            *self.per_trip.entry(trip).or_insert_with(Emissions::default) += emissions;
+        }
+        *self
+            .per_hour
+            .entry(time.get_hours())
+            .or_insert_with(Emissions::default) += emissions;
+    }
+
+    pub fn total(&self) -> Emissions {
+        let mut total = Emissions::default();
+        for x in self.per_hour.values() {
+            total += *x;
+        }
+        total
+    }
+}
+
+/// Follows motorized vehicles as they move, measuring each lane or turn they cross.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct EmissionsTracker {
+    enabled: bool,
+    current: BTreeMap<CarID, CurrentSegment>,
+    model: PhysicalEmissionModel,
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+struct CurrentSegment {
+    /// None when the vehicle just appeared. The next event says where it's been.
+    on: Option<Traversable>,
+    since: Time,
+    initial_speed: Speed,
+    /// How fast the vehicle is crossing, recorded when it starts. None if it wasn't moving yet,
+    /// like when it's still leaving a parking spot.
+    speed: Option<Speed>,
+    trip: Option<TripID>,
+}
+
+impl EmissionsTracker {
+    pub fn new(opts: &SimOptions) -> EmissionsTracker {
+        EmissionsTracker {
+            // Electric cars need to know how much energy they use, even without analytics
+            enabled: !opts.skip_analytics || opts.pct_electric_cars > 0,
+            current: BTreeMap::new(),
+            model: PhysicalEmissionModel::default(),
+        }
+    }
+
+    pub fn set_model(&mut self, model: PhysicalEmissionModel) {
+        self.model = model;
+    }
+
+    /// If a vehicle just finished crossing a lane or turn, returns the vehicle, its trip, the road,
+    /// and what it emitted.
+    pub fn handle_event(
+        &mut self,
+        now: Time,
+        ev: &Event,
+        charging: &ChargingSimState,
+        driving: &DrivingSimState,
+        map: &Map,
+    ) -> Option<(CarID, Option<TripID>, RoadID, Emissions)> {
+        if !self.enabled {
+            return None;
+        }
+        match ev {
+            Event::CarLeftParkingSpot(car, _) | Event::PersonEntersMap(_, AgentID::Car(car), _) => {
+                if car.vehicle_type != VehicleType::Bike {
+                    self.current.insert(
+                        *car,
+                        CurrentSegment {
+                            on: None,
+                            since: now,
+                            initial_speed: Speed::ZERO,
+                            speed: driving.crossing_speed(*car),
+                            trip: None,
+                        },
+                    );
+                }
+                None
+            }
+            Event::AgentEntersTraversable(AgentID::Car(car), trip, on, _) => {
+                if car.vehicle_type == VehicleType::Bike {
+                    return None;
+                }
+                let prev = self.current.remove(car);
+                // The lane before a turn is always known. Vehicles that just appeared haven't been
+                // anywhere else.
+                let finished = match on {
+                    Traversable::Turn(t) => Some(Traversable::Lane(t.src)),
+                    Traversable::Lane(_) => prev.as_ref().and_then(|x| x.on),
+                };
+                let mut result = None;
+                let mut initial_speed = Speed::ZERO;
+                if let (Some(prev), Some(finished)) = (prev, finished) {
+                    let (segment, end_speed) =
+                        self.make_segment(*car, finished, &prev, now, charging, map);
+                    initial_speed = end_speed;
+                    result = Some((
+                        *car,
+                        trip.or(prev.trip),
+                        road(finished),
+                        self.model.emissions(&segment),
+                    ));
+                }
+                self.current.insert(
+                    *car,
+                    CurrentSegment {
+                        on: Some(*on),
+                        since: now,
+                        initial_speed,
+                        speed: driving.crossing_speed(*car),
+                        trip: *trip,
+                    },
+                );
+                result
+            }
+            Event::CarReachedParkingSpot(car, _)
+            | Event::PersonLeavesMap(_, Some(AgentID::Car(car)), _) => {
+                let prev = self.current.remove(car)?;
+                let finished = prev.on?;
+                let (segment, _) = self.make_segment(*car, finished, &prev, now, charging, map);
+                Some((
+                    *car,
+                    prev.trip,
+                    road(finished),
+                    self.model.emissions(&segment),
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// Also returns the speed at the end of the segment
+    fn make_segment(
+        &self,
+        car: CarID,
+        on: Traversable,
+        prev: &CurrentSegment,
+        now: Time,
+        charging: &ChargingSimState,
+        map: &Map,
+    ) -> (DrivingSegment, Speed) {
+        let step = match on {
+            Traversable::Lane(l) => PathStep::Lane(l),
+            Traversable::Turn(t) => PathStep::Turn(t),
+        };
+        let (max_speed, percent_incline) =
+            step.max_speed_and_incline_along(None, car.vehicle_type.to_constraints(), map);
+        let (speed, distance, idle_time) =
+            measure_segment(prev, now, max_speed, on.get_polyline(map).length());
+        let end_speed = if idle_time > Duration::ZERO {
+            Speed::ZERO
+        } else {
+            speed
+        };
+        (
+            DrivingSegment {
+                vehicle_type: car.vehicle_type,
+                electric: charging.is_electric(car),
+                initial_speed: prev.initial_speed,
+                cruising_speed: speed,
+                distance,
+                idle_time,
+                percent_incline,
+            },
+            end_speed,
+        )
+    }
+}
+
+/// How fast a vehicle crossed something `length` long, how far it got, and how long it was stopped.
+/// Without a recorded speed, assume it went as fast as allowed.
+fn measure_segment(
+    prev: &CurrentSegment,
+    now: Time,
+    max_speed: Speed,
+    length: Distance,
+) -> (Speed, Distance, Duration) {
+    let speed = prev.speed.unwrap_or(max_speed);
+    let elapsed = now - prev.since;
+    if speed > Speed::ZERO {
+        // Vehicles don't always cross the entire lane, like when they start or park in the
+        // middle
+        let distance = (speed * elapsed).min(length);
+        (
+            speed,
+            distance,
+            (elapsed - distance / speed).max(Duration::ZERO),
+        )
+    } else {
+        // Stuck the entire time, like on a closed lane
+        (speed, Distance::ZERO, elapsed)
+    }
+}
+
+fn road(on: Traversable) -> RoadID {
+    match on {
+        Traversable::Lane(l) => l.road,
+        Traversable::Turn(t) => t.src.road,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(vehicle_type: VehicleType, percent_incline: f64) -> DrivingSegment {
+        DrivingSegment {
+            vehicle_type,
+            electric: false,
+            initial_speed: Speed::ZERO,
+            cruising_speed: Speed::miles_per_hour(25.0),
+            distance: Distance::meters(500.0),
+            idle_time: Duration::seconds(30.0),
+            percent_incline,
+        }
+    }
+
+    #[test]
+    fn test_physical_emission_model() {
+        let model = PhysicalEmissionModel::default();
+
+        let flat = model.emissions(&segment(VehicleType::Car, 0.0));
+        let uphill = model.emissions(&segment(VehicleType::Car, 0.05));
+        let downhill = model.emissions(&segment(VehicleType::Car, -0.05));
+        assert!(flat.co2_grams > 0.0);
+        assert!(uphill.co2_grams > flat.co2_grams);
+        assert!(downhill.co2_grams < flat.co2_grams);
+        // Idling still burns fuel
+        assert!(downhill.co2_grams > 0.0);
+
+        // Between 4 and 20 L/100km, counting the stop at the end
+        assert!(flat.fuel_liters > 0.02 && flat.fuel_liters < 0.1);
+
+        let train = model.emissions(&segment(VehicleType::Train, 0.0));
+        assert!(train.energy_kwh > 0.0);
+        assert_eq!(train.co2_grams, 0.0);
+
+        let electric = model.emissions(&DrivingSegment {
+            electric: true,
+            ..segment(VehicleType::Car, 0.0)
+        });
+        assert!(electric.energy_kwh > 0.0 && electric.energy_kwh < flat.energy_kwh);
+        assert_eq!(electric.co2_grams, 0.0);
+
+        assert_eq!(
+            model.emissions(&segment(VehicleType::Bike, 0.0)),
+            Emissions::default()
+        );
+    }
+
+    #[test]
+    fn recorded_speeds() {
+        let speed_limit = Speed::miles_per_hour(25.0);
+        let length = Distance::meters(100.0);
+        let measure = |speed| {
+            let prev = CurrentSegment {
+                on: None,
+                since: Time::START_OF_DAY,
+                initial_speed: Speed::ZERO,
+                speed,
+                trip: None,
+            };
+            let now = Time::START_OF_DAY + Duration::seconds(2.0);
+            measure_segment(&prev, now, speed_limit, length)
+        };
+
+        // Slower than the speed limit, like a car stuck behind an incident
+        let slow = Speed::meters_per_second(5.0);
+        assert_eq!(
+            measure(Some(slow)),
+            (slow, Distance::meters(10.0), Duration::ZERO)
+        );
+
+        let (_, distance, idle_time) = measure(Some(Speed::ZERO));
+        assert_eq!(distance, Distance::ZERO);
+        assert_eq!(idle_time, Duration::seconds(2.0));
+        let stuck = DrivingSegment {
+            cruising_speed: Speed::ZERO,
+            distance,
+            idle_time,
+            ..segment(VehicleType::Car, 0.0)
+        };
+        assert!(
+            PhysicalEmissionModel::default()
+                .emissions(&stuck)
+                .fuel_liters
+                > 0.0
+        );
+
+        // Vehicles can't go past the end
+        let (_, distance, idle_time) = measure(Some(Speed::meters_per_second(100.0)));
+        assert_eq!(distance, length);
+        assert_eq!(idle_time, Duration::seconds(1.0));
+
+        // Without a recorded speed, assume the speed limit
+        assert_eq!(measure(None).0, speed_limit);
+    }
+
+    #[test]
+    fn custom_model_is_saved() {
+        let mut model = PhysicalEmissionModel::default();
+        model.electric_car.mass_kg = 1000.0;
+        let mut tracker = EmissionsTracker::new(&SimOptions::default());
+        tracker.set_model(model);
+
+        let restored: EmissionsTracker =
+            abstutil::from_json(abstutil::to_json(&tracker).as_bytes()).unwrap();
+        assert_eq!(restored.model.electric_car.mass_kg, 1000.0);
+    }
+}
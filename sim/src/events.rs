@@ -1,13 +1,14 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use geom::Duration;
 use map_model::{
-    BuildingID, IntersectionID, LaneID, Map, Path, PathRequest, TransitRouteID, TransitStopID,
-    Traversable, TurnID,
+    BuildingID, ChargerLocation, IntersectionID, LaneID, Map, Path, PathRequest, TransitRouteID,
+    TransitStopID, Traversable, TurnID,
 };
 use synthpop::TripMode;
 
-use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID};
+use crate::{AgentID, CarID, IncidentID, ParkingSpot, PedestrianID, PersonID, Problem, TripID};
 
 /// As a simulation runs, different systems emit Events. This cleanly separates the internal
 /// mechanics of the simulation from consumers that just want to know what's happening.
@@ -21,12 +22,37 @@ use crate::{AgentID, CarID, ParkingSpot, PedestrianID, PersonID, Problem, TripID
 pub enum Event {
     CarReachedParkingSpot(CarID, ParkingSpot),
     CarLeftParkingSpot(CarID, ParkingSpot),
+    /// A driver paid up-front to park for their whole planned stay.
+    ParkingFeePaid(CarID, TripID, ParkingSpot, f64),
+    /// An electric car parked at a charger and plugged in.
+    EVChargingStarted(CarID, ChargerLocation),
+    /// An electric car left a charger, after plugged in for some time and charging this many kWh.
+    EVChargingStopped(CarID, ChargerLocation, Duration, f64),
+    /// An electric car headed for a charger, but all of the spots there were taken by the time it
+    /// arrived.
+    EVChargerFull(CarID, ChargerLocation),
 
     BusArrivedAtStop(CarID, TransitRouteID, TransitStopID),
-    BusDepartedFromStop(CarID, TransitRouteID, TransitStopID),
+    /// How many passengers are on board after leaving the stop, and the vehicle's capacity?
+    BusDepartedFromStop(CarID, TransitRouteID, TransitStopID, usize, usize),
+    /// How late did the vehicle arrive at the stop, compared to the published schedule? Negative
+    /// if it's early. Only emitted for routes with a known schedule.
+    TransitScheduleDeviation(CarID, TransitRouteID, TransitStopID, Duration),
+    /// A traffic signal moved to a new stage, zero-based.
+    SignalStageChanged(IntersectionID, usize),
+    /// A transit or emergency vehicle changed the timing of a traffic signal, saving it roughly
+    /// this much delay.
+    SignalPriorityGranted(IntersectionID, CarID, SignalPriority, Duration),
+    /// A scheduled incident started disrupting traffic, and this many cars changed their route.
+    IncidentStarted(IncidentID, usize),
+    /// An incident ended, and this many cars changed their route.
+    IncidentEnded(IncidentID, usize),
     /// How long waiting at the stop?
     PassengerBoardsTransit(PersonID, CarID, TransitRouteID, TransitStopID, Duration),
     PassengerAlightsTransit(PersonID, CarID, TransitRouteID, TransitStopID),
+    /// Somebody got in or out of a shared car. How many people are inside now, including the
+    /// driver?
+    CarOccupancyChanged(CarID, usize),
 
     PersonEntersBuilding(PersonID, BuildingID),
     PersonLeavesBuilding(PersonID, BuildingID),
@@ -62,7 +88,7 @@ pub enum Event {
     Alert(AlertLocation, String),
 }
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum AlertLocation {
     Nil,
     Intersection(IntersectionID),
@@ -70,6 +96,18 @@ pub enum AlertLocation {
     Building(BuildingID),
 }
 
+/// How a traffic signal changed its timing for a transit or emergency vehicle
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Serialize, Deserialize)]
+pub enum SignalPriority {
+    /// Kept the current stage going until the vehicle arrives
+    GreenExtension,
+    /// Ended the current stage early, because the next one lets the vehicle go
+    RedTruncation,
+    /// Ended the current stage almost immediately and skipped ahead to a stage that lets an
+    /// emergency vehicle go
+    Preemption,
+}
+
 #[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
 pub enum TripPhaseType {
     Driving,
@@ -79,6 +117,8 @@ pub enum TripPhaseType {
     WaitingForBus(TransitRouteID, TransitStopID),
     /// What stop did they board at?
     RidingBus(TransitRouteID, TransitStopID, CarID),
+    /// Riding in somebody else's car
+    RidingInCar(CarID),
     Cancelled,
     Finished,
     DelayedStart,
@@ -97,6 +137,7 @@ impl TripPhaseType {
             TripPhaseType::RidingBus(r, _, _) => {
                 format!("Riding route {}", map.get_tr(r).long_name)
             }
+            TripPhaseType::RidingInCar(_) => "Riding in a shared car".to_string(),
             TripPhaseType::Cancelled => "Trip was cancelled due to some bug".to_string(),
             TripPhaseType::Finished => "Trip finished".to_string(),
             TripPhaseType::DelayedStart => "Delayed by a previous trip taking too long".to_string(),
@@ -0,0 +1,302 @@
+//! Temporary disruptions like crashes, work zones, and closed intersections. They start and end at
+//! scheduled times during a simulation, without editing the map, so different incident scenarios
+//! can be compared on the same map and scenario.
+//!
+//! Only cars reroute around incidents. Bikes, buses, and drivers already searching for parking
+//! wait at a closed turn until the incident ends.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
+use geom::Time;
+use map_model::{IntersectionID, LaneID, Map, Path, PathStep, RoadID, RoutingParams, TurnID};
+
+use crate::{Command, Scheduler, SimOptions};
+
+/// Something disrupting traffic from `start` to `end`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct Incident {
+    pub start: Time,
+    pub end: Time,
+    pub disruption: Disruption,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub enum Disruption {
+    /// Vehicles can't enter these lanes. Vehicles already on one can still leave.
+    BlockLanes(Vec<LaneID>),
+    /// Vehicles cross the road more slowly, like in a work zone. The factor (between 0 and 1)
+    /// multiplies their usual speed. Vehicles already partway along the road keep going at their
+    /// old speed until they reach the next lane.
+    ReduceCapacity(RoadID, f64),
+    /// Vehicles can't turn through the intersection. Pedestrians can still cross.
+    CloseIntersection(IntersectionID),
+}
+
+#[derive(
+    Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize, JsonSchema,
+)]
+pub struct IncidentID(pub usize);
+
+impl Disruption {
+    pub(crate) fn blocks_turn(&self, t: TurnID) -> bool {
+        match self {
+            Disruption::BlockLanes(lanes) => lanes.contains(&t.dst),
+            Disruption::ReduceCapacity(_, _) => false,
+            Disruption::CloseIntersection(i) => t.parent == *i,
+        }
+    }
+
+    /// Does this block or slow down the rest of the path, after the current step?
+    fn affects_path(&self, path: &Path) -> bool {
+        path.get_steps()
+            .iter()
+            .skip(1)
+            .any(|step| match (self, step) {
+                (Disruption::BlockLanes(lanes), PathStep::Lane(l)) => lanes.contains(l),
+                (Disruption::ReduceCapacity(r, _), PathStep::Lane(l)) => l.road == *r,
+                (_, PathStep::Turn(t)) => self.blocks_turn(*t),
+                _ => false,
+            })
+    }
+
+    /// Does the rest of the path pass through an intersection next to this disruption?
+    pub(crate) fn near_path(&self, path: &Path, map: &Map) -> bool {
+        let roads: Vec<RoadID> = match self {
+            Disruption::BlockLanes(lanes) => lanes.iter().map(|l| l.road).collect(),
+            Disruption::ReduceCapacity(r, _) => vec![*r],
+            Disruption::CloseIntersection(i) => map
+                .maybe_get_i(*i)
+                .map(|i| i.roads.iter().cloned().collect())
+                .unwrap_or_else(Vec::new),
+        };
+        let near: BTreeSet<IntersectionID> = roads
+            .into_iter()
+            .filter_map(|r| map.maybe_get_r(r))
+            .flat_map(|road| [road.src_i, road.dst_i])
+            .collect();
+        path.get_steps()
+            .iter()
+            .any(|step| matches!(step, PathStep::Turn(t) if near.contains(&t.parent)))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct IncidentSimState {
+    /// Indexed by IncidentID
+    incidents: Vec<Incident>,
+    active: BTreeSet<IncidentID>,
+
+    // These're all derived from the active incidents
+    closed_lanes: BTreeSet<LaneID>,
+    closed_intersections: BTreeSet<IntersectionID>,
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    slow_roads: BTreeMap<RoadID, f64>,
+}
+
+impl IncidentSimState {
+    pub fn new(opts: &SimOptions, scheduler: &mut Scheduler) -> IncidentSimState {
+        let mut state = IncidentSimState {
+            incidents: Vec::new(),
+            active: BTreeSet::new(),
+            closed_lanes: BTreeSet::new(),
+            closed_intersections: BTreeSet::new(),
+            slow_roads: BTreeMap::new(),
+        };
+        if let Some(ref path) = opts.incidents {
+            let list: Vec<Incident> = abstio::read_json(path.clone(), &mut Timer::throwaway());
+            for incident in list {
+                if let Err(err) = state.add(incident, Time::START_OF_DAY, scheduler) {
+                    panic!("Bad incident in {}: {}", path, err);
+                }
+            }
+        }
+        state
+    }
+
+    /// Schedule an incident. If it should've already started, it starts immediately.
+    pub fn add(
+        &mut self,
+        incident: Incident,
+        now: Time,
+        scheduler: &mut Scheduler,
+    ) -> Result<IncidentID> {
+        if incident.end <= incident.start {
+            bail!(
+                "Incident ends at {}, before it starts at {}",
+                incident.end,
+                incident.start
+            );
+        }
+        if incident.end <= now {
+            bail!(
+                "Incident ends at {}, but it's already {}",
+                incident.end,
+                now
+            );
+        }
+        if let Disruption::ReduceCapacity(_, factor) = incident.disruption {
+            if factor <= 0.0 || factor > 1.0 {
+                bail!("Speed factor {} must be in (0, 1]", factor);
+            }
+        }
+
+        let id = IncidentID(self.incidents.len());
+        scheduler.push(incident.start.max(now), Command::StartIncident(id));
+        scheduler.push(incident.end, Command::EndIncident(id));
+        self.incidents.push(incident);
+        Ok(id)
+    }
+
+    pub fn start(&mut self, id: IncidentID, map: &Map) {
+        self.active.insert(id);
+        self.recalculate(map);
+    }
+
+    /// Returns the disruption that just ended
+    pub fn end(&mut self, id: IncidentID, map: &Map) -> Disruption {
+        self.active.remove(&id);
+        self.recalculate(map);
+        self.incidents[id.0].disruption.clone()
+    }
+
+    fn recalculate(&mut self, map: &Map) {
+        self.closed_lanes.clear();
+        self.closed_intersections.clear();
+        self.slow_roads.clear();
+        for id in &self.active {
+            match self.incidents[id.0].disruption {
+                Disruption::BlockLanes(ref lanes) => {
+                    self.closed_lanes.extend(lanes.iter().cloned());
+                }
+                Disruption::ReduceCapacity(r, factor) => {
+                    // If several work zones overlap, the slowest wins
+                    let entry = self.slow_roads.entry(r).or_insert(1.0);
+                    *entry = entry.min(factor);
+                }
+                Disruption::CloseIntersection(i) => {
+                    self.closed_intersections.insert(i);
+                }
+            }
+        }
+        // Live edits might've deleted things since the incident was scheduled
+        self.closed_lanes.retain(|l| map.maybe_get_l(*l).is_some());
+        self.closed_intersections
+            .retain(|i| map.maybe_get_i(*i).is_some());
+        self.slow_roads.retain(|r, _| map.maybe_get_r(*r).is_some());
+    }
+}
+
+// Queries
+impl IncidentSimState {
+    pub fn get_all(&self) -> Vec<(IncidentID, &Incident, bool)> {
+        self.incidents
+            .iter()
+            .enumerate()
+            .map(|(idx, incident)| {
+                let id = IncidentID(idx);
+                (id, incident, self.active.contains(&id))
+            })
+            .collect()
+    }
+
+    pub fn any_active(&self) -> bool {
+        !self.active.is_empty()
+    }
+
+    pub fn is_lane_closed(&self, l: LaneID) -> bool {
+        self.closed_lanes.contains(&l)
+    }
+
+    /// Can vehicles start this turn right now?
+    pub fn is_turn_closed(&self, t: TurnID) -> bool {
+        self.closed_intersections.contains(&t.parent) || self.closed_lanes.contains(&t.dst)
+    }
+
+    /// Multiplies the speed of vehicles crossing something
+    pub fn speed_factor(&self, step: PathStep) -> f64 {
+        match step {
+            PathStep::Lane(l) => self.slow_roads.get(&l.road).cloned().unwrap_or(1.0),
+            _ => 1.0,
+        }
+    }
+
+    /// Does the rest of the path, after the current step, go somewhere closed?
+    pub fn blocks_path(&self, path: &Path) -> bool {
+        path.get_steps().iter().skip(1).any(|step| match step {
+            PathStep::Lane(l) => self.is_lane_closed(*l),
+            PathStep::Turn(t) => self.is_turn_closed(*t),
+            _ => false,
+        })
+    }
+
+    /// Does any active incident affect the rest of the path, including slowing it down?
+    pub fn affects_path(&self, path: &Path) -> bool {
+        self.blocks_path(path)
+            || path.get_steps().iter().any(|step| match step {
+                PathStep::Lane(l) => self.slow_roads.contains_key(&l.road),
+                _ => false,
+            })
+    }
+
+    /// The active incidents blocking or slowing down the rest of the path
+    pub fn affecting_path(&self, path: &Path) -> BTreeSet<IncidentID> {
+        self.active
+            .iter()
+            .filter(|id| self.incidents[id.0].disruption.affects_path(path))
+            .cloned()
+            .collect()
+    }
+
+    /// Adjust routing to avoid closed roads and intersections and to account for slow roads.
+    pub fn update_routing_params(&self, params: &mut RoutingParams, map: &Map) {
+        for i in &self.closed_intersections {
+            let roads = &map.get_i(*i).roads;
+            for r1 in roads {
+                for r2 in roads {
+                    params.avoid_movements_between.insert((*r1, *r2));
+                }
+            }
+        }
+        // Only avoid a road entirely when every lane cars could use is closed. If some lanes are
+        // still open, vehicles pick one of those when they choose a lane.
+        let roads: BTreeSet<RoadID> = self.closed_lanes.iter().map(|l| l.road).collect();
+        for r in roads {
+            let road = map.get_r(r);
+            if road
+                .lanes
+                .iter()
+                .filter(|l| l.is_driving())
+                .all(|l| self.closed_lanes.contains(&l.id))
+            {
+                params.avoid_roads.insert(r);
+            }
+        }
+        for (r, factor) in &self.slow_roads {
+            let road = map.get_r(*r);
+            let free_flow = road.length() / road.speed_limit;
+            for dr in r.both_directions() {
+                let i = dr.dst_i(map);
+                for mvmnt in map.get_i(i).movements.keys() {
+                    if mvmnt.from == dr {
+                        let usual = params
+                            .observed_movement_times
+                            .get(mvmnt)
+                            .cloned()
+                            .unwrap_or(free_flow);
+                        params
+                            .observed_movement_times
+                            .insert(*mvmnt, usual / *factor);
+                    }
+                }
+            }
+        }
+    }
+}
@@ -6,8 +6,8 @@
 //! glue together individual goals executed by the agents.
 //!
 //! Helpful terminology:
-//! - sov = single occupancy vehicle, a car with just a driver and no passengers. (Car passengers
-//!   are not currently modelled)
+//! - sov = single occupancy vehicle, a car with just a driver and no passengers. The only car
+//!   passengers modelled are people sharing a ride in a carpool (see `synthpop::Carpool`).
 
 // Disable some noisy clippy warnings
 #![allow(clippy::type_complexity, clippy::too_many_arguments)]
@@ -19,6 +19,7 @@ extern crate log;
 
 use std::fmt;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
@@ -35,12 +36,20 @@ pub use crate::render::{
 };
 
 pub use self::analytics::{Analytics, Problem, ProblemType, SlidingWindow, TripPhase};
-pub(crate) use self::events::Event;
-pub use self::events::{AlertLocation, TripPhaseType};
+pub(crate) use self::charging::{charger_location, ChargingSimState};
+pub use self::cosim::{ExternalCarState, ExternalDecision};
+pub(crate) use self::emissions::EmissionsTracker;
+pub use self::emissions::{
+    DrivingSegment, Emissions, EmissionsSummary, PhysicalEmissionModel, VehicleEmissionParams,
+};
+pub use self::events::{AlertLocation, Event, SignalPriority, TripPhaseType};
+pub(crate) use self::incidents::IncidentSimState;
+pub use self::incidents::{Disruption, Incident, IncidentID};
 pub use self::make::SimFlags;
 pub(crate) use self::make::{StartTripArgs, TripSpec};
 pub(crate) use self::mechanics::{
-    DrivingSimState, IntersectionSimState, ParkingSim, ParkingSimState, WalkingSimState,
+    parking_rules, planned_stay, spot_cost, DrivingSimState, IntersectionSimState, ParkingSim,
+    ParkingSimState, WalkingSimState,
 };
 pub(crate) use self::pandemic::PandemicModel;
 pub use self::prebake::PrebakeSummary;
@@ -52,12 +61,18 @@ pub use self::sim::{
     SimCallback, SimOptions,
 };
 pub(crate) use self::transit::TransitSimState;
+pub use self::travel_times::ObservedTravelTimes;
+pub(crate) use self::travel_times::TravelTimes;
 pub use self::trips::{CommutersVehiclesCounts, Person, PersonState, TripInfo, TripResult};
 pub(crate) use self::trips::{TripLeg, TripManager};
 pub use synthpop::make::{fork_rng, BorderSpawnOverTime, ScenarioGenerator, SpawnOverTime};
 
 mod analytics;
+mod charging;
+mod cosim;
+mod emissions;
 mod events;
+mod incidents;
 mod make;
 mod mechanics;
 mod pandemic;
@@ -68,6 +83,7 @@ mod router;
 mod scheduler;
 mod sim;
 mod transit;
+mod travel_times;
 mod trips;
 
 // http://pccsc.net/bicycle-parking-info/ says 68 inches, which is 1.73m
@@ -78,6 +94,11 @@ pub(crate) const MAX_CAR_LENGTH: Distance = Distance::const_meters(6.5);
 pub(crate) const BUS_LENGTH: Distance = Distance::const_meters(12.5);
 pub(crate) const LIGHT_RAIL_LENGTH: Distance = Distance::const_meters(60.0);
 
+/// How many passengers, seated and standing, fit on a transit vehicle by default. Roughly a
+/// standard 40-foot bus and a two-car light rail train.
+pub(crate) const BUS_CAPACITY: usize = 80;
+pub(crate) const LIGHT_RAIL_CAPACITY: usize = 400;
+
 /// At all speeds (including at rest), cars must be at least this far apart, measured from front of
 /// one car to the back of the other.
 pub(crate) const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
@@ -87,7 +108,9 @@ pub(crate) const FOLLOWING_DISTANCE: Distance = Distance::const_meters(1.0);
 pub(crate) const SPAWN_DIST: Distance = Distance::const_meters(0.05);
 
 // TODO Implement Eq, Hash, Ord manually to guarantee this.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct CarID {
     /// The numeric ID must be globally unique, without considering VehicleType.
     #[serde(
@@ -110,7 +133,9 @@ impl fmt::Display for CarID {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct PedestrianID(
     #[serde(
         serialize_with = "serialize_usize",
@@ -125,12 +150,16 @@ impl fmt::Display for PedestrianID {
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
+#[derive(
+    Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash,
+)]
 pub enum AgentID {
     Car(CarID),
     Pedestrian(PedestrianID),
     // TODO Rename...
     BusPassenger(PersonID, CarID),
+    /// Riding in somebody else's car, as part of a carpool
+    CarPassenger(PersonID, CarID),
 }
 
 impl AgentID {
@@ -151,6 +180,7 @@ impl AgentID {
             },
             AgentID::Pedestrian(_) => AgentType::Pedestrian,
             AgentID::BusPassenger(_, _) => AgentType::TransitRider,
+            AgentID::CarPassenger(_, _) => AgentType::CarPassenger,
         }
     }
 
@@ -158,7 +188,7 @@ impl AgentID {
         match self {
             AgentID::Car(c) => Some(c.vehicle_type),
             AgentID::Pedestrian(_) => None,
-            AgentID::BusPassenger(_, _) => None,
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => None,
         }
     }
 
@@ -182,11 +212,14 @@ impl fmt::Display for AgentID {
             AgentID::Car(id) => write!(f, "AgentID({})", id),
             AgentID::Pedestrian(id) => write!(f, "AgentID({})", id),
             AgentID::BusPassenger(person, bus) => write!(f, "AgentID({} on {})", person, bus),
+            AgentID::CarPassenger(person, car) => write!(f, "AgentID({} in {})", person, car),
         }
     }
 }
 
-#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash)]
+#[derive(
+    Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Debug, Hash,
+)]
 pub enum AgentType {
     Car,
     Bike,
@@ -194,6 +227,7 @@ pub enum AgentType {
     Train,
     Pedestrian,
     TransitRider,
+    CarPassenger,
 }
 
 impl AgentType {
@@ -205,6 +239,7 @@ impl AgentType {
             AgentType::Train,
             AgentType::Pedestrian,
             AgentType::TransitRider,
+            AgentType::CarPassenger,
         ]
     }
 
@@ -216,6 +251,7 @@ impl AgentType {
             AgentType::Train => "Train",
             AgentType::Pedestrian => "Pedestrian",
             AgentType::TransitRider => "Transit rider",
+            AgentType::CarPassenger => "Car passenger",
         }
     }
 
@@ -227,6 +263,7 @@ impl AgentType {
             AgentType::Train => "trains",
             AgentType::Pedestrian => "pedestrians",
             AgentType::TransitRider => "transit riders",
+            AgentType::CarPassenger => "car passengers",
         }
     }
 
@@ -237,11 +274,14 @@ impl AgentType {
             AgentType::Bus | AgentType::Train => unreachable!(),
             AgentType::Pedestrian => "walking",
             AgentType::TransitRider => "riding transit",
+            AgentType::CarPassenger => "riding in a car",
         }
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct TripID(
     #[serde(
         serialize_with = "serialize_usize",
@@ -258,7 +298,9 @@ impl fmt::Display for TripID {
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize)]
+#[derive(
+    Clone, Copy, Debug, Eq, Hash, PartialEq, PartialOrd, Ord, Serialize, Deserialize, JsonSchema,
+)]
 pub struct PersonID(
     #[serde(
         serialize_with = "serialize_usize",
@@ -273,7 +315,9 @@ impl fmt::Display for PersonID {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord)]
+#[derive(
+    Serialize, Deserialize, JsonSchema, Clone, Copy, PartialEq, Eq, Debug, Hash, PartialOrd, Ord,
+)]
 pub enum VehicleType {
     Car,
     Bus,
@@ -310,6 +354,25 @@ impl VehicleType {
             VehicleType::Bike => false,
         }
     }
+
+    /// The default number of passengers a transit vehicle can carry.
+    pub(crate) fn transit_capacity(self) -> usize {
+        match self {
+            VehicleType::Bus => BUS_CAPACITY,
+            VehicleType::Train => LIGHT_RAIL_CAPACITY,
+            VehicleType::Car | VehicleType::Bike => unreachable!(),
+        }
+    }
+
+    /// How quickly this type of vehicle speeds up from a stop, in meters per second squared
+    pub(crate) fn max_acceleration(self) -> f64 {
+        match self {
+            VehicleType::Car => 2.5,
+            VehicleType::Bus => 1.2,
+            VehicleType::Train => 1.0,
+            VehicleType::Bike => 1.0,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
@@ -319,13 +382,19 @@ pub struct Vehicle {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    /// Electric cars have a battery that drains as they drive and charges while they're parked at
+    /// a charger.
+    pub electric: bool,
 }
 
-#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, PartialEq)]
 pub struct VehicleSpec {
     pub vehicle_type: VehicleType,
     pub length: Distance,
     pub max_speed: Option<Speed>,
+    /// Only cars can be electric
+    #[serde(default)]
+    pub electric: bool,
 }
 
 impl VehicleSpec {
@@ -337,6 +406,7 @@ impl VehicleSpec {
             vehicle_type: self.vehicle_type,
             length: self.length,
             max_speed: self.max_speed,
+            electric: self.electric,
         }
     }
 }
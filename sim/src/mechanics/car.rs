@@ -2,12 +2,12 @@ use std::collections::{BTreeSet, VecDeque};
 
 use serde::{Deserialize, Serialize};
 
-use geom::{Distance, Duration, PolyLine, Time, EPSILON_DIST};
+use geom::{Distance, Duration, PolyLine, Speed, Time, EPSILON_DIST};
 use map_model::{Direction, LaneID, Map, Traversable};
 
 use crate::{
-    CarID, CarStatus, DistanceInterval, DrawCarInput, Intent, ParkingSpot, PersonID, Router,
-    TimeInterval, TransitSimState, TripID, Vehicle, VehicleType,
+    CarID, CarStatus, DistanceInterval, DrawCarInput, IncidentSimState, Intent, ParkingSpot,
+    PersonID, Router, TimeInterval, TransitSimState, TripID, Vehicle, VehicleType,
 };
 
 /// Represents a single vehicle. Note "car" is a misnomer; it could also be a bus or bike.
@@ -29,11 +29,20 @@ pub(crate) struct Car {
     /// Since lane over-taking isn't implemented yet, a vehicle tends to be stuck behind a slow
     /// leader for a while. Avoid duplicate events.
     pub wants_to_overtake: BTreeSet<CarID>,
+    /// Does crossing a lane or turn take longer when starting from a stop? Only with
+    /// `SimOptions::lane_level_dynamics`.
+    pub accelerates: bool,
 }
 
 impl Car {
     /// Assumes the current head of the path is the thing to cross.
-    pub fn crossing_state(&self, start_dist: Distance, start_time: Time, map: &Map) -> CarState {
+    pub fn crossing_state(
+        &self,
+        start_dist: Distance,
+        start_time: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> CarState {
         let end_dist = if self.router.last_step() {
             self.router.get_end_dist()
         } else {
@@ -47,7 +56,7 @@ impl Car {
         }
 
         let dist_int = DistanceInterval::new_driving(start_dist, end_dist);
-        self.crossing_state_with_end_dist(dist_int, start_time, map)
+        self.crossing_state_with_end_dist(dist_int, start_time, map, incidents)
     }
 
     pub fn crossing_state_with_end_dist(
@@ -55,17 +64,23 @@ impl Car {
         dist_int: DistanceInterval,
         start_time: Time,
         map: &Map,
+        incidents: &IncidentSimState,
     ) -> CarState {
-        let (speed, percent_incline) = self
-            .router
-            .get_path()
-            .current_step()
-            .max_speed_and_incline_along(
-                self.vehicle.max_speed,
-                self.vehicle.vehicle_type.to_constraints(),
-                map,
-            );
-        let dt = (dist_int.end - dist_int.start) / speed;
+        let step = self.router.get_path().current_step();
+        let (speed, percent_incline) = step.max_speed_and_incline_along(
+            self.vehicle.max_speed,
+            self.vehicle.vehicle_type.to_constraints(),
+            map,
+        );
+        let speed = speed * incidents.speed_factor(step);
+        let dist = dist_int.end - dist_int.start;
+        let dt = if self.accelerates && self.state.is_stopped(start_time) {
+            // Positions are still interpolated linearly over the crossing, so this just delays
+            // when the vehicle reaches the end.
+            time_from_rest(dist, speed, self.vehicle.vehicle_type.max_acceleration())
+        } else {
+            dist / speed
+        };
         CarState::Crossing {
             time_int: TimeInterval::new(start_time, start_time + dt),
             dist_int,
@@ -354,6 +369,20 @@ pub(crate) enum CarState {
 }
 
 impl CarState {
+    /// Is the vehicle actually stopped, not just passing through a blocked state for an instant?
+    /// Vehicles about to change lanes are assumed to keep moving.
+    fn is_stopped(&self, now: Time) -> bool {
+        match *self {
+            CarState::Queued {
+                blocked_since,
+                want_to_change_lanes: None,
+            }
+            | CarState::WaitingToAdvance { blocked_since } => blocked_since < now,
+            CarState::Unparking { .. } | CarState::IdlingAtStop(_, _) => true,
+            _ => false,
+        }
+    }
+
     pub fn get_end_time(&self) -> Time {
         match self {
             CarState::Crossing { ref time_int, .. } => time_int.end,
@@ -367,6 +396,28 @@ impl CarState {
         }
     }
 
+    /// A vehicle heading to the same place as its leader can't get there until the leader's back
+    /// is out of the way, so a slow or accelerating leader holds back everybody following them.
+    /// `leader` is when that happens and where, from `DrivingSimState::leader_clears`.
+    pub fn follow_leader(&mut self, leader: Option<(Time, Distance)>) {
+        let (clears_at, leader_end) = match leader {
+            Some(x) => x,
+            None => {
+                return;
+            }
+        };
+        if let CarState::Crossing {
+            ref mut time_int,
+            ref dist_int,
+            ..
+        } = *self
+        {
+            if dist_int.end >= leader_end && clears_at > time_int.end {
+                *time_int = TimeInterval::new(time_int.start, clears_at);
+            }
+        }
+    }
+
     pub fn time_spent_waiting(&self, now: Time) -> Duration {
         match self {
             CarState::Queued { blocked_since, .. }
@@ -375,3 +426,77 @@ impl CarState {
         }
     }
 }
+
+/// How long it takes to cover some distance starting from a stop, accelerating at a constant rate
+/// until reaching the cruising speed.
+fn time_from_rest(dist: Distance, speed: Speed, accel: f64) -> Duration {
+    let d = dist.inner_meters();
+    let v = speed.inner_meters_per_second();
+    if v <= 0.0 {
+        return dist / speed;
+    }
+    let dist_to_cruise = v * v / (2.0 * accel);
+    if d <= dist_to_cruise {
+        Duration::seconds((2.0 * d / accel).sqrt())
+    } else {
+        Duration::seconds(v / accel + (d - dist_to_cruise) / v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_time_from_rest() {
+        let speed = Speed::meters_per_second(10.0);
+        let accel = 2.5;
+
+        // Never reaching the cruising speed: d = a * t^2 / 2
+        let dt = time_from_rest(Distance::meters(5.0), speed, accel);
+        assert!((dt.inner_seconds() - 2.0).abs() < 0.01);
+
+        // 4s and 20m to reach the cruising speed, then 8s more
+        let dt = time_from_rest(Distance::meters(100.0), speed, accel);
+        assert!((dt.inner_seconds() - 12.0).abs() < 0.01);
+
+        // Always slower than moving at the cruising speed the whole way, but the difference is
+        // the same for any distance past the acceleration phase
+        for meters in [1.0, 20.0, 100.0, 500.0] {
+            let dist = Distance::meters(meters);
+            assert!(time_from_rest(dist, speed, accel) > dist / speed);
+        }
+        assert_eq!(
+            time_from_rest(Distance::meters(500.0), speed, accel) - Distance::meters(500.0) / speed,
+            time_from_rest(Distance::meters(100.0), speed, accel) - Distance::meters(100.0) / speed
+        );
+    }
+
+    #[test]
+    fn followers_wait_for_leader() {
+        let t = |secs: f64| Time::START_OF_DAY + Duration::seconds(secs);
+        let crossing = |end: f64| CarState::Crossing {
+            time_int: TimeInterval::new(t(0.0), t(10.0)),
+            dist_int: DistanceInterval::new_driving(Distance::ZERO, Distance::meters(end)),
+            steep_uphill: false,
+        };
+
+        // Held back by a leader heading to the same place
+        let mut state = crossing(100.0);
+        state.follow_leader(Some((t(15.0), Distance::meters(100.0))));
+        assert_eq!(state.get_end_time(), t(15.0));
+
+        // A leader clearing the way sooner doesn't speed anybody up
+        let mut state = crossing(100.0);
+        state.follow_leader(Some((t(5.0), Distance::meters(100.0))));
+        assert_eq!(state.get_end_time(), t(10.0));
+
+        // Stopping before the leader's end, or without a leader, isn't affected
+        let mut state = crossing(50.0);
+        state.follow_leader(Some((t(15.0), Distance::meters(100.0))));
+        assert_eq!(state.get_end_time(), t(10.0));
+        let mut state = crossing(100.0);
+        state.follow_leader(None);
+        assert_eq!(state.get_end_time(), t(10.0));
+    }
+}
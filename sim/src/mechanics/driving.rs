@@ -1,9 +1,10 @@
 use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_hashmap, serialize_hashmap, FixedMap, IndexableKey};
-use geom::{Distance, Duration, PolyLine, Time};
+use geom::{Distance, Duration, PolyLine, Speed, Time};
 use map_model::{DrivingSide, IntersectionID, LaneID, Map, Path, PathStep, Position, Traversable};
 
 use crate::mechanics::car::{Car, CarState};
@@ -11,9 +12,10 @@ use crate::mechanics::queue::{Queue, QueueEntry, Queued};
 use crate::sim::Ctx;
 use crate::{
     ActionAtEnd, AgentID, AgentProperties, CarID, CarStatus, Command, CreateCar, DelayCause,
-    DistanceInterval, DrawCarInput, Event, IntersectionSimState, ParkedCar, ParkingSim,
-    ParkingSpot, PersonID, Problem, SimOptions, TimeInterval, TransitSimState, TripID, TripManager,
-    UnzoomedAgent, Vehicle, VehicleType, WalkingSimState, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
+    Disruption, DistanceInterval, DrawCarInput, Event, ExternalCarState, ExternalDecision,
+    IncidentID, IntersectionSimState, ParkedCar, ParkingSim, ParkingSpot, PersonID, Problem,
+    SimOptions, TimeInterval, TransitSimState, TripID, TripManager, UnzoomedAgent, Vehicle,
+    VehicleType, WalkingSimState, FOLLOWING_DISTANCE, MAX_CAR_LENGTH,
 };
 
 const TIME_TO_WAIT_AT_BUS_STOP: Duration = Duration::const_seconds(10.0);
@@ -45,6 +47,7 @@ pub(crate) struct DrivingSimState {
 
     recalc_lanechanging: bool,
     handle_uber_turns: bool,
+    lane_level_dynamics: bool,
 
     time_to_unpark_onstreet: Duration,
     time_to_park_onstreet: Duration,
@@ -61,6 +64,7 @@ impl DrivingSimState {
             events: Vec::new(),
             recalc_lanechanging: !opts.dont_recalc_lanechanging,
             handle_uber_turns: !opts.dont_handle_uber_turns,
+            lane_level_dynamics: opts.lane_level_dynamics,
             waiting_to_spawn: BTreeMap::new(),
 
             time_to_unpark_onstreet: Duration::seconds(10.0),
@@ -191,6 +195,7 @@ impl DrivingSimState {
                 total_blocked_time: Duration::ZERO,
                 trip_and_person: params.trip_and_person,
                 wants_to_overtake: BTreeSet::new(),
+                accelerates: self.lane_level_dynamics,
             };
             let mut start_crossing = false;
             if let Some(p) = params.maybe_parked_car {
@@ -233,6 +238,7 @@ impl DrivingSimState {
                 // Have to do this early
                 if car.router.last_step() {
                     match car.router.maybe_handle_end(
+                        now,
                         start_dist,
                         &car.vehicle,
                         ctx.parking,
@@ -266,7 +272,7 @@ impl DrivingSimState {
                     }
                 }
 
-                car.state = car.crossing_state(start_dist, now, ctx.map);
+                car.state = car.crossing_state(start_dist, now, ctx.map, ctx.incidents);
                 start_crossing = true;
             }
             ctx.scheduler
@@ -280,7 +286,7 @@ impl DrivingSimState {
             if start_crossing {
                 // Don't call this earlier where we set crossing_state, because we're not in the
                 // queue yet
-                self.new_crossing_state(ctx, &car);
+                self.new_crossing_state(ctx, &car, now);
             }
 
             self.cars.insert(car.vehicle.id, car);
@@ -296,6 +302,10 @@ impl DrivingSimState {
         self.waiting_to_spawn.insert(id, (pos, person));
     }
 
+    pub fn cancel_waiting_to_spawn(&mut self, id: CarID) {
+        self.waiting_to_spawn.remove(&id);
+    }
+
     /// State transitions for this car:
     ///
     /// Crossing -> Queued or WaitingToAdvance
@@ -398,6 +408,7 @@ impl DrivingSimState {
                         car.router.opportunistically_lanechange(
                             &self.queues,
                             ctx.map,
+                            ctx.incidents,
                             self.handle_uber_turns,
                         );
                     }
@@ -407,18 +418,21 @@ impl DrivingSimState {
                     // spending the freeflow time possibly moving very slowly.
                     let first_conflict = car.wants_to_overtake.insert(slow_leader);
 
-                    // Record when a vehicle wants to pass a bike
+                    // Record when a vehicle wants to pass a bike. Externally controlled bikes
+                    // don't have a trip.
                     if first_conflict
                         && slow_leader.vehicle_type == VehicleType::Bike
                         && car.vehicle.vehicle_type != VehicleType::Bike
                     {
-                        self.events.push(Event::ProblemEncountered(
-                            self.cars[&slow_leader].trip_and_person.unwrap().0,
-                            Problem::OvertakeDesired(queue.id),
-                        ));
+                        if let Some((trip, _)) = self.cars[&slow_leader].trip_and_person {
+                            self.events.push(Event::ProblemEncountered(
+                                trip,
+                                Problem::OvertakeDesired(queue.id),
+                            ));
+                        }
                     }
 
-                    if let Some(target_lane) = self.pick_overtaking_lane(car, ctx.map) {
+                    if let Some(target_lane) = self.pick_overtaking_lane(car, ctx) {
                         // We need the current position of the car to see if lane-changing is
                         // actually feasible right now, so record our intention and trigger
                         // update_car_with_distances.
@@ -428,6 +442,15 @@ impl DrivingSimState {
                         };
                         return true;
                     }
+                } else if self.lane_level_dynamics {
+                    if let Some(target_lane) = self.pick_shorter_queue(car, ctx) {
+                        // Same as overtaking
+                        car.state = CarState::Queued {
+                            blocked_since: now,
+                            want_to_change_lanes: Some(target_lane),
+                        };
+                        return true;
+                    }
                 }
             }
             CarState::Unparking {
@@ -470,6 +493,7 @@ impl DrivingSimState {
                     // the next loop will pick that up. Just trigger the side effect of choosing an
                     // end_dist.
                     car.router.maybe_handle_end(
+                        now,
                         front,
                         &car.vehicle,
                         ctx.parking,
@@ -478,10 +502,10 @@ impl DrivingSimState {
                         &mut self.events,
                     );
                 }
-                car.state = car.crossing_state(front, now, ctx.map);
+                car.state = car.crossing_state(front, now, ctx.map, ctx.incidents);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                self.new_crossing_state(ctx, car);
+                self.new_crossing_state(ctx, car, now);
             }
             CarState::WaitingToAdvance { blocked_since } => {
                 // 'car' is the leader.
@@ -490,6 +514,13 @@ impl DrivingSimState {
                 assert!(from != goto);
 
                 if let Traversable::Turn(t) = goto {
+                    if ctx.incidents.is_turn_closed(t) {
+                        // Wait for the incident to end, without holding up anybody else at the
+                        // intersection.
+                        ctx.intersections
+                            .cancel_request(AgentID::Car(car.vehicle.id), t);
+                        return false;
+                    }
                     if !ctx.intersections.maybe_start_turn(
                         AgentID::Car(car.vehicle.id),
                         t,
@@ -525,6 +556,7 @@ impl DrivingSimState {
                 // way, until laggy_head is None.
 
                 let last_step = car.router.advance(
+                    now,
                     &car.vehicle,
                     ctx.parking,
                     ctx.map,
@@ -532,7 +564,9 @@ impl DrivingSimState {
                     &mut self.events,
                 );
                 car.total_blocked_time += now - blocked_since;
-                car.state = car.crossing_state(Distance::ZERO, now, ctx.map);
+                car.state = car.crossing_state(Distance::ZERO, now, ctx.map, ctx.incidents);
+                car.state
+                    .follow_leader(self.leader_clears(self.queues[&goto].get_last_vehicle()));
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
                 self.events.push(Event::AgentEntersTraversable(
@@ -545,6 +579,18 @@ impl DrivingSimState {
                         None
                     },
                 ));
+                if let Traversable::Lane(_) = goto {
+                    if ctx.travel_times.maybe_reroute(
+                        car.vehicle.id,
+                        &mut car.router,
+                        now,
+                        ctx.map,
+                        ctx.incidents,
+                    ) {
+                        self.events
+                            .push(Event::PathAmended(car.router.get_path().clone()));
+                    }
+                }
 
                 // Don't mark turn_finished until our back is out of the turn.
                 car.last_steps.push_front(last_step);
@@ -560,6 +606,7 @@ impl DrivingSimState {
                         ),
                         now,
                         ctx.map,
+                        ctx.incidents,
                     )
                     .get_end_time(),
                     Command::UpdateLaggyHead(car.vehicle.id),
@@ -634,11 +681,21 @@ impl DrivingSimState {
                 // Two totally different reasons we'll wind up here: we want to lane-change, and
                 // we're on our last step.
                 if let Some(target_lane) = want_to_change_lanes {
-                    self.try_start_lc(car, our_dist, idx, target_lane, now, ctx);
+                    if !self.try_start_lc(car, our_dist, idx, target_lane, now, ctx)
+                        && self.lane_level_dynamics
+                    {
+                        // Stuck here after all. Give up on this lane, so the car can look for a
+                        // shorter queue again later.
+                        car.state = CarState::Queued {
+                            blocked_since,
+                            want_to_change_lanes: None,
+                        };
+                    }
                     return true;
                 }
 
                 match car.router.maybe_handle_end(
+                    now,
                     our_dist,
                     &car.vehicle,
                     ctx.parking,
@@ -661,6 +718,10 @@ impl DrivingSimState {
                         }
                         false
                     }
+                    Some(ActionAtEnd::ExternalVanish) => {
+                        car.total_blocked_time += now - blocked_since;
+                        false
+                    }
                     Some(ActionAtEnd::GiveUpOnParking) => {
                         car.total_blocked_time += now - blocked_since;
                         trips.cancel_trip(
@@ -694,10 +755,10 @@ impl DrivingSimState {
                     }
                     Some(ActionAtEnd::GotoLaneEnd) => {
                         car.total_blocked_time += now - blocked_since;
-                        car.state = car.crossing_state(our_dist, now, ctx.map);
+                        car.state = car.crossing_state(our_dist, now, ctx.map, ctx.incidents);
                         ctx.scheduler
                             .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                        self.new_crossing_state(ctx, car);
+                        self.new_crossing_state(ctx, car, now);
                         true
                     }
                     Some(ActionAtEnd::StopBiking(bike_rack)) => {
@@ -728,10 +789,14 @@ impl DrivingSimState {
                         }
                     }
                     None => {
-                        ctx.scheduler.push(
-                            now + BLIND_RETRY_TO_REACH_END_DIST,
-                            Command::UpdateCar(car.vehicle.id),
-                        );
+                        // Externally controlled cars that reached the end of their path wait for
+                        // a decision, and resume_external_car wakes them up when it arrives.
+                        if !(car.router.is_external() && our_dist >= car.router.get_end_dist()) {
+                            ctx.scheduler.push(
+                                now + BLIND_RETRY_TO_REACH_END_DIST,
+                                Command::UpdateCar(car.vehicle.id),
+                            );
+                        }
 
                         // TODO For now, always use BLIND_RETRY_TO_REACH_END_DIST. Measured things
                         // to be slower otherwise. :(
@@ -776,10 +841,10 @@ impl DrivingSimState {
                 car.router = transit.bus_departed_from_stop(car.vehicle.id, ctx.map);
                 self.events
                     .push(Event::PathAmended(car.router.get_path().clone()));
-                car.state = car.crossing_state(dist, now, ctx.map);
+                car.state = car.crossing_state(dist, now, ctx.map, ctx.incidents);
                 ctx.scheduler
                     .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
-                self.new_crossing_state(ctx, car);
+                self.new_crossing_state(ctx, car, now);
 
                 self.update_follower(idx, dists, now, ctx);
 
@@ -889,6 +954,9 @@ impl DrivingSimState {
                 }
             }
 
+            let leader = self.leader_clears(
+                self.queues[&self.cars[&follower_id].router.head()].get_leader(follower_id),
+            );
             let mut follower = self.cars.get_mut(&follower_id).unwrap();
             // TODO If the leader vanished at a border node, this still jumps a bit -- the lead
             // car's back is still sticking out. Need to still be bound by them, even though they
@@ -900,26 +968,30 @@ impl DrivingSimState {
 
                     // Prevent them from jumping forwards.
                     follower.total_blocked_time += now - blocked_since;
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state =
+                        follower.crossing_state(follower_dist, now, ctx.map, ctx.incidents);
+                    follower.state.follow_leader(leader);
                     ctx.scheduler.update(
                         follower.state.get_end_time(),
                         Command::UpdateCar(follower_id),
                     );
                     let follower = &self.cars[&follower_id];
-                    self.new_crossing_state(ctx, follower);
+                    self.new_crossing_state(ctx, follower, now);
                 }
                 CarState::Crossing { .. } => {
                     // If the follower was still Crossing, they might not've been blocked by the
                     // leader yet. But recalculating their Crossing state isn't necessarily a no-op
                     // -- this could prevent them from suddenly warping past a blockage.
-                    follower.state = follower.crossing_state(follower_dist, now, ctx.map);
+                    follower.state =
+                        follower.crossing_state(follower_dist, now, ctx.map, ctx.incidents);
+                    follower.state.follow_leader(leader);
                     ctx.scheduler.update(
                         follower.state.get_end_time(),
                         Command::UpdateCar(follower_id),
                     );
                     // This'll possibly update the ETA
                     let follower = &self.cars[&follower_id];
-                    self.new_crossing_state(ctx, follower);
+                    self.new_crossing_state(ctx, follower, now);
                 }
                 CarState::ChangingLanes {
                     from, to, lc_time, ..
@@ -935,6 +1007,7 @@ impl DrivingSimState {
                         DistanceInterval::new_driving(follower_dist, ctx.map.get_l(to).length()),
                         now,
                         ctx.map,
+                        ctx.incidents,
                     ) {
                         CarState::Crossing {
                             time_int, dist_int, ..
@@ -1006,6 +1079,7 @@ impl DrivingSimState {
                     ),
                     now,
                     ctx.map,
+                    ctx.incidents,
                 )
                 .get_end_time();
             // Sometimes due to rounding, retry_at will be exactly time, but we really need to
@@ -1078,6 +1152,7 @@ impl DrivingSimState {
                                         follower.router.opportunistically_lanechange(
                                             &self.queues,
                                             ctx.map,
+                                            ctx.incidents,
                                             self.handle_uber_turns,
                                         );
                                     }
@@ -1109,9 +1184,41 @@ impl DrivingSimState {
     ///   yellow line yet.
     /// - Prefer passing on the left (for DrivingSide::Right)
     /// For now, just pick one candidate lane, even if both might be usable.
-    fn pick_overtaking_lane(&self, car: &Car, map: &Map) -> Option<LaneID> {
-        // Don't overtake in the middle of a turn!
-        let current_lane = map.get_l(car.router.head().maybe_lane()?);
+    fn pick_overtaking_lane(&self, car: &Car, ctx: &Ctx) -> Option<LaneID> {
+        self.lanechange_candidates(car, ctx).into_iter().next()
+    }
+
+    /// Move to an adjacent lane if its queue is much shorter. Only with lane-level dynamics.
+    fn pick_shorter_queue(&self, car: &Car, ctx: &Ctx) -> Option<LaneID> {
+        if car.router.is_external() {
+            return None;
+        }
+        let current = self.queues[&car.router.head()].target_lane_penalty().0;
+        self.lanechange_candidates(car, ctx)
+            .into_iter()
+            .map(|l| {
+                (
+                    self.queues[&Traversable::Lane(l)].target_lane_penalty().0,
+                    l,
+                )
+            })
+            // Require a big difference, so vehicles don't keep hopping back and forth
+            .filter(|(n, _)| *n + 2 <= current)
+            .min()
+            .map(|(_, l)| l)
+    }
+
+    /// Adjacent lanes that a car could change to mid-block, preferring to pass on the side
+    /// opposite the curb.
+    fn lanechange_candidates(&self, car: &Car, ctx: &Ctx) -> Vec<LaneID> {
+        let map = ctx.map;
+        // Don't change lanes in the middle of a turn!
+        let current_lane = match car.router.head() {
+            Traversable::Lane(l) => map.get_l(l),
+            Traversable::Turn(_) => {
+                return Vec::new();
+            }
+        };
         let road = map.get_parent(current_lane.id);
         let idx = current_lane.id.offset;
 
@@ -1126,12 +1233,16 @@ impl DrivingSimState {
             candidates.reverse();
         }
 
+        let mut results = Vec::new();
         for l in candidates {
             let target_lane = map.get_l(l);
             // Must be the same direction -- no crossing into oncoming traffic yet
             if current_lane.dir != target_lane.dir {
                 continue;
             }
+            if ctx.incidents.is_lane_closed(target_lane.id) {
+                continue;
+            }
             // The lane types can differ, as long as the vehicle can use the target. Imagine
             // overtaking a slower cyclist in a bike lane using the rest of the road.
             if !car
@@ -1150,12 +1261,12 @@ impl DrivingSimState {
             {
                 continue;
             }
-            return Some(target_lane.id);
+            results.push(target_lane.id);
         }
-
-        None
+        results
     }
 
+    /// Returns true if the car started changing lanes
     fn try_start_lc(
         &mut self,
         car: &mut Car,
@@ -1164,16 +1275,16 @@ impl DrivingSimState {
         target_lane: LaneID,
         now: Time,
         ctx: &mut Ctx,
-    ) {
+    ) -> bool {
         // If we are a laggy head somewhere else (our back is still sticking into another lane or
         // turn), don't start lane-changing!
         if !car.last_steps.is_empty() {
-            return;
+            return false;
         }
         // If the lanes are very different lengths and we're too close to the end at the target,
         // not going to work.
         if front_current_queue >= ctx.map.get_l(target_lane).length() {
-            return;
+            return false;
         }
         let current_lane = car.router.head().as_lane();
         let front_target_queue = Position::new(current_lane, front_current_queue)
@@ -1186,6 +1297,7 @@ impl DrivingSimState {
             DistanceInterval::new_driving(front_target_queue, ctx.map.get_l(target_lane).length()),
             now,
             ctx.map,
+            ctx.incidents,
         ) {
             CarState::Crossing {
                 time_int, dist_int, ..
@@ -1197,7 +1309,7 @@ impl DrivingSimState {
         // possible in the target?
         let lc_time = TimeInterval::new(now, now + TIME_TO_CHANGE_LANES);
         if lc_time.end >= new_time.end {
-            return;
+            return false;
         }
 
         // Is there room for us to sliiiide on over into that lane's DMs?
@@ -1243,15 +1355,231 @@ impl DrivingSimState {
                 new_dist,
                 lc_time,
             };
+            // An externally controlled car might already have an update scheduled
             ctx.scheduler
-                .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+                .update(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+            return true;
         }
+        false
+    }
+
+    /// Carry out a decision from the external controller of a car.
+    pub fn external_decision(
+        &mut self,
+        id: CarID,
+        decision: ExternalDecision,
+        now: Time,
+        ctx: &mut Ctx,
+    ) -> Result<()> {
+        let car = self
+            .cars
+            .get(&id)
+            .ok_or_else(|| anyhow!("{} isn't on the map", id))?;
+        if !car.router.is_external() {
+            bail!("{} isn't externally controlled", id);
+        }
+        let dists =
+            self.queues[&car.router.head()].get_car_positions(now, &self.cars, &self.queues);
+        let idx = dists
+            .iter()
+            .position(|entry| entry.member == Queued::Vehicle(id))
+            .unwrap();
+        let front = dists[idx].front;
+
+        // Same trick as update_car
+        let mut car = self.cars.remove(&id).unwrap();
+        let result = self.apply_external_decision(&mut car, decision, front, idx, now, ctx);
+        self.cars.insert(id, car);
+        result
+    }
+
+    fn apply_external_decision(
+        &mut self,
+        car: &mut Car,
+        decision: ExternalDecision,
+        front: Distance,
+        idx: usize,
+        now: Time,
+        ctx: &mut Ctx,
+    ) -> Result<()> {
+        let was_last_step = car.router.last_step();
+        match decision {
+            ExternalDecision::Turn(t) => {
+                car.router.external_turn(t, &car.vehicle, ctx.map)?;
+            }
+            ExternalDecision::StopAt(dist) => {
+                if !was_last_step {
+                    bail!(
+                        "{} has already planned a turn; it can only stop on its last lane",
+                        car.vehicle.id
+                    );
+                }
+                let lane = ctx.map.get_l(car.router.head().as_lane());
+                if dist < front || dist > lane.length() {
+                    bail!(
+                        "{} is at {} along {}, so it can't stop at {}",
+                        car.vehicle.id,
+                        front,
+                        lane.id,
+                        dist
+                    );
+                }
+                car.router.external_stop_at(dist);
+            }
+            ExternalDecision::ChangeLanes(target) => {
+                let current = match car.router.head() {
+                    Traversable::Lane(l) => ctx.map.get_l(l),
+                    Traversable::Turn(_) => bail!("{} is in the middle of a turn", car.vehicle.id),
+                };
+                let target_lane = ctx
+                    .map
+                    .maybe_get_l(target)
+                    .ok_or_else(|| anyhow!("{} doesn't exist", target))?;
+                if target.road != current.id.road
+                    || (target.offset as isize - current.id.offset as isize).abs() != 1
+                    || target_lane.dir != current.dir
+                {
+                    bail!(
+                        "{} isn't next to {} in the same direction",
+                        target,
+                        current.id
+                    );
+                }
+                if !car
+                    .vehicle
+                    .vehicle_type
+                    .to_constraints()
+                    .can_use(target_lane, ctx.map)
+                {
+                    bail!("{} can't use {}", car.vehicle.id, target);
+                }
+                if !was_last_step && !car.router.can_lanechange(current.id, target, ctx.map) {
+                    bail!("The next planned turn doesn't start from {}", target);
+                }
+                if !matches!(
+                    car.state,
+                    CarState::Crossing { .. } | CarState::Queued { .. }
+                ) || !self.try_start_lc(car, front, idx, target, now, ctx)
+                {
+                    bail!(
+                        "{} doesn't have room to change lanes right now",
+                        car.vehicle.id
+                    );
+                }
+                return Ok(());
+            }
+            ExternalDecision::Vanish => {
+                car.router.external_vanish();
+            }
+        }
+
+        if was_last_step {
+            self.resume_external_car(car, front, now, ctx);
+        }
+        Ok(())
+    }
+
+    /// After an externally controlled car on its last lane gets a new decision, recalculate where
+    /// it's headed.
+    fn resume_external_car(&mut self, car: &mut Car, front: Distance, now: Time, ctx: &mut Ctx) {
+        let blocked_since = match car.state {
+            CarState::Queued { blocked_since, .. } => Some(blocked_since),
+            CarState::Crossing { .. } => None,
+            // The other states finish on their own, then notice the decision
+            _ => {
+                return;
+            }
+        };
+        if car.router.last_step() && front >= car.router.get_end_dist() {
+            // Already there, so just make sure update_car notices soon
+            if blocked_since.is_some() {
+                ctx.scheduler
+                    .update(now, Command::UpdateCar(car.vehicle.id));
+            }
+            return;
+        }
+
+        if let Some(t) = blocked_since {
+            car.total_blocked_time += now - t;
+        }
+        ctx.scheduler.cancel(Command::UpdateCar(car.vehicle.id));
+        car.state = car.crossing_state(front, now, ctx.map, ctx.incidents);
+        ctx.scheduler
+            .push(car.state.get_end_time(), Command::UpdateCar(car.vehicle.id));
+        self.new_crossing_state(ctx, car, now);
     }
 
     pub fn collect_events(&mut self) -> Vec<Event> {
         std::mem::take(&mut self.events)
     }
 
+    /// An incident started, or one ended. Cars whose route crosses an active incident look for a
+    /// new one, and when an incident ends, every car that can reroute checks for a better way.
+    /// Cars waiting for a turn that just reopened try again. Returns how many cars changed their
+    /// route.
+    pub fn handle_incidents_changed(
+        &mut self,
+        ended: Option<(IncidentID, Disruption)>,
+        now: Time,
+        ctx: &mut Ctx,
+    ) -> usize {
+        let mut rerouted = 0;
+        let ids: Vec<CarID> = self.cars.values().map(|car| car.vehicle.id).collect();
+        for id in ids {
+            let car = self.cars.get_mut(&id).unwrap();
+            let waiting = match car.state {
+                CarState::Crossing { .. }
+                | CarState::Queued {
+                    want_to_change_lanes: None,
+                    ..
+                } => false,
+                CarState::WaitingToAdvance { .. } => true,
+                _ => {
+                    continue;
+                }
+            };
+            let old_turn = match car.router.maybe_next() {
+                Some(Traversable::Turn(t)) => Some(t),
+                _ => None,
+            };
+
+            let affected = match ended {
+                // The way might be faster now, but only for cars that passed near the disruption
+                // anyway, or that an earlier reroute steered around it
+                Some((incident, ref disruption)) => {
+                    let was_avoiding = car.router.incident_ended(incident);
+                    was_avoiding || disruption.near_path(car.router.get_path(), ctx.map)
+                }
+                None => ctx.incidents.affects_path(car.router.get_path()),
+            };
+            if affected
+                && car.vehicle.vehicle_type == VehicleType::Car
+                && car.router.can_reroute()
+                && ctx
+                    .travel_times
+                    .reroute(&mut car.router, now, ctx.map, ctx.incidents)
+            {
+                rerouted += 1;
+                self.events
+                    .push(Event::PathAmended(car.router.get_path().clone()));
+                if waiting {
+                    // The request for the old turn is stale
+                    if let Some(t) = old_turn {
+                        ctx.intersections.cancel_request(AgentID::Car(id), t);
+                    }
+                    ctx.scheduler.update(now, Command::UpdateCar(id));
+                }
+            } else if waiting {
+                if let (Some(t), Some((_, disruption))) = (old_turn, ended.as_ref()) {
+                    if disruption.blocks_turn(t) {
+                        ctx.scheduler.update(now, Command::UpdateCar(id));
+                    }
+                }
+            }
+        }
+        rerouted
+    }
+
     pub fn handle_live_edits(&mut self, map: &Map) {
         // Calculate all queues that should exist now.
         let mut new_queues = HashSet::new();
@@ -1291,13 +1619,45 @@ impl DrivingSimState {
         }
     }
 
-    fn new_crossing_state(&self, ctx: &mut Ctx, car: &Car) {
+    /// With lane-level dynamics, vehicles can't go faster than the leader crossing ahead of them.
+    /// Returns when the leader's back will be out of the way of the end of its crossing, and where
+    /// that is.
+    fn leader_clears(&self, leader: Option<CarID>) -> Option<(Time, Distance)> {
+        if !self.lane_level_dynamics {
+            return None;
+        }
+        let leader = self.cars.get(&leader?)?;
+        match leader.state {
+            CarState::Crossing {
+                ref time_int,
+                ref dist_int,
+                ..
+            } => {
+                let dist = dist_int.end - dist_int.start;
+                if dist <= Distance::ZERO || time_int.end <= time_int.start {
+                    return None;
+                }
+                // Assume the leader keeps going at their average speed
+                let speed = Speed::from_dist_time(dist, time_int.end - time_int.start);
+                Some((
+                    time_int.end + (leader.vehicle.length + FOLLOWING_DISTANCE) / speed,
+                    dist_int.end,
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    fn new_crossing_state(&self, ctx: &mut Ctx, car: &Car, now: Time) {
         if self.queues[&car.router.head()].is_car_at_front(car.vehicle.id) {
             if let Some(Traversable::Turn(turn)) = car.router.maybe_next() {
                 ctx.intersections.approaching_leader(
                     AgentID::Car(car.vehicle.id),
                     turn,
                     car.state.get_end_time(),
+                    now,
+                    ctx.map,
+                    ctx.scheduler,
                 );
             }
         }
@@ -1518,6 +1878,62 @@ impl DrivingSimState {
         }
     }
 
+    /// How fast a car is crossing its current lane or turn, on average. None if it isn't moving.
+    pub fn crossing_speed(&self, id: CarID) -> Option<Speed> {
+        let car = self.cars.get(&id)?;
+        if let CarState::Crossing {
+            ref time_int,
+            ref dist_int,
+            ..
+        } = car.state
+        {
+            let dt = time_int.end - time_int.start;
+            if dt > Duration::ZERO {
+                return Some(Speed::from_dist_time(dist_int.end - dist_int.start, dt));
+            }
+        }
+        None
+    }
+
+    /// None if the car isn't on the map or isn't externally controlled
+    pub fn external_car_state(&self, id: CarID, now: Time, map: &Map) -> Option<ExternalCarState> {
+        let car = self.cars.get(&id)?;
+        if !car.router.is_external() {
+            return None;
+        }
+        let planned: Vec<Traversable> = car
+            .router
+            .get_path()
+            .get_steps()
+            .iter()
+            .map(|step| step.as_traversable())
+            .collect();
+        let constraints = car.vehicle.vehicle_type.to_constraints();
+        let next_turns = map
+            .get_turns_from_lane(planned.last().unwrap().as_lane())
+            .into_iter()
+            .filter(|t| constraints.can_use(map.get_l(t.id.dst), map))
+            .map(|t| t.id)
+            .collect();
+        Some(ExternalCarState {
+            id,
+            on: car.router.head(),
+            dist_along: self.get_car_front(now, car),
+            planned,
+            stop_at: car.router.get_external_stop(),
+            waiting: car.router.last_step() && matches!(car.state, CarState::Queued { .. }),
+            next_turns,
+        })
+    }
+
+    pub fn get_all_external_cars(&self) -> Vec<CarID> {
+        self.cars
+            .values()
+            .filter(|car| car.router.is_external())
+            .map(|car| car.vehicle.id)
+            .collect()
+    }
+
     pub fn get_path(&self, id: CarID) -> Option<&Path> {
         let car = self.cars.get(&id)?;
         Some(car.router.get_path())
@@ -1657,12 +2073,26 @@ impl DrivingSimState {
 
     /// Does the given car want to over-take the vehicle in front of it?
     fn wants_to_overtake(&self, car: &Car) -> Option<CarID> {
+        // External controllers decide when to change lanes
+        if car.router.is_external() {
+            return None;
+        }
         let queue = &self.queues[&car.router.head()];
         let leader = &self.cars[&queue.get_leader(car.vehicle.id)?];
 
+        // Pass buses waiting at stops and cars pulling into or out of a parking spot
+        if self.lane_level_dynamics
+            && matches!(
+                leader.state,
+                CarState::IdlingAtStop(_, _)
+                    | CarState::Parking(_, _, _)
+                    | CarState::Unparking { .. }
+            )
+        {
+            return Some(leader.vehicle.id);
+        }
+
         // Are we faster than them?
-        // TODO This shouldn't be a blocking check; we also want to pass parking cars and buses
-        // waiting at stops.
         let their_speed = leader.vehicle.max_speed?;
         if car
             .vehicle
@@ -5,19 +5,34 @@ use serde::{Deserialize, Serialize};
 use abstutil::{deserialize_btreemap, prettyprint_usize, serialize_btreemap, FixedMap};
 use geom::{Duration, Time};
 use map_model::{
-    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, StageType,
-    Traversable, TurnID, TurnPriority, TurnType, UberTurn,
+    ControlStopSign, ControlTrafficSignal, Intersection, IntersectionID, LaneID, Map, Stage,
+    StageType, Traversable, TurnID, TurnPriority, TurnType, UberTurn,
 };
 
 use crate::mechanics::car::{Car, CarState};
 use crate::mechanics::Queue;
 use crate::{
-    AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SimOptions, Speed,
+    AgentID, AlertLocation, CarID, Command, DelayCause, Event, Scheduler, SignalPriority,
+    SimOptions, Speed,
 };
 
 const WAIT_AT_STOP_SIGN: Duration = Duration::const_seconds(0.5);
 const WAIT_BEFORE_YIELD_AT_TRAFFIC_SIGNAL: Duration = Duration::const_seconds(0.2);
 
+// Transit signal priority
+const TSP_MAX_EXTENSION: Duration = Duration::const_seconds(10.0);
+const TSP_MAX_TRUNCATION: Duration = Duration::const_seconds(10.0);
+const TSP_MIN_STAGE: Duration = Duration::const_seconds(5.0);
+// Emergency vehicles get much more
+const EMERGENCY_MAX_EXTENSION: Duration = Duration::const_seconds(30.0);
+const EMERGENCY_CLEARANCE: Duration = Duration::const_seconds(3.0);
+// Everyone else yields to emergency vehicles expected at the intersection this soon
+const EMERGENCY_YIELD_AHEAD: Duration = Duration::const_seconds(10.0);
+// Hold the green a little past the vehicle's predicted arrival
+const PRIORITY_MARGIN: Duration = Duration::const_seconds(1.0);
+// Coordinated signals treat points in the cycle this close together as the same
+const COORDINATION_TOLERANCE: Duration = Duration::const_seconds(0.01);
+
 /// Manages conflicts at intersections. When an agent has reached the end of a lane, they call
 /// maybe_start_turn to make a Request. Based on the intersection type (stop sign, traffic signal,
 /// or a "freeform policy"), the Request gets queued or immediately accepted. When agents finish
@@ -34,6 +49,9 @@ pub(crate) struct IntersectionSimState {
     break_turn_conflict_cycles: bool,
     handle_uber_turns: bool,
     disable_turn_conflicts: bool,
+    transit_signal_priority: bool,
+    // These preempt traffic signals, and everyone else yields to them at intersections
+    emergency_vehicles: BTreeSet<CarID>,
     // (x, y) means x is blocked by y. It's a many-to-many relationship. TODO Better data
     // structure.
     blocked_by: BTreeSet<(CarID, CarID)>,
@@ -87,8 +105,14 @@ struct SignalState {
     current_stage: usize,
     // The time when the signal is checked for advancing
     stage_ends_at: Time,
+    // When the current stage began, so actuated stages know when they max out
+    stage_started_at: Time,
     // The number of times a variable signal has been extended during the current stage.
     extensions_count: usize,
+    // A transit or emergency vehicle already changed when the current stage ends
+    priority_used: bool,
+    // An emergency vehicle needs to skip ahead to this stage next
+    preempt_to: Option<usize>,
 }
 
 #[derive(PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, Clone, Debug)]
@@ -107,6 +131,8 @@ impl IntersectionSimState {
             break_turn_conflict_cycles: !opts.dont_break_turn_conflict_cycles,
             handle_uber_turns: !opts.dont_handle_uber_turns,
             disable_turn_conflicts: opts.disable_turn_conflicts,
+            transit_signal_priority: opts.transit_signal_priority,
+            emergency_vehicles: BTreeSet::new(),
             blocked_by: BTreeSet::new(),
             events: Vec::new(),
 
@@ -129,7 +155,11 @@ impl IntersectionSimState {
                 leader_eta: BTreeMap::new(),
             };
             if i.is_traffic_signal() {
-                state.signal = Some(SignalState::new(i.id, Time::START_OF_DAY, map, scheduler));
+                state.signal = Some(SignalState::new(
+                    map.get_traffic_signal(i.id),
+                    Time::START_OF_DAY,
+                    scheduler,
+                ));
             }
             if let Some(mut set) = map_model::IntersectionCluster::autodetect(i.id, map) {
                 set.remove(&i.id);
@@ -196,6 +226,11 @@ impl IntersectionSimState {
     /// turn.
     pub fn vehicle_gone(&mut self, car: CarID) {
         self.blocked_by.retain(|(c1, c2)| *c1 != car && *c2 != car);
+        self.emergency_vehicles.remove(&car);
+    }
+
+    pub fn add_emergency_vehicle(&mut self, car: CarID) {
+        self.emergency_vehicles.insert(car);
     }
 
     pub fn agent_deleted_mid_turn(&mut self, agent: AgentID, turn: TurnID) {
@@ -300,11 +335,14 @@ impl IntersectionSimState {
             signal: &ControlTrafficSignal,
             i: &Intersection,
             allow_crosswalk_skip: bool,
+            now: Time,
         ) -> Duration {
+            signal_state.stage_started_at = now;
+            signal_state.priority_used = false;
             signal_state.current_stage = (signal_state.current_stage + 1) % signal.stages.len();
             let stage = &signal.stages[signal_state.current_stage];
-            // only skip for variable all-walk crosswalk
-            if let StageType::Variable(_, _, _) = stage.stage_type {
+            // only skip for variable or actuated all-walk crosswalk
+            if let StageType::Variable(_, _, _) | StageType::Actuated(_, _, _) = stage.stage_type {
                 if allow_crosswalk_skip && stage.max_crosswalk_time(i).is_some() {
                     // we can skip this stage, as its all walk and we're allowed to skip (no
                     // pedestrian waiting).
@@ -312,9 +350,14 @@ impl IntersectionSimState {
                         (signal_state.current_stage + 1) % signal.stages.len();
                 }
             }
-            signal.stages[signal_state.current_stage]
-                .stage_type
-                .simple_duration()
+            if signal.coordinated && signal_state.current_stage == 0 {
+                return coordinated_stage_duration(signal, now);
+            }
+            match signal.stages[signal_state.current_stage].stage_type {
+                // Always check for demand at least a little later, even if there's no minimum
+                StageType::Actuated(min_green, _, _) => min_green.max(Duration::seconds(1.0)),
+                ref stage_type => stage_type.simple_duration(),
+            }
         }
         let state = self.state.get_mut(&id).unwrap();
         let signal_state = state.signal.as_mut().unwrap();
@@ -326,55 +369,93 @@ impl IntersectionSimState {
             false
         });
         let duration: Duration;
+        let old_stage_idx = signal_state.current_stage;
         // Switch to a new stage?
         assert_eq!(now, signal_state.stage_ends_at);
         let old_stage = &signal.stages[signal_state.current_stage];
-        match old_stage.stage_type {
-            StageType::Fixed(_) => {
-                duration = advance(signal_state, signal, i, !ped_waiting);
-            }
-            StageType::Variable(min, delay, additional) => {
-                // test if anyone is waiting in current stage, and if so, extend the signal cycle.
-                // Filter out pedestrians, as they've had their chance and the delay
-                // could be short enough to keep them on the curb.
-                let delay = std::cmp::max(Duration::const_seconds(1.0), delay);
-                // Only extend for the fixed additional time
-                if signal_state.extensions_count as f64 * delay.inner_seconds()
-                    >= additional.inner_seconds()
-                {
-                    self.events.push(Event::Alert(
-                        AlertLocation::Intersection(id),
-                        format!(
-                            "exhausted a variable stage {},{},{},{}",
-                            min, delay, additional, signal_state.extensions_count
-                        ),
-                    ));
-                    duration = advance(signal_state, signal, i, !ped_waiting);
-                    signal_state.extensions_count = 0;
-                } else if state.waiting.keys().all(|req| {
-                    if let AgentID::Pedestrian(_) = req.agent {
-                        return true;
+        // The first stage of a coordinated signal always lasts for its fixed window
+        let coordinated_stage = signal.coordinated && signal_state.current_stage == 0;
+        if let Some(next) = signal_state.preempt_to.take() {
+            signal_state.current_stage = next;
+            signal_state.stage_started_at = now;
+            signal_state.extensions_count = 0;
+            // Keep priority_used set, so the emergency vehicle gets this whole stage
+            duration = signal.stages[next].stage_type.simple_duration();
+        } else {
+            match old_stage.stage_type {
+                StageType::Fixed(_) => {
+                    duration = advance(signal_state, signal, i, !ped_waiting, now);
+                }
+                _ if coordinated_stage => {
+                    duration = advance(signal_state, signal, i, !ped_waiting, now);
+                }
+                StageType::Actuated(_, max_green, passage) => {
+                    let detected = vehicle_detected(
+                        &state.waiting,
+                        &state.leader_eta,
+                        old_stage,
+                        i,
+                        now,
+                        passage,
+                    );
+                    duration = match actuated_extension(
+                        now - signal_state.stage_started_at,
+                        max_green,
+                        passage,
+                        detected,
+                    ) {
+                        Some(dt) => dt,
+                        // Gap-out or max-out
+                        None => advance(signal_state, signal, i, !ped_waiting, now),
+                    };
+                }
+                StageType::Variable(min, delay, additional) => {
+                    // test if anyone is waiting in current stage, and if so, extend the signal cycle.
+                    // Filter out pedestrians, as they've had their chance and the delay
+                    // could be short enough to keep them on the curb.
+                    let delay = std::cmp::max(Duration::const_seconds(1.0), delay);
+                    // Only extend for the fixed additional time
+                    if signal_state.extensions_count as f64 * delay.inner_seconds()
+                        >= additional.inner_seconds()
+                    {
+                        self.events.push(Event::Alert(
+                            AlertLocation::Intersection(id),
+                            format!(
+                                "exhausted a variable stage {},{},{},{}",
+                                min, delay, additional, signal_state.extensions_count
+                            ),
+                        ));
+                        duration = advance(signal_state, signal, i, !ped_waiting, now);
+                        signal_state.extensions_count = 0;
+                    } else if state.waiting.keys().all(|req| {
+                        if let AgentID::Pedestrian(_) = req.agent {
+                            return true;
+                        }
+                        // Should we only allow protected to extend or any not banned?
+                        // currently only the protected demand control extended.
+                        old_stage.get_priority_of_turn(req.turn, i) != TurnPriority::Protected
+                    }) {
+                        signal_state.extensions_count = 0;
+                        duration = advance(signal_state, signal, i, !ped_waiting, now);
+                    } else {
+                        signal_state.extensions_count += 1;
+                        duration = delay;
+                        self.events.push(Event::Alert(
+                            AlertLocation::Intersection(id),
+                            format!(
+                                "Extending a variable stage {},{},{},{}",
+                                min, delay, additional, signal_state.extensions_count
+                            ),
+                        ));
                     }
-                    // Should we only allow protected to extend or any not banned?
-                    // currently only the protected demand control extended.
-                    old_stage.get_priority_of_turn(req.turn, i) != TurnPriority::Protected
-                }) {
-                    signal_state.extensions_count = 0;
-                    duration = advance(signal_state, signal, i, !ped_waiting);
-                } else {
-                    signal_state.extensions_count += 1;
-                    duration = delay;
-                    self.events.push(Event::Alert(
-                        AlertLocation::Intersection(id),
-                        format!(
-                            "Extending a variable stage {},{},{},{}",
-                            min, delay, additional, signal_state.extensions_count
-                        ),
-                    ));
                 }
             }
         }
 
+        if signal_state.current_stage != old_stage_idx {
+            self.events
+                .push(Event::SignalStageChanged(id, signal_state.current_stage));
+        }
         signal_state.stage_ends_at = now + duration;
         scheduler.push(signal_state.stage_ends_at, Command::UpdateIntersection(id));
         self.wakeup_waiting(now, id, scheduler, map);
@@ -435,6 +516,8 @@ impl IntersectionSimState {
 
         if repeat_request {
             self.total_repeat_requests += 1;
+        } else {
+            self.request_signal_priority(&req, now, now, map, scheduler);
         }
 
         let shared_sidewalk_corner =
@@ -452,6 +535,8 @@ impl IntersectionSimState {
         {
             // It's never OK to perform a conflicting turn
             false
+        } else if self.must_yield_to_emergency_vehicle(&req, now, map, scheduler) {
+            false
         } else if maybe_cars_and_queues
             .as_ref()
             .map(|(car, _, _)| started_uber_turn(self, *car))
@@ -620,8 +705,8 @@ impl IntersectionSimState {
                         );
                     }
                 }
-                (Some(_), None) => {
-                    state.signal = Some(SignalState::new(state.id, now, map, scheduler));
+                (Some(ts), None) => {
+                    state.signal = Some(SignalState::new(ts, now, scheduler));
                 }
                 (None, Some(_)) => {
                     state.signal = None;
@@ -672,14 +757,171 @@ impl IntersectionSimState {
     // This is "best effort". If we get something wrong, somebody might start a turn and cut off an
     // approaching vehicle.
     // And it's idempotent -- can call to update an ETA.
-    pub fn approaching_leader(&mut self, agent: AgentID, turn: TurnID, eta: Time) {
+    pub fn approaching_leader(
+        &mut self,
+        agent: AgentID,
+        turn: TurnID,
+        eta: Time,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
         let state = self.state.get_mut(&turn.parent).unwrap();
         // If there was a previous entry here for turn.src, then this leader is spawning in front
         // of the previous leader on a driveway
-        state
-            .leader_eta
-            .insert(turn.src, (Request { agent, turn }, eta));
+        let req = Request { agent, turn };
+        state.leader_eta.insert(turn.src, (req.clone(), eta));
+        self.request_signal_priority(&req, eta, now, map, scheduler);
+    }
+
+    /// Transit (if enabled) and emergency vehicles approaching or waiting at a traffic signal may
+    /// change its timing. `eta` is when they'll reach the stop line. Each stage only changes once.
+    fn request_signal_priority(
+        &mut self,
+        req: &Request,
+        eta: Time,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) {
+        let (car, emergency) = match req.agent {
+            AgentID::Car(c) if self.emergency_vehicles.contains(&c) => (c, true),
+            AgentID::Car(c) if self.transit_signal_priority && c.vehicle_type.is_transit() => {
+                (c, false)
+            }
+            _ => {
+                return;
+            }
+        };
+        if self.use_freeform_policy_everywhere {
+            return;
+        }
+        let signal = if let Some(signal) = map.maybe_get_traffic_signal(req.turn.parent) {
+            signal
+        } else {
+            return;
+        };
+        let i = map.get_i(req.turn.parent);
+        let signal_state = self
+            .state
+            .get_mut(&req.turn.parent)
+            .unwrap()
+            .signal
+            .as_mut()
+            .unwrap();
+        let stages: Vec<(bool, Duration)> = signal
+            .stages
+            .iter()
+            .map(|stage| {
+                (
+                    stage.get_priority_of_turn(req.turn, i) != TurnPriority::Banned,
+                    stage.stage_type.simple_duration(),
+                )
+            })
+            .collect();
+        let min_stage = signal.stages[signal_state.current_stage]
+            .max_crosswalk_time(i)
+            .unwrap_or(Duration::ZERO)
+            .max(TSP_MIN_STAGE);
+        if let Some((priority, saved)) =
+            change_for_priority(signal_state, &stages, min_stage, emergency, eta, now)
+        {
+            scheduler.update(
+                signal_state.stage_ends_at,
+                Command::UpdateIntersection(req.turn.parent),
+            );
+            self.events.push(Event::SignalPriorityGranted(
+                req.turn.parent,
+                car,
+                priority,
+                saved,
+            ));
+        }
+    }
+}
+
+/// Changes when the current stage ends for a transit or emergency vehicle asking for priority.
+/// `stages` says whether each stage lets the vehicle go, and how long it usually lasts.
+/// `min_stage` is the least time the current stage needs, especially for pedestrians. Returns the
+/// kind of change and how much waiting it saves the vehicle.
+fn change_for_priority(
+    signal_state: &mut SignalState,
+    stages: &[(bool, Duration)],
+    min_stage: Duration,
+    emergency: bool,
+    eta: Time,
+    now: Time,
+) -> Option<(SignalPriority, Duration)> {
+    // Transit only changes each stage once, but emergency vehicles always get their way
+    if signal_state.priority_used && !emergency {
+        return None;
+    }
+
+    let current = signal_state.current_stage;
+    let old_end = signal_state.stage_ends_at;
+    let num_stages = stages.len();
+    // Without priority, the vehicle could go once this stage ends and this much more time
+    // passes
+    let mut wait_after_stage = Duration::ZERO;
+    let mut next_serving = None;
+    for offset in 1..=num_stages {
+        let idx = (current + offset) % num_stages;
+        if stages[idx].0 {
+            next_serving = Some(idx);
+            break;
+        }
+        wait_after_stage += stages[idx].1;
+    }
+
+    let (priority, new_end, saved) = if stages[current].0 {
+        // Will it still be green when the vehicle arrives? If the next stage also lets it go,
+        // no need to extend.
+        let max_extension = if emergency {
+            EMERGENCY_MAX_EXTENSION
+        } else {
+            TSP_MAX_EXTENSION
+        };
+        if eta < old_end || wait_after_stage == Duration::ZERO || eta - old_end > max_extension {
+            return None;
+        }
+        (
+            SignalPriority::GreenExtension,
+            eta + PRIORITY_MARGIN,
+            old_end + wait_after_stage - eta,
+        )
+    } else if emergency {
+        let next = next_serving?;
+        // Vehicles and pedestrians already in the intersection still finish their turns
+        // before the emergency vehicle can go
+        let new_end = old_end.min(now + EMERGENCY_CLEARANCE);
+        signal_state.preempt_to = Some(next);
+        (
+            SignalPriority::Preemption,
+            new_end,
+            (old_end + wait_after_stage - new_end).max(Duration::ZERO),
+        )
+    } else {
+        // Only end the red early if the very next stage lets the vehicle go, and the current
+        // stage still gets enough time
+        if next_serving != Some((current + 1) % num_stages) {
+            return None;
+        }
+        let new_end = (old_end - TSP_MAX_TRUNCATION)
+            .max(signal_state.stage_started_at + min_stage)
+            .max(now);
+        if new_end >= old_end {
+            return None;
+        }
+        (SignalPriority::RedTruncation, new_end, old_end - new_end)
+    };
+
+    if new_end == old_end {
+        // An emergency vehicle already changed this stage
+        return None;
     }
+    signal_state.priority_used = true;
+    signal_state.stage_ends_at = new_end;
+    Some((priority, saved))
 }
 
 // Queries
@@ -915,6 +1157,55 @@ impl IntersectionSimState {
         true
     }
 
+    /// Everyone else waits for emergency vehicles at or about to reach the same intersection to
+    /// go first.
+    fn must_yield_to_emergency_vehicle(
+        &self,
+        req: &Request,
+        now: Time,
+        map: &Map,
+        scheduler: &mut Scheduler,
+    ) -> bool {
+        let is_emergency = |agent: AgentID| matches!(agent, AgentID::Car(c) if self.emergency_vehicles.contains(&c));
+        if self.emergency_vehicles.is_empty() || is_emergency(req.agent) {
+            return false;
+        }
+        let turn = map.get_t(req.turn);
+        let state = &self.state[&req.turn.parent];
+        // When the emergency vehicle finishes its turn, waiting agents get woken up
+        if state
+            .waiting
+            .keys()
+            .any(|other| is_emergency(other.agent) && turn.conflicts_with(map.get_t(other.turn)))
+        {
+            return true;
+        }
+
+        // Don't wait forever if the emergency vehicle never shows up
+        let approaching = state
+            .leader_eta
+            .values()
+            .filter(|(other, eta)| {
+                is_emergency(other.agent)
+                    && expecting_emergency_vehicle(*eta, now)
+                    && turn.conflicts_with(map.get_t(other.turn))
+            })
+            .map(|(_, eta)| *eta)
+            .min();
+        if let Some(eta) = approaching {
+            // By then, the emergency vehicle should be waiting here, or it's late and we can
+            // check again.
+            // Since we have "ownership" of scheduling for req.agent, don't need to use
+            // scheduler.update.
+            scheduler.push(
+                eta.max(now) + PRIORITY_MARGIN,
+                Command::update_agent(req.agent),
+            );
+            return true;
+        }
+        false
+    }
+
     fn traffic_signal_policy(
         &mut self,
         req: &Request,
@@ -1104,16 +1395,22 @@ impl IntersectionSimState {
 }
 
 impl SignalState {
-    fn new(id: IntersectionID, now: Time, map: &Map, scheduler: &mut Scheduler) -> SignalState {
+    fn new(signal: &ControlTrafficSignal, now: Time, scheduler: &mut Scheduler) -> SignalState {
         let mut state = SignalState {
             current_stage: 0,
             stage_ends_at: now,
+            stage_started_at: now,
             extensions_count: 0,
+            priority_used: false,
+            preempt_to: None,
         };
 
-        let signal = map.get_traffic_signal(id);
         // What stage are we starting with?
-        let mut offset = (now - Time::START_OF_DAY) + signal.offset;
+        let mut offset = if signal.coordinated {
+            coordinated_cycle_position(signal, now)
+        } else {
+            (now - Time::START_OF_DAY) + signal.offset
+        };
         loop {
             let dt = signal.stages[state.current_stage]
                 .stage_type
@@ -1129,11 +1426,89 @@ impl SignalState {
                 break;
             }
         }
-        scheduler.push(state.stage_ends_at, Command::UpdateIntersection(id));
+        scheduler.push(state.stage_ends_at, Command::UpdateIntersection(signal.id));
         state
     }
 }
 
+/// Is an emergency vehicle reaching an intersection at `eta` close enough to yield to? If it's
+/// much later than expected, stop waiting for it.
+fn expecting_emergency_vehicle(eta: Time, now: Time) -> bool {
+    eta <= now + EMERGENCY_YIELD_AHEAD && now <= eta + EMERGENCY_YIELD_AHEAD
+}
+
+/// Virtual loop detectors sit on the approach lanes of every protected movement in the stage. A
+/// vehicle occupies one if it's waiting at the stop line, or close enough to reach it within the
+/// passage time.
+fn vehicle_detected(
+    waiting: &BTreeMap<Request, (Time, bool)>,
+    leader_eta: &BTreeMap<LaneID, (Request, Time)>,
+    stage: &Stage,
+    i: &Intersection,
+    now: Time,
+    passage: Duration,
+) -> bool {
+    let protected_vehicle = |req: &Request| {
+        matches!(req.agent, AgentID::Car(_))
+            && stage.get_priority_of_turn(req.turn, i) == TurnPriority::Protected
+    };
+    waiting.keys().any(protected_vehicle)
+        || leader_eta
+            .values()
+            .any(|(req, eta)| *eta <= now + passage && protected_vehicle(req))
+}
+
+/// An actuated stage with a vehicle detected keeps going one passage time at a time, but not past
+/// the maximum green. None means the stage gaps out or maxes out.
+fn actuated_extension(
+    elapsed: Duration,
+    max_green: Duration,
+    passage: Duration,
+    detected: bool,
+) -> Option<Duration> {
+    if elapsed < max_green && detected {
+        Some(passage.min(max_green - elapsed))
+    } else {
+        None
+    }
+}
+
+/// Coordinated signals have a fixed cycle length. The first stage always ends at the same point
+/// in the cycle, relative to the central clock and the signal's offset, so it absorbs any time
+/// that earlier actuated stages didn't use.
+fn coordinated_stage_duration(signal: &ControlTrafficSignal, now: Time) -> Duration {
+    let cycle = signal.simple_cycle_duration();
+    let split = signal.stages[0].stage_type.simple_duration();
+    let mut cycle_pos = coordinated_cycle_position(signal, now);
+    // Don't start a sliver of a window just because of floating point drift
+    if (cycle_pos - split).abs() < COORDINATION_TOLERANCE {
+        cycle_pos = split;
+    }
+    if cycle_pos < split {
+        split - cycle_pos
+    } else {
+        cycle - cycle_pos + split
+    }
+}
+
+/// How far into its cycle a coordinated signal is. The offset delays the start of the first stage
+/// relative to the central clock.
+fn coordinated_cycle_position(signal: &ControlTrafficSignal, now: Time) -> Duration {
+    let cycle = signal.simple_cycle_duration();
+    if cycle == Duration::ZERO {
+        return Duration::ZERO;
+    }
+    let mut cycle_pos = ((now - Time::START_OF_DAY) - signal.offset) % cycle;
+    if cycle_pos < Duration::ZERO {
+        cycle_pos = cycle_pos + cycle;
+    }
+    // Repeatedly adding stage durations can leave this just short of a new cycle
+    if cycle - cycle_pos < COORDINATION_TOLERANCE {
+        cycle_pos = Duration::ZERO;
+    }
+    cycle_pos
+}
+
 fn allow_block_the_box(i: &Intersection) -> bool {
     // Degenerate intersections are often just artifacts of how roads are split up in OSM. Allow
     // vehicles to get stuck in them, since the only possible thing they could block is pedestrians
@@ -1173,3 +1548,170 @@ fn allow_block_the_box(i: &Intersection) -> bool {
     }
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn signal_state(stage_ends_at: Time) -> SignalState {
+        SignalState {
+            current_stage: 0,
+            stage_ends_at,
+            stage_started_at: Time::START_OF_DAY,
+            extensions_count: 0,
+            priority_used: false,
+            preempt_to: None,
+        }
+    }
+
+    #[test]
+    fn transit_green_extension() {
+        let now = Time::START_OF_DAY;
+        let old_end = now + Duration::seconds(30.0);
+        // Served by the first stage, but not the second
+        let stages = vec![
+            (true, Duration::seconds(30.0)),
+            (false, Duration::seconds(30.0)),
+        ];
+        let mut state = signal_state(old_end);
+
+        // The bus would just miss the green
+        let eta = old_end + Duration::seconds(5.0);
+        assert_eq!(
+            change_for_priority(&mut state, &stages, TSP_MIN_STAGE, false, eta, now),
+            Some((SignalPriority::GreenExtension, Duration::seconds(25.0)))
+        );
+        assert_eq!(state.stage_ends_at, eta + PRIORITY_MARGIN);
+
+        // The stage only changes once for transit
+        let later = eta + PRIORITY_MARGIN + Duration::seconds(2.0);
+        assert_eq!(
+            change_for_priority(&mut state, &stages, TSP_MIN_STAGE, false, later, now),
+            None
+        );
+        assert_eq!(state.stage_ends_at, eta + PRIORITY_MARGIN);
+
+        // Too far away to extend the green for
+        let mut state = signal_state(old_end);
+        let eta = old_end + TSP_MAX_EXTENSION + Duration::seconds(1.0);
+        assert_eq!(
+            change_for_priority(&mut state, &stages, TSP_MIN_STAGE, false, eta, now),
+            None
+        );
+        assert_eq!(state.stage_ends_at, old_end);
+    }
+
+    #[test]
+    fn emergency_preemption() {
+        let now = Time::START_OF_DAY;
+        let old_end = now + Duration::seconds(30.0);
+        // Only the last stage lets the vehicle go
+        let stages = vec![
+            (false, Duration::seconds(30.0)),
+            (false, Duration::seconds(20.0)),
+            (true, Duration::seconds(30.0)),
+        ];
+        let mut state = signal_state(old_end);
+        // Even if transit already changed this stage, emergency vehicles still preempt
+        state.priority_used = true;
+        let eta = now + Duration::seconds(20.0);
+        assert_eq!(
+            change_for_priority(&mut state, &stages, TSP_MIN_STAGE, false, eta, now),
+            None
+        );
+
+        assert_eq!(
+            change_for_priority(&mut state, &stages, TSP_MIN_STAGE, true, eta, now),
+            Some((SignalPriority::Preemption, Duration::seconds(47.0)))
+        );
+        assert_eq!(state.stage_ends_at, now + EMERGENCY_CLEARANCE);
+        assert_eq!(state.preempt_to, Some(2));
+        // Asking again doesn't change anything
+        assert_eq!(
+            change_for_priority(&mut state, &stages, TSP_MIN_STAGE, true, eta, now),
+            None
+        );
+
+        // Somebody else about to start a conflicting turn yields to the approaching ambulance,
+        // but not forever, if the ambulance never shows up
+        assert!(expecting_emergency_vehicle(
+            eta,
+            eta - Duration::seconds(5.0)
+        ));
+        assert!(!expecting_emergency_vehicle(
+            eta,
+            eta + EMERGENCY_YIELD_AHEAD + Duration::seconds(1.0)
+        ));
+    }
+
+    #[test]
+    fn actuated_timing() {
+        let (max_green, passage) = (Duration::seconds(30.0), Duration::seconds(3.0));
+
+        // Nobody's around, so the stage gaps out
+        assert_eq!(
+            actuated_extension(Duration::seconds(5.0), max_green, passage, false),
+            None
+        );
+
+        // A vehicle waiting for a protected movement extends the green, one passage time at a
+        // time, but not past the maximum
+        let mut elapsed = Duration::seconds(5.0);
+        assert_eq!(
+            actuated_extension(elapsed, max_green, passage, true),
+            Some(passage)
+        );
+        while let Some(dt) = actuated_extension(elapsed, max_green, passage, true) {
+            assert!(dt <= passage);
+            elapsed += dt;
+        }
+        assert_eq!(elapsed, max_green);
+    }
+
+    #[test]
+    fn coordinated_timing() {
+        let split = Duration::seconds(20.0);
+        let offset = Duration::seconds(10.0);
+        let mut stage = Stage::new();
+        stage.stage_type =
+            StageType::Actuated(Duration::seconds(5.0), split, Duration::seconds(3.0));
+        let signal = ControlTrafficSignal {
+            id: IntersectionID(0),
+            stages: vec![stage.clone(), stage.clone(), stage],
+            offset,
+            coordinated: true,
+        };
+        let cycle = signal.simple_cycle_duration();
+        let start = Time::START_OF_DAY;
+
+        // The offset delays the first stage
+        assert_eq!(
+            coordinated_cycle_position(&signal, start + offset),
+            Duration::ZERO
+        );
+        assert_eq!(coordinated_cycle_position(&signal, start), cycle - offset);
+        let state = SignalState::new(&signal, start, &mut Scheduler::new());
+        assert_eq!(state.stage_ends_at, start + offset);
+
+        // Starting on time, the first stage gets its whole split. Starting late, it still ends on
+        // schedule.
+        assert_eq!(coordinated_stage_duration(&signal, start + offset), split);
+        let late = start + offset + Duration::seconds(4.0);
+        assert_eq!(
+            coordinated_stage_duration(&signal, late),
+            split - Duration::seconds(4.0)
+        );
+        // Earlier stages that gapped out leave time for the first stage to start early
+        let early = start + offset + cycle - Duration::seconds(6.0);
+        assert_eq!(
+            coordinated_stage_duration(&signal, early),
+            split + Duration::seconds(6.0)
+        );
+
+        // Tiny floating point errors don't cause a sliver of green or skip a whole window
+        let almost = start + offset + cycle - Duration::seconds(0.001);
+        assert_eq!(coordinated_stage_duration(&signal, almost), split);
+        let barely_over = start + offset + split - Duration::seconds(0.001);
+        assert_eq!(coordinated_stage_duration(&signal, barely_over), cycle);
+    }
+}
@@ -1,6 +1,8 @@
 pub(crate) use self::driving::DrivingSimState;
 pub(crate) use self::intersection::IntersectionSimState;
-pub(crate) use self::parking::{ParkingSim, ParkingSimState};
+pub(crate) use self::parking::{
+    parking_rules, planned_stay, spot_cost, ParkingSim, ParkingSimState,
+};
 pub(crate) use self::queue::Queue;
 pub(crate) use self::walking::WalkingSimState;
 
@@ -9,10 +9,10 @@ use abstutil::{
     deserialize_btreemap, deserialize_multimap, serialize_btreemap, serialize_multimap, MultiMap,
     Timer,
 };
-use geom::{Distance, PolyLine, Pt2D};
+use geom::{Distance, Duration, PolyLine, Pt2D, Time};
 use map_model::{
-    BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, PathConstraints,
-    PathStep, Position, Traversable, TurnID,
+    BuildingID, Lane, LaneID, LaneType, Map, OffstreetParking, ParkingLotID, ParkingRules,
+    PathConstraints, PathStep, Position, Traversable, TurnID, MAX_WALKING_SPEED,
 };
 
 use crate::{CarID, CarStatus, DrawCarInput, Event, ParkedCar, ParkingSpot, PersonID, Vehicle};
@@ -56,6 +56,8 @@ pub trait ParkingSim {
     fn spot_to_driving_pos(&self, spot: ParkingSpot, vehicle: &Vehicle, map: &Map) -> Position;
     fn spot_to_sidewalk_pos(&self, spot: ParkingSpot, map: &Map) -> Position;
     fn get_owner_of_car(&self, id: CarID) -> Option<PersonID>;
+    /// Remember where a car's owner lives, so the car can use resident permit zones there.
+    fn set_home(&mut self, car: CarID, home: BuildingID);
     fn lookup_parked_car(&self, id: CarID) -> Option<&ParkedCar>;
     /// (Filled, available)
     fn get_all_parking_spots(&self) -> (Vec<ParkingSpot>, Vec<ParkingSpot>);
@@ -65,11 +67,16 @@ pub trait ParkingSim {
     /// the implementation has some internal jitter between different vehicles, to discourage
     /// everybody near one spot from all competing for it.
     /// Note the first PathStep is the turn after start, NOT PathStep::Lane(start).
+    /// When the map has a parking policy, drivers keep searching a bit farther and pick the spot
+    /// that best trades off cruising, walking, and fees. `leave_at` is when the driver plans to
+    /// leave again, or None if they're staying for the rest of the day.
     fn path_to_free_parking_spot(
         &self,
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        leave_at: Option<Time>,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)>;
     fn collect_events(&mut self) -> Vec<Event>;
@@ -147,6 +154,13 @@ pub struct NormalParkingSimState {
     )]
     driving_to_lots: MultiMap<LaneID, ParkingLotID>,
 
+    // Where the owner of a car lives, for permit zones
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    homes: BTreeMap<CarID, BuildingID>,
+
     events: Vec<Event>,
 }
 
@@ -164,6 +178,8 @@ impl NormalParkingSimState {
             num_spots_per_lot: BTreeMap::new(),
             driving_to_lots: MultiMap::new(),
 
+            homes: BTreeMap::new(),
+
             events: Vec::new(),
         };
         for l in map.all_lanes() {
@@ -449,8 +465,13 @@ impl ParkingSim for NormalParkingSimState {
         map: &Map,
     ) -> Vec<(ParkingSpot, Position)> {
         let mut candidates = Vec::new();
+        let policy = map.get_parking_policy();
+        let home = self.homes.get(&vehicle.id).cloned();
 
         for l in self.driving_to_parking_lanes.get(driving_pos.lane()) {
+            if !policy.has_permit(*l, home) {
+                continue;
+            }
             for spot in self.onstreet_lanes[l].spots() {
                 if self.is_free(spot)
                     && driving_pos.dist_along()
@@ -526,6 +547,9 @@ impl ParkingSim for NormalParkingSimState {
     fn get_owner_of_car(&self, id: CarID) -> Option<PersonID> {
         self.parked_cars.get(&id).and_then(|p| p.vehicle.owner)
     }
+    fn set_home(&mut self, car: CarID, home: BuildingID) {
+        self.homes.insert(car, home);
+    }
     fn lookup_parked_car(&self, id: CarID) -> Option<&ParkedCar> {
         self.parked_cars.get(&id)
     }
@@ -563,6 +587,8 @@ impl ParkingSim for NormalParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        leave_at: Option<Time>,
+        now: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         let mut backrefs: HashMap<LaneID, TurnID> = HashMap::new();
@@ -579,37 +605,52 @@ impl ParkingSim for NormalParkingSimState {
         let mut rng =
             XorShiftRng::seed_from_u64((vehicle.id.id + start.encode_u32() as usize) as u64);
 
+        // Only used with a parking policy. Cruising time to reach each lane, and the best (total
+        // cost, lane, spot, position) so far.
+        let weigh_costs = !map.get_parking_policy().is_empty();
+        let mut cruising: HashMap<LaneID, Duration> = HashMap::new();
+        cruising.insert(start, Duration::ZERO);
+        let mut best: Option<(Duration, LaneID, ParkingSpot, Position)> = None;
+
         while !queue.is_empty() {
             let (dist_so_far, current) = queue.pop().unwrap();
+            if let Some((cost, lane, spot, pos)) = best {
+                // Nothing farther away can be better. Because of the jitter, this isn't exactly
+                // true, but close enough.
+                if cruising[&current] >= cost {
+                    return Some((backtrack(start, lane, &backrefs), spot, pos));
+                }
+            }
             // If the current lane has a spot open, we wouldn't be asking. This can happen if a spot
             // opens up on the 'start' lane, but behind the car.
             if current != start {
-                // Pick the closest to the start of the lane, since that's closest to where we came
-                // from
-                if let Some((spot, pos)) = self
-                    .get_all_free_spots(Position::start(current), vehicle, target, map)
+                let candidates =
+                    self.get_all_free_spots(Position::start(current), vehicle, target, map);
+                if weigh_costs {
+                    for (spot, pos) in candidates {
+                        if let Some(cost) = spot_cost(self, spot, target, leave_at, now, map) {
+                            let total = cruising[&current] + cost;
+                            if best.map(|(c, _, _, _)| total < c).unwrap_or(true) {
+                                best = Some((total, current, spot, pos));
+                            }
+                        }
+                    }
+                } else if let Some((spot, pos)) = candidates
                     .into_iter()
+                    // Pick the closest to the start of the lane, since that's closest to where we
+                    // came from
                     .min_by_key(|(_, pos)| pos.dist_along())
                 {
-                    let mut steps = vec![PathStep::Lane(current)];
-                    let mut current = current;
-                    loop {
-                        if current == start {
-                            // Don't include PathStep::Lane(start)
-                            steps.pop();
-                            steps.reverse();
-                            return Some((steps, spot, pos));
-                        }
-                        let turn = backrefs[&current];
-                        steps.push(PathStep::Turn(turn));
-                        steps.push(PathStep::Lane(turn.src));
-                        current = turn.src;
-                    }
+                    return Some((backtrack(start, current, &backrefs), spot, pos));
                 }
             }
             for turn in map.get_turns_for(current, PathConstraints::Car) {
                 if let Entry::Vacant(e) = backrefs.entry(turn.id.dst) {
-                    let dist_this_step = turn.geom.length() + map.get_l(current).length();
+                    let current_lane = map.get_l(current);
+                    let dist_this_step = turn.geom.length() + current_lane.length();
+                    let time_this_step =
+                        dist_this_step / map.get_r(current_lane.id.road).speed_limit;
+                    cruising.insert(turn.id.dst, cruising[&current] + time_this_step);
                     // When vehicles search away from the first lane for a spot, don't all go in
                     // the same direction! Do this by jittering which turn they explore.
                     // At worst, they consider a route to be 10% of its true length, so somebody
@@ -625,7 +666,7 @@ impl ParkingSim for NormalParkingSimState {
             }
         }
 
-        None
+        best.map(|(_, lane, spot, pos)| (backtrack(start, lane, &backrefs), spot, pos))
     }
 
     fn collect_events(&mut self) -> Vec<Event> {
@@ -656,6 +697,74 @@ impl ParkingSim for NormalParkingSimState {
     }
 }
 
+// Walk backwards from the end of a search to the start. Doesn't include PathStep::Lane(start).
+fn backtrack(start: LaneID, end: LaneID, backrefs: &HashMap<LaneID, TurnID>) -> Vec<PathStep> {
+    let mut steps = vec![PathStep::Lane(end)];
+    let mut current = end;
+    while current != start {
+        let turn = backrefs[&current];
+        steps.push(PathStep::Turn(turn));
+        steps.push(PathStep::Lane(turn.src));
+        current = turn.src;
+    }
+    steps.pop();
+    steps.reverse();
+    steps
+}
+
+/// The parking policy that applies to a spot. Private and offstreet parking is never regulated.
+pub(crate) fn parking_rules(spot: ParkingSpot, map: &Map) -> Option<&ParkingRules> {
+    let policy = map.get_parking_policy();
+    match spot {
+        ParkingSpot::Onstreet(l, _) => policy.onstreet.get(&l),
+        ParkingSpot::Offstreet(_, _) => None,
+        ParkingSpot::Lot(pl, _) => policy.lots.get(&pl),
+    }
+}
+
+/// How long a driver plans to park. Drivers without another trip stay until the end of the day.
+pub(crate) fn planned_stay(leave_at: Option<Time>, now: Time) -> Duration {
+    let leave_at = leave_at.unwrap_or(Time::START_OF_DAY + Duration::hours(24));
+    if leave_at > now {
+        leave_at - now
+    } else {
+        Duration::ZERO
+    }
+}
+
+/// How much a driver heading to `target` minds parking in a spot, combining the walk to the
+/// building with the fee, using the map's value of time. None if the spot's time limit is shorter
+/// than the planned stay.
+pub(crate) fn spot_cost<P: ParkingSim>(
+    parking: &P,
+    spot: ParkingSpot,
+    target: BuildingID,
+    leave_at: Option<Time>,
+    now: Time,
+    map: &Map,
+) -> Option<Duration> {
+    let walk = parking
+        .spot_to_sidewalk_pos(spot, map)
+        .pt(map)
+        .dist_to(map.get_b(target).sidewalk_pos.pt(map))
+        / MAX_WALKING_SPEED;
+    let fee = match parking_rules(spot, map) {
+        Some(rules) => {
+            if !rules.allows_stay(leave_at.map(|t| planned_stay(Some(t), now))) {
+                return None;
+            }
+            rules.fee(now, planned_stay(leave_at, now))
+        }
+        None => 0.0,
+    };
+    let value_of_time = map.routing_params().value_of_time;
+    if fee > 0.0 && value_of_time > 0.0 {
+        Some(walk + Duration::seconds(3600.0 * fee / value_of_time))
+    } else {
+        Some(walk)
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 struct ParkingLane {
     parking_lane: LaneID,
@@ -976,6 +1085,7 @@ impl ParkingSim for InfiniteParkingSimState {
     fn get_owner_of_car(&self, id: CarID) -> Option<PersonID> {
         self.parked_cars.get(&id).and_then(|p| p.vehicle.owner)
     }
+    fn set_home(&mut self, _: CarID, _: BuildingID) {}
     fn lookup_parked_car(&self, id: CarID) -> Option<&ParkedCar> {
         self.parked_cars.get(&id)
     }
@@ -989,6 +1099,8 @@ impl ParkingSim for InfiniteParkingSimState {
         start: LaneID,
         vehicle: &Vehicle,
         target: BuildingID,
+        _: Option<Time>,
+        _: Time,
         map: &Map,
     ) -> Option<(Vec<PathStep>, ParkingSpot, Position)> {
         // TODO This impl is copied from NormalParkingSimState. Instead, we already know the
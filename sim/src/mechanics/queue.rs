@@ -460,6 +460,14 @@ impl Queue {
         None
     }
 
+    /// The vehicle at the end of the queue, unless a blockage is behind them
+    pub fn get_last_vehicle(&self) -> Option<CarID> {
+        match self.members.back() {
+            Some(Queued::Vehicle(car)) => Some(*car),
+            _ => None,
+        }
+    }
+
     /// Record that a car is blocking a static portion of the queue (from front to back). Must use
     /// the index from can_block_from_driveway.
     pub fn add_static_blockage(
@@ -4,11 +4,22 @@ use crate::{AlertHandler, Sim, SimFlags, SimOptions};
 use abstutil::{prettyprint_usize, Timer};
 use geom::{Duration, Time};
 use map_model::Map;
-use synthpop::Scenario;
+use synthpop::{Scenario, ScenarioModifier};
 
 /// Simulate a curated list of scenarios to completion, and save the analytics as "prebaked
 /// results," to later compare simulation metrics against the baseline without map edits.
 pub fn prebake(map: &Map, scenario: Scenario, timer: &mut Timer) -> PrebakeSummary {
+    prebake_with_modifiers(map, scenario, &[], timer)
+}
+
+/// Like `prebake`, but transforms the scenario first. The results are saved under the name of the
+/// modified scenario, so runs using the same modifiers will be compared against them.
+pub fn prebake_with_modifiers(
+    map: &Map,
+    mut scenario: Scenario,
+    modifiers: &[ScenarioModifier],
+    timer: &mut Timer,
+) -> PrebakeSummary {
     timer.start(format!(
         "prebake for {} / {}",
         scenario.map_name.describe(),
@@ -20,6 +31,10 @@ pub fn prebake(map: &Map, scenario: Scenario, timer: &mut Timer) -> PrebakeSumma
     let mut sim = Sim::new(map, opts);
     // Bit of an abuse of this, but just need to fix the rng seed.
     let mut rng = SimFlags::for_test("prebaked").make_rng();
+    // Like the UI, use the same RNG to apply modifiers and instantiate the scenario
+    for m in modifiers {
+        scenario = m.apply(map, scenario, &mut rng);
+    }
     sim.instantiate(&scenario, map, &mut rng, timer);
 
     // Run until a few hours after the end of the day. Some trips start close to midnight, and we
@@ -1,4 +1,4 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use geom::Time;
 use map_model::{IntersectionID, LaneID, Map, PathStep, Position, Traversable};
@@ -110,6 +110,7 @@ impl TrafficRecorder {
                 })
                 .collect::<Vec<_>>(),
             only_seed_buses: None,
+            transit_capacities: BTreeMap::new(),
         }
         .save();
     }
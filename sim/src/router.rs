@@ -1,20 +1,22 @@
 //! For vehicles only, not pedestrians. Follows a Path from map_model, but can opportunistically
 //! lane-change to avoid a slow lane, can can handle re-planning to look for available parking.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
-use geom::Distance;
+use geom::{Distance, Time};
 use map_model::{
     BuildingID, IntersectionID, LaneID, Map, Path, PathConstraints, PathRequest, PathStep,
-    Position, Traversable, Turn, TurnID,
+    Position, RoutingParams, Traversable, Turn, TurnID,
 };
 
 use crate::mechanics::Queue;
 use crate::{
-    AlertLocation, CarID, Event, ParkingSim, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot,
-    TripID, TripPhaseType, Vehicle, VehicleType,
+    charger_location, spot_cost, AlertLocation, CarID, Event, IncidentID, IncidentSimState,
+    ParkingSim, ParkingSimState, ParkingSpot, PersonID, SidewalkSpot, TripID, TripPhaseType,
+    Vehicle, VehicleType,
 };
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -23,6 +25,11 @@ pub(crate) struct Router {
     path: Path,
     goal: Goal,
     owner: CarID,
+    /// The trip's own routing params, if the path wasn't found with the map's. Rerouting keeps
+    /// using them.
+    routing_params: Option<Box<RoutingParams>>,
+    /// Incidents that an earlier reroute steered the path around
+    avoiding: BTreeSet<IncidentID>,
 }
 
 #[derive(Debug)]
@@ -33,6 +40,7 @@ pub(crate) enum ActionAtEnd {
     StopBiking(SidewalkSpot),
     BusAtStop,
     GiveUpOnParking,
+    ExternalVanish,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
@@ -44,6 +52,10 @@ enum Goal {
         /// No parking available at all!
         stuck_end_dist: Option<Distance>,
         started_looking: bool,
+        /// An electric car headed for a charger, chosen before the trip started
+        heading_to_charger: bool,
+        /// When the driver plans to leave again. None means the rest of the day.
+        leave_at: Option<Time>,
     },
     EndAtBorder {
         end_dist: Distance,
@@ -55,6 +67,12 @@ enum Goal {
     FollowTransitRoute {
         end_dist: Distance,
     },
+    /// An external controller extends the path one turn at a time
+    External {
+        /// Along the last lane of the path
+        end_dist: Distance,
+        vanish: bool,
+    },
 }
 
 impl Router {
@@ -68,6 +86,8 @@ impl Router {
             path,
             goal: Goal::EndAtBorder { end_dist, i },
             owner,
+            routing_params: None,
+            avoiding: BTreeSet::new(),
         }
     }
 
@@ -79,8 +99,48 @@ impl Router {
                 spot: None,
                 stuck_end_dist: None,
                 started_looking: false,
+                heading_to_charger: false,
+                leave_at: None,
+            },
+            owner,
+            routing_params: None,
+            avoiding: BTreeSet::new(),
+        }
+    }
+
+    /// Like `park_near`, but the path leads to a charger that was free when the trip started. If
+    /// it's taken by the time the car arrives, the driver looks for parking like usual.
+    pub fn park_at_charger(
+        owner: CarID,
+        path: Path,
+        bldg: BuildingID,
+        spot: ParkingSpot,
+        end_dist: Distance,
+    ) -> Router {
+        Router {
+            path,
+            goal: Goal::ParkNearBuilding {
+                target: bldg,
+                spot: Some((spot, end_dist)),
+                stuck_end_dist: None,
+                started_looking: false,
+                heading_to_charger: true,
+                leave_at: None,
             },
             owner,
+            routing_params: None,
+            avoiding: BTreeSet::new(),
+        }
+    }
+
+    /// Tell a driver looking for parking how long they'll stay, so they can weigh fees and time
+    /// limits.
+    pub fn set_leave_at(&mut self, time: Option<Time>) {
+        if let Goal::ParkNearBuilding {
+            ref mut leave_at, ..
+        } = self.goal
+        {
+            *leave_at = time;
         }
     }
 
@@ -89,6 +149,8 @@ impl Router {
             goal: Goal::BikeThenStop { goal },
             path,
             owner,
+            routing_params: None,
+            avoiding: BTreeSet::new(),
         }
     }
 
@@ -99,6 +161,22 @@ impl Router {
             },
             path,
             owner,
+            routing_params: None,
+            avoiding: BTreeSet::new(),
+        }
+    }
+
+    /// The path has to be just one lane.
+    pub fn external(owner: CarID, path: Path) -> Router {
+        Router {
+            goal: Goal::External {
+                end_dist: path.get_req().end.dist_along(),
+                vanish: false,
+            },
+            path,
+            owner,
+            routing_params: None,
+            avoiding: BTreeSet::new(),
         }
     }
 
@@ -130,6 +208,7 @@ impl Router {
             } => stuck_end_dist.unwrap_or_else(|| spot.unwrap().1),
             Goal::BikeThenStop { ref goal } => goal.sidewalk_pos.dist_along(),
             Goal::FollowTransitRoute { end_dist } => end_dist,
+            Goal::External { end_dist, .. } => end_dist,
         }
     }
 
@@ -137,9 +216,52 @@ impl Router {
         &self.path
     }
 
+    /// Is it safe to replace the rest of the path right now? Only while heading somewhere fixed,
+    /// not in the middle of an uber-turn or looking for parking.
+    pub fn can_reroute(&self) -> bool {
+        let heading_somewhere = match self.goal {
+            Goal::EndAtBorder { .. } => true,
+            Goal::ParkNearBuilding {
+                started_looking, ..
+            } => !started_looking,
+            Goal::BikeThenStop { .. } | Goal::FollowTransitRoute { .. } | Goal::External { .. } => {
+                false
+            }
+        };
+        heading_somewhere
+            && self.path.isnt_last_step()
+            && self.path.currently_inside_ut().is_none()
+            && self.path.about_to_start_ut().is_none()
+    }
+
+    /// Replace the rest of the path. The new path has to start with the current step and end in
+    /// the same place.
+    pub fn reroute(&mut self, new_path: Path, map: &Map) {
+        self.path.reroute(new_path, map);
+    }
+
+    pub fn routing_params(&self) -> Option<&RoutingParams> {
+        self.routing_params.as_deref()
+    }
+
+    pub fn set_routing_params(&mut self, params: RoutingParams) {
+        self.routing_params = Some(Box::new(params));
+    }
+
+    /// Remember that the current path steers around these incidents.
+    pub fn avoiding_incidents(&mut self, ids: BTreeSet<IncidentID>) {
+        self.avoiding.extend(ids);
+    }
+
+    /// An incident ended. Returns true if the current path was steering around it.
+    pub fn incident_ended(&mut self, id: IncidentID) -> bool {
+        self.avoiding.remove(&id)
+    }
+
     /// Returns the step just finished
     pub fn advance(
         &mut self,
+        now: Time,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
         map: &Map,
@@ -150,6 +272,7 @@ impl Router {
         if self.last_step() {
             // Do this to trigger the side-effect of looking for parking.
             self.maybe_handle_end(
+                now,
                 Distance::ZERO,
                 vehicle,
                 parking,
@@ -177,6 +300,7 @@ impl Router {
     /// step.
     pub fn maybe_handle_end(
         &mut self,
+        now: Time,
         front: Distance,
         vehicle: &Vehicle,
         parking: &ParkingSimState,
@@ -200,6 +324,8 @@ impl Router {
                 ref mut stuck_end_dist,
                 target,
                 ref mut started_looking,
+                ref mut heading_to_charger,
+                leave_at,
             } => {
                 if let Some(d) = stuck_end_dist {
                     if *d == front {
@@ -214,6 +340,13 @@ impl Router {
                     None => true,
                 };
                 if need_new_spot {
+                    if *heading_to_charger {
+                        *heading_to_charger = false;
+                        events.push(Event::EVChargerFull(
+                            vehicle.id,
+                            charger_location(spot.unwrap().0).0,
+                        ));
+                    }
                     *started_looking = true;
                     let current_lane = self.path.current_step().as_lane();
                     let candidates = parking.get_all_free_spots(
@@ -222,26 +355,36 @@ impl Router {
                         target,
                         map,
                     );
-                    let best =
-                        if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map) {
-                            if driving_pos.lane() == current_lane {
-                                let target_dist = driving_pos.dist_along();
-                                // Closest to the building
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
-                            } else {
-                                // Closest to the road endpoint, I guess
-                                candidates
-                                    .into_iter()
-                                    .min_by_key(|(_, pos)| pos.dist_along())
-                            }
+                    let best = if !map.get_parking_policy().is_empty() {
+                        // Weigh the walk, fees, and time limits
+                        candidates
+                            .into_iter()
+                            .filter_map(|(spot, pos)| {
+                                spot_cost(parking, spot, target, leave_at, now, map)
+                                    .map(|cost| (cost, spot, pos))
+                            })
+                            .min_by_key(|(cost, _, _)| *cost)
+                            .map(|(_, spot, pos)| (spot, pos))
+                    } else if let Some((driving_pos, _)) = map.get_b(target).driving_connection(map)
+                    {
+                        if driving_pos.lane() == current_lane {
+                            let target_dist = driving_pos.dist_along();
+                            // Closest to the building
+                            candidates
+                                .into_iter()
+                                .min_by_key(|(_, pos)| (pos.dist_along() - target_dist).abs())
                         } else {
                             // Closest to the road endpoint, I guess
                             candidates
                                 .into_iter()
                                 .min_by_key(|(_, pos)| pos.dist_along())
-                        };
+                        }
+                    } else {
+                        // Closest to the road endpoint, I guess
+                        candidates
+                            .into_iter()
+                            .min_by_key(|(_, pos)| pos.dist_along())
+                    };
                     if let Some((new_spot, new_pos)) = best {
                         if let Some((t, p)) = trip_and_person {
                             events.push(Event::TripPhaseStarting(
@@ -259,8 +402,15 @@ impl Router {
                         assert!(new_pos.dist_along() >= front);
                         *spot = Some((new_spot, new_pos.dist_along()));
                     } else {
-                        if let Some((new_path_steps, new_spot, new_pos)) =
-                            parking.path_to_free_parking_spot(current_lane, vehicle, target, map)
+                        if let Some((new_path_steps, new_spot, new_pos)) = parking
+                            .path_to_free_parking_spot(
+                                current_lane,
+                                vehicle,
+                                target,
+                                leave_at,
+                                now,
+                                map,
+                            )
                         {
                             assert!(!new_path_steps.is_empty());
                             for step in new_path_steps {
@@ -318,6 +468,14 @@ impl Router {
                     None
                 }
             }
+            Goal::External { end_dist, vanish } => {
+                // Changing lanes can overshoot a little
+                if vanish && front >= end_dist {
+                    Some(ActionAtEnd::ExternalVanish)
+                } else {
+                    None
+                }
+            }
         }
     }
 
@@ -325,6 +483,7 @@ impl Router {
         &mut self,
         queues: &HashMap<Traversable, Queue>,
         map: &Map,
+        incidents: &IncidentSimState,
         handle_uber_turns: bool,
     ) {
         // if we're already in the uber-turn, we're committed, but if we're about to enter one, lock
@@ -332,6 +491,10 @@ impl Router {
         if handle_uber_turns && self.path.currently_inside_ut().is_some() {
             return;
         }
+        // External controllers pick their own lanes
+        if self.is_external() {
+            return;
+        }
 
         let mut segment = 0;
         loop {
@@ -368,6 +531,8 @@ impl Router {
                 // 4) Are there lots of vehicles stacked up in one lane?
                 // 5) Are we changing lanes?
                 //
+                // Before all of that, avoid lanes closed by an incident.
+                //
                 // A linear combination of these penalties is hard to reason about. We mostly
                 // make our choice based on each penalty in order, breaking ties by moving onto the
                 // next thing. With one exception: To produce more realistic behavior, we combine
@@ -378,7 +543,13 @@ impl Router {
                     slow_lane = 0;
                 }
 
-                (lt, bike, slow_lane, vehicles + lc)
+                (
+                    incidents.is_lane_closed(lane),
+                    lt,
+                    bike,
+                    slow_lane,
+                    vehicles + lc,
+                )
             };
 
             // Look for other candidates, and assign a cost to each.
@@ -475,6 +646,16 @@ impl Router {
     pub fn confirm_lanechange(&mut self, to: LaneID, map: &Map) {
         // No assertions, blind trust!
         self.path.modify_step(0, PathStep::Lane(to), map);
+        if self.path.is_last_step() {
+            // Only externally controlled vehicles change lanes before choosing their next turn
+            if let Goal::External {
+                ref mut end_dist, ..
+            } = self.goal
+            {
+                *end_dist = end_dist.min(map.get_l(to).length());
+            }
+            return;
+        }
         let mut turn = match self.path.get_steps()[1] {
             PathStep::Turn(t) => t,
             _ => unreachable!(),
@@ -492,6 +673,75 @@ impl Router {
         }
     }
 
+    pub fn is_external(&self) -> bool {
+        matches!(self.goal, Goal::External { .. })
+    }
+
+    /// For externally controlled vehicles, continue through a turn at the end of the path
+    pub fn external_turn(&mut self, t: TurnID, vehicle: &Vehicle, map: &Map) -> Result<()> {
+        let last_lane = match self.path.get_steps().back() {
+            Some(PathStep::Lane(l)) => *l,
+            _ => unreachable!(),
+        };
+        if t.src != last_lane {
+            bail!(
+                "{} doesn't start from {}, the last planned lane",
+                t,
+                last_lane
+            );
+        }
+        if map.maybe_get_t(t).is_none() {
+            bail!("{} doesn't exist", t);
+        }
+        if !vehicle
+            .vehicle_type
+            .to_constraints()
+            .can_use(map.get_l(t.dst), map)
+        {
+            bail!("{} can't use {}", vehicle.id, t.dst);
+        }
+        match self.goal {
+            Goal::External {
+                ref mut end_dist,
+                vanish,
+            } => {
+                if vanish {
+                    bail!("{} is already planning to vanish", vehicle.id);
+                }
+                *end_dist = map.get_l(t.dst).length();
+            }
+            _ => unreachable!(),
+        }
+        self.path.add(PathStep::Turn(t), map);
+        self.path.add(PathStep::Lane(t.dst), map);
+        Ok(())
+    }
+
+    /// For externally controlled vehicles, change where to stop on the last lane. The caller
+    /// checks the distance.
+    pub fn external_stop_at(&mut self, dist: Distance) {
+        if let Goal::External {
+            ref mut end_dist, ..
+        } = self.goal
+        {
+            *end_dist = dist;
+        }
+    }
+
+    /// Where an externally controlled vehicle will stop along its last planned lane
+    pub fn get_external_stop(&self) -> Distance {
+        match self.goal {
+            Goal::External { end_dist, .. } => end_dist,
+            _ => unreachable!(),
+        }
+    }
+
+    pub fn external_vanish(&mut self) {
+        if let Goal::External { ref mut vanish, .. } = self.goal {
+            *vanish = true;
+        }
+    }
+
     pub fn get_parking_spot_goal(&self) -> Option<&ParkingSpot> {
         match self.goal {
             Goal::ParkNearBuilding { ref spot, .. } => spot.as_ref().map(|(s, _)| s),
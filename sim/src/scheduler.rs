@@ -8,7 +8,8 @@ use geom::{Duration, Histogram, Time};
 use map_model::{IntersectionID, TransitRouteID};
 
 use crate::{
-    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, PedestrianID, StartTripArgs, TripID,
+    pandemic, AgentID, CarID, CreateCar, CreatePedestrian, IncidentID, PedestrianID, StartTripArgs,
+    TripID,
 };
 
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -26,6 +27,8 @@ pub(crate) enum Command {
     Pandemic(pandemic::Cmd),
     /// The Time is redundant, just used to dedupe commands
     StartBus(TransitRouteID, Time),
+    StartIncident(IncidentID),
+    EndIncident(IncidentID),
 }
 
 impl Command {
@@ -33,7 +36,7 @@ impl Command {
         match id {
             AgentID::Car(c) => Command::UpdateCar(c),
             AgentID::Pedestrian(p) => Command::UpdatePed(p),
-            AgentID::BusPassenger(_, _) => unreachable!(),
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => unreachable!(),
         }
     }
 
@@ -49,6 +52,8 @@ impl Command {
             Command::Callback(_) => CommandType::Callback,
             Command::Pandemic(ref p) => CommandType::Pandemic(p.clone()),
             Command::StartBus(r, t) => CommandType::StartBus(*r, *t),
+            Command::StartIncident(id) => CommandType::Incident(*id, true),
+            Command::EndIncident(id) => CommandType::Incident(*id, false),
         }
     }
 
@@ -64,6 +69,7 @@ impl Command {
             Command::Callback(_) => SimpleCommandType::Callback,
             Command::Pandemic(_) => SimpleCommandType::Pandemic,
             Command::StartBus(_, _) => SimpleCommandType::StartBus,
+            Command::StartIncident(_) | Command::EndIncident(_) => SimpleCommandType::Incident,
         }
     }
 }
@@ -80,6 +86,8 @@ enum CommandType {
     Callback,
     Pandemic(pandemic::Cmd),
     StartBus(TransitRouteID, Time),
+    /// True for starting
+    Incident(IncidentID, bool),
 }
 
 /// A more compressed form of CommandType, just used for keeping stats on event processing.
@@ -93,6 +101,7 @@ enum SimpleCommandType {
     Callback,
     Pandemic,
     StartBus,
+    Incident,
 }
 
 /// The priority queue driving the discrete event simulation. Different pieces of the simulation
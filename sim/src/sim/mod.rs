@@ -1,6 +1,6 @@
 // This file has a jumbled mess of queries, setup, and mutating methods.
 
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeMap, BTreeSet, HashSet};
 
 use anyhow::Result;
 use instant::Instant;
@@ -14,19 +14,21 @@ use abstutil::{prettyprint_usize, serialized_size_bytes, Timer};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
     BuildingID, IntersectionID, LaneID, Map, ParkingLotID, Path, PathConstraints, PathRequest,
-    Position, TransitRoute, Traversable,
+    Position, RoutingParams, TransitRoute, TransitRouteID, Traversable,
 };
-use synthpop::OrigPersonID;
+use synthpop::{CarpoolID, CarpoolRole, OrigPersonID};
 
 pub use self::queries::{AgentProperties, DelayCause};
 // TODO Super weird for both of these to wind up here
 pub use self::scenario::{count_parked_cars_per_bldg, rand_dist};
 use crate::{
-    AgentID, AlertLocation, Analytics, CarID, Command, CreateCar, DrivingSimState, Event,
-    IntersectionSimState, PandemicModel, ParkedCar, ParkingSim, ParkingSimState, ParkingSpot,
-    Person, PersonID, Router, Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder,
-    TransitSimState, TripID, TripInfo, TripManager, TripPhaseType, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
+    AgentID, AlertLocation, Analytics, CarID, ChargingSimState, Command, CreateCar, Disruption,
+    DrivingSimState, EmissionsTracker, Event, ExternalDecision, Incident, IncidentID,
+    IncidentSimState, IntersectionSimState, ObservedTravelTimes, PandemicModel, ParkedCar,
+    ParkingSim, ParkingSimState, ParkingSpot, Person, PersonID, PhysicalEmissionModel, Router,
+    Scheduler, SidewalkPOI, SidewalkSpot, StartTripArgs, TrafficRecorder, TransitSimState,
+    TravelTimes, TripID, TripInfo, TripManager, TripPhaseType, Vehicle, VehicleSpec, VehicleType,
+    WalkingSimState, BUS_LENGTH, LIGHT_RAIL_LENGTH, MIN_CAR_LENGTH,
 };
 
 mod queries;
@@ -59,12 +61,58 @@ pub struct Sim {
     highlighted_people: Option<BTreeSet<PersonID>>,
 
     analytics: Analytics,
+    travel_times: TravelTimes,
+    emissions: EmissionsTracker,
+    charging: ChargingSimState,
+    incidents: IncidentSimState,
     // This is created interactively, and there's no reason to preserve one for savestates.
     #[serde(skip_serializing, skip_deserializing)]
     recorder: Option<TrafficRecorder>,
 
     #[serde(skip_serializing, skip_deserializing)]
     alerts: AlertHandler,
+    // Only kept when somebody asked to stream events
+    #[serde(skip_serializing, skip_deserializing)]
+    event_subscribers: EventSubscribers,
+}
+
+/// Events that each subscriber hasn't taken yet. Copies of a Sim start without any subscribers.
+#[derive(Default)]
+struct EventSubscribers {
+    next_id: usize,
+    queues: BTreeMap<usize, Vec<(Time, Event)>>,
+}
+
+impl Clone for EventSubscribers {
+    fn clone(&self) -> EventSubscribers {
+        EventSubscribers::default()
+    }
+}
+
+impl EventSubscribers {
+    fn subscribe(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.queues.insert(id, Vec::new());
+        id
+    }
+
+    fn unsubscribe(&mut self, subscriber: usize) {
+        self.queues.remove(&subscriber);
+    }
+
+    fn take(&mut self, subscriber: usize) -> Vec<(Time, Event)> {
+        self.queues
+            .get_mut(&subscriber)
+            .map(std::mem::take)
+            .unwrap_or_default()
+    }
+
+    fn publish(&mut self, time: Time, ev: &Event) {
+        for queue in self.queues.values_mut() {
+            queue.push((time, ev.clone()));
+        }
+    }
 }
 
 pub(crate) struct Ctx<'a> {
@@ -75,6 +123,12 @@ pub(crate) struct Ctx<'a> {
     /// If present, live map edits are being processed, and the agents specified are in the process
     /// of being deleted. Some regular work should maybe be skipped.
     pub handling_live_edits: Option<BTreeSet<AgentID>>,
+    /// Used to route cars, possibly accounting for congestion
+    pub travel_times: &'a mut TravelTimes,
+    /// Used by electric cars to find a charger
+    pub charging: &'a ChargingSimState,
+    /// Temporary disruptions that vehicles must respect
+    pub incidents: &'a IncidentSimState,
 }
 
 /// Options controlling the traffic simulation.
@@ -127,6 +181,36 @@ pub struct SimOptions {
     /// quickly.
     #[structopt(long)]
     pub skip_analytics: bool,
+    /// Route cars using how long it actually took to cross each road recently, instead of
+    /// assuming free-flow speeds. Travel times observed during previous runs can also be provided
+    /// with `Sim::set_prior_travel_times`, so that trips departing at a certain hour avoid the
+    /// congestion seen around then.
+    #[structopt(long)]
+    pub congestion_aware_routing: bool,
+    /// When a car enters a congested road, or sees one shortly ahead, look for a faster route to
+    /// its destination. Implies `congestion_aware_routing`.
+    #[structopt(long)]
+    pub reroute_on_congestion: bool,
+    /// What percent of cars in a scenario are electric. Their battery drains as they drive, and
+    /// when it's low, drivers try to park at a charger near their destination. Chargers come from
+    /// the map's parking policy.
+    #[structopt(long, default_value = "0")]
+    pub pct_electric_cars: usize,
+    /// Model more of what happens within a lane. Vehicles move to an adjacent lane mid-block when
+    /// it has a much shorter queue, and overtake buses at stops and cars parking, not just slow
+    /// cyclists. Vehicles starting from a stop take longer to cross the next lane or turn, as if
+    /// accelerating at a constant rate. Followers can't go faster than their leader, so one
+    /// vehicle pulling away from a stop holds back everybody behind it.
+    #[structopt(long)]
+    pub lane_level_dynamics: bool,
+    /// Let buses and trains approaching a traffic signal extend the green or cut the red short.
+    /// Emergency vehicles always preempt signals.
+    #[structopt(long)]
+    pub transit_signal_priority: bool,
+    /// The path to a JSON file listing incidents (`Vec<sim::Incident>`) that temporarily block
+    /// lanes, slow down roads, or close intersections
+    #[structopt(long)]
+    pub incidents: Option<String>,
 }
 
 impl SimOptions {
@@ -143,6 +227,12 @@ impl SimOptions {
             infinite_parking: false,
             disable_turn_conflicts: false,
             skip_analytics: false,
+            congestion_aware_routing: false,
+            reroute_on_congestion: false,
+            pct_electric_cars: 0,
+            lane_level_dynamics: false,
+            transit_signal_priority: false,
+            incidents: None,
         }
     }
 }
@@ -206,6 +296,10 @@ impl Sim {
             opts.allow_block_the_box = true;
         }
 
+        let travel_times = TravelTimes::new(&opts);
+        let emissions = EmissionsTracker::new(&opts);
+        let charging = ChargingSimState::new(&opts);
+        let incidents = IncidentSimState::new(&opts, &mut scheduler);
         Sim {
             driving: DrivingSimState::new(map, &opts),
             parking: ParkingSimState::new(map, opts.infinite_parking, &mut timer),
@@ -225,7 +319,12 @@ impl Sim {
             alerts: opts.alerts,
 
             analytics: Analytics::new(!opts.skip_analytics),
+            travel_times,
+            emissions,
+            charging,
+            incidents,
             recorder: None,
+            event_subscribers: EventSubscribers::default(),
         }
     }
 
@@ -236,18 +335,30 @@ impl Sim {
         timer: &mut Timer,
     ) {
         timer.start_iter("spawn trips", input.len());
+        let mut carpools: BTreeMap<CarpoolID, Vec<TripID>> = BTreeMap::new();
         for (p, info, args) in input {
             timer.next();
 
             let trip = self.trips.new_trip(p, info.clone());
+            if let Some(carpool) = info.carpool {
+                carpools
+                    .entry(carpool.id)
+                    .or_insert_with(Vec::new)
+                    .push(trip);
+            }
             // This might be immediately true due to ScenarioModifiers
             if let Some(msg) = info.cancellation_reason {
                 self.trips.cancel_unstarted_trip(trip, msg);
+            } else if info.carpool.map(|c| c.role) == Some(CarpoolRole::Passenger) {
+                // The driver starts this trip when they arrive to pick up the passenger
             } else {
                 self.scheduler
                     .push(info.departure, Command::StartTrip(trip, args));
             }
         }
+        for (_, trips) in carpools {
+            self.trips.new_carpool(trips);
+        }
 
         if let Some(ref mut m) = self.pandemic {
             m.initialize(self.trips.get_all_people(), &mut self.scheduler);
@@ -256,6 +367,23 @@ impl Sim {
         self.dispatch_events(Vec::new(), map);
     }
 
+    /// Use travel times observed in a previous run to route cars. Only has an effect when
+    /// congestion-aware routing is enabled. Call this before the simulation starts.
+    pub fn set_prior_travel_times(&mut self, prior: ObservedTravelTimes) {
+        self.travel_times.set_prior(prior);
+    }
+
+    /// Route this trip's vehicle with custom params instead of the map's, including whenever it
+    /// reroutes. Call this before the trip starts.
+    pub fn set_trip_routing_params(&mut self, trip: TripID, params: RoutingParams) {
+        self.trips.set_routing_params(trip, params);
+    }
+
+    /// Change how vehicle emissions are calculated. Call this before the simulation starts.
+    pub fn set_emission_model(&mut self, model: PhysicalEmissionModel) {
+        self.emissions.set_model(model);
+    }
+
     pub fn get_free_onstreet_spots(&self, l: LaneID) -> Vec<ParkingSpot> {
         self.parking.get_free_onstreet_spots(l)
     }
@@ -287,6 +415,7 @@ impl Sim {
             vehicle_type: VehicleType::Car,
             length: MIN_CAR_LENGTH,
             max_speed: None,
+            electric: false,
         };
         let driving_lane = map.find_driving_lane_near_building(b);
 
@@ -300,9 +429,14 @@ impl Sim {
         {
             *spot
         } else {
-            let (_, spot, _) =
-                self.parking
-                    .path_to_free_parking_spot(driving_lane, &vehicle, b, map)?;
+            let (_, spot, _) = self.parking.path_to_free_parking_spot(
+                driving_lane,
+                &vehicle,
+                b,
+                None,
+                self.time,
+                map,
+            )?;
             spot
         };
 
@@ -317,9 +451,21 @@ impl Sim {
         ped_speed: Speed,
         vehicle_specs: Vec<VehicleSpec>,
     ) -> &Person {
-        self.trips.new_person(orig_id, ped_speed, vehicle_specs)
+        let id = self.trips.new_person(orig_id, ped_speed, vehicle_specs).id;
+        let person = self.trips.get_person(id).unwrap();
+        for vehicle in &person.vehicles {
+            self.charging.add_vehicle(vehicle);
+        }
+        person
     }
-    pub(crate) fn seed_parked_car(&mut self, vehicle: Vehicle, spot: ParkingSpot) {
+    /// The car belongs to somebody living at `home`.
+    pub(crate) fn seed_parked_car(
+        &mut self,
+        vehicle: Vehicle,
+        spot: ParkingSpot,
+        home: BuildingID,
+    ) {
+        self.parking.set_home(vehicle.id, home);
         self.parking.reserve_spot(spot, vehicle.id);
         self.parking.add_parked_car(ParkedCar {
             vehicle,
@@ -334,6 +480,12 @@ impl Sim {
         }
     }
 
+    /// Override how many passengers fit on each vehicle serving a route. None uses the default
+    /// capacity for buses or trains. Only affects vehicles spawned after this call.
+    pub fn set_transit_route_capacity(&mut self, route: TransitRouteID, capacity: Option<usize>) {
+        self.transit.set_route_capacity(route, capacity);
+    }
+
     fn start_bus(&mut self, route: &TransitRoute, map: &Map) {
         // Spawn one bus for the first leg.
         let path = self.transit.create_empty_route(route, map);
@@ -349,6 +501,7 @@ impl Sim {
             vehicle_type,
             length,
             max_speed: None,
+            electric: false,
         }
         .make(
             CarID {
@@ -357,6 +510,7 @@ impl Sim {
             },
             None,
         );
+        self.transit.bus_scheduled(vehicle.id, route.id, self.time);
 
         self.scheduler.push(
             self.time,
@@ -441,6 +595,9 @@ impl Sim {
             scheduler: &mut self.scheduler,
             map,
             handling_live_edits: None,
+            travel_times: &mut self.travel_times,
+            charging: &self.charging,
+            incidents: &self.incidents,
         };
 
         match cmd {
@@ -470,13 +627,16 @@ impl Sim {
                     }
                 }
                 if !ok {
-                    self.trips.cancel_trip(
-                        self.time,
-                        create_car.trip_and_person.unwrap().0,
-                        "path is no longer valid after map edits".to_string(),
-                        Some(create_car.vehicle),
-                        &mut ctx,
-                    );
+                    // Externally controlled cars have no trip; they just don't appear
+                    if let Some((trip, _)) = create_car.trip_and_person {
+                        self.trips.cancel_trip(
+                            self.time,
+                            trip,
+                            "path is no longer valid after map edits".to_string(),
+                            Some(create_car.vehicle),
+                            &mut ctx,
+                        );
+                    }
                 } else {
                     // create_car contains a Path, which is expensive to clone. We need different
                     // parts of create_car after attempting start_car_on_lane.
@@ -547,7 +707,7 @@ impl Sim {
                             self.parking.remove_parked_car(parked_car);
                         }
                         if let Some(route) = maybe_route {
-                            self.transit.bus_created(id, route);
+                            self.transit.bus_created(self.time, id, route);
                         }
                         self.analytics
                             .record_demand(self.driving.get_path(id).unwrap(), map);
@@ -632,7 +792,20 @@ impl Sim {
                     .handle_cmd(self.time, cmd, &mut self.scheduler);
             }
             Command::StartBus(r, _) => {
-                self.start_bus(map.get_tr(r), map);
+                // The route may have been deleted by live map edits
+                if let Some(route) = map.maybe_get_tr(r) {
+                    self.start_bus(route, map);
+                }
+            }
+            Command::StartIncident(id) => {
+                self.incidents.start(id, map);
+                let rerouted = self.incidents_changed(None, map);
+                events.push(Event::IncidentStarted(id, rerouted));
+            }
+            Command::EndIncident(id) => {
+                let disruption = self.incidents.end(id, map);
+                let rerouted = self.incidents_changed(Some((id, disruption)), map);
+                events.push(Event::IncidentEnded(id, rerouted));
             }
         }
 
@@ -657,6 +830,25 @@ impl Sim {
                 r.handle_event(self.time, &ev, map, &self.driving);
             }
 
+            self.travel_times.handle_event(self.time, &ev, map);
+            if let Some((car, trip, road, emissions)) =
+                self.emissions
+                    .handle_event(self.time, &ev, &self.charging, &self.driving, map)
+            {
+                self.charging.consume(car, emissions.energy_kwh);
+                self.analytics
+                    .record_emissions(self.time, trip, road, emissions);
+            }
+            // Charging happens as cars park and leave, so it has to see those events after the
+            // energy used to get there is counted.
+            if let Some(charging_ev) =
+                self.charging
+                    .handle_event(self.time, &ev, map.get_parking_policy())
+            {
+                self.event_subscribers.publish(self.time, &charging_ev);
+                self.analytics.event(charging_ev, self.time, map);
+            }
+            self.event_subscribers.publish(self.time, &ev);
             self.analytics.event(ev, self.time, map);
         }
     }
@@ -829,6 +1021,141 @@ impl Sim {
     }
 }
 
+// Externally controlled vehicles
+impl Sim {
+    /// Start a car or bike whose movement comes from an external controller. It appears at
+    /// `start` once there's room, then drives to the end of that lane and waits for decisions.
+    ///
+    /// Emergency vehicles preempt traffic signals, and other vehicles yield to them at
+    /// intersections.
+    pub fn spawn_external_car(
+        &mut self,
+        spec: VehicleSpec,
+        start: Position,
+        emergency: bool,
+        map: &Map,
+    ) -> Result<CarID> {
+        if !matches!(spec.vehicle_type, VehicleType::Car | VehicleType::Bike) {
+            bail!("Only cars and bikes can be controlled externally");
+        }
+        if emergency && spec.vehicle_type != VehicleType::Car {
+            bail!("Only cars can be emergency vehicles");
+        }
+        if spec.electric && spec.vehicle_type != VehicleType::Car {
+            bail!("Only cars can be electric");
+        }
+        let lane = map
+            .maybe_get_l(start.lane())
+            .ok_or_else(|| anyhow!("{} doesn't exist", start.lane()))?;
+        let constraints = spec.vehicle_type.to_constraints();
+        if !constraints.can_use(lane, map) {
+            bail!("A {:?} can't use {}", spec.vehicle_type, lane.id);
+        }
+        if start.dist_along() > lane.length() {
+            bail!("{} is only {} long", lane.id, lane.length());
+        }
+
+        let vehicle_type = spec.vehicle_type;
+        let vehicle = spec.make(
+            CarID {
+                id: self.trips.new_car_id(),
+                vehicle_type,
+            },
+            None,
+        );
+        let id = vehicle.id;
+        if emergency {
+            self.intersections.add_emergency_vehicle(id);
+        }
+        self.charging.add_vehicle(&vehicle);
+        let path = Path::one_step(
+            PathRequest::vehicle(start, Position::end(lane.id, map), constraints),
+            map,
+        );
+        self.scheduler.push(
+            self.time,
+            Command::SpawnCar(
+                CreateCar {
+                    router: Router::external(id, path),
+                    vehicle,
+                    maybe_parked_car: None,
+                    trip_and_person: None,
+                    maybe_route: None,
+                },
+                true,
+            ),
+        );
+        Ok(id)
+    }
+
+    /// Turn any car currently on the road into an emergency vehicle, like an ambulance answering
+    /// a call. It preempts traffic signals, and other vehicles yield to it at intersections, until
+    /// it parks or leaves the map.
+    pub fn make_emergency_vehicle(&mut self, id: CarID) -> Result<()> {
+        if id.vehicle_type != VehicleType::Car {
+            bail!("Only cars can be emergency vehicles");
+        }
+        if !self.driving.does_car_exist(id) {
+            bail!("{} isn't driving right now", id);
+        }
+        self.intersections.add_emergency_vehicle(id);
+        Ok(())
+    }
+
+    /// Carry out a decision for an externally controlled car. This happens immediately, but the
+    /// car only moves when the simulation advances.
+    pub fn external_decision(
+        &mut self,
+        id: CarID,
+        decision: ExternalDecision,
+        map: &Map,
+    ) -> Result<()> {
+        let mut ctx = Ctx {
+            parking: &mut self.parking,
+            intersections: &mut self.intersections,
+            scheduler: &mut self.scheduler,
+            map,
+            handling_live_edits: None,
+            travel_times: &mut self.travel_times,
+            charging: &self.charging,
+            incidents: &self.incidents,
+        };
+        self.driving
+            .external_decision(id, decision, self.time, &mut ctx)
+    }
+}
+
+// Incidents
+impl Sim {
+    /// Schedule a temporary disruption. If it should've already started, it starts immediately.
+    pub fn add_incident(&mut self, incident: Incident) -> Result<IncidentID> {
+        self.incidents.add(incident, self.time, &mut self.scheduler)
+    }
+
+    /// Every incident added so far, and whether it's happening right now
+    pub fn get_incidents(&self) -> Vec<(IncidentID, &Incident, bool)> {
+        self.incidents.get_all()
+    }
+
+    /// An incident just started or ended. Reroute the cars it affects and returns how many changed
+    /// their route.
+    fn incidents_changed(&mut self, ended: Option<(IncidentID, Disruption)>, map: &Map) -> usize {
+        self.travel_times.incidents_changed();
+        let mut ctx = Ctx {
+            parking: &mut self.parking,
+            intersections: &mut self.intersections,
+            scheduler: &mut self.scheduler,
+            map,
+            handling_live_edits: None,
+            travel_times: &mut self.travel_times,
+            charging: &self.charging,
+            incidents: &self.incidents,
+        };
+        self.driving
+            .handle_incidents_changed(ended, self.time, &mut ctx)
+    }
+}
+
 // Live edits
 impl Sim {
     pub fn handle_live_edited_traffic_signals(&mut self, map: &Map) {
@@ -840,20 +1167,26 @@ impl Sim {
     /// (trips cancelled, parked cars displaced).
     pub fn handle_live_edits(&mut self, map: &Map, timer: &mut Timer) -> (usize, usize) {
         self.edits_name = map.get_edits().edits_name.clone();
+        self.travel_times.map_edited();
 
-        let (affected, num_parked_cars) = self.find_trips_affected_by_live_edits(map, timer);
+        let (mut affected, num_parked_cars) = self.find_trips_affected_by_live_edits(map, timer);
+        // Anybody riding or planning to ride a changed transit route
+        let affected_routes = self.transit.find_routes_affected_by_live_edits(map);
+        affected.extend(self.trips.find_trips_riding_transit(&affected_routes));
         let num_trips_cancelled = affected.len();
         let affected_agents: BTreeSet<AgentID> = affected.iter().map(|(a, _)| *a).collect();
 
         // V1: Just cancel every trip crossing an affected area.
         // (V2 is probably rerouting everyone, only cancelling when that fails)
-        // TODO If we delete a bus, deal with all its passengers
         let mut ctx = Ctx {
             parking: &mut self.parking,
             intersections: &mut self.intersections,
             scheduler: &mut self.scheduler,
             map,
             handling_live_edits: Some(affected_agents),
+            travel_times: &mut self.travel_times,
+            charging: &self.charging,
+            incidents: &self.incidents,
         };
         for (agent, trip) in affected {
             match agent {
@@ -881,7 +1214,42 @@ impl Sim {
                     self.trips
                         .trip_abruptly_cancelled(trip, AgentID::Pedestrian(ped));
                 }
-                AgentID::BusPassenger(_, _) => unreachable!(),
+                AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => {
+                    // The vehicle itself is deleted below
+                    self.trips.cancel_trip(
+                        self.time,
+                        trip,
+                        "map edited without reset".to_string(),
+                        None,
+                        &mut ctx,
+                    );
+                    self.trips.trip_abruptly_cancelled(trip, agent);
+                }
+            }
+        }
+
+        let (delete_vehicles, cancel_spawns) =
+            self.transit.handle_live_edits(&affected_routes, map);
+        for bus in delete_vehicles {
+            self.driving.delete_car(bus, self.time, &mut ctx);
+        }
+        for bus in cancel_spawns {
+            // This also cancels a pending SpawnCar
+            ctx.scheduler.cancel(Command::UpdateCar(bus));
+            self.driving.cancel_waiting_to_spawn(bus);
+        }
+        // Start vehicles for routes that were just created or rerouted. Re-scheduling a route
+        // that's already scheduled is harmless.
+        for (id, orig) in &map.get_edits().original_transit_routes {
+            if !(orig.is_none() || affected_routes.contains(id)) {
+                continue;
+            }
+            if let Some(route) = map.maybe_get_tr(*id) {
+                for t in &route.spawn_times {
+                    if *t > self.time {
+                        ctx.scheduler.update(*t, Command::StartBus(route.id, *t));
+                    }
+                }
             }
         }
 
@@ -969,6 +1337,9 @@ impl Sim {
                 scheduler: &mut self.scheduler,
                 map,
                 handling_live_edits: None,
+                travel_times: &mut self.travel_times,
+                charging: &self.charging,
+                incidents: &self.incidents,
             };
             let vehicle = self.driving.delete_car(id, self.time, &mut ctx);
             self.trips.cancel_trip(
@@ -1025,9 +1396,75 @@ impl Sim {
     }
 }
 
+// Streaming events
+impl Sim {
+    /// Start keeping every event as it happens for a new subscriber, who should periodically drain
+    /// them with `take_events` and call `unsubscribe_events` when done. Each subscriber gets its
+    /// own copy of every event.
+    pub fn subscribe_events(&mut self) -> usize {
+        self.event_subscribers.subscribe()
+    }
+
+    /// Stop keeping events for this subscriber.
+    pub fn unsubscribe_events(&mut self, subscriber: usize) {
+        self.event_subscribers.unsubscribe(subscriber);
+    }
+
+    /// Returns everything that happened since this subscriber last called, in order. Always empty
+    /// for unknown subscribers.
+    pub fn take_events(&mut self, subscriber: usize) -> Vec<(Time, Event)> {
+        self.event_subscribers.take(subscriber)
+    }
+}
+
 // Managing highlighted people
 impl Sim {
     pub fn set_highlighted_people(&mut self, people: BTreeSet<PersonID>) {
         self.highlighted_people = Some(people);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AlertLocation;
+
+    #[test]
+    fn event_subscribers() {
+        let mut subscribers = EventSubscribers::default();
+        let alert = |msg: &str| Event::Alert(AlertLocation::Nil, msg.to_string());
+
+        let a = subscribers.subscribe();
+        subscribers.publish(Time::START_OF_DAY, &alert("first"));
+        let b = subscribers.subscribe();
+        subscribers.publish(Time::START_OF_DAY, &alert("second"));
+
+        // Taking events doesn't affect other subscribers
+        assert_eq!(
+            subscribers.take(a),
+            vec![
+                (Time::START_OF_DAY, alert("first")),
+                (Time::START_OF_DAY, alert("second"))
+            ]
+        );
+        assert!(subscribers.take(a).is_empty());
+        assert_eq!(
+            subscribers.take(b),
+            vec![(Time::START_OF_DAY, alert("second"))]
+        );
+
+        // Neither does unsubscribing
+        subscribers.unsubscribe(a);
+        subscribers.publish(Time::START_OF_DAY, &alert("third"));
+        assert!(subscribers.take(a).is_empty());
+        assert_eq!(
+            subscribers.take(b),
+            vec![(Time::START_OF_DAY, alert("third"))]
+        );
+
+        // Copies don't keep events for the original's subscribers
+        let mut copy = subscribers.clone();
+        copy.publish(Time::START_OF_DAY, &alert("fourth"));
+        assert!(copy.take(b).is_empty());
+    }
+}
@@ -1,6 +1,7 @@
 //! All sorts of read-only queries about a simulation
 
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::Serialize;
 use std::collections::{BTreeMap, BTreeSet};
 
@@ -15,8 +16,9 @@ use synthpop::{OrigPersonID, Scenario, TripMode};
 use crate::analytics::SlidingWindow;
 use crate::{
     AgentID, AgentType, Analytics, CarID, CommutersVehiclesCounts, DrawCarInput, DrawPedCrowdInput,
-    DrawPedestrianInput, PandemicModel, ParkedCar, ParkingSim, PedestrianID, Person, PersonID,
-    PersonState, Sim, TripEndpoint, TripID, TripInfo, TripResult, UnzoomedAgent, VehicleType,
+    DrawPedestrianInput, ExternalCarState, ObservedTravelTimes, PandemicModel, ParkedCar,
+    ParkingSim, PedestrianID, Person, PersonID, PersonState, Sim, TripEndpoint, TripID, TripInfo,
+    TripResult, UnzoomedAgent, VehicleType,
 };
 
 // TODO Many of these just delegate to an inner piece. This is unorganized and hard to maintain.
@@ -68,7 +70,9 @@ impl Sim {
         match id {
             AgentID::Car(id) => self.driving.debug_car_json(id),
             AgentID::Pedestrian(id) => self.walking.debug_ped_json(id),
-            AgentID::BusPassenger(_, id) => self.driving.debug_car_json(id),
+            AgentID::BusPassenger(_, id) | AgentID::CarPassenger(_, id) => {
+                self.driving.debug_car_json(id)
+            }
         }
     }
 
@@ -86,7 +90,7 @@ impl Sim {
             AgentID::Pedestrian(id) => self.walking.agent_properties(map, id, self.time),
             AgentID::Car(id) => self.driving.agent_properties(id, self.time),
             // TODO Harder to measure some of this stuff
-            AgentID::BusPassenger(_, _) => AgentProperties {
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => AgentProperties {
                 total_time: Duration::ZERO,
                 waiting_here: Duration::ZERO,
                 total_waiting: Duration::ZERO,
@@ -100,6 +104,11 @@ impl Sim {
         self.transit.get_passengers(car).len()
     }
 
+    /// How many passengers can fit on this transit vehicle?
+    pub fn transit_capacity(&self, bus: CarID) -> usize {
+        self.transit.get_capacity(bus)
+    }
+
     pub fn bus_route_id(&self, maybe_bus: CarID) -> Option<TransitRouteID> {
         if maybe_bus.vehicle_type == VehicleType::Bus
             || maybe_bus.vehicle_type == VehicleType::Train
@@ -226,11 +235,21 @@ impl Sim {
         None
     }
 
+    /// None if the car isn't on the map (maybe still waiting to spawn) or isn't externally
+    /// controlled.
+    pub fn external_car_state(&self, id: CarID, map: &Map) -> Option<ExternalCarState> {
+        self.driving.external_car_state(id, self.time, map)
+    }
+
+    pub fn get_all_external_cars(&self) -> Vec<CarID> {
+        self.driving.get_all_external_cars()
+    }
+
     pub fn get_path(&self, id: AgentID) -> Option<&Path> {
         match id {
             AgentID::Car(car) => self.driving.get_path(car),
             AgentID::Pedestrian(ped) => self.walking.get_path(ped),
-            AgentID::BusPassenger(_, _) => None,
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => None,
         }
     }
     pub fn get_all_driving_paths(&self) -> Vec<&Path> {
@@ -241,7 +260,7 @@ impl Sim {
         match id {
             AgentID::Car(car) => self.driving.trace_route(self.time, car, map),
             AgentID::Pedestrian(ped) => self.walking.trace_route(self.time, ped, map),
-            AgentID::BusPassenger(_, _) => None,
+            AgentID::BusPassenger(_, _) | AgentID::CarPassenger(_, _) => None,
         }
     }
 
@@ -272,7 +291,9 @@ impl Sim {
                 .canonical_pt(id, map)
                 .or_else(|| Some(self.get_draw_car(id, map)?.body.last_pt())),
             AgentID::Pedestrian(id) => Some(self.get_draw_ped(id, map)?.pos),
-            AgentID::BusPassenger(_, bus) => Some(self.get_draw_car(bus, map)?.body.last_pt()),
+            AgentID::BusPassenger(_, car) | AgentID::CarPassenger(_, car) => {
+                Some(self.get_draw_car(car, map)?.body.last_pt())
+            }
         }
     }
 
@@ -313,6 +334,17 @@ impl Sim {
         &self.analytics
     }
 
+    /// How long cars took to get through each part of the map so far. Only recorded when
+    /// congestion-aware routing is enabled.
+    pub fn get_observed_travel_times(&self) -> &ObservedTravelTimes {
+        self.travel_times.get_current()
+    }
+
+    /// For electric cars, the current charge and capacity of the battery, in kWh
+    pub fn get_battery_charge(&self, car: CarID) -> Option<(f64, f64)> {
+        self.charging.get_charge(car, self.time)
+    }
+
     /// For intersections with an agent waiting beyond some threshold, return when they started
     /// waiting. Sorted by earliest waiting (likely the root cause of gridlock).
     pub fn delayed_intersections(&self, threshold: Duration) -> Vec<(IntersectionID, Time)> {
@@ -540,7 +572,7 @@ pub struct AgentProperties {
 
 /// Why is an agent delayed? If there are multiple reasons, arbitrarily pick one -- ie, somebody
 /// could be blocked by two conflicting turns.
-#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize)]
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, JsonSchema)]
 pub enum DelayCause {
     /// Queued behind someone, or someone's doing a conflicting turn, or someone's eating up space
     /// in a target queue
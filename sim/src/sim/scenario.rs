@@ -11,8 +11,8 @@ use synthpop::make::fork_rng;
 use synthpop::{PersonSpec, Scenario, TripEndpoint, TripMode};
 
 use crate::{
-    ParkingSpot, Sim, StartTripArgs, TripInfo, Vehicle, VehicleSpec, VehicleType, BIKE_LENGTH,
-    MAX_CAR_LENGTH, MIN_CAR_LENGTH,
+    parking_rules, ParkingSpot, Sim, StartTripArgs, TripInfo, Vehicle, VehicleSpec, VehicleType,
+    BIKE_LENGTH, MAX_CAR_LENGTH, MIN_CAR_LENGTH,
 };
 
 impl Sim {
@@ -41,6 +41,12 @@ impl Sim {
 
         timer.start(format!("Instantiating {}", scenario.scenario_name));
 
+        // Capacity only applies to vehicles created afterwards, so set it before seeding
+        for route in map.all_transit_routes() {
+            if let Some(capacity) = scenario.transit_capacities.get(&route.long_name) {
+                self.set_transit_route_capacity(route.id, Some(*capacity));
+            }
+        }
         if let Some(ref routes) = scenario.only_seed_buses {
             for route in map.all_transit_routes() {
                 if routes.contains(&route.long_name) {
@@ -54,6 +60,7 @@ impl Sim {
             }
         }
 
+        let pct_electric_cars = self.charging.pct_electric_cars();
         timer.start_iter("trips for People", scenario.people.len());
         let mut parked_cars: Vec<(Vehicle, BuildingID)> = Vec::new();
         let mut schedule_trips = Vec::new();
@@ -65,7 +72,7 @@ impl Sim {
             }
 
             let (vehicle_specs, cars_initially_parked_at, vehicle_foreach_trip) =
-                get_vehicles(p, rng);
+                get_vehicles(p, pct_electric_cars, rng);
             let person = self.new_person(p.orig_id, rand_ped_speed(rng), vehicle_specs);
             for (idx, b) in cars_initially_parked_at {
                 parked_cars.push((person.vehicles[idx].clone(), b));
@@ -80,6 +87,7 @@ impl Sim {
                         end: trip.destination,
                         purpose: trip.purpose,
                         modified: trip.modified,
+                        carpool: trip.carpool,
                         cancellation_reason: if trip.cancelled {
                             Some("cancelled by ScenarioModifier".to_string())
                         } else {
@@ -105,6 +113,7 @@ impl Sim {
 
 fn get_vehicles(
     person: &PersonSpec,
+    pct_electric_cars: usize,
     rng: &mut XorShiftRng,
 ) -> (
     Vec<VehicleSpec>,
@@ -123,6 +132,8 @@ fn get_vehicles(
     for trip in &person.trips {
         let use_for_trip = match trip.mode {
             TripMode::Walk | TripMode::Transit => None,
+            // Somebody else's car is used
+            TripMode::Drive if trip.is_carpool_passenger() => None,
             TripMode::Bike => {
                 if bike_idx.is_none() {
                     bike_idx = Some(vehicle_specs.len());
@@ -146,7 +157,7 @@ fn get_vehicles(
                 } else {
                     // Need a new car, starting in the right spot
                     let idx = vehicle_specs.len();
-                    vehicle_specs.push(rand_car(rng));
+                    vehicle_specs.push(rand_car(pct_electric_cars, rng));
                     if let Some(b) = need_parked_at {
                         cars_initially_parked_at.push((idx, b));
                     }
@@ -188,12 +199,15 @@ fn get_vehicles(
     )
 }
 
-fn rand_car(rng: &mut XorShiftRng) -> VehicleSpec {
+fn rand_car(pct_electric_cars: usize, rng: &mut XorShiftRng) -> VehicleSpec {
     let length = rand_dist(rng, MIN_CAR_LENGTH, MAX_CAR_LENGTH);
+    // Only draw when needed, so scenarios without electric cars turn out the same as before
+    let electric = pct_electric_cars > 0 && rng.gen_range(0..100) < pct_electric_cars;
     VehicleSpec {
         vehicle_type: VehicleType::Car,
         length,
         max_speed: None,
+        electric,
     }
 }
 
@@ -207,6 +221,7 @@ fn rand_bike(rng: &mut XorShiftRng) -> VehicleSpec {
         vehicle_type: VehicleType::Bike,
         length: BIKE_LENGTH,
         max_speed,
+        electric: false,
     }
 }
 
@@ -243,7 +258,7 @@ fn seed_parked_cars(
         for (vehicle, b) in parked_cars {
             timer.next();
             if let Some(spot) = sim.get_free_offstreet_spots(b).pop() {
-                sim.seed_parked_car(vehicle, spot);
+                sim.seed_parked_car(vehicle, spot, b);
             } else {
                 blackholed += 1;
             }
@@ -295,7 +310,7 @@ fn seed_parked_cars(
         }
         if let Some(spot) = find_spot_near_building(b, &mut open_spots_per_road, map) {
             seeded += 1;
-            sim.seed_parked_car(vehicle, spot);
+            sim.seed_parked_car(vehicle, spot, b);
         } else {
             warn!(
                 "Not enough room to seed parked cars. Only found spots for {} of {}",
@@ -334,10 +349,9 @@ fn find_spot_near_building(
             {
                 return Some(spots.remove(idx).0);
             }
-            if let Some(idx) = spots
-                .iter()
-                .position(|(_, restriction)| restriction.is_none())
-            {
+            if let Some(idx) = spots.iter().position(|(spot, restriction)| {
+                restriction.is_none() && can_seed_overnight(*spot, b, map)
+            }) {
                 return Some(spots.remove(idx).0);
             }
         }
@@ -351,12 +365,25 @@ fn find_spot_near_building(
     }
 }
 
+// Cars parked at the start of the simulation may stay all day, and only residents can use permit
+// zones.
+fn can_seed_overnight(spot: ParkingSpot, home: BuildingID, map: &Map) -> bool {
+    if let ParkingSpot::Onstreet(l, _) = spot {
+        if !map.get_parking_policy().has_permit(l, Some(home)) {
+            return false;
+        }
+    }
+    parking_rules(spot, map)
+        .map(|rules| rules.allows_stay(None))
+        .unwrap_or(true)
+}
+
 pub fn count_parked_cars_per_bldg(scenario: &Scenario) -> Counter<BuildingID> {
     let mut per_bldg = Counter::new();
     // Pass in a dummy RNG
     let mut rng = XorShiftRng::seed_from_u64(0);
     for p in &scenario.people {
-        let (_, cars_initially_parked_at, _) = get_vehicles(p, &mut rng);
+        let (_, cars_initially_parked_at, _) = get_vehicles(p, 0, &mut rng);
         for (_, b) in cars_initially_parked_at {
             per_bldg.inc(b);
         }
@@ -8,12 +8,14 @@ use map_model::{Map, Path, TransitRoute, TransitRouteID, TransitStopID};
 
 use crate::sim::Ctx;
 use crate::{
-    AgentID, CarID, DrivingSimState, Event, PedestrianID, PersonID, Router, TripID, TripManager,
-    TripPhaseType, UnzoomedAgent, VehicleType, WalkingSimState,
+    AgentID, CarID, DrivingSimState, Event, PedestrianID, PersonID, Problem, Router, TripID,
+    TripManager, TripPhaseType, UnzoomedAgent, VehicleType, WalkingSimState,
 };
 
 // These index stops along a route, not stops along a single sidewalk.
 type StopIdx = usize;
+/// (ped, route, bound for, started waiting)
+type WaitingRider = (PedestrianID, TransitRouteID, Option<TransitStopID>, Time);
 
 #[derive(Serialize, Deserialize, Clone)]
 struct Route {
@@ -30,6 +32,10 @@ struct Bus {
     route: TransitRouteID,
     /// Where does each passenger want to deboard?
     passengers: Vec<(PersonID, Option<TransitStopID>)>,
+    /// Once this many passengers are on board, nobody else can board.
+    capacity: usize,
+    /// When was this vehicle scheduled to spawn? Schedule adherence is measured relative to this.
+    scheduled_departure: Time,
     state: BusState,
 }
 
@@ -44,6 +50,9 @@ enum BusState {
 
 /// Manages public transit vehicles (buses and trains) that follow a route. The transit model is
 /// currently kind of broken, so not describing the state machine yet.
+///
+/// Each vehicle has a limited capacity. When a full vehicle reaches a stop, riders waiting there
+/// are left behind for the next vehicle.
 #[derive(Serialize, Deserialize, Clone)]
 pub(crate) struct TransitSimState {
     #[serde(
@@ -61,8 +70,19 @@ pub(crate) struct TransitSimState {
         serialize_with = "serialize_btreemap",
         deserialize_with = "deserialize_btreemap"
     )]
-    peds_waiting:
-        BTreeMap<TransitStopID, Vec<(PedestrianID, TransitRouteID, Option<TransitStopID>, Time)>>,
+    peds_waiting: BTreeMap<TransitStopID, Vec<WaitingRider>>,
+    /// Overrides the default capacity of vehicles serving some routes
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    route_capacities: BTreeMap<TransitRouteID, usize>,
+    /// Vehicles that have been scheduled, but may be waiting for room to spawn
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    pending_departures: BTreeMap<CarID, (TransitRouteID, Time)>,
 
     events: Vec<Event>,
 }
@@ -79,6 +99,8 @@ impl TransitSimState {
             buses: BTreeMap::new(),
             routes: BTreeMap::new(),
             peds_waiting,
+            route_capacities: BTreeMap::new(),
+            pending_departures: BTreeMap::new(),
             events: Vec::new(),
         }
     }
@@ -105,15 +127,41 @@ impl TransitSimState {
         self.routes[&bus_route.id].paths[0].clone()
     }
 
-    pub fn bus_created(&mut self, bus: CarID, r: TransitRouteID) {
+    /// Only affects vehicles created after this call. None restores the default capacity for the
+    /// vehicle type.
+    pub fn set_route_capacity(&mut self, r: TransitRouteID, capacity: Option<usize>) {
+        if let Some(capacity) = capacity {
+            self.route_capacities.insert(r, capacity);
+        } else {
+            self.route_capacities.remove(&r);
+        }
+    }
+
+    /// The vehicle might not spawn immediately if there's no room.
+    pub fn bus_scheduled(&mut self, bus: CarID, r: TransitRouteID, now: Time) {
+        self.pending_departures.insert(bus, (r, now));
+    }
+
+    pub fn bus_created(&mut self, now: Time, bus: CarID, r: TransitRouteID) {
         let route = self.routes.get_mut(&r).unwrap();
         route.active_vehicles.insert(bus);
+        let capacity = self
+            .route_capacities
+            .get(&r)
+            .cloned()
+            .unwrap_or_else(|| bus.vehicle_type.transit_capacity());
         self.buses.insert(
             bus,
             Bus {
                 car: bus,
                 route: r,
                 passengers: Vec::new(),
+                capacity,
+                scheduled_departure: self
+                    .pending_departures
+                    .remove(&bus)
+                    .map(|(_, t)| t)
+                    .unwrap_or(now),
                 state: BusState::DrivingToStop(0),
             },
         );
@@ -138,6 +186,18 @@ impl TransitSimState {
                 let stop1 = self.routes[&bus.route].stops[stop_idx];
                 self.events
                     .push(Event::BusArrivedAtStop(id, bus.route, stop1));
+                if let Some(expected) = ctx.map.get_tr(bus.route).scheduled_arrival(
+                    ctx.map,
+                    bus.scheduled_departure,
+                    stop_idx,
+                ) {
+                    self.events.push(Event::TransitScheduleDeviation(
+                        id,
+                        bus.route,
+                        stop1,
+                        now - expected,
+                    ));
+                }
 
                 // Deboard existing passengers.
                 let mut still_riding = Vec::new();
@@ -154,41 +214,44 @@ impl TransitSimState {
                 bus.passengers = still_riding;
 
                 // Board new passengers.
-                let mut still_waiting = Vec::new();
-                for (ped, route, maybe_stop2, started_waiting) in
-                    self.peds_waiting.remove(&stop1).unwrap()
-                {
-                    if bus.route == route {
-                        let (trip, person) = trips.ped_boarded_bus(
-                            now,
-                            ped,
-                            bus.car,
-                            now - started_waiting,
-                            walking,
-                        );
-                        self.events.push(Event::PassengerBoardsTransit(
-                            person,
-                            bus.car,
-                            bus.route,
-                            stop1,
-                            now - started_waiting,
-                        ));
-                        // TODO Recording the PathRequest for the passenger is actually hard. We
-                        // don't want to route directly between their first and last stop, because
-                        // there might be a much shorter path there. Should we record a leg per leg
-                        // of the transit route being followed?
-                        self.events.push(Event::TripPhaseStarting(
+                let (boarding, still_waiting, denied) = pick_boarding_riders(
+                    self.peds_waiting.remove(&stop1).unwrap(),
+                    bus.route,
+                    bus.capacity.saturating_sub(bus.passengers.len()),
+                );
+                for (ped, route, maybe_stop2, started_waiting) in boarding {
+                    let (trip, person) =
+                        trips.ped_boarded_bus(now, ped, bus.car, now - started_waiting, walking);
+                    self.events.push(Event::PassengerBoardsTransit(
+                        person,
+                        bus.car,
+                        bus.route,
+                        stop1,
+                        now - started_waiting,
+                    ));
+                    // TODO Recording the PathRequest for the passenger is actually hard. We
+                    // don't want to route directly between their first and last stop, because
+                    // there might be a much shorter path there. Should we record a leg per leg
+                    // of the transit route being followed?
+                    self.events.push(Event::TripPhaseStarting(
+                        trip,
+                        person,
+                        None,
+                        TripPhaseType::RidingBus(route, stop1, bus.car),
+                    ));
+                    bus.passengers.push((person, maybe_stop2));
+                }
+                self.peds_waiting.insert(stop1, still_waiting);
+                // Like in ped_waiting_for_bus, every rider left behind by a full vehicle has a
+                // problem
+                for ped in denied {
+                    if let Some(trip) = trips.agent_to_trip(AgentID::Pedestrian(ped)) {
+                        self.events.push(Event::ProblemEncountered(
                             trip,
-                            person,
-                            None,
-                            TripPhaseType::RidingBus(route, stop1, bus.car),
+                            Problem::TransitBoardingDenied(stop1, bus.route),
                         ));
-                        bus.passengers.push((person, maybe_stop2));
-                    } else {
-                        still_waiting.push((ped, route, maybe_stop2, started_waiting));
                     }
                 }
-                self.peds_waiting.insert(stop1, still_waiting);
                 true
             }
             BusState::DrivingOffMap => {
@@ -223,6 +286,8 @@ impl TransitSimState {
                     id,
                     bus.route,
                     route.stops[stop_idx],
+                    bus.passengers.len(),
+                    bus.capacity,
                 ));
 
                 if stop_idx == route.stops.len() - 1 {
@@ -255,11 +320,15 @@ impl TransitSimState {
             for bus in &route.active_vehicles {
                 if let BusState::AtStop(idx) = self.buses[bus].state {
                     if route.stops[idx] == stop1 {
-                        self.buses
-                            .get_mut(bus)
-                            .unwrap()
-                            .passengers
-                            .push((person, maybe_stop2));
+                        let vehicle = self.buses.get_mut(bus).unwrap();
+                        if vehicle.passengers.len() >= vehicle.capacity {
+                            self.events.push(Event::ProblemEncountered(
+                                trip,
+                                Problem::TransitBoardingDenied(stop1, route_id),
+                            ));
+                            continue;
+                        }
+                        vehicle.passengers.push((person, maybe_stop2));
                         // TODO Same problem as elsewhere with recording the PathRequest
                         self.events.push(Event::TripPhaseStarting(
                             trip,
@@ -285,6 +354,70 @@ impl TransitSimState {
         None
     }
 
+    /// Find instantiated routes that were deleted or rerouted, or whose stops moved.
+    pub fn find_routes_affected_by_live_edits(&self, map: &Map) -> BTreeSet<TransitRouteID> {
+        let mut affected = BTreeSet::new();
+        for (id, route) in &self.routes {
+            let still_valid = if let Some(tr) = map.maybe_get_tr(*id) {
+                tr.stops == route.stops
+                    && route
+                        .stops
+                        .iter()
+                        .zip(route.paths.iter())
+                        .all(|(ts, path)| {
+                            map.maybe_get_ts(*ts)
+                                .map(|ts| ts.driving_pos == path.get_req().end)
+                                .unwrap_or(false)
+                        })
+            } else {
+                false
+            };
+            if !still_valid {
+                affected.insert(*id);
+            }
+        }
+        affected
+    }
+
+    /// Forget about some routes, so the next vehicle to start them will use the edited version.
+    /// The caller must first cancel the trips of anybody riding or waiting for these routes.
+    /// Returns (vehicles to delete, vehicles whose spawning should be cancelled).
+    pub fn handle_live_edits(
+        &mut self,
+        affected: &BTreeSet<TransitRouteID>,
+        map: &Map,
+    ) -> (Vec<CarID>, Vec<CarID>) {
+        let mut delete_vehicles = Vec::new();
+        for r in affected {
+            if let Some(route) = self.routes.remove(r) {
+                for bus in route.active_vehicles {
+                    self.buses.remove(&bus);
+                    delete_vehicles.push(bus);
+                }
+            }
+        }
+
+        let mut cancel_spawns = Vec::new();
+        self.pending_departures.retain(|bus, (r, _)| {
+            if affected.contains(r) {
+                cancel_spawns.push(*bus);
+                false
+            } else {
+                true
+            }
+        });
+
+        for waiting in self.peds_waiting.values_mut() {
+            waiting.retain(|(_, r, _, _)| !affected.contains(r));
+        }
+        // Stops may have been created
+        for ts in map.all_transit_stops().keys() {
+            self.peds_waiting.entry(*ts).or_insert_with(Vec::new);
+        }
+
+        (delete_vehicles, cancel_spawns)
+    }
+
     pub fn collect_events(&mut self) -> Vec<Event> {
         self.events.drain(..).collect()
     }
@@ -297,6 +430,10 @@ impl TransitSimState {
         self.buses[&bus].route
     }
 
+    pub fn get_capacity(&self, bus: CarID) -> usize {
+        self.buses[&bus].capacity
+    }
+
     /// also stop idx that the bus is coming from
     pub fn buses_for_route(&self, route: TransitRouteID) -> Vec<(CarID, Option<usize>)> {
         if let Some(r) = self.routes.get(&route) {
@@ -379,3 +516,156 @@ impl TransitSimState {
         results
     }
 }
+
+/// Splits the riders waiting at a stop into those boarding a vehicle on this route with room for
+/// `room` more passengers, and those staying at the stop. Whoever started waiting first boards
+/// first. Also returns the riders left behind because the vehicle filled up.
+fn pick_boarding_riders(
+    waiting: Vec<WaitingRider>,
+    route: TransitRouteID,
+    mut room: usize,
+) -> (Vec<WaitingRider>, Vec<WaitingRider>, Vec<PedestrianID>) {
+    let mut boarding = Vec::new();
+    let mut still_waiting = Vec::new();
+    let mut denied = Vec::new();
+    for rider in waiting {
+        if rider.1 != route {
+            still_waiting.push(rider);
+        } else if room == 0 {
+            // The vehicle is full, so they'll have to wait for the next one.
+            denied.push(rider.0);
+            still_waiting.push(rider);
+        } else {
+            room -= 1;
+            boarding.push(rider);
+        }
+    }
+    (boarding, still_waiting, denied)
+}
+
+#[cfg(test)]
+mod tests {
+    use geom::Duration;
+
+    use super::*;
+
+    fn rider(id: usize, route: usize, minutes: usize) -> WaitingRider {
+        (
+            PedestrianID(id),
+            TransitRouteID(route),
+            None,
+            Time::START_OF_DAY + Duration::minutes(minutes),
+        )
+    }
+
+    fn bus(id: usize) -> CarID {
+        CarID {
+            id,
+            vehicle_type: VehicleType::Bus,
+        }
+    }
+
+    #[test]
+    fn full_vehicles_leave_riders_behind() {
+        let waiting = vec![
+            rider(0, 1, 0),
+            rider(1, 2, 1),
+            rider(2, 1, 2),
+            rider(3, 1, 3),
+        ];
+
+        // Riders for other routes stay, and the longest waiting board first
+        let (boarding, still_waiting, denied) =
+            pick_boarding_riders(waiting.clone(), TransitRouteID(1), 2);
+        assert_eq!(boarding, vec![rider(0, 1, 0), rider(2, 1, 2)]);
+        assert_eq!(still_waiting, vec![rider(1, 2, 1), rider(3, 1, 3)]);
+        assert_eq!(denied, vec![PedestrianID(3)]);
+
+        // Nobody is denied when there's room for everyone
+        let (boarding, _, denied) = pick_boarding_riders(waiting.clone(), TransitRouteID(1), 10);
+        assert_eq!(boarding.len(), 3);
+        assert!(denied.is_empty());
+
+        // Everybody on the route is left behind by a full vehicle
+        let (boarding, still_waiting, denied) = pick_boarding_riders(waiting, TransitRouteID(1), 0);
+        assert!(boarding.is_empty());
+        assert_eq!(still_waiting.len(), 4);
+        assert_eq!(
+            denied,
+            vec![PedestrianID(0), PedestrianID(2), PedestrianID(3)]
+        );
+    }
+
+    #[test]
+    fn route_capacity() {
+        // Only map_model can make stop IDs, but none of this looks at the map
+        let map = Map::blank();
+        let stop: TransitStopID = abstutil::from_json(br#"{"road": 0, "idx": 0}"#).unwrap();
+        let route = TransitRouteID(0);
+        let mut state = TransitSimState::new(&map);
+        state.peds_waiting.insert(stop, Vec::new());
+        state.routes.insert(
+            route,
+            Route {
+                paths: Vec::new(),
+                stops: vec![stop],
+                active_vehicles: BTreeSet::new(),
+            },
+        );
+
+        // Overrides only apply to vehicles created afterwards
+        state.set_route_capacity(route, Some(1));
+        state.bus_created(Time::START_OF_DAY, bus(0), route);
+        state.set_route_capacity(route, None);
+        state.bus_created(Time::START_OF_DAY, bus(1), route);
+        assert_eq!(state.get_capacity(bus(0)), 1);
+        assert_eq!(
+            state.get_capacity(bus(1)),
+            VehicleType::Bus.transit_capacity()
+        );
+        state
+            .routes
+            .get_mut(&route)
+            .unwrap()
+            .active_vehicles
+            .remove(&bus(1));
+
+        // The first rider fills up the vehicle waiting at the stop
+        state.buses.get_mut(&bus(0)).unwrap().state = BusState::AtStop(0);
+        let now = Time::START_OF_DAY;
+        assert_eq!(
+            state.ped_waiting_for_bus(
+                now,
+                PedestrianID(0),
+                TripID(0),
+                PersonID(0),
+                stop,
+                route,
+                None,
+                &map
+            ),
+            Some(bus(0))
+        );
+        assert_eq!(
+            state.ped_waiting_for_bus(
+                now,
+                PedestrianID(1),
+                TripID(1),
+                PersonID(1),
+                stop,
+                route,
+                None,
+                &map
+            ),
+            None
+        );
+        assert_eq!(state.get_passengers(bus(0)).len(), 1);
+        assert_eq!(state.get_people_waiting_at_stop(stop).len(), 1);
+        let problems = state
+            .collect_events()
+            .into_iter()
+            .filter(|ev| matches!(ev, Event::ProblemEncountered(_, _)))
+            .count();
+        assert_eq!(problems, 1);
+    }
+}
@@ -0,0 +1,453 @@
+//! Measures how long cars actually take to get through each part of the map, so that routing can
+//! account for congestion instead of always assuming free-flow speeds.
+
+use std::collections::BTreeMap;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use abstutil::{deserialize_btreemap, serialize_btreemap, Timer};
+use geom::{Duration, Time};
+use map_model::{
+    DirectedRoadID, Map, MovementID, Path, PathConstraints, PathRequest, PathStep, Pathfinder,
+    PathfinderCaching, Position, RoutingParams, Traversable,
+};
+
+use crate::{AgentID, CarID, Event, IncidentSimState, Router, SimOptions, VehicleType};
+
+/// Observations older than this don't describe current conditions.
+const RECENT_WINDOW: Duration = Duration::const_seconds(15.0 * 60.0);
+/// Rebuilding a pathfinder isn't free, so only pick up new observations this often.
+const REBUILD_EVERY: Duration = Duration::const_seconds(15.0 * 60.0);
+/// A road counts as congested when crossing it takes this many times longer than free-flow...
+const CONGESTION_FACTOR: f64 = 2.0;
+/// ... and the difference is at least this much.
+const MIN_CONGESTION_DELAY: Duration = Duration::const_seconds(30.0);
+/// When a car enters a road, how many roads ahead to check for congestion.
+const LOOKAHEAD_ROADS: usize = 4;
+
+/// For every movement, how long cars took from entering the road to starting the turn at the
+/// end, including any time spent waiting in a queue. Bucketed by hour of the day, so routing can
+/// depend on the time of departure. This can be saved from one run and fed into the next one.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct ObservedTravelTimes {
+    /// (Sum, count) per (hour, movement)
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    times: BTreeMap<(usize, MovementID), (Duration, usize)>,
+}
+
+impl ObservedTravelTimes {
+    pub fn new() -> ObservedTravelTimes {
+        ObservedTravelTimes::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.times.is_empty()
+    }
+
+    /// The average observed time during some hour of the day
+    pub fn get(&self, hour: usize, mvmnt: MovementID) -> Option<Duration> {
+        self.times
+            .get(&(hour, mvmnt))
+            .map(|(sum, count)| *sum / (*count as f64))
+    }
+
+    /// All movements observed during some hour of the day, with their average time
+    pub fn during_hour(&self, hour: usize) -> BTreeMap<MovementID, Duration> {
+        self.times
+            .iter()
+            .filter(|((h, _), _)| *h == hour)
+            .map(|((_, mvmnt), (sum, count))| (*mvmnt, *sum / (*count as f64)))
+            .collect()
+    }
+
+    /// Combine with observations from another run, giving the other run `weight` (between 0 and
+    /// 1). Movements only observed in one of the runs keep that value. Repeatedly blending with a
+    /// weight of `1 / day` is the method of successive averages.
+    pub fn blend(&self, other: &ObservedTravelTimes, weight: f64) -> ObservedTravelTimes {
+        let mut result = ObservedTravelTimes::new();
+        for key in self.times.keys().chain(other.times.keys()) {
+            if result.times.contains_key(key) {
+                continue;
+            }
+            let dt = match (self.get(key.0, key.1), other.get(key.0, key.1)) {
+                (Some(old), Some(new)) => (1.0 - weight) * old + weight * new,
+                (Some(dt), None) | (None, Some(dt)) => dt,
+                (None, None) => unreachable!(),
+            };
+            result.times.insert(*key, (dt, 1));
+        }
+        result
+    }
+
+    /// Observations are bucketed by this hour, so later days of a multi-day simulation share
+    /// buckets with the first.
+    pub fn hour_of_day(time: Time) -> usize {
+        time.get_hours() % 24
+    }
+
+    fn record(&mut self, hour: usize, mvmnt: MovementID, dt: Duration) {
+        let entry = self
+            .times
+            .entry((hour, mvmnt))
+            .or_insert((Duration::ZERO, 0));
+        entry.0 += dt;
+        entry.1 += 1;
+    }
+}
+
+/// Observes travel times during a simulation and routes cars using them. Also routes cars when
+/// road pricing depends on the time of day, since the map's pathfinder can't account for that.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct TravelTimes {
+    enabled: bool,
+    reroute_on_congestion: bool,
+
+    /// From previous runs
+    prior: ObservedTravelTimes,
+    /// From this run
+    current: ObservedTravelTimes,
+    /// A smoothed value of the latest observations for each movement, and when they happened
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    recent: BTreeMap<MovementID, (Time, Duration)>,
+    /// Which road each car is currently crossing, and when they entered it
+    entered_road: BTreeMap<CarID, (DirectedRoadID, Time)>,
+
+    /// The routing params each pathfinder starts from, when it was built, and the pathfinder
+    /// itself. None means no observations were available yet, so just use the map's regular
+    /// pathfinding.
+    #[serde(skip_serializing, skip_deserializing)]
+    pathfinders: Vec<(RoutingParams, Time, Option<Pathfinder>)>,
+}
+
+impl TravelTimes {
+    pub fn new(opts: &SimOptions) -> TravelTimes {
+        TravelTimes {
+            enabled: opts.congestion_aware_routing || opts.reroute_on_congestion,
+            reroute_on_congestion: opts.reroute_on_congestion,
+
+            prior: ObservedTravelTimes::new(),
+            current: ObservedTravelTimes::new(),
+            recent: BTreeMap::new(),
+            entered_road: BTreeMap::new(),
+
+            pathfinders: Vec::new(),
+        }
+    }
+
+    pub fn set_prior(&mut self, prior: ObservedTravelTimes) {
+        self.prior = prior;
+        self.pathfinders.clear();
+    }
+
+    pub fn get_current(&self) -> &ObservedTravelTimes {
+        &self.current
+    }
+
+    /// The map changed, so any pathfinder built from it is stale.
+    pub fn map_edited(&mut self) {
+        self.pathfinders.clear();
+    }
+
+    /// An incident started or ended, so any pathfinder built around the old ones is stale.
+    pub fn incidents_changed(&mut self) {
+        self.pathfinders.clear();
+    }
+
+    pub fn handle_event(&mut self, now: Time, ev: &Event, map: &Map) {
+        if !self.enabled {
+            return;
+        }
+        if let Event::AgentEntersTraversable(AgentID::Car(car), _, on, _) = ev {
+            if car.vehicle_type != VehicleType::Car {
+                return;
+            }
+            match on {
+                Traversable::Lane(l) => {
+                    self.entered_road
+                        .insert(*car, (map.get_l(*l).get_directed_parent(), now));
+                }
+                Traversable::Turn(t) => {
+                    if let Some((dr, since)) = self.entered_road.remove(car) {
+                        if map.get_l(t.src).get_directed_parent() == dr {
+                            let dt = now - since;
+                            let mvmnt = t.to_movement(map);
+                            self.current
+                                .record(ObservedTravelTimes::hour_of_day(now), mvmnt, dt);
+                            let smoothed = match self.recent.get(&mvmnt) {
+                                Some((last, prev)) if now - *last <= RECENT_WINDOW => {
+                                    0.5 * *prev + 0.5 * dt
+                                }
+                                _ => dt,
+                            };
+                            self.recent.insert(mvmnt, (now, smoothed));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Finds a path, using observed travel times for cars if this is enabled. Cars also account
+    /// for road pricing that changes through the day and avoid incidents.
+    pub fn pathfind(
+        &mut self,
+        req: PathRequest,
+        now: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> Result<Path> {
+        let params = map.routing_params().clone();
+        self.pathfind_with_params(req, &params, now, map, incidents)
+    }
+
+    /// Like `pathfind`, but starting from custom routing params instead of the map's. Observed
+    /// travel times, road pricing, and incidents are layered on top of them.
+    pub fn pathfind_with_params(
+        &mut self,
+        req: PathRequest,
+        params: &RoutingParams,
+        now: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> Result<Path> {
+        if req.constraints != PathConstraints::Car
+            || !(self.enabled || map.get_road_pricing().varies_by_time() || incidents.any_active())
+        {
+            return map.pathfind_with_params(req, params, PathfinderCaching::CacheDijkstra);
+        }
+        match self.get_pathfinder(params, now, map, incidents) {
+            Some(pathfinder) => pathfinder
+                .pathfind_v2(req.clone(), map)
+                .ok_or_else(|| anyhow!("can't fulfill {}", req))?
+                .into_v1(map),
+            None => map.pathfind_with_params(req, params, PathfinderCaching::CacheDijkstra),
+        }
+    }
+
+    /// A car just entered a lane. If an incident blocks the rest of the way, or the road has
+    /// recently been congested, look for a faster way to the same destination. Returns true if
+    /// the route changed.
+    pub fn maybe_reroute(
+        &mut self,
+        car: CarID,
+        router: &mut Router,
+        now: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> bool {
+        if car.vehicle_type != VehicleType::Car || !router.can_reroute() {
+            return false;
+        }
+        if !incidents.blocks_path(router.get_path()) {
+            if !self.reroute_on_congestion {
+                return false;
+            }
+            // Look at the road just entered and the next few after it
+            let congested = router
+                .get_path()
+                .get_steps()
+                .iter()
+                .filter_map(|step| match step {
+                    PathStep::Lane(l) => Some(map.get_l(*l).get_directed_parent()),
+                    _ => None,
+                })
+                .take(LOOKAHEAD_ROADS)
+                .any(|dr| self.is_congested(dr, now, map));
+            if !congested {
+                return false;
+            }
+        }
+        self.reroute(router, now, map, incidents)
+    }
+
+    /// Look for the best way from the end of the current lane to the same destination, using the
+    /// trip's own routing params if it has any. Returns true if the route changed. The caller must
+    /// check `Router::can_reroute`.
+    pub fn reroute(
+        &mut self,
+        router: &mut Router,
+        now: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> bool {
+        let lane = match router.head() {
+            Traversable::Lane(l) => l,
+            Traversable::Turn(_) => {
+                return false;
+            }
+        };
+        let req = PathRequest::vehicle(
+            Position::end(lane, map),
+            router.get_path().get_req().end,
+            PathConstraints::Car,
+        );
+        let params = router
+            .routing_params()
+            .cloned()
+            .unwrap_or_else(|| map.routing_params().clone());
+        match self.pathfind_with_params(req, &params, now, map, incidents) {
+            // If the destination is somewhere along the current lane, the original path looped
+            // around and came back, so it's not worth changing anything.
+            Ok(path)
+                if !path.is_last_step() && path.get_steps() != router.get_path().get_steps() =>
+            {
+                let avoiding = incidents.affecting_path(router.get_path());
+                router.reroute(path, map);
+                router.avoiding_incidents(avoiding);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn is_congested(&self, dr: DirectedRoadID, now: Time, map: &Map) -> bool {
+        let road = map.get_r(dr.road);
+        let free_flow = road.length() / road.speed_limit;
+        map.get_i(dr.dst_i(map))
+            .movements
+            .keys()
+            .filter(|mvmnt| mvmnt.from == dr)
+            .filter_map(|mvmnt| self.recent.get(mvmnt))
+            .any(|(last, dt)| {
+                now - *last <= RECENT_WINDOW
+                    && *dt >= CONGESTION_FACTOR * free_flow
+                    && *dt - free_flow >= MIN_CONGESTION_DELAY
+            })
+    }
+
+    fn get_pathfinder(
+        &mut self,
+        base: &RoutingParams,
+        now: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> Option<&Pathfinder> {
+        let hour = ObservedTravelTimes::hour_of_day(now);
+        let idx = match self.pathfinders.iter().position(|(p, _, _)| p == base) {
+            Some(idx) => {
+                let built = self.pathfinders[idx].1;
+                if now - built >= REBUILD_EVERY || ObservedTravelTimes::hour_of_day(built) != hour {
+                    self.pathfinders[idx] = self.build_pathfinder(base, now, map, incidents);
+                }
+                idx
+            }
+            None => {
+                let entry = self.build_pathfinder(base, now, map, incidents);
+                self.pathfinders.push(entry);
+                self.pathfinders.len() - 1
+            }
+        };
+        self.pathfinders[idx].2.as_ref()
+    }
+
+    fn build_pathfinder(
+        &self,
+        base: &RoutingParams,
+        now: Time,
+        map: &Map,
+        incidents: &IncidentSimState,
+    ) -> (RoutingParams, Time, Option<Pathfinder>) {
+        let mut params = base.clone();
+        params.pricing_time = Some(now);
+        // Prefer the typical time at this hour from previous runs, but what's happening right
+        // now matters more.
+        params.observed_movement_times = self
+            .prior
+            .during_hour(ObservedTravelTimes::hour_of_day(now));
+        for (mvmnt, (last, dt)) in &self.recent {
+            if now - *last <= RECENT_WINDOW {
+                params.observed_movement_times.insert(*mvmnt, *dt);
+            }
+        }
+        incidents.update_routing_params(&mut params, map);
+        let pathfinder = if params.observed_movement_times.is_empty()
+            && !map.get_road_pricing().varies_by_time()
+            && !incidents.any_active()
+        {
+            None
+        } else {
+            Some(Pathfinder::new_dijkstra(
+                map,
+                params,
+                vec![PathConstraints::Car],
+                &mut Timer::throwaway(),
+            ))
+        };
+        (base.clone(), now, pathfinder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use map_model::{Direction, IntersectionID, RoadID};
+
+    use super::*;
+
+    fn mvmnt(from: usize, to: usize) -> MovementID {
+        MovementID {
+            from: DirectedRoadID {
+                road: RoadID(from),
+                dir: Direction::Fwd,
+            },
+            to: DirectedRoadID {
+                road: RoadID(to),
+                dir: Direction::Fwd,
+            },
+            parent: IntersectionID(0),
+            crosswalk: false,
+        }
+    }
+
+    #[test]
+    fn record_and_query() {
+        let mut times = ObservedTravelTimes::new();
+        assert!(times.is_empty());
+        times.record(8, mvmnt(1, 2), Duration::seconds(10.0));
+        times.record(8, mvmnt(1, 2), Duration::seconds(30.0));
+        times.record(8, mvmnt(2, 3), Duration::seconds(5.0));
+        times.record(9, mvmnt(1, 2), Duration::seconds(60.0));
+
+        assert_eq!(times.get(8, mvmnt(1, 2)), Some(Duration::seconds(20.0)));
+        assert_eq!(times.get(9, mvmnt(1, 2)), Some(Duration::seconds(60.0)));
+        assert_eq!(times.get(10, mvmnt(1, 2)), None);
+
+        let mut expected = BTreeMap::new();
+        expected.insert(mvmnt(1, 2), Duration::seconds(20.0));
+        expected.insert(mvmnt(2, 3), Duration::seconds(5.0));
+        assert_eq!(times.during_hour(8), expected);
+        assert!(times.during_hour(10).is_empty());
+    }
+
+    #[test]
+    fn blend() {
+        let mut old = ObservedTravelTimes::new();
+        old.record(8, mvmnt(1, 2), Duration::seconds(10.0));
+        old.record(8, mvmnt(2, 3), Duration::seconds(5.0));
+        let mut new = ObservedTravelTimes::new();
+        new.record(8, mvmnt(1, 2), Duration::seconds(30.0));
+        new.record(9, mvmnt(1, 2), Duration::seconds(40.0));
+
+        let blended = old.blend(&new, 0.25);
+        assert_eq!(blended.get(8, mvmnt(1, 2)), Some(Duration::seconds(15.0)));
+        // Only observed in one run
+        assert_eq!(blended.get(8, mvmnt(2, 3)), Some(Duration::seconds(5.0)));
+        assert_eq!(blended.get(9, mvmnt(1, 2)), Some(Duration::seconds(40.0)));
+
+        // Blending again weighs the blended value, not the original sums
+        let twice = blended.blend(&new, 0.5);
+        assert_eq!(twice.get(8, mvmnt(1, 2)), Some(Duration::seconds(22.5)));
+    }
+
+    #[test]
+    fn later_days_share_hours() {
+        let t = Time::START_OF_DAY + Duration::hours(24 + 8) + Duration::minutes(30);
+        assert_eq!(ObservedTravelTimes::hour_of_day(t), 8);
+    }
+}
@@ -1,23 +1,25 @@
-use std::collections::{BTreeMap, VecDeque};
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
 
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_btreemap, serialize_btreemap, Counter};
 use geom::{Distance, Duration, Speed, Time};
 use map_model::{
-    BuildingID, IntersectionID, Map, PathConstraints, PathRequest, Position, TransitRouteID,
-    TransitStopID,
+    BuildingID, IntersectionID, Map, Path, PathConstraints, PathRequest, Position, RoutingParams,
+    TransitRouteID, TransitStopID,
 };
 use synthpop::{
-    IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose,
+    Carpool, CarpoolRole, IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint, TripMode,
+    TripPurpose, MAX_CARPOOL_PASSENGERS,
 };
 
 use crate::sim::Ctx;
 use crate::{
-    AgentID, AgentType, AlertLocation, CarID, Command, CreateCar, CreatePedestrian, DrivingGoal,
-    Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID, PersonID, SidewalkPOI, SidewalkSpot,
-    StartTripArgs, TransitSimState, TripID, TripPhaseType, TripSpec, Vehicle, VehicleSpec,
-    VehicleType, WalkingSimState,
+    parking_rules, planned_stay, AgentID, AgentType, AlertLocation, CarID, Command, CreateCar,
+    CreatePedestrian, DrivingGoal, Event, ParkedCar, ParkingSim, ParkingSpot, PedestrianID,
+    PersonID, Router, SidewalkPOI, SidewalkSpot, StartTripArgs, TransitSimState, TripID,
+    TripPhaseType, TripSpec, Vehicle, VehicleSpec, VehicleType, WalkingSimState,
 };
 
 /// Manages people, each of which executes some trips through the day. Each trip is further broken
@@ -37,6 +39,12 @@ pub(crate) struct TripManager {
     )]
     active_trip_mode: BTreeMap<AgentID, TripID>,
     unfinished_trips: usize,
+    /// Trips whose vehicles route with their own params, instead of the map's
+    #[serde(
+        serialize_with = "serialize_btreemap",
+        deserialize_with = "deserialize_btreemap"
+    )]
+    routing_params: BTreeMap<TripID, RoutingParams>,
 
     car_id_counter: usize,
 
@@ -51,6 +59,7 @@ impl TripManager {
             people: Vec::new(),
             active_trip_mode: BTreeMap::new(),
             unfinished_trips: 0,
+            routing_params: BTreeMap::new(),
             car_id_counter: 0,
             events: Vec::new(),
         }
@@ -106,6 +115,7 @@ impl TripManager {
             total_blocked_time: Duration::ZERO,
             total_distance: Distance::ZERO,
             legs: VecDeque::new(),
+            shared_ride: None,
         };
         self.unfinished_trips += 1;
         let person = &mut self.people[trip.person.0];
@@ -133,6 +143,64 @@ impl TripManager {
         id
     }
 
+    /// Route this trip's vehicle with custom params instead of the map's, including whenever it
+    /// reroutes.
+    pub fn set_routing_params(&mut self, trip: TripID, params: RoutingParams) {
+        self.routing_params.insert(trip, params);
+    }
+
+    /// Link all of the trips sharing one car. Passengers don't start their trip on their own;
+    /// the driver does when they arrive to pick them up. Passengers without anybody to drive them
+    /// are cancelled.
+    pub fn new_carpool(&mut self, trips: Vec<TripID>) {
+        let mut driver = None;
+        let mut passengers = Vec::new();
+        for id in trips {
+            let info = &self.trips[id.0].info;
+            if info.cancellation_reason.is_some() {
+                continue;
+            }
+            match info.carpool.unwrap().role {
+                CarpoolRole::Driver => {
+                    if driver.is_none() && info.mode == TripMode::Drive {
+                        driver = Some(id);
+                    }
+                }
+                CarpoolRole::Passenger => {
+                    passengers.push(id);
+                }
+            }
+        }
+        passengers.sort_by_key(|id| self.trips[id.0].info.departure);
+
+        let mut linked = Vec::new();
+        for id in passengers {
+            let info = &self.trips[id.0].info;
+            let problem = if driver.is_none() {
+                Some("nobody is driving their carpool")
+            } else if !matches!(info.start, TripEndpoint::Building(_))
+                || !matches!(info.end, TripEndpoint::Building(_))
+            {
+                Some("carpool passengers must start and end at a building")
+            } else if linked.len() == MAX_CARPOOL_PASSENGERS {
+                Some("their carpool is full")
+            } else {
+                None
+            };
+            if let Some(reason) = problem {
+                self.cancel_unstarted_trip(id, reason.to_string());
+            } else {
+                self.trips[id.0].shared_ride = Some(SharedRide::Passenger(driver.unwrap()));
+                linked.push(id);
+            }
+        }
+        if let Some(driver) = driver {
+            if !linked.is_empty() {
+                self.trips[driver.0].shared_ride = Some(SharedRide::Driver(linked));
+            }
+        }
+    }
+
     pub fn start_trip(&mut self, now: Time, trip: TripID, args: StartTripArgs, ctx: &mut Ctx) {
         assert!(self.trips[trip.0].info.cancellation_reason.is_none());
 
@@ -178,6 +246,9 @@ impl TripManager {
         let (spec, legs) = spec.into_plan(ctx.map);
         assert!(self.trips[trip.0].legs.is_empty());
         self.trips[trip.0].legs.extend(legs);
+        if let Some(legs) = carpool_legs(&self.trips, trip) {
+            self.trips[trip.0].legs = legs;
+        }
 
         match spec {
             TripSpec::VehicleAppearing {
@@ -201,16 +272,20 @@ impl TripManager {
                 } else {
                     PathConstraints::Car
                 };
-                let req = PathRequest::vehicle(
-                    start_pos,
-                    goal.goal_pos(constraints, ctx.map).unwrap(),
-                    constraints,
-                );
+                let charger = choose_charger(&vehicle, &goal, self.trips[trip.0].legs.get(1), ctx);
+                let end = charger
+                    .map(|(_, _, pos)| pos)
+                    .unwrap_or_else(|| goal.goal_pos(constraints, ctx.map).unwrap());
+                let req = PathRequest::vehicle(start_pos, end, constraints);
                 let person = person.id;
 
-                match ctx.map.pathfind(req) {
+                match self.pathfind_vehicle(trip, req, now, ctx) {
                     Ok(path) => {
-                        let router = goal.make_router(vehicle.id, path, ctx.map);
+                        let mut router = make_router(vehicle.id, path, &goal, charger, ctx.map);
+                        router.set_leave_at(self.next_departure(trip));
+                        if let Some(params) = self.routing_params.get(&trip) {
+                            router.set_routing_params(params.clone());
+                        }
                         ctx.scheduler.push(
                             now,
                             Command::SpawnCar(
@@ -472,6 +547,21 @@ impl TripManager {
             _ => unreachable!(),
         };
 
+        if let TripLeg::PickUp(_) | TripLeg::DropOff(_) = trip.legs[0] {
+            let id = trip.id;
+            self.carpool_stop(now, id, car, spot, ctx);
+            return;
+        }
+
+        let id = trip.id;
+        if let Some(rules) = parking_rules(spot, ctx.map) {
+            let fee = rules.fee(now, planned_stay(self.next_departure(id), now));
+            if fee > 0.0 {
+                self.events.push(Event::ParkingFeePaid(car, id, spot, fee));
+            }
+        }
+        let trip = &mut self.trips[id.0];
+
         match &trip.legs[0] {
             TripLeg::Walk(to) => match (spot, &to.connection) {
                 (ParkingSpot::Offstreet(b1, _), SidewalkPOI::Building(b2)) if b1 == *b2 => {
@@ -518,6 +608,34 @@ impl TripManager {
         trip.total_distance += distance_crossed;
 
         trip.assert_walking_leg(SidewalkSpot::deferred_parking_spot());
+        let id = trip.id;
+        if let Some(TripLeg::RideInCar(car)) = trip.legs.front() {
+            let car = *car;
+            self.passenger_reached_car(now, id, car, spot, ctx);
+        } else {
+            self.drive_from_parking_spot(now, id, spot, ctx);
+        }
+    }
+
+    fn pathfind_vehicle(
+        &self,
+        trip: TripID,
+        req: PathRequest,
+        now: Time,
+        ctx: &mut Ctx,
+    ) -> Result<Path> {
+        match self.routing_params.get(&trip) {
+            Some(params) => {
+                ctx.travel_times
+                    .pathfind_with_params(req, params, now, ctx.map, ctx.incidents)
+            }
+            None => ctx.travel_times.pathfind(req, now, ctx.map, ctx.incidents),
+        }
+    }
+
+    /// Start driving a parked car towards the goal of the trip's next leg.
+    fn drive_from_parking_spot(&mut self, now: Time, id: TripID, spot: ParkingSpot, ctx: &mut Ctx) {
+        let trip = &mut self.trips[id.0];
         let parked_car = ctx.parking.get_car_at_spot(spot).unwrap().clone();
         let drive_to = match trip.legs[0] {
             TripLeg::Drive(c, ref to) => {
@@ -531,6 +649,22 @@ impl TripManager {
             ctx.parking
                 .spot_to_driving_pos(parked_car.spot, &parked_car.vehicle, ctx.map);
         let end = drive_to.goal_pos(PathConstraints::Car, ctx.map).unwrap();
+
+        // A carpool driver may already be parked along the road of the next stop
+        if base_start.lane().road == end.lane().road
+            && matches!(
+                trip.legs.get(1),
+                Some(TripLeg::PickUp(_)) | Some(TripLeg::DropOff(_))
+            )
+        {
+            trip.legs.pop_front();
+            self.carpool_stop(now, id, parked_car.vehicle.id, spot, ctx);
+            return;
+        }
+
+        let charger = choose_charger(&parked_car.vehicle, &drive_to, trip.legs.get(1), ctx);
+        let end = charger.map(|(_, _, pos)| pos).unwrap_or(end);
+
         let req = match spot {
             ParkingSpot::Onstreet(_, _) => {
                 PathRequest::vehicle(base_start, end, PathConstraints::Car)
@@ -547,9 +681,14 @@ impl TripManager {
 
         let person = trip.person;
         let trip = trip.id;
-        match ctx.map.pathfind(req) {
+        match self.pathfind_vehicle(trip, req, now, ctx) {
             Ok(path) => {
-                let router = drive_to.make_router(parked_car.vehicle.id, path, ctx.map);
+                let mut router =
+                    make_router(parked_car.vehicle.id, path, &drive_to, charger, ctx.map);
+                router.set_leave_at(self.next_departure(trip));
+                if let Some(params) = self.routing_params.get(&trip) {
+                    router.set_routing_params(params.clone());
+                }
                 ctx.scheduler.push(
                     now,
                     Command::SpawnCar(
@@ -566,6 +705,206 @@ impl TripManager {
         }
     }
 
+    /// When does the person plan to start their next trip? None if this is their last one.
+    fn next_departure(&self, id: TripID) -> Option<Time> {
+        let person = &self.people[self.trips[id.0].person.0];
+        let idx = person.trips.iter().position(|t| *t == id)?;
+        person
+            .trips
+            .get(idx + 1)
+            .map(|t| self.trips[t.0].info.departure)
+    }
+
+    /// A carpool driver has parked to pick up or drop off somebody.
+    fn carpool_stop(
+        &mut self,
+        now: Time,
+        driver: TripID,
+        car: CarID,
+        spot: ParkingSpot,
+        ctx: &mut Ctx,
+    ) {
+        match self.trips[driver.0].legs[0] {
+            TripLeg::PickUp(passenger) => {
+                self.pick_up(now, driver, passenger, car, spot, ctx);
+            }
+            TripLeg::DropOff(passenger) => {
+                self.trips[driver.0].legs.pop_front();
+                self.drop_off(now, passenger, car, spot, ctx);
+                let occupancy = self.car_occupancy(driver, car);
+                self.events.push(Event::CarOccupancyChanged(car, occupancy));
+                self.drive_from_parking_spot(now, driver, spot, ctx);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    /// Start the passenger's trip, walking to the parked car. If the driver is early, the
+    /// passenger still leaves at their usual time. The driver waits until they arrive. If the
+    /// passenger isn't there, the driver just moves on.
+    fn pick_up(
+        &mut self,
+        now: Time,
+        driver: TripID,
+        passenger: TripID,
+        car: CarID,
+        spot: ParkingSpot,
+        ctx: &mut Ctx,
+    ) {
+        let trip = &self.trips[passenger.0];
+        let (start, end) = match (trip.info.start, trip.info.end) {
+            (TripEndpoint::Building(b1), TripEndpoint::Building(b2)) => (b1, b2),
+            _ => unreachable!(),
+        };
+        let person = &self.people[trip.person.0];
+
+        let problem = if trip.info.cancellation_reason.is_some() {
+            None
+        } else if trip.started || person.state != PersonState::Inside(start) {
+            Some(format!("{} wasn't at {} to be picked up", person.id, start))
+        } else {
+            let walk_from = SidewalkSpot::building(start, ctx.map);
+            let walk_to = SidewalkSpot::parking_spot(spot, ctx.map, ctx.parking);
+            let req = PathRequest::walking(walk_from.sidewalk_pos, walk_to.sidewalk_pos);
+            match ctx.map.pathfind(req) {
+                Ok(path) => {
+                    ctx.scheduler.push(
+                        trip.info.departure.max(now),
+                        Command::SpawnPed(CreatePedestrian {
+                            id: person.ped,
+                            speed: person.ped_speed,
+                            start: walk_from,
+                            goal: walk_to,
+                            path,
+                            trip: passenger,
+                            person: person.id,
+                        }),
+                    );
+                    let person = person.id;
+                    self.people[person.0].state = PersonState::Trip(passenger);
+                    let trip = &mut self.trips[passenger.0];
+                    trip.started = true;
+                    trip.legs.extend(vec![
+                        TripLeg::Walk(SidewalkSpot::deferred_parking_spot()),
+                        TripLeg::RideInCar(car),
+                        TripLeg::Walk(SidewalkSpot::building(end, ctx.map)),
+                    ]);
+                    return;
+                }
+                Err(err) => Some(err.to_string()),
+            }
+        };
+
+        // Nobody's getting in the car
+        if let Some(reason) = problem {
+            self.cancel_unstarted_trip(passenger, reason);
+        }
+        let legs = &mut self.trips[driver.0].legs;
+        assert_eq!(legs.pop_front(), Some(TripLeg::PickUp(passenger)));
+        if let Some(idx) = legs
+            .iter()
+            .position(|leg| *leg == TripLeg::DropOff(passenger))
+        {
+            // Also skip driving to the drop-off
+            legs.remove(idx);
+            legs.remove(idx - 1);
+        }
+        self.drive_from_parking_spot(now, driver, spot, ctx);
+    }
+
+    fn passenger_reached_car(
+        &mut self,
+        now: Time,
+        passenger: TripID,
+        car: CarID,
+        spot: ParkingSpot,
+        ctx: &mut Ctx,
+    ) {
+        let driver = match self.trips[passenger.0].shared_ride {
+            Some(SharedRide::Passenger(driver)) => driver,
+            _ => unreachable!(),
+        };
+        if self.trips[driver.0].info.cancellation_reason.is_some()
+            || self.trips[driver.0].legs.front() != Some(&TripLeg::PickUp(passenger))
+        {
+            self.cancel_trip(
+                now,
+                passenger,
+                "their ride left without them".to_string(),
+                None,
+                ctx,
+            );
+            return;
+        }
+        self.trips[driver.0].legs.pop_front();
+
+        let person = self.trips[passenger.0].person;
+        self.active_trip_mode
+            .insert(AgentID::CarPassenger(person, car), passenger);
+        self.events.push(Event::TripPhaseStarting(
+            passenger,
+            person,
+            None,
+            TripPhaseType::RidingInCar(car),
+        ));
+        let occupancy = self.car_occupancy(driver, car);
+        self.events.push(Event::CarOccupancyChanged(car, occupancy));
+
+        self.drive_from_parking_spot(now, driver, spot, ctx);
+    }
+
+    fn drop_off(
+        &mut self,
+        now: Time,
+        passenger: TripID,
+        car: CarID,
+        spot: ParkingSpot,
+        ctx: &mut Ctx,
+    ) {
+        let trip = &mut self.trips[passenger.0];
+        if trip.info.cancellation_reason.is_some()
+            || trip.legs.front() != Some(&TripLeg::RideInCar(car))
+        {
+            return;
+        }
+        trip.legs.pop_front();
+        self.active_trip_mode
+            .remove(&AgentID::CarPassenger(trip.person, car))
+            .unwrap();
+
+        // Just like for drivers, if we parked in the destination building, there's no need to
+        // walk anywhere
+        if let (ParkingSpot::Offstreet(b1, _), TripLeg::Walk(to)) = (spot, &trip.legs[0]) {
+            if to.connection == SidewalkPOI::Building(b1) {
+                trip.legs.pop_front().unwrap();
+                self.people[trip.person.0].state = PersonState::Inside(b1);
+                self.events
+                    .push(Event::PersonEntersBuilding(trip.person, b1));
+                self.trip_finished(now, passenger, ctx);
+                return;
+            }
+        }
+
+        self.spawn_ped(
+            now,
+            passenger,
+            SidewalkSpot::parking_spot(spot, ctx.map, ctx.parking),
+            ctx,
+        );
+    }
+
+    /// How many people are in a carpool driver's car, including them?
+    fn car_occupancy(&self, driver: TripID, car: CarID) -> usize {
+        let passengers = match self.trips[driver.0].shared_ride {
+            Some(SharedRide::Driver(ref passengers)) => passengers,
+            _ => unreachable!(),
+        };
+        1 + passengers
+            .iter()
+            .filter(|id| self.trips[id.0].legs.front() == Some(&TripLeg::RideInCar(car)))
+            .count()
+    }
+
     pub fn ped_ready_to_bike(
         &mut self,
         now: Time,
@@ -1026,7 +1365,14 @@ impl TripManager {
                         .map(|(spot, _)| *spot)
                         .or_else(|| {
                             ctx.parking
-                                .path_to_free_parking_spot(driving_lane, &vehicle, b, ctx.map)
+                                .path_to_free_parking_spot(
+                                    driving_lane,
+                                    &vehicle,
+                                    b,
+                                    None,
+                                    now,
+                                    ctx.map,
+                                )
                                 .map(|(_, spot, _)| spot)
                         })
                     {
@@ -1064,9 +1410,34 @@ impl TripManager {
             }
         }
 
+        // Anybody sharing the car is stranded too
+        if let Some(SharedRide::Driver(passengers)) = self.trips[id.0].shared_ride.clone() {
+            for passenger in passengers {
+                self.strand_passenger(now, passenger, ctx);
+            }
+        }
+
         self.start_delayed_trip(now, person, ctx);
     }
 
+    fn strand_passenger(&mut self, now: Time, id: TripID, ctx: &mut Ctx) {
+        let trip = &self.trips[id.0];
+        if trip.finished_at.is_some() || trip.info.cancellation_reason.is_some() {
+            return;
+        }
+        if !trip.started {
+            self.cancel_unstarted_trip(id, "their ride was cancelled".to_string());
+            return;
+        }
+        if let Some(TripLeg::RideInCar(car)) = trip.legs.front() {
+            self.active_trip_mode
+                .remove(&AgentID::CarPassenger(trip.person, *car));
+            self.cancel_trip(now, id, "their ride was cancelled".to_string(), None, ctx);
+        }
+        // Otherwise they're walking to or from the car. If they're on the way to it, they'll
+        // notice it's gone when they arrive.
+    }
+
     pub fn trip_abruptly_cancelled(&mut self, trip: TripID, agent: AgentID) {
         assert_eq!(self.active_trip_mode.remove(&agent), Some(trip));
     }
@@ -1105,6 +1476,11 @@ impl TripManager {
             TripLeg::Walk(_) => AgentID::Pedestrian(person.ped),
             TripLeg::Drive(c, _) => AgentID::Car(*c),
             TripLeg::RideBus(_, _) => AgentID::BusPassenger(person.id, person.on_bus.unwrap()),
+            TripLeg::RideInCar(c) => AgentID::CarPassenger(person.id, *c),
+            // The car is parked while somebody gets in or out
+            TripLeg::PickUp(_) | TripLeg::DropOff(_) => {
+                return TripResult::ModeChange;
+            }
         };
         if self.active_trip_mode.get(&a) == Some(&id) {
             TripResult::Ok(a)
@@ -1114,6 +1490,26 @@ impl TripManager {
         }
     }
 
+    /// Find active trips that are riding, waiting for, or walking towards any of these routes.
+    pub fn find_trips_riding_transit(
+        &self,
+        routes: &BTreeSet<TransitRouteID>,
+    ) -> Vec<(AgentID, TripID)> {
+        if routes.is_empty() {
+            return Vec::new();
+        }
+        let mut results = Vec::new();
+        for (agent, trip) in &self.active_trip_mode {
+            if self.trips[trip.0].legs.iter().any(|leg| match leg {
+                TripLeg::RideBus(r, _) => routes.contains(r),
+                _ => false,
+            }) {
+                results.push((*agent, *trip));
+            }
+        }
+        results
+    }
+
     /// This will be None for parked cars and buses. Should always work for pedestrians.
     pub fn agent_to_trip(&self, id: AgentID) -> Option<TripID> {
         self.active_trip_mode.get(&id).cloned()
@@ -1159,6 +1555,7 @@ impl TripManager {
             cyclists: 0,
 
             sov_drivers: 0,
+            car_passengers: 0,
 
             buses,
             trains,
@@ -1186,6 +1583,9 @@ impl TripManager {
                     }
                     VehicleType::Car | VehicleType::Bike => unreachable!(),
                 },
+                AgentID::CarPassenger(_, _) => {
+                    cnt.car_passengers += 1;
+                }
                 // These're counted separately
                 AgentID::Pedestrian(_) => {}
             }
@@ -1291,13 +1691,15 @@ impl TripManager {
                     .iter()
                     .map(|t| {
                         let trip = &self.trips[t.0];
-                        IndividTrip::new(
+                        let mut individ_trip = IndividTrip::new(
                             trip.info.departure,
                             trip.info.purpose,
                             trip.info.start,
                             trip.info.end,
                             trip.info.mode,
-                        )
+                        );
+                        individ_trip.carpool = trip.info.carpool;
+                        individ_trip
                     })
                     .collect(),
             });
@@ -1317,6 +1719,15 @@ struct Trip {
     // Not filled out until the trip starts
     legs: VecDeque<TripLeg>,
     person: PersonID,
+    shared_ride: Option<SharedRide>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+enum SharedRide {
+    /// The passengers' trips, in the order they're picked up
+    Driver(Vec<TripID>),
+    /// The driver's trip
+    Passenger(TripID),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -1329,6 +1740,8 @@ pub struct TripInfo {
     pub purpose: TripPurpose,
     /// Did a ScenarioModifier apply to this?
     pub modified: bool,
+    /// Is this trip part of a shared ride?
+    pub carpool: Option<Carpool>,
     pub cancellation_reason: Option<String>,
 }
 
@@ -1343,6 +1756,77 @@ impl Trip {
     }
 }
 
+/// Electric cars low on charge head for a charger near the destination instead, unless they're
+/// stopping for a carpool passenger first. Returns the destination, the charger's spot, and where
+/// to drive to park there.
+fn choose_charger(
+    vehicle: &Vehicle,
+    goal: &DrivingGoal,
+    next_leg: Option<&TripLeg>,
+    ctx: &Ctx,
+) -> Option<(BuildingID, ParkingSpot, Position)> {
+    match goal {
+        DrivingGoal::ParkNear(b)
+            if !matches!(
+                next_leg,
+                Some(TripLeg::PickUp(_)) | Some(TripLeg::DropOff(_))
+            ) =>
+        {
+            ctx.charging
+                .choose_charger(vehicle, *b, ctx.parking, ctx.map)
+                .map(|(spot, pos)| (*b, spot, pos))
+        }
+        _ => None,
+    }
+}
+
+fn make_router(
+    car: CarID,
+    path: Path,
+    goal: &DrivingGoal,
+    charger: Option<(BuildingID, ParkingSpot, Position)>,
+    map: &Map,
+) -> Router {
+    match charger {
+        Some((b, spot, pos)) => Router::park_at_charger(car, path, b, spot, pos.dist_along()),
+        None => goal.make_router(car, path, map),
+    }
+}
+
+/// Carpool drivers first pick up every passenger, then drop them off in the same order, before
+/// continuing to their own destination. Returns None if the trip isn't driving anybody.
+fn carpool_legs(trips: &[Trip], driver: TripID) -> Option<VecDeque<TripLeg>> {
+    let trip = &trips[driver.0];
+    let passengers = match trip.shared_ride {
+        Some(SharedRide::Driver(ref passengers)) => passengers,
+        _ => return None,
+    };
+    // The spawning might've failed
+    let idx = trip.legs.iter().position(
+        |leg| matches!(leg, TripLeg::Drive(c, _) if c.vehicle_type == VehicleType::Car),
+    )?;
+    let car = match trip.legs[idx] {
+        TripLeg::Drive(c, _) => c,
+        _ => unreachable!(),
+    };
+
+    let mut legs: VecDeque<TripLeg> = trip.legs.iter().take(idx).cloned().collect();
+    for id in passengers {
+        if let TripEndpoint::Building(b) = trips[id.0].info.start {
+            legs.push_back(TripLeg::Drive(car, DrivingGoal::ParkNear(b)));
+            legs.push_back(TripLeg::PickUp(*id));
+        }
+    }
+    for id in passengers {
+        if let TripEndpoint::Building(b) = trips[id.0].info.end {
+            legs.push_back(TripLeg::Drive(car, DrivingGoal::ParkNear(b)));
+            legs.push_back(TripLeg::DropOff(*id));
+        }
+    }
+    legs.extend(trip.legs.iter().skip(idx).cloned());
+    Some(legs)
+}
+
 /// These don't specify where the leg starts, since it might be unknown -- like when we drive and
 /// don't know where we'll wind up parking.
 #[derive(Serialize, Deserialize, PartialEq, Debug, Clone)]
@@ -1352,6 +1836,12 @@ pub(crate) enum TripLeg {
     Drive(CarID, DrivingGoal),
     /// Maybe get off at a stop, maybe ride off-map
     RideBus(TransitRouteID, Option<TransitStopID>),
+    /// Ride in somebody else's car. The driver's trip decides where it goes.
+    RideInCar(CarID),
+    /// A carpool driver has parked and waits for this passenger's trip to get in
+    PickUp(TripID),
+    /// A carpool driver has parked to let this passenger's trip out
+    DropOff(TripID),
 }
 
 pub enum TripResult<T> {
@@ -1422,9 +1912,138 @@ pub struct CommutersVehiclesCounts {
     pub cyclists: usize,
 
     pub sov_drivers: usize,
+    pub car_passengers: usize,
 
     pub buses: usize,
     pub trains: usize,
     pub bus_riders: usize,
     pub train_riders: usize,
 }
+
+#[cfg(test)]
+mod tests {
+    use synthpop::CarpoolID;
+
+    use super::*;
+
+    fn carpool_trip(
+        trips: &mut TripManager,
+        departure: Time,
+        role: CarpoolRole,
+        start: TripEndpoint,
+        end: TripEndpoint,
+    ) -> TripID {
+        let person = trips
+            .new_person(None, Speed::meters_per_second(1.0), Vec::new())
+            .id;
+        trips.new_trip(
+            person,
+            TripInfo {
+                departure,
+                mode: TripMode::Drive,
+                start,
+                end,
+                purpose: TripPurpose::Work,
+                modified: false,
+                carpool: Some(Carpool {
+                    id: CarpoolID(0),
+                    role,
+                }),
+                cancellation_reason: None,
+            },
+        )
+    }
+
+    fn at(minutes: usize) -> Time {
+        Time::START_OF_DAY + Duration::minutes(minutes)
+    }
+
+    fn b(id: usize) -> TripEndpoint {
+        TripEndpoint::Building(BuildingID(id))
+    }
+
+    #[test]
+    fn pick_up_then_drop_off() {
+        let mut trips = TripManager::new();
+        let driver = carpool_trip(&mut trips, at(0), CarpoolRole::Driver, b(0), b(1));
+        // Passengers are picked up in the order they leave, not the order they're listed
+        let late = carpool_trip(&mut trips, at(20), CarpoolRole::Passenger, b(2), b(3));
+        let early = carpool_trip(&mut trips, at(10), CarpoolRole::Passenger, b(4), b(5));
+        trips.new_carpool(vec![driver, late, early]);
+
+        assert_eq!(
+            trips.trips[late.0].shared_ride,
+            Some(SharedRide::Passenger(driver))
+        );
+        assert_eq!(
+            trips.trips[driver.0].shared_ride,
+            Some(SharedRide::Driver(vec![early, late]))
+        );
+
+        let car = CarID {
+            id: 0,
+            vehicle_type: VehicleType::Car,
+        };
+        trips.trips[driver.0]
+            .legs
+            .push_back(TripLeg::Drive(car, DrivingGoal::ParkNear(BuildingID(1))));
+        let park_near = |id| TripLeg::Drive(car, DrivingGoal::ParkNear(BuildingID(id)));
+        assert_eq!(
+            carpool_legs(&trips.trips, driver).unwrap(),
+            vec![
+                park_near(4),
+                TripLeg::PickUp(early),
+                park_near(2),
+                TripLeg::PickUp(late),
+                park_near(5),
+                TripLeg::DropOff(early),
+                park_near(3),
+                TripLeg::DropOff(late),
+                park_near(1),
+            ]
+        );
+        assert!(carpool_legs(&trips.trips, early).is_none());
+    }
+
+    #[test]
+    fn cancel_passengers_who_cant_ride() {
+        let mut trips = TripManager::new();
+        let alone = carpool_trip(&mut trips, at(0), CarpoolRole::Passenger, b(0), b(1));
+        trips.new_carpool(vec![alone]);
+        assert!(trips.trips[alone.0].info.cancellation_reason.is_some());
+        assert!(trips.trips[alone.0].shared_ride.is_none());
+
+        let driver = carpool_trip(&mut trips, at(0), CarpoolRole::Driver, b(0), b(1));
+        let from_border = carpool_trip(
+            &mut trips,
+            at(1),
+            CarpoolRole::Passenger,
+            TripEndpoint::Border(IntersectionID(0)),
+            b(1),
+        );
+        let mut passengers = Vec::new();
+        for i in 0..=MAX_CARPOOL_PASSENGERS {
+            passengers.push(carpool_trip(
+                &mut trips,
+                at(2 + i),
+                CarpoolRole::Passenger,
+                b(2),
+                b(3),
+            ));
+        }
+        let mut carpool = vec![driver, from_border];
+        carpool.extend(passengers.clone());
+        trips.new_carpool(carpool);
+
+        assert!(trips.trips[from_border.0]
+            .info
+            .cancellation_reason
+            .is_some());
+        let full = passengers.pop().unwrap();
+        assert!(trips.trips[full.0].info.cancellation_reason.is_some());
+        assert_eq!(
+            trips.trips[driver.0].shared_ride,
+            Some(SharedRide::Driver(passengers))
+        );
+    }
+}
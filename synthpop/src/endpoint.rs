@@ -1,11 +1,14 @@
 use geom::Pt2D;
 use map_model::{BuildingID, IntersectionID, Map, PathConstraints, PathRequest, Position};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::TripMode;
 
 /// Specifies where a trip begins or ends.
-#[derive(Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy)]
+#[derive(
+    Serialize, Deserialize, JsonSchema, PartialEq, Eq, PartialOrd, Ord, Debug, Clone, Copy,
+)]
 pub enum TripEndpoint {
     Building(BuildingID),
     Border(IntersectionID),
@@ -2,6 +2,7 @@
 //! simulation input data; import it here.
 
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::Deserialize;
 
 use geom::{Distance, FindClosest, LonLat, Time};
@@ -9,12 +10,12 @@ use map_model::Map;
 
 use crate::{IndividTrip, MapBorders, PersonSpec, TripEndpoint, TripMode, TripPurpose};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct ExternalPerson {
     pub trips: Vec<ExternalTrip>,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct ExternalTrip {
     pub departure: Time,
     pub origin: ExternalTripEndpoint,
@@ -23,7 +24,7 @@ pub struct ExternalTrip {
     pub purpose: TripPurpose,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub enum ExternalTripEndpoint {
     TripEndpoint(TripEndpoint),
     Position(LonLat),
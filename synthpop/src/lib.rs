@@ -10,6 +10,7 @@ extern crate anyhow;
 #[macro_use]
 extern crate log;
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use abstutil::{deserialize_usize, serialize_usize};
@@ -20,7 +21,10 @@ pub use self::counts::TrafficCounts;
 pub use self::endpoint::TripEndpoint;
 pub use self::external::{ExternalPerson, ExternalTrip, ExternalTripEndpoint};
 pub use self::modifier::ScenarioModifier;
-pub use self::scenario::{IndividTrip, PersonSpec, Scenario, TripPurpose};
+pub use self::scenario::{
+    Carpool, CarpoolID, CarpoolRole, IndividTrip, PersonSpec, Scenario, TripPurpose,
+    MAX_CARPOOL_PASSENGERS,
+};
 
 mod borders;
 mod counts;
@@ -34,7 +38,9 @@ mod scenario;
 ///
 /// Note most trips are "multi-modal" -- somebody has to walk a bit before and after parking their
 /// car.
-#[derive(Serialize, Deserialize, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord)]
+#[derive(
+    Serialize, Deserialize, JsonSchema, PartialEq, Eq, Debug, Clone, Copy, PartialOrd, Ord,
+)]
 pub enum TripMode {
     Walk,
     Bike,
@@ -1,19 +1,23 @@
 extern crate rand;
 
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 
 use rand::Rng;
 use rand_xorshift::XorShiftRng;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use abstutil::Timer;
-use geom::{Duration, Time};
+use geom::{Distance, Duration, LonLat, Polygon, Ring, Time};
 use map_model::Map;
 
-use crate::{Scenario, TripMode};
+use crate::{
+    Carpool, CarpoolRole, IndividTrip, OrigPersonID, PersonSpec, Scenario, TripEndpoint, TripMode,
+    MAX_CARPOOL_PASSENGERS,
+};
 
 /// Transforms an existing Scenario before instantiating it.
-#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
+#[derive(PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum ScenarioModifier {
     RepeatDays(usize),
     RepeatDaysNoise {
@@ -26,9 +30,25 @@ pub enum ScenarioModifier {
         from_modes: BTreeSet<TripMode>,
         /// If `None`, then just cancel the trip.
         to_mode: Option<TripMode>,
+        /// When changing to `TripMode::Drive`, try to share a ride with somebody already driving
+        /// between nearby places at a similar time, instead of driving alone.
+        #[serde(default)]
+        carpool: bool,
+        /// Only change trips starting or ending inside this polygon
+        #[serde(default)]
+        area: Option<Vec<LonLat>>,
     },
     /// Scenario name
     AddExtraTrips(String),
+    /// Scale the number of people by a percentage. Below 100 removes people, above 100 copies
+    /// them.
+    ScaleDemand(usize),
+    /// Only keep trips starting or ending inside this polygon. People with no trips left are
+    /// removed.
+    FilterArea(Vec<LonLat>),
+    /// Move every departure earlier or later. Departures can't move before midnight, so trips
+    /// piling up there leave one second apart, in their original order.
+    ShiftDepartures(Duration),
 }
 
 impl ScenarioModifier {
@@ -46,7 +66,20 @@ impl ScenarioModifier {
                 departure_filter,
                 from_modes,
                 to_mode,
+                carpool,
+                area,
             } => {
+                let polygon = match area {
+                    Some(pts) => match area_polygon(pts, map) {
+                        Some(polygon) => Some(polygon),
+                        None => {
+                            warn!("Not changing modes, because the area is invalid");
+                            return s;
+                        }
+                    },
+                    None => None,
+                };
+                let mut new_passengers = Vec::new();
                 for (idx, person) in s.people.iter_mut().enumerate() {
                     // This is "stable" as percentage increases. If you modify 10% of people in one
                     // run, then modify 11% in another, the modified people in the 11% run will be
@@ -55,7 +88,7 @@ impl ScenarioModifier {
                         continue;
                     }
                     let mut cancel_rest = false;
-                    for trip in &mut person.trips {
+                    for (trip_idx, trip) in person.trips.iter_mut().enumerate() {
                         if cancel_rest {
                             trip.modified = true;
                             trip.cancelled = true;
@@ -68,9 +101,20 @@ impl ScenarioModifier {
                         if !from_modes.contains(&trip.mode) {
                             continue;
                         }
+                        if let Some(ref polygon) = polygon {
+                            if !touches_area(trip, polygon, map) {
+                                continue;
+                            }
+                        }
+                        // Any shared ride this trip was part of is broken up. If this was the
+                        // driver, the passengers drive themselves instead.
+                        trip.carpool = None;
                         if let Some(to_mode) = *to_mode {
                             trip.mode = to_mode;
                             trip.modified = true;
+                            if *carpool && to_mode == TripMode::Drive {
+                                new_passengers.push((idx, trip_idx));
+                            }
                         } else {
                             trip.modified = true;
                             trip.cancelled = true;
@@ -80,6 +124,8 @@ impl ScenarioModifier {
                         }
                     }
                 }
+                form_carpools(&mut s, new_passengers, map);
+                drop_orphaned_passengers(&mut s);
                 s
             }
             // TODO This doesn't work on web!
@@ -88,14 +134,27 @@ impl ScenarioModifier {
                     abstio::path_scenario(map.get_name(), name),
                     &mut Timer::throwaway(),
                 );
+                let carpool_offset = s.new_carpool_id().0;
                 for mut p in other.people {
                     for trip in &mut p.trips {
                         trip.modified = true;
+                        if let Some(ref mut carpool) = trip.carpool {
+                            carpool.id.0 += carpool_offset;
+                        }
                     }
                     s.people.push(p);
                 }
                 s
             }
+            ScenarioModifier::ScaleDemand(pct) => scale_demand(s, *pct),
+            ScenarioModifier::FilterArea(pts) => match area_polygon(pts, map) {
+                Some(polygon) => filter_area(s, &polygon, map),
+                None => {
+                    warn!("Not filtering trips, because the area is invalid");
+                    s
+                }
+            },
+            ScenarioModifier::ShiftDepartures(dt) => shift_departures(s, *dt),
         }
     }
 
@@ -114,16 +173,35 @@ impl ScenarioModifier {
                 to_mode,
                 departure_filter,
                 from_modes,
+                carpool,
+                area,
             } => format!(
                 "change all trips for {}% of people of types {:?} leaving between {} and {} to \
-                 {:?}",
+                 {:?}{}{}",
                 pct_ppl,
                 from_modes,
                 departure_filter.0.ampm_tostring(),
                 departure_filter.1.ampm_tostring(),
-                to_mode.map(|m| m.verb())
+                to_mode.map(|m| m.verb()),
+                if *carpool { " (carpooling)" } else { "" },
+                if area.is_some() {
+                    ", only in an area"
+                } else {
+                    ""
+                }
             ),
             ScenarioModifier::AddExtraTrips(name) => format!("Add extra trips from {}", name),
+            ScenarioModifier::ScaleDemand(pct) => format!("scale the number of people to {}%", pct),
+            ScenarioModifier::FilterArea(_) => {
+                "only keep trips starting or ending in an area".to_string()
+            }
+            ScenarioModifier::ShiftDepartures(dt) => {
+                if *dt < Duration::ZERO {
+                    format!("depart {} earlier", -*dt)
+                } else {
+                    format!("depart {} later", dt)
+                }
+            }
         }
     }
 }
@@ -143,13 +221,18 @@ fn repeat_days(
     rng: &mut XorShiftRng,
 ) -> Scenario {
     s.scenario_name = format!("{} (repeated {} days)", s.scenario_name, days);
+    // Each day's shared rides are different carpools
+    let num_carpools = s.new_carpool_id().0;
     for person in &mut s.people {
         let mut trips = Vec::new();
         let mut offset = Duration::ZERO;
-        for _ in 0..days {
+        for day in 0..days {
             for trip in &person.trips {
                 let mut new = trip.clone();
                 new.depart += offset;
+                if let Some(ref mut carpool) = new.carpool {
+                    carpool.id.0 += day * num_carpools;
+                }
                 if let Some(noise_v) = noise {
                     // + or - noise_v
                     let noise_rnd = Duration::seconds(
@@ -166,3 +249,330 @@ fn repeat_days(
     }
     s
 }
+
+// The same percentage of every 100 people is kept or copied, so this is "stable" like ChangeMode.
+// Copies drive their own car, instead of joining any shared ride.
+fn scale_demand(mut s: Scenario, pct: usize) -> Scenario {
+    let orig_people = std::mem::take(&mut s.people);
+    for (idx, person) in orig_people.into_iter().enumerate() {
+        let copies = pct / 100 + if idx % 100 < pct % 100 { 1 } else { 0 };
+        for copy in 0..copies {
+            let mut new = person.clone();
+            if copy > 0 {
+                for trip in &mut new.trips {
+                    trip.carpool = None;
+                    trip.modified = true;
+                }
+            }
+            s.people.push(new);
+        }
+    }
+    drop_orphaned_passengers(&mut s);
+    s
+}
+
+// Each person keeps the trips touching the area. Since a person's trips have to be continuous, a
+// gap in the middle splits them into separate people. The first piece keeps the person's original
+// ID, and the others get new ones, so no two people share an ID.
+fn filter_area(s: Scenario, polygon: &Polygon, map: &Map) -> Scenario {
+    keep_trips(s, |trip| touches_area(trip, polygon, map))
+}
+
+fn keep_trips<F: Fn(&IndividTrip) -> bool>(mut s: Scenario, keep: F) -> Scenario {
+    let orig_people = std::mem::take(&mut s.people);
+    let mut next_id = orig_people
+        .iter()
+        .filter_map(|person| person.orig_id)
+        .map(|id| id.0 + 1)
+        .max()
+        .unwrap_or(0);
+    for person in orig_people {
+        let mut pieces: Vec<Vec<IndividTrip>> = vec![Vec::new()];
+        for trip in person.trips {
+            if keep(&trip) {
+                pieces.last_mut().unwrap().push(trip);
+            } else if !pieces.last().unwrap().is_empty() {
+                pieces.push(Vec::new());
+            }
+        }
+        for (idx, trips) in pieces.into_iter().filter(|x| !x.is_empty()).enumerate() {
+            let orig_id = if idx == 0 {
+                person.orig_id
+            } else {
+                person.orig_id.map(|_| {
+                    next_id += 1;
+                    OrigPersonID(next_id - 1, 0)
+                })
+            };
+            s.people.push(PersonSpec { orig_id, trips });
+        }
+    }
+    drop_orphaned_passengers(&mut s);
+    s
+}
+
+// A person's departures have to stay strictly increasing, so trips clamped to midnight are spread
+// out, one second after the previous.
+fn shift_departures(mut s: Scenario, dt: Duration) -> Scenario {
+    for person in &mut s.people {
+        let mut prev: Option<Time> = None;
+        for trip in &mut person.trips {
+            trip.depart = trip.depart.clamped_sub(-dt);
+            if let Some(prev) = prev {
+                if trip.depart <= prev {
+                    trip.depart = prev + Duration::seconds(1.0);
+                }
+            }
+            prev = Some(trip.depart);
+            trip.modified = true;
+        }
+    }
+    s
+}
+
+// If the driver of a shared ride was removed, the passengers drive themselves instead.
+fn drop_orphaned_passengers(s: &mut Scenario) {
+    let drivers: BTreeSet<_> = s
+        .all_trips()
+        .filter_map(|trip| trip.carpool)
+        .filter(|carpool| carpool.role == CarpoolRole::Driver)
+        .map(|carpool| carpool.id)
+        .collect();
+    for person in &mut s.people {
+        for trip in &mut person.trips {
+            if let Some(carpool) = trip.carpool {
+                if carpool.role == CarpoolRole::Passenger && !drivers.contains(&carpool.id) {
+                    trip.carpool = None;
+                    trip.modified = true;
+                }
+            }
+        }
+    }
+}
+
+// None if the points don't form a valid polygon
+fn area_polygon(pts: &[LonLat], map: &Map) -> Option<Polygon> {
+    if pts.len() < 3 {
+        return None;
+    }
+    let mut pts = map.get_gps_bounds().convert(pts);
+    if pts.first() != pts.last() {
+        pts.push(pts[0]);
+    }
+    Ring::new(pts).ok().map(|ring| ring.into_polygon())
+}
+
+fn touches_area(trip: &IndividTrip, polygon: &Polygon, map: &Map) -> bool {
+    polygon.contains_pt(trip.origin.pt(map)) || polygon.contains_pt(trip.destination.pt(map))
+}
+
+// Match each of these (person, trip) indices to somebody else driving alone between nearby places
+// at a similar time, turning the trip into a passenger of that driver. Trips that can't be matched
+// stay as solo driving trips.
+fn form_carpools(s: &mut Scenario, new_passengers: Vec<(usize, usize)>, map: &Map) {
+    let max_departure_diff = Duration::minutes(15);
+    let max_endpoint_dist = Distance::meters(1000.0);
+
+    // Bucket potential drivers by departure time and origin, so we only check nearby candidates
+    let bucket = |depart: Time, endpt: TripEndpoint| {
+        let pt = endpt.pt(map);
+        (
+            (depart.inner_seconds() / max_departure_diff.inner_seconds()).floor() as i64,
+            (pt.x() / max_endpoint_dist.inner_meters()).floor() as i64,
+            (pt.y() / max_endpoint_dist.inner_meters()).floor() as i64,
+        )
+    };
+    let passenger_set: BTreeSet<(usize, usize)> = new_passengers.iter().cloned().collect();
+    let mut drivers: BTreeMap<(i64, i64, i64), Vec<(usize, usize)>> = BTreeMap::new();
+    for (person_idx, person) in s.people.iter().enumerate() {
+        for (trip_idx, trip) in person.trips.iter().enumerate() {
+            if trip.mode == TripMode::Drive
+                && !trip.cancelled
+                && trip.carpool.is_none()
+                && !passenger_set.contains(&(person_idx, trip_idx))
+            {
+                drivers
+                    .entry(bucket(trip.depart, trip.origin))
+                    .or_insert_with(Vec::new)
+                    .push((person_idx, trip_idx));
+            }
+        }
+    }
+
+    let mut next_id = s.new_carpool_id();
+    // For each driver with passengers, the carpool and how many passengers they have
+    let mut assigned: BTreeMap<(usize, usize), (Carpool, usize)> = BTreeMap::new();
+    for (person_idx, trip_idx) in new_passengers {
+        let trip = &s.people[person_idx].trips[trip_idx];
+        // Passengers are picked up and dropped off at buildings
+        if !matches!(trip.origin, TripEndpoint::Building(_))
+            || !matches!(trip.destination, TripEndpoint::Building(_))
+        {
+            continue;
+        }
+        let (time_bucket, x, y) = bucket(trip.depart, trip.origin);
+        let mut found = None;
+        'search: for dt in -1..=1 {
+            for dx in -1..=1 {
+                for dy in -1..=1 {
+                    for (driver_person, driver_trip) in drivers
+                        .get(&(time_bucket + dt, x + dx, y + dy))
+                        .into_iter()
+                        .flatten()
+                    {
+                        if *driver_person == person_idx {
+                            continue;
+                        }
+                        if assigned
+                            .get(&(*driver_person, *driver_trip))
+                            .map(|(_, cnt)| *cnt >= MAX_CARPOOL_PASSENGERS)
+                            .unwrap_or(false)
+                        {
+                            continue;
+                        }
+                        let driver = &s.people[*driver_person].trips[*driver_trip];
+                        if (driver.depart - trip.depart).abs() <= max_departure_diff
+                            && driver.origin.pt(map).dist_to(trip.origin.pt(map))
+                                <= max_endpoint_dist
+                            && driver.destination.pt(map).dist_to(trip.destination.pt(map))
+                                <= max_endpoint_dist
+                        {
+                            found = Some((*driver_person, *driver_trip));
+                            break 'search;
+                        }
+                    }
+                }
+            }
+        }
+
+        if let Some(driver) = found {
+            let (carpool, cnt) = assigned.entry(driver).or_insert_with(|| {
+                let carpool = Carpool {
+                    id: next_id,
+                    role: CarpoolRole::Driver,
+                };
+                next_id.0 += 1;
+                (carpool, 0)
+            });
+            *cnt += 1;
+            let id = carpool.id;
+
+            let trip = &mut s.people[person_idx].trips[trip_idx];
+            trip.carpool = Some(Carpool {
+                id,
+                role: CarpoolRole::Passenger,
+            });
+        }
+    }
+
+    for ((person_idx, trip_idx), (carpool, _)) in assigned {
+        let trip = &mut s.people[person_idx].trips[trip_idx];
+        trip.carpool = Some(carpool);
+        trip.modified = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use abstio::MapName;
+    use map_model::IntersectionID;
+
+    use super::*;
+    use crate::{CarpoolID, TripPurpose};
+
+    // A trip between two borders
+    fn trip(from: usize, to: usize) -> IndividTrip {
+        IndividTrip::new(
+            Time::START_OF_DAY,
+            TripPurpose::Work,
+            TripEndpoint::Border(IntersectionID(from)),
+            TripEndpoint::Border(IntersectionID(to)),
+            TripMode::Drive,
+        )
+    }
+
+    fn scenario(people: Vec<(Option<OrigPersonID>, Vec<IndividTrip>)>) -> Scenario {
+        Scenario {
+            scenario_name: "test".to_string(),
+            map_name: MapName::new("zz", "test", "test"),
+            people: people
+                .into_iter()
+                .map(|(orig_id, trips)| PersonSpec { orig_id, trips })
+                .collect(),
+            only_seed_buses: None,
+            transit_capacities: BTreeMap::new(),
+        }
+    }
+
+    #[test]
+    fn split_people_get_distinct_ids() {
+        let s = scenario(vec![
+            (
+                Some(OrigPersonID(5, 1)),
+                vec![trip(0, 1), trip(1, 2), trip(2, 0), trip(0, 3)],
+            ),
+            (Some(OrigPersonID(7, 2)), vec![trip(1, 2)]),
+            (None, vec![trip(0, 1), trip(1, 2), trip(2, 0)]),
+        ]);
+        // Only keep trips touching the first border
+        let s = keep_trips(s, |trip| {
+            trip.origin == TripEndpoint::Border(IntersectionID(0))
+                || trip.destination == TripEndpoint::Border(IntersectionID(0))
+        });
+
+        let people: Vec<(Option<OrigPersonID>, usize)> = s
+            .people
+            .iter()
+            .map(|person| (person.orig_id, person.trips.len()))
+            .collect();
+        assert_eq!(
+            people,
+            vec![
+                (Some(OrigPersonID(5, 1)), 1),
+                (Some(OrigPersonID(8, 0)), 2),
+                (None, 1),
+                (None, 1),
+            ]
+        );
+    }
+
+    #[test]
+    fn shift_departures_before_midnight() {
+        let mut trips = vec![trip(0, 1), trip(1, 2), trip(2, 0)];
+        trips[0].depart = Time::START_OF_DAY + Duration::minutes(10);
+        trips[1].depart = Time::START_OF_DAY + Duration::minutes(20);
+        trips[2].depart = Time::START_OF_DAY + Duration::hours(3);
+        let s = shift_departures(scenario(vec![(None, trips)]), -Duration::hours(1));
+
+        let departures: Vec<Time> = s.people[0].trips.iter().map(|t| t.depart).collect();
+        assert_eq!(
+            departures,
+            vec![
+                Time::START_OF_DAY,
+                Time::START_OF_DAY + Duration::seconds(1.0),
+                Time::START_OF_DAY + Duration::hours(2),
+            ]
+        );
+        assert!(s.people[0].check_schedule().is_ok());
+    }
+
+    #[test]
+    fn scale_demand_copies() {
+        let mut shared = trip(0, 1);
+        shared.carpool = Some(Carpool {
+            id: CarpoolID(0),
+            role: CarpoolRole::Driver,
+        });
+        let s = scale_demand(
+            scenario(vec![(None, vec![shared]), (None, vec![trip(1, 0)])]),
+            101,
+        );
+
+        // Only the first of every 100 people is copied
+        assert_eq!(s.people.len(), 3);
+        // Copies drive alone
+        assert!(s.people[0].trips[0].carpool.is_some());
+        assert!(s.people[1].trips[0].carpool.is_none());
+        assert!(s.people[1].trips[0].modified);
+    }
+}
@@ -1,11 +1,12 @@
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt;
 
 use anyhow::Result;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use abstio::{CityName, MapName};
-use abstutil::prettyprint_usize;
+use abstutil::{deserialize_appended, prettyprint_usize};
 use geom::Time;
 use map_model::Map;
 
@@ -20,6 +21,10 @@ pub struct Scenario {
     pub people: Vec<PersonSpec>,
     /// None means seed all buses. Otherwise the route name must be present here.
     pub only_seed_buses: Option<BTreeSet<String>>,
+    /// How many riders fit on each vehicle of some transit routes, keyed by the route name. Other
+    /// routes use the default capacity of their vehicle type.
+    #[serde(default, deserialize_with = "deserialize_appended")]
+    pub transit_capacities: BTreeMap<String, usize>,
 }
 
 #[derive(Clone, Serialize, Deserialize, Debug)]
@@ -42,8 +47,33 @@ pub struct IndividTrip {
     pub cancelled: bool,
     /// Did a ScenarioModifier affect this?
     pub modified: bool,
+    /// Is this trip part of a shared ride?
+    #[serde(default)]
+    pub carpool: Option<Carpool>,
 }
 
+/// Identifies one shared ride within a scenario.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct CarpoolID(pub usize);
+
+/// Several people can share one car. Every trip in a carpool uses `TripMode::Drive`. Exactly one
+/// of them drives their own car; the passengers are picked up at their origin and dropped off at
+/// their destination along the way. Passengers must start and end at buildings.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Carpool {
+    pub id: CarpoolID,
+    pub role: CarpoolRole,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CarpoolRole {
+    Driver,
+    Passenger,
+}
+
+/// A car can pick up at most this many passengers.
+pub const MAX_CARPOOL_PASSENGERS: usize = 3;
+
 impl IndividTrip {
     pub fn new(
         depart: Time,
@@ -60,12 +90,24 @@ impl IndividTrip {
             purpose,
             cancelled: false,
             modified: false,
+            carpool: None,
         }
     }
+
+    /// Does somebody else drive this person around?
+    pub fn is_carpool_passenger(&self) -> bool {
+        matches!(
+            self.carpool,
+            Some(Carpool {
+                role: CarpoolRole::Passenger,
+                ..
+            })
+        )
+    }
 }
 
 /// Lifted from Seattle's Soundcast model, but seems general enough to use anyhere.
-#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+#[derive(Serialize, Deserialize, JsonSchema, Debug, Clone, Copy)]
 pub enum TripPurpose {
     Home,
     Work,
@@ -117,6 +159,7 @@ impl Scenario {
             map_name: map.get_name().clone(),
             people: Vec::new(),
             only_seed_buses: Some(BTreeSet::new()),
+            transit_capacities: BTreeMap::new(),
         }
     }
 
@@ -143,6 +186,16 @@ impl Scenario {
         self.people.iter().flat_map(|p| p.trips.iter())
     }
 
+    /// Returns an ID not used by any carpool in this scenario yet.
+    pub fn new_carpool_id(&self) -> CarpoolID {
+        CarpoolID(
+            self.all_trips()
+                .filter_map(|trip| trip.carpool.map(|c| c.id.0 + 1))
+                .max()
+                .unwrap_or(0),
+        )
+    }
+
     pub fn default_scenario_for_map(name: &MapName) -> String {
         if name.city == CityName::seattle()
             && abstio::file_exists(abstio::path_scenario(name, "weekday"))
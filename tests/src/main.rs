@@ -1,5 +1,6 @@
 //! Integration tests
 
+use std::collections::{BTreeMap, BTreeSet};
 use std::io::Write;
 
 use anyhow::{bail, Result};
@@ -8,18 +9,28 @@ use rand::seq::SliceRandom;
 
 use abstio::{CityName, MapName};
 use abstutil::Timer;
-use geom::{Duration, Time};
-use map_model::{IntersectionID, LaneType, Map, Perimeter, RoadID};
-use sim::{AlertHandler, PrebakeSummary, Sim, SimFlags, SimOptions};
+use geom::{Distance, Duration, Time};
+use map_model::{
+    AlternativeRoutes, Charge, DirectedRoadID, EditCmd, EditTransitRoute, EditTransitStop,
+    IntersectionID, LaneID, LaneType, Map, PathConstraints, PathRequest, PathStepV2, Perimeter,
+    Position, RoadID, RoadPricing, RoutingParams, Traversable,
+};
+use sim::{
+    AgentID, AlertHandler, Analytics, CarID, Event, ExternalDecision, PrebakeSummary, Sim,
+    SimFlags, SimOptions, TripID, VehicleSpec, VehicleType,
+};
 use synthpop::{IndividTrip, PersonSpec, Scenario, TripEndpoint, TripMode, TripPurpose};
 
 fn main() -> Result<()> {
     abstutil::logger::setup();
     test_blockfinding()?;
-    test_lane_changing(&import_map(abstio::path(
-        "../tests/input/lane_selection.osm",
-    )))?;
+    let lane_selection = import_map(abstio::path("../tests/input/lane_selection.osm"));
+    test_lane_changing(&lane_selection)?;
+    test_external_cars(&lane_selection)?;
+    test_road_pricing_fees(&lane_selection)?;
     test_map_importer()?;
+    test_transit_edits()?;
+    test_alternative_routes()?;
     check_proposals()?;
     ab_test_spurious_diff()?;
     bus_test()?;
@@ -98,6 +109,76 @@ fn smoke_test() -> Result<()> {
     Ok(())
 }
 
+/// Verify alternative routes between far-apart points are really different and not much slower
+/// than the best, with default and custom routing params.
+fn test_alternative_routes() -> Result<()> {
+    let mut timer = Timer::new("test alternative routes");
+    let map = map_model::Map::load_synchronously(MapName::seattle("montlake").path(), &mut timer);
+    let opts = AlternativeRoutes::default();
+
+    let driving_lanes: Vec<_> = map.all_lanes().filter(|l| l.is_driving()).collect();
+    let start = driving_lanes[0];
+    let end = driving_lanes
+        .iter()
+        .max_by_key(|l| {
+            start
+                .lane_center_pts
+                .middle()
+                .dist_to(l.lane_center_pts.middle())
+        })
+        .unwrap();
+    let req = PathRequest::vehicle(
+        Position::start(start.id),
+        Position::end(end.id, &map),
+        PathConstraints::Car,
+    );
+
+    let custom = RoutingParams {
+        unprotected_turn_penalty: Duration::seconds(60.0),
+        ..Default::default()
+    };
+    for params in [map.routing_params().clone(), custom] {
+        let routes = map.pathfind_alternatives_with_params(req.clone(), &params, &opts);
+        if routes.len() != opts.max_routes {
+            bail!("Only found {} routes for {}", routes.len(), req);
+        }
+
+        let best = routes[0].get_cost();
+        let roads: Vec<BTreeSet<DirectedRoadID>> = routes
+            .iter()
+            .map(|path| {
+                path.get_steps()
+                    .iter()
+                    .filter_map(|step| match step {
+                        PathStepV2::Along(dr) => Some(*dr),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .collect();
+        for (idx, path) in routes.iter().enumerate() {
+            if path.get_cost() > opts.max_cost_ratio * best {
+                bail!(
+                    "Route {} for {} costs {}, but the best costs {}",
+                    idx,
+                    req,
+                    path.get_cost(),
+                    best
+                );
+            }
+            let length = |dr: &DirectedRoadID| map.get_r(dr.road).length().inner_meters();
+            let total: f64 = roads[idx].iter().map(length).sum();
+            for prev in &roads[..idx] {
+                let shared: f64 = roads[idx].intersection(prev).map(length).sum();
+                if shared / total > opts.max_overlap {
+                    bail!("Route {} for {} isn't different enough", idx, req);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
 /// Verify all edits under version control can be correctly apply to their map.
 fn check_proposals() -> Result<()> {
     let mut timer = Timer::new("check all proposals");
@@ -383,3 +464,204 @@ fn bus_test() -> Result<()> {
     }
     Ok(())
 }
+
+/// Verify externally controlled cars wait for decisions, and bad decisions are errors instead of
+/// crashes.
+fn test_external_cars(map: &Map) -> Result<()> {
+    let mut sim = Sim::new(map, SimOptions::new("test_external_cars"));
+    let spec = VehicleSpec {
+        vehicle_type: VehicleType::Car,
+        length: Distance::meters(4.5),
+        max_speed: None,
+        electric: false,
+    };
+
+    let missing = LaneID {
+        road: RoadID(map.all_roads().len()),
+        offset: 0,
+    };
+    if sim
+        .spawn_external_car(
+            spec.clone(),
+            Position::new(missing, Distance::ZERO),
+            false,
+            map,
+        )
+        .is_ok()
+    {
+        bail!("Spawned a car on {}, which doesn't exist", missing);
+    }
+
+    let turn = map
+        .all_lanes()
+        .filter(|l| l.is_driving() && l.length() > Distance::meters(20.0))
+        .flat_map(|l| map.get_turns_from_lane(l.id))
+        .find(|t| map.get_l(t.id.dst).is_driving())
+        .unwrap()
+        .id;
+    let car = sim.spawn_external_car(spec, Position::start(turn.src), false, map)?;
+    sim.timed_step(
+        map,
+        Duration::minutes(5),
+        &mut None,
+        &mut Timer::throwaway(),
+    );
+    let state = sim.external_car_state(car, map).unwrap();
+    if !state.waiting || state.on != Traversable::Lane(turn.src) {
+        bail!("{} should be waiting at the end of {}", car, turn.src);
+    }
+
+    if sim
+        .external_decision(car, ExternalDecision::ChangeLanes(missing), map)
+        .is_ok()
+    {
+        bail!("{} changed to {}, which doesn't exist", car, missing);
+    }
+
+    // Cars waiting for a decision resume as soon as it arrives
+    sim.external_decision(car, ExternalDecision::Turn(turn), map)?;
+    sim.timed_step(
+        map,
+        Duration::minutes(2),
+        &mut None,
+        &mut Timer::throwaway(),
+    );
+    if sim.external_car_state(car, map).unwrap().on == Traversable::Lane(turn.src) {
+        bail!("{} didn't resume after deciding to take {}", car, turn);
+    }
+    Ok(())
+}
+
+/// Verify only private cars pay road pricing fees, every time they enter a tolled road.
+fn test_road_pricing_fees(map: &Map) -> Result<()> {
+    let mut map = map.clone();
+    let turn = map
+        .all_turns()
+        .find(|t| {
+            !t.between_sidewalks()
+                && map.get_l(t.id.src).is_driving()
+                && map.get_l(t.id.dst).is_driving()
+        })
+        .unwrap()
+        .id;
+    let mut pricing = RoadPricing::default();
+    pricing.tolls.insert(turn.dst.road, Charge::flat(3.0));
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(EditCmd::ChangeRoadPricing {
+        old: map.get_road_pricing().clone(),
+        new: pricing,
+    });
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+
+    let mut analytics = Analytics::new(true);
+    // Normally recorded when each vehicle starts its path
+    if let Some((mvmnt, _)) = map.get_movement_for_traffic_signal(turn) {
+        analytics.demand.insert(mvmnt, 5);
+    }
+    let enter = |vehicle_type, trip| {
+        Event::AgentEntersTraversable(
+            AgentID::Car(CarID {
+                id: 0,
+                vehicle_type,
+            }),
+            trip,
+            Traversable::Turn(turn),
+            None,
+        )
+    };
+    for ev in [
+        enter(VehicleType::Car, Some(TripID(0))),
+        enter(VehicleType::Car, Some(TripID(0))),
+        enter(VehicleType::Car, Some(TripID(1))),
+        enter(VehicleType::Bike, Some(TripID(2))),
+        enter(VehicleType::Bus, None),
+    ] {
+        analytics.event(ev, Time::START_OF_DAY, &map);
+    }
+    let expected: BTreeMap<TripID, f64> = vec![(TripID(0), 6.0), (TripID(1), 3.0)]
+        .into_iter()
+        .collect();
+    if analytics.road_pricing_fees != expected {
+        bail!(
+            "Collected fees {:?}, but expected {:?}",
+            analytics.road_pricing_fees,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Verify edits deleting a transit stop that a route still uses are rejected.
+fn test_transit_edits() -> Result<()> {
+    let mut map = import_map(abstio::path("../tests/input/left_turn_and_bike_lane.osm"));
+
+    // Add a stop and a route using it
+    let road = map
+        .all_roads()
+        .iter()
+        .find(|r| {
+            r.lanes.iter().any(|l| l.is_walkable())
+                && r.lanes
+                    .iter()
+                    .any(|l| PathConstraints::Bus.can_use(l, &map))
+        })
+        .unwrap();
+    let sidewalk = road.lanes.iter().find(|l| l.is_walkable()).unwrap();
+    let driving = road
+        .lanes
+        .iter()
+        .find(|l| PathConstraints::Bus.can_use(l, &map))
+        .unwrap();
+    let sidewalk_pos = Position::new(sidewalk.id, sidewalk.length() / 2.0);
+    let stop_id = map.new_transit_stop_id(road.id);
+    let stop = EditTransitStop {
+        name: "test stop".to_string(),
+        gtfs_id: String::new(),
+        driving_pos: sidewalk_pos.equiv_pos(driving.id, &map),
+        sidewalk_pos,
+        is_train_stop: false,
+    };
+    let route = EditTransitRoute {
+        long_name: "test route".to_string(),
+        short_name: "test".to_string(),
+        gtfs_id: String::new(),
+        stops: vec![stop_id],
+        start: driving.id,
+        end_border: None,
+        route_type: PathConstraints::Bus,
+        spawn_times: Vec::new(),
+        orig_spawn_times: Vec::new(),
+        stop_times: Vec::new(),
+    };
+    let mut edits = map.get_edits().clone();
+    edits.commands.push(EditCmd::ChangeTransitStop {
+        id: stop_id,
+        old: None,
+        new: Some(stop.clone()),
+    });
+    edits.commands.push(EditCmd::ChangeTransitRoute {
+        id: map.new_transit_route_id(),
+        old: None,
+        new: Some(route),
+    });
+    map.must_apply_edits(edits, &mut Timer::throwaway());
+
+    // The edit goes through the same format API clients use
+    let result = EditCmd::ChangeTransitStop {
+        id: stop_id,
+        old: Some(stop),
+        new: None,
+    }
+    .to_perma(&map)
+    .into_cmd(&map)
+    .and_then(|cmd| {
+        let mut edits = map.get_edits().clone();
+        edits.commands.push(cmd);
+        map.check_transit_edits(&edits)
+    });
+    match result {
+        Ok(()) => bail!("Deleted {}, even though a route uses it", stop_id),
+        Err(err) if err.to_string().contains("doesn't exist") => Ok(()),
+        Err(err) => bail!("Deleting {} failed for the wrong reason: {}", stop_id, err),
+    }
+}
@@ -31,6 +31,12 @@ pub struct Plan {
     pub stages: Vec<Stage>,
     /// Relative to a central clock, delay the first stage by this many seconds.
     pub offset_seconds: usize,
+    /// If true, this signal is coordinated with others along a corridor. Every cycle has a fixed
+    /// length (the sum of each stage's fixed duration or maximum green) and the first stage always
+    /// ends at the same point in the cycle, according to `offset_seconds`. Any time that
+    /// actuated stages don't use goes to the first stage, which should serve the main corridor.
+    #[serde(default)]
+    pub coordinated: bool,
 }
 
 /// A traffic signal is in one stage at any time. The stage describes what movements are possible.
@@ -60,6 +66,13 @@ pub enum StageType {
     /// is 20, and additional is 40, the maximum cycle duration is 60.
     /// If there are crosswalks, the minimum is the minimum for the maximum crosswalks
     Variable(usize, usize, usize),
+    /// Minimum green, maximum green, passage time, all in seconds.
+    /// Virtual loop detectors sit on the approach lanes of each protected movement. After the
+    /// minimum green, the stage is extended as long as vehicles keep reaching the detectors no
+    /// more than the passage time apart. The stage ends when there's a longer gap ("gap-out") or
+    /// after the maximum green ("max-out"). The minimum green must allow enough time to cross any
+    /// crosswalks.
+    Actuated(usize, usize, usize),
 }
 
 /// A movement through an intersection.